@@ -0,0 +1,112 @@
+mod detect;
+
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use detect::{Finding, Kind, Markers, Scanner};
+
+/// What to do when a body has findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Return the findings and leave the body alone.
+    Report,
+    /// Return the body with every finding replaced by `[REDACTED:<kind>]`.
+    Redact,
+    /// Fail with `POLICY_VIOLATION`.
+    Block,
+}
+
+/// The result of scanning a request body. Matches `ScanReport` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanReport {
+    pub action: Action,
+    pub findings: Vec<Finding>,
+    /// The redacted body; only present for the `redact` action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    action: String,
+    detectors: Option<Vec<String>>,
+    injection_markers: Option<Vec<String>>,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string("action").default(DefaultValue::Str("report")),
+        Field::string_list("detectors"),
+        Field::string_list("injection_markers"),
+    ];
+}
+
+impl Config {
+    fn action(&self) -> Result<Action> {
+        match self.action.as_str() {
+            "report" => Ok(Action::Report),
+            "redact" => Ok(Action::Redact),
+            "block" => Ok(Action::Block),
+            other => Err(
+                PluginError::config("action must be report, redact or block")
+                    .with_detail("field", "action")
+                    .with_detail("value", other),
+            ),
+        }
+    }
+
+    fn scanner(&self) -> Result<Scanner> {
+        let kinds = match &self.detectors {
+            None => Kind::ALL.to_vec(),
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    Kind::from_name(name).ok_or_else(|| {
+                        let known: Vec<&str> = Kind::ALL.iter().map(|k| k.name()).collect();
+                        PluginError::config("detectors lists an unknown detector")
+                            .with_detail("field", "detectors")
+                            .with_detail("value", name.as_str())
+                            .with_detail("known", known)
+                    })
+                })
+                .collect::<Result<_>>()?,
+        };
+        let markers = match &self.injection_markers {
+            Some(markers) => Markers::Configured(markers.clone()),
+            None => Markers::BuiltIn(&detect::DEFAULT_MARKERS),
+        };
+        Ok(Scanner { kinds, markers })
+    }
+}
+
+#[firelynx_plugin]
+fn scan_body(request: Request, static_data: StaticData) -> Result<ScanReport> {
+    let config = Config::from_static_data(&static_data)?;
+    let action = config.action()?;
+    let scanner = config.scanner()?;
+
+    let text = request.body.text();
+    let findings = scanner.scan(&text);
+
+    let body = match action {
+        Action::Block if !findings.is_empty() => {
+            let mut kinds: Vec<&str> = Vec::new();
+            for finding in &findings {
+                if !kinds.contains(&finding.kind.name()) {
+                    kinds.push(finding.kind.name());
+                }
+            }
+            return Err(PluginError::policy("Request body contains blocked content")
+                .with_detail("findings", kinds));
+        }
+        Action::Redact => Some(detect::redact(&text, &findings)),
+        _ => None,
+    };
+    Ok(ScanReport {
+        action,
+        findings,
+        body,
+    })
+}
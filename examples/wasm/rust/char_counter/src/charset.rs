@@ -0,0 +1,214 @@
+//! `search_characters` with Unicode class references and ranges.
+//!
+//! Besides literal characters, the set may name classes as `\p{Name}` (or
+//! `\P{Name}` for the complement), with any general category, script or
+//! binary property the `regex` crate knows (`\p{Letter}`, `\p{Nd}`,
+//! `\p{Greek}`), or one of the shorter aliases in `ALIASES`. An alias may
+//! stand for several Unicode classes: `\p{emoji}` is pictographs and the
+//! regional indicators that pair up into flags.
+//!
+//! With `Syntax::Literal` (the default) any other character, backslashes
+//! included, stands for itself. `Syntax::Ranges` adds `a-z` ranges and comma
+//! separators, so "a-z,0-9" is 36 characters; `\-`, `\,` and `\\` are the
+//! literal characters there.
+
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, HirKind};
+use regex_syntax::ParserBuilder;
+
+/// Names that read better in a config than the Unicode ones, and the
+/// classes each stands for.
+const ALIASES: &[(&str, &[&str])] = &[
+    ("letters", &["L"]),
+    ("digits", &["Nd"]),
+    ("numbers", &["N"]),
+    ("punctuation", &["P"]),
+    ("symbols", &["S"]),
+    ("whitespace", &["White_Space"]),
+    ("uppercase", &["Uppercase"]),
+    ("lowercase", &["Lowercase"]),
+    // Not `Emoji`, which has the digits, `#` and `*` for keycaps.
+    ("emoji", &["Extended_Pictographic", "Regional_Indicator"]),
+];
+
+/// How `search_characters` is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Literal,
+    Ranges,
+}
+
+/// A parsed search set: literal characters plus the code point ranges of
+/// any classes and ranges, sorted and merged.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SearchSet {
+    pub literals: String,
+    ranges: Vec<(char, char)>,
+}
+
+/// An unusable part of a search set and why.
+pub type SetError = (String, String);
+
+/// One piece of a search set.
+enum Token<'a> {
+    Char(char),
+    /// `\p{...}` or `\P{...}`, as written.
+    Class(&'a str),
+    /// `Syntax::Ranges` only.
+    Dash,
+    /// `Syntax::Ranges` only.
+    Separator,
+}
+
+/// The token at the start of `rest` and its length in bytes.
+fn next_token(rest: &str, syntax: Syntax) -> Result<Option<(Token<'_>, usize)>, SetError> {
+    let Some(c) = rest.chars().next() else {
+        return Ok(None);
+    };
+    if rest.starts_with("\\p{") || rest.starts_with("\\P{") {
+        if let Some(end) = rest.find('}') {
+            return Ok(Some((Token::Class(&rest[..=end]), end + 1)));
+        }
+    }
+    let token = match (c, syntax) {
+        (_, Syntax::Literal) => (Token::Char(c), c.len_utf8()),
+        ('\\', Syntax::Ranges) => match rest[1..].chars().next() {
+            Some(escaped) => (Token::Char(escaped), 1 + escaped.len_utf8()),
+            None => {
+                return Err((
+                    "\\".to_string(),
+                    "a trailing backslash escapes nothing".to_string(),
+                ))
+            }
+        },
+        ('-', Syntax::Ranges) => (Token::Dash, 1),
+        (',', Syntax::Ranges) => (Token::Separator, 1),
+        (c, Syntax::Ranges) => (Token::Char(c), c.len_utf8()),
+    };
+    Ok(Some(token))
+}
+
+impl SearchSet {
+    /// Parses `spec`. With `case_insensitive`, classes and ranges also match
+    /// the other case of their members (`\p{Lu}` and `A-Z` match "a"), as in
+    /// a regex. Returns the offending part and the reason it was rejected.
+    pub fn parse(
+        spec: &str,
+        syntax: Syntax,
+        case_insensitive: bool,
+    ) -> Result<SearchSet, SetError> {
+        let mut set = SearchSet::default();
+        // The last character, held back in case a range starts with it.
+        let mut pending: Option<char> = None;
+        let mut rest = spec;
+        while let Some((token, len)) = next_token(rest, syntax)? {
+            rest = &rest[len..];
+            match token {
+                Token::Char(c) => set.literals.extend(pending.replace(c)),
+                Token::Separator => set.literals.extend(pending.take()),
+                Token::Class(reference) => {
+                    set.literals.extend(pending.take());
+                    set.add_class(reference, case_insensitive)?;
+                }
+                Token::Dash => {
+                    let (Some(start), Some((Token::Char(end), len))) =
+                        (pending.take(), next_token(rest, syntax)?)
+                    else {
+                        return Err((
+                            "-".to_string(),
+                            "a range needs a character on both sides of '-'; write \\- for a dash"
+                                .to_string(),
+                        ));
+                    };
+                    rest = &rest[len..];
+                    set.add_range(start, end, case_insensitive)?;
+                }
+            }
+        }
+        set.literals.extend(pending);
+
+        set.ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(set.ranges.len());
+        for (start, end) in set.ranges.drain(..) {
+            match merged.last_mut() {
+                Some((_, last)) if start <= *last => *last = (*last).max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        set.ranges = merged;
+        Ok(set)
+    }
+
+    fn add_range(
+        &mut self,
+        start: char,
+        end: char,
+        case_insensitive: bool,
+    ) -> Result<(), SetError> {
+        if start > end {
+            return Err((
+                format!("{}-{}", start, end),
+                "the range is reversed".to_string(),
+            ));
+        }
+        let mut class = ClassUnicode::new([ClassUnicodeRange::new(start, end)]);
+        if case_insensitive {
+            class.case_fold_simple();
+        }
+        self.ranges
+            .extend(class.ranges().iter().map(|r| (r.start(), r.end())));
+        Ok(())
+    }
+
+    fn add_class(&mut self, reference: &str, case_insensitive: bool) -> Result<(), SetError> {
+        let negated = reference.starts_with("\\P");
+        let name = &reference[3..reference.len() - 1];
+        let unaliased = [name];
+        let names = ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name.trim()))
+            .map_or(&unaliased[..], |&(_, unicode)| unicode);
+        let classes: String = names
+            .iter()
+            .map(|name| format!("\\p{{{}}}", name))
+            .collect();
+        let pattern = format!("[{}{}]", if negated { "^" } else { "" }, classes);
+        let hir = ParserBuilder::new()
+            .case_insensitive(case_insensitive)
+            .build()
+            .parse(&pattern)
+            .map_err(|e| {
+                // The kinds alone; the full message quotes our pattern.
+                let reason = match e {
+                    regex_syntax::Error::Parse(e) => e.kind().to_string(),
+                    regex_syntax::Error::Translate(e) => e.kind().to_string(),
+                    e => e.to_string(),
+                };
+                (reference.to_string(), reason)
+            })?;
+        match hir.kind() {
+            HirKind::Class(Class::Unicode(class)) => self
+                .ranges
+                .extend(class.ranges().iter().map(|r| (r.start(), r.end()))),
+            // A class of one character comes back as that literal.
+            HirKind::Literal(literal) => {
+                let c = std::str::from_utf8(&literal.0)
+                    .ok()
+                    .and_then(|s| s.chars().next());
+                self.ranges.extend(c.map(|c| (c, c)));
+            }
+            _ => return Err((reference.to_string(), "not a character class".to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn has_classes(&self) -> bool {
+        !self.ranges.is_empty()
+    }
+
+    /// Whether `c` is in one of the classes or ranges; literals are the
+    /// caller's.
+    pub fn in_class(&self, c: char) -> bool {
+        let after = self.ranges.partition_point(|&(start, _)| start <= c);
+        after > 0 && self.ranges[after - 1].1 >= c
+    }
+}
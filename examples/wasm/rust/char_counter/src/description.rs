@@ -0,0 +1,79 @@
+//! The `match_description` line of a report, for people reading it.
+//!
+//! A route names what it counts ("vowels", "z letters") and the report
+//! carries the name as `description`. Placeholders put the result into the
+//! sentence: `"{count} vowels in the body"` reads "3 vowels in the body".
+//! `{{` and `}}` are literal braces.
+
+/// The placeholders a description can use.
+pub const PLACEHOLDERS: [&str; 2] = ["count", "characters"];
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+enum Part {
+    Text(String),
+    Count,
+    Characters,
+}
+
+/// A parsed `match_description`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Description {
+    parts: Vec<Part>,
+}
+
+impl Description {
+    /// Reads `template`, returning why it does not parse as the error: an
+    /// unknown placeholder or an unmatched brace.
+    pub fn parse(template: &str) -> Result<Description, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        return Err("a { is not closed; write {{ for a brace".to_string());
+                    };
+                    let part = match &rest[..end] {
+                        "count" => Part::Count,
+                        "characters" => Part::Characters,
+                        name => return Err(format!("{{{}}} is not a placeholder", name)),
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err("a } is not opened; write }} for a brace".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Description { parts })
+    }
+
+    /// The description of a report with `count` matches of `characters`.
+    pub fn render(&self, count: i32, characters: &str) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Count => out.push_str(&count.to_string()),
+                Part::Characters => out.push_str(characters),
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,303 @@
+//! Markup removal for HTML bodies, before anything else sees the text.
+//!
+//! Counting an HTML page as text counts its markup: every `<`, `>`, `=` and
+//! quote, the letters of tag and attribute names, the script and style
+//! sources. `strip_html` keeps only what a browser would show as text: tags,
+//! comments and the content of `script` and `style` elements are removed
+//! (without leaving a space behind), and character references such as
+//! `&eacute;` or `&#233;` become the character they stand for.
+//!
+//! This is a tokenizer, not a parser: it does not build or repair a tree,
+//! so it follows the markup as written. A `<` that cannot start a tag ("a <
+//! b") and an unknown reference (`&copy2;`) stay text. Named references are
+//! the Latin-1 set of HTML 4 and a few common punctuation marks, which is
+//! what text rendered by most editors uses; the rest stay as written.
+
+/// The longest character reference read; a longer run after `&` is text.
+const MAX_REFERENCE: usize = 32;
+
+/// The longest element name kept; only `RAW_TEXT` names matter.
+const MAX_NAME: usize = 16;
+
+/// Elements whose content is not text.
+const RAW_TEXT: [&str; 2] = ["script", "style"];
+
+/// Named references other than the Latin-1 letters and signs, which are
+/// `LATIN1` indexed from U+00A0.
+const NAMED: [(&str, char); 16] = [
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("ndash", '\u{2013}'),
+    ("mdash", '\u{2014}'),
+    ("lsquo", '\u{2018}'),
+    ("rsquo", '\u{2019}'),
+    ("ldquo", '\u{201c}'),
+    ("rdquo", '\u{201d}'),
+    ("bull", '\u{2022}'),
+    ("hellip", '\u{2026}'),
+    ("euro", '\u{20ac}'),
+    ("trade", '\u{2122}'),
+    ("oelig", '\u{153}'),
+];
+
+/// The HTML 4 names of U+00A0 to U+00FF, in order.
+const LATIN1: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy", "ordf",
+    "laquo", "not", "shy", "reg", "macr", "deg", "plusmn", "sup2", "sup3", "acute", "micro",
+    "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute",
+    "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocirc", "Otilde", "Ouml", "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute",
+    "THORN", "szlig", "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil",
+    "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml", "eth", "ntilde",
+    "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave", "uacute", "ucirc",
+    "uuml", "yacute", "thorn", "yuml",
+];
+
+/// The character a reference names, given what is between `&` and `;`.
+/// Numeric references outside Unicode are U+FFFD, as a browser shows them.
+fn decode(reference: &str) -> Option<char> {
+    if let Some(number) = reference.strip_prefix('#') {
+        let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (number, 10),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        let code = u32::from_str_radix(digits, radix).unwrap_or(u32::MAX);
+        return Some(
+            char::from_u32(code)
+                .filter(|&c| c != '\0')
+                .unwrap_or('\u{fffd}'),
+        );
+    }
+    if let Some(&(_, c)) = NAMED.iter().find(|(name, _)| *name == reference) {
+        return Some(c);
+    }
+    let i = LATIN1.iter().position(|name| *name == reference)?;
+    char::from_u32(0xa0 + i as u32)
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+enum State {
+    Text,
+    /// After `<`, which starts a tag only if a name, `/`, `!` or `?`
+    /// follows.
+    TagOpen,
+    /// After `<!`: a comment if `--` follows.
+    Declaration,
+    /// Inside a tag, up to the `>` that is not in a quoted value.
+    Tag {
+        /// The element name, lowercased, while it is being read.
+        name: String,
+        naming: bool,
+        closing: bool,
+        quote: Option<char>,
+        after_equals: bool,
+        self_closing: bool,
+    },
+    /// Inside `<!-- ... -->`; `dashes` is how many `-` just went by.
+    Comment {
+        dashes: u8,
+    },
+    /// After `&`, reading a reference.
+    Reference,
+    /// The content of a `script` or `style` element, up to its end tag.
+    RawText {
+        element: String,
+    },
+}
+
+/// Strips HTML that arrives in pieces. A tag, comment or reference split
+/// between pieces is held back until the piece that completes it.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct HtmlStripper {
+    state: State,
+    /// The text a construct that turns out not to be markup gives back:
+    /// `<`, `<!`, an unfinished reference, or a partial end tag in raw
+    /// text.
+    pending: String,
+}
+
+impl Default for HtmlStripper {
+    fn default() -> HtmlStripper {
+        HtmlStripper {
+            state: State::Text,
+            pending: String::new(),
+        }
+    }
+}
+
+impl HtmlStripper {
+    /// Appends to `out` the text that `text` settles.
+    pub fn push(&mut self, text: &str, out: &mut String) {
+        for c in text.chars() {
+            self.step(c, out);
+        }
+    }
+
+    /// Appends the rest of the text to `out`. Markup cut off by the end is
+    /// dropped; an unfinished reference is text.
+    pub fn finish(&mut self, out: &mut String) {
+        if matches!(self.state, State::TagOpen | State::Reference) {
+            out.push_str(&self.pending);
+        }
+        self.pending.clear();
+        self.state = State::Text;
+    }
+
+    fn step(&mut self, c: char, out: &mut String) {
+        match &mut self.state {
+            State::Text => match c {
+                '<' => self.hold(State::TagOpen, c),
+                '&' => self.hold(State::Reference, c),
+                c => out.push(c),
+            },
+            State::TagOpen => match c {
+                '!' => self.hold(State::Declaration, c),
+                '/' => self.enter_tag(true),
+                '?' => self.enter_tag(false),
+                c if c.is_ascii_alphabetic() => {
+                    self.enter_tag(false);
+                    self.step(c, out);
+                }
+                c => self.give_back(c, out),
+            },
+            State::Declaration => {
+                if c == '-' && self.pending == "<!-" {
+                    self.pending.clear();
+                    self.state = State::Comment { dashes: 0 };
+                } else if c == '-' && self.pending == "<!" {
+                    self.pending.push(c);
+                } else {
+                    // A doctype or other declaration, up to `>`.
+                    self.enter_tag(false);
+                    self.step(c, out);
+                }
+            }
+            State::Tag {
+                name,
+                naming,
+                closing,
+                quote,
+                after_equals,
+                self_closing,
+            } => {
+                if let Some(q) = *quote {
+                    if c == q {
+                        *quote = None;
+                    }
+                    return;
+                }
+                if *naming && c.is_ascii_alphanumeric() {
+                    if name.len() < MAX_NAME {
+                        name.push(c.to_ascii_lowercase());
+                    }
+                    return;
+                }
+                *naming = false;
+                match c {
+                    '>' => {
+                        let element = std::mem::take(name);
+                        let raw = !*closing && !*self_closing && RAW_TEXT.contains(&&*element);
+                        self.state = if raw {
+                            State::RawText { element }
+                        } else {
+                            State::Text
+                        };
+                    }
+                    '"' | '\'' if *after_equals => *quote = Some(c),
+                    c if c.is_whitespace() => {}
+                    c => {
+                        *after_equals = c == '=';
+                        *self_closing = c == '/';
+                    }
+                }
+            }
+            State::Comment { dashes } => match c {
+                '>' if *dashes >= 2 => self.state = State::Text,
+                '-' => *dashes = (*dashes + 1).min(2),
+                _ => *dashes = 0,
+            },
+            State::Reference => match c {
+                ';' => {
+                    match decode(&self.pending[1..]) {
+                        Some(decoded) => out.push(decoded),
+                        None => {
+                            out.push_str(&self.pending);
+                            out.push(c);
+                        }
+                    }
+                    self.pending.clear();
+                    self.state = State::Text;
+                }
+                c if (c.is_ascii_alphanumeric() || c == '#')
+                    && self.pending.len() < MAX_REFERENCE =>
+                {
+                    self.pending.push(c)
+                }
+                c => self.give_back(c, out),
+            },
+            State::RawText { element } => {
+                // `pending` is the end tag read so far, all ASCII.
+                let len = self.pending.len();
+                let expected = "</".chars().chain(element.chars()).nth(len);
+                if expected.is_none() {
+                    if c == '>' || c == '/' || c.is_whitespace() {
+                        self.pending.clear();
+                        self.enter_tag(true);
+                        self.step(c, out);
+                        return;
+                    }
+                    self.pending.clear();
+                } else if expected == Some(c.to_ascii_lowercase()) {
+                    self.pending.push(c);
+                    return;
+                } else {
+                    self.pending.clear();
+                }
+                if c == '<' {
+                    self.pending.push(c);
+                }
+            }
+        }
+    }
+
+    fn hold(&mut self, state: State, c: char) {
+        self.pending.push(c);
+        self.state = state;
+    }
+
+    fn enter_tag(&mut self, closing: bool) {
+        self.pending.clear();
+        self.state = State::Tag {
+            name: String::new(),
+            naming: true,
+            closing,
+            quote: None,
+            after_equals: false,
+            self_closing: false,
+        };
+    }
+
+    /// Writes out what was held back as text and reads `c` as text again.
+    fn give_back(&mut self, c: char, out: &mut String) {
+        out.push_str(&self.pending);
+        self.pending.clear();
+        self.state = State::Text;
+        self.step(c, out);
+    }
+}
+
+/// `text` with its markup stripped.
+pub fn strip(text: &str) -> String {
+    let mut stripper = HtmlStripper::default();
+    let mut out = String::with_capacity(text.len());
+    stripper.push(text, &mut out);
+    stripper.finish(&mut out);
+    out
+}
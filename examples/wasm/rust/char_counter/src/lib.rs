@@ -1,113 +1,1665 @@
-mod pdk;
+mod charset;
+mod description;
+mod html;
+mod normalize;
+mod preprocess;
+mod render;
+mod text;
 
-use pdk::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use firelynx_pdk::body::{Body, DEFAULT_CHUNK_BYTES};
+use firelynx_pdk::fold::{fold_simple, Locale};
+use firelynx_pdk::prelude::*;
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
+use unicode_segmentation::UnicodeSegmentation;
 
-#[derive(serde::Deserialize)]
-struct UrlData {
-    // Unused fields from go-polyscript URL structure
-    // #[serde(rename = "Scheme")]
-    // scheme: String,
-    // #[serde(rename = "Path")]
-    // path: String,
-    // #[serde(rename = "Host")]
-    // host: String,
-    // #[serde(rename = "RawQuery")]
-    // raw_query: String,
-    // #[serde(rename = "Fragment")]
-    // fragment: String,
+use charset::{SearchSet, Syntax};
+use description::Description;
+use html::HtmlStripper;
+use normalize::{Normalization, NormalizedChunks, Normalizer, TextForm};
+use preprocess::{Preprocess, Preprocessor};
+use render::Format;
+use text::{LineCounter, WordCounter, Words};
+
+/// The result of counting configurable characters in the request input.
+/// Matches `CharacterReport` in `schema.yaml`.
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CharacterReport {
+    /// The count of matching characters for input string.
+    pub count: i32,
+
+    /// The set of characters used to get the count, e.g. "aAeEiIoOuU", "0123456789", etc.
+    /// Empty when a `search_pattern` or `search_sets` was counted instead.
+    pub characters: String,
+
+    /// The regular expression whose matches were counted, if one was configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Matches per search character, only with `include_histogram = true`.
+    /// Keyed like `CharacterReportV2::counts`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<BTreeMap<String, i32>>,
+
+    /// Matches per named set, only with `search_sets`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sets: Option<BTreeMap<String, i32>>,
+
+    /// The `top_n` most frequent matched characters, only with `top_n`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<Vec<TopCharacter>>,
+
+    /// Characters (or graphemes) scanned, only with `include_stats = true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_chars: Option<u64>,
+
+    /// `count / total_chars`, 0 for empty text; only with `include_stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_ratio: Option<f64>,
+
+    /// Each named set's matches as a percentage of `total_chars`, only
+    /// with `include_stats` and `search_sets`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set_percentages: Option<BTreeMap<String, f64>>,
+
+    /// As `CharacterReportV2::positions`, only with `include_positions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub positions: Option<Vec<Position>>,
+
+    /// As `CharacterReportV2::positions_truncated`, only with
+    /// `include_positions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub positions_truncated: Option<bool>,
+
+    /// The route's `match_description` with its placeholders filled in,
+    /// only when it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-#[derive(serde::Deserialize)]
-struct RequestData {
-    #[serde(rename = "Body")]
-    body: String,
-    
-    // Unused fields from go-polyscript request structure
-    // #[serde(rename = "Headers", default)]
-    // headers: std::collections::HashMap<String, Vec<String>>,
-    // #[serde(rename = "QueryParams", default)]
-    // query_params: std::collections::HashMap<String, Vec<String>>,
-    // #[serde(rename = "Method")]
-    // method: String,
-    // #[serde(rename = "Proto")]
-    // proto: String,
-    // #[serde(rename = "Host")]
-    // host: String,
-    // #[serde(rename = "RemoteAddr")]
-    // remote_addr: String,
-    // #[serde(rename = "ContentLength")]
-    // content_length: i64,
-    // #[serde(rename = "URL")]
-    // url: UrlData,
-    // #[serde(rename = "URL_Path")]
-    // url_path: String,
-    // #[serde(rename = "URL_Scheme")]
-    // url_scheme: String,
-    // #[serde(rename = "URL_Host")]
-    // url_host: String,
-    // #[serde(rename = "URL_String")]
-    // url_string: String,
+/// Version 2 of the report, returned when `report_version = 2`. Matches
+/// `CharacterReportV2` in `schema.yaml`.
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CharacterReportV2 {
+    /// Always 2, so consumers can tell the versions apart.
+    pub report_version: u32,
+
+    /// The count of matches, as in version 1.
+    pub count: i32,
+
+    /// The set of characters used to get the count; empty for a
+    /// `search_pattern` or `search_sets`.
+    pub characters: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Matches per search character (or grapheme), case-folded unless
+    /// `case_sensitive`. A `search_pattern` has a single entry keyed by the
+    /// pattern. Characters with no matches are omitted.
+    pub counts: BTreeMap<String, i32>,
+
+    /// Matches per named set, including sets with none, only with
+    /// `search_sets`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sets: Option<BTreeMap<String, i32>>,
+
+    /// As in version 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<Vec<TopCharacter>>,
+
+    /// As in version 1, as a percentage of `stats.units`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set_percentages: Option<BTreeMap<String, f64>>,
+
+    pub stats: ReportStats,
+
+    /// Where the first `max_positions` matches are, as byte ranges of the
+    /// decoded body (after `normalize` and `strip_diacritics`, when set).
+    pub positions: Vec<Position>,
+
+    /// True when there were more matches than `positions` lists.
+    pub positions_truncated: bool,
+
+    /// As in version 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-#[derive(serde::Deserialize)]
-struct StaticData {
-    search_characters: Option<String>,
-    case_sensitive: Option<bool>,
-    
-    // Unused fields from TOML configuration
-    // match_description: Option<String>,
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ReportStats {
+    /// Decoded body size in bytes.
+    pub bytes: u64,
+    /// Characters (or graphemes, with `count_mode = "graphemes"`) scanned.
+    pub units: u64,
+    /// `count / units`, 0 for an empty body.
+    pub density: f64,
+}
+
+/// One of the most frequent matched characters, keyed like `counts`.
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TopCharacter {
+    pub character: String,
+    pub count: i32,
+}
+
+/// A match as the byte range `start..end` of the decoded body.
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Position {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// The export output. Untagged, so a version 1 report serializes exactly as
+/// it did before version 2 existed.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(untagged)]
+pub enum Report {
+    V1(CharacterReport),
+    V2(CharacterReportV2),
+}
+
+impl Report {
+    /// Fills in `description` from the route's `match_description`.
+    /// `{characters}` is the pattern when one was counted.
+    fn describe(&mut self, description: Option<&Description>) {
+        let Some(description) = description else {
+            return;
+        };
+        let (count, characters, pattern, slot) = match self {
+            Report::V1(r) => (r.count, &r.characters, &r.pattern, &mut r.description),
+            Report::V2(r) => (r.count, &r.characters, &r.pattern, &mut r.description),
+        };
+        *slot = Some(description.render(count, pattern.as_deref().unwrap_or(characters)));
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+struct Config {
+    search_characters: String,
+    case_sensitive: bool,
+    count_mode: String,
+    search_pattern: Option<String>,
+    report_version: i64,
+    max_positions: i64,
+    include_histogram: bool,
+    include_positions: bool,
+    top_n: Option<i64>,
+    include_stats: bool,
+    invert: bool,
+    body_encoding: String,
+    normalize: Option<String>,
+    strip_diacritics: bool,
+    search_syntax: String,
+    search_sets: Option<BTreeMap<String, serde_json::Value>>,
+    source: String,
+    locale: Option<String>,
+    max_body_bytes: Option<i64>,
+    preprocess: Option<Vec<String>>,
+    strip_html: bool,
+    match_description: Option<String>,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string("search_characters")
+            .non_empty()
+            .default(DefaultValue::Str("aeiouAEIOU")), // Default vowels
+        Field::bool("case_sensitive").default(DefaultValue::Bool(false)), // Default case insensitive
+        Field::string("count_mode").default(DefaultValue::Str("chars")),
+        Field::string("search_pattern").non_empty(),
+        // Existing routes get version 1 until they opt in.
+        Field::integer("report_version").default(DefaultValue::Int(1)),
+        Field::integer("max_positions").default(DefaultValue::Int(100)),
+        Field::bool("include_histogram").default(DefaultValue::Bool(false)),
+        Field::bool("include_positions").default(DefaultValue::Bool(false)),
+        Field::integer("top_n"),
+        Field::bool("include_stats").default(DefaultValue::Bool(false)),
+        Field::bool("invert").default(DefaultValue::Bool(false)),
+        Field::string("body_encoding").default(DefaultValue::Str("text")),
+        Field::string("normalize").non_empty(),
+        Field::bool("strip_diacritics").default(DefaultValue::Bool(false)),
+        // Literal, so sets written before ranges existed (".,;", "+-") keep
+        // their meaning.
+        Field::string("search_syntax").default(DefaultValue::Str("literal")),
+        Field::table("search_sets"),
+        Field::string("source").default(DefaultValue::Str("body")),
+        Field::string("locale").non_empty(),
+        Field::integer("max_body_bytes"),
+        Field::string_list("preprocess"),
+        Field::bool("strip_html").default(DefaultValue::Bool(false)),
+        Field::string("match_description").non_empty(),
+    ];
+    // A route without static_data is almost always a wiring mistake (wrong
+    // app, or the table left off); `static_data = {}` picks every default.
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+    // Descriptive keys the example configs set on every route.
+    const IGNORED_KEYS: &'static [&'static str] = &["service_name", "version"];
+
+    fn check(&self, static_data: &StaticData, lint: &mut Lint) {
+        // The config the route runs with, deployment default included.
+        let mut config = self.clone();
+        match settings() {
+            Ok(settings) => config.apply_settings(static_data, settings),
+            Err(e) => lint.reject(&e),
+        }
+        config.review(static_data, lint);
+    }
 }
 
+/// Deployment settings from the host's extism config, shared by every route
+/// the instance serves.
 #[derive(serde::Deserialize)]
-struct InputData {
-    request: RequestData,
-    static_data: Option<StaticData>,
-}
-
-pub fn count_characters(input_json: String) -> Result<types::CharacterReport, extism_pdk::Error> {
-    let input_data: InputData = serde_json::from_str(&input_json).map_err(|e| {
-        extism_pdk::Error::msg(format!("Invalid JSON input: {}", e))
-    })?;
-
-    // Use static_data if available, otherwise defaults
-    let matching_chars = input_data.static_data
-        .as_ref()
-        .and_then(|sd| sd.search_characters.as_ref())
-        .map(|s| s.as_str())
-        .unwrap_or("aeiouAEIOU"); // Default vowels
-    
-    let case_sensitive = input_data.static_data
-        .as_ref()
-        .and_then(|sd| sd.case_sensitive)
-        .unwrap_or(false); // Default case insensitive
-
-    // Validate character set is not empty
-    if matching_chars.is_empty() {
-        return Err(extism_pdk::Error::msg("Character set cannot be empty"));
-    }
-
-    // Apply case sensitivity to search text if needed
-    let search_text = if case_sensitive {
-        input_data.request.body.clone()
-    } else {
-        input_data.request.body.to_lowercase()
+struct Settings {
+    /// `search_characters` for routes whose `static_data` leaves it out, so
+    /// a set used across many routes is written once.
+    default_search_characters: Option<String>,
+}
+
+impl PluginConfig for Settings {
+    const FIELDS: &'static [Field] = &[Field::string("default_search_characters").non_empty()];
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// The deployment settings, read once per plugin instance.
+fn settings() -> Result<&'static Settings> {
+    if let Some(settings) = SETTINGS.get() {
+        return Ok(settings);
+    }
+    let settings = Settings::load()?;
+    Ok(SETTINGS.get_or_init(|| settings))
+}
+
+impl Config {
+    /// Parses `static_data` and fills in the deployment settings it does
+    /// not override.
+    fn load(static_data: &StaticData) -> Result<Config> {
+        let mut config = Config::from_static_data(static_data)?;
+        config.apply_settings(static_data, settings()?);
+        Ok(config)
+    }
+
+    /// A route's own `search_characters` wins over the deployment default,
+    /// which wins over the built-in vowels.
+    fn apply_settings(&mut self, static_data: &StaticData, settings: &Settings) {
+        if let (false, Some(default)) = (
+            static_data.contains_key("search_characters"),
+            &settings.default_search_characters,
+        ) {
+            self.search_characters = default.clone();
+        }
+    }
+
+    fn review(&self, static_data: &StaticData, lint: &mut Lint) {
+        let count_mode = match self.count_mode() {
+            Ok(mode) => Some(mode),
+            Err(e) => {
+                lint.reject(&e);
+                None
+            }
+        };
+        let normalization = self.normalization().unwrap_or_else(|e| {
+            lint.reject(&e);
+            None
+        });
+        if let Some(mode) = &count_mode {
+            if let Err(e) = self.pattern(mode, normalization) {
+                lint.reject(&e);
+            }
+        }
+        let syntax = match self.syntax() {
+            Ok(syntax) => Some(syntax),
+            Err(e) => {
+                lint.reject(&e);
+                None
+            }
+        };
+        let v2 = self.wants_v2().unwrap_or_else(|e| {
+            lint.reject(&e);
+            false
+        });
+        if let Err(e) = self.source() {
+            lint.reject(&e);
+        }
+        if let Err(e) = self.tally(v2) {
+            lint.reject(&e);
+        }
+        if let Err(e) = self.invert() {
+            lint.reject(&e);
+        }
+        if let Err(e) = self.max_body_bytes() {
+            lint.reject(&e);
+        }
+        if let Err(e) = self.preprocess() {
+            lint.reject(&e);
+        }
+        if let Err(e) = self.strip_html() {
+            lint.reject(&e);
+        }
+        if let Err(e) = self.description() {
+            lint.reject(&e);
+        }
+        if let Some(mode) = count_mode {
+            if let Err(e) = self.check_bytes(mode, normalization) {
+                lint.reject(&e);
+            }
+        }
+        if let (Some(CountMode::Bytes), Some(syntax), None) =
+            (count_mode, syntax, &self.search_pattern)
+        {
+            // Sets that do not parse are reported on their own.
+            let parsed = match self.search_sets(syntax) {
+                Ok(Some(sets)) => {
+                    let (names, targets): (Vec<String>, Vec<SearchSet>) = sets.into_iter().unzip();
+                    Some((targets, Some(names)))
+                }
+                Ok(None) => self.search_set(syntax).ok().map(|set| (vec![set], None)),
+                Err(_) => None,
+            };
+            if let Some((targets, names)) = parsed {
+                if let Err(e) = check_byte_literals(&targets, names.as_deref()) {
+                    lint.reject(&e);
+                }
+            }
+        }
+        match self.fold() {
+            Err(e) => lint.reject(&e),
+            Ok(_) if self.locale.is_none() => {}
+            Ok(None) => lint.warn(
+                "locale",
+                LintKind::Suspicious,
+                "locale has no effect when case_sensitive is true",
+            ),
+            Ok(Some(_)) if self.search_pattern.is_some() => lint.warn(
+                "locale",
+                LintKind::Suspicious,
+                "locale does not apply to search_pattern, which folds case the same way in every language",
+            ),
+            Ok(Some(_)) => {}
+        }
+
+        let explicit = |key| static_data.contains_key(key);
+        if self.search_pattern.is_some() && explicit("search_characters") {
+            lint.warn(
+                "search_characters",
+                LintKind::Suspicious,
+                "search_characters is ignored when search_pattern is set",
+            );
+        }
+        if self.search_sets.is_some() {
+            if let Err(e) = self.search_sets(syntax.unwrap_or(Syntax::Literal)) {
+                lint.reject(&e);
+            }
+            if self.search_pattern.is_none() && explicit("search_characters") {
+                lint.warn(
+                    "search_characters",
+                    LintKind::Suspicious,
+                    "search_characters is ignored when search_sets is set",
+                );
+            }
+        } else if let (None, Some(syntax)) = (&self.search_pattern, syntax) {
+            self.check_characters(syntax, count_mode, explicit("case_sensitive"), lint);
+            if normalization
+                == Some(TextForm {
+                    form: Some(Normalization::Nfd),
+                    strip_diacritics: false,
+                })
+                && matches!(count_mode, Some(CountMode::Chars))
+                && Normalization::Nfd.apply(&self.search_characters) != self.search_characters
+            {
+                lint.warn(
+                    "normalize",
+                    LintKind::Suspicious,
+                    "nfd splits accented search_characters into a letter and a mark, each \
+                     counted on its own; use nfc, or count_mode = \"graphemes\"",
+                );
+            }
+        }
+        if self.include_histogram && v2 {
+            lint.warn(
+                "include_histogram",
+                LintKind::Suspicious,
+                "include_histogram has no effect with report_version 2, which always has counts",
+            );
+        }
+        if self.include_stats && v2 && self.search_sets.is_none() {
+            lint.warn(
+                "include_stats",
+                LintKind::Suspicious,
+                "include_stats has no effect with report_version 2, which always has stats, \
+                 unless search_sets is set",
+            );
+        }
+        if self.include_positions && v2 {
+            lint.warn(
+                "include_positions",
+                LintKind::Suspicious,
+                "include_positions has no effect with report_version 2, which always has positions",
+            );
+        }
+        if explicit("max_positions") && !v2 && !self.include_positions {
+            lint.warn(
+                "max_positions",
+                LintKind::Suspicious,
+                "max_positions only applies to report_version 2 or include_positions",
+            );
+        }
+    }
+}
+
+/// Report versions this plugin can produce.
+const REPORT_VERSIONS: [i64; 2] = [1, 2];
+
+/// What one counted unit of the body is.
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+enum CountMode {
+    /// Unicode scalar values (Rust `char`s).
+    Chars,
+    /// Extended grapheme clusters: what a reader sees as one character,
+    /// e.g. "e" plus a combining accent, or an emoji with a skin tone.
+    Graphemes,
+    /// Bytes of the UTF-8 text, or of the decoded binary with
+    /// `body_encoding = "base64"`. A byte is matched as the character
+    /// U+0000 to U+00FF with its value, so ASCII means what it says and
+    /// `\P{ASCII}` is every byte of a multi-byte character.
+    Bytes,
+}
+
+/// The part of the request that is counted.
+enum Source {
+    Body,
+    /// Every value of the header, joined with ", " as HTTP combines
+    /// repeated fields. The name is matched case-insensitively.
+    Header(String),
+    /// Every value of the query parameter, decoded and joined with ", ".
+    Query(String),
+    /// The decoded URL path.
+    Path,
+}
+
+impl Source {
+    /// The text to count, or `None` for the body, which is read in chunks
+    /// where it lies. A header or parameter the request lacks is empty.
+    fn select(&self, request: &Request) -> Option<String> {
+        match self {
+            Source::Body => None,
+            Source::Header(name) => {
+                Some(request.header_values(name).unwrap_or_default().join(", "))
+            }
+            Source::Query(name) => Some(
+                request
+                    .query_params
+                    .get(name)
+                    .map(|values| values.join(", "))
+                    .unwrap_or_default(),
+            ),
+            Source::Path => Some(request.url.path.clone()),
+        }
+    }
+}
+
+impl Config {
+    /// The case folding to match with, `None` when `case_sensitive`.
+    fn fold(&self) -> Result<Option<Locale>> {
+        let locale = match &self.locale {
+            Some(tag) => Locale::from_tag(tag).ok_or_else(|| {
+                PluginError::config("locale must be a BCP 47 language tag such as tr or en-US")
+                    .with_detail("field", "locale")
+                    .with_detail("value", tag.as_str())
+            })?,
+            None => Locale::Root,
+        };
+        Ok((!self.case_sensitive).then_some(locale))
+    }
+
+    fn source(&self) -> Result<Source> {
+        let source = match self.source.split_once(':') {
+            None if self.source == "body" => Some(Source::Body),
+            None if self.source == "path" => Some(Source::Path),
+            Some(("header", name)) if !name.is_empty() => Some(Source::Header(name.to_string())),
+            Some(("query", name)) if !name.is_empty() => Some(Source::Query(name.to_string())),
+            _ => None,
+        };
+        source.ok_or_else(|| {
+            PluginError::config("source must be body, path, header:<name> or query:<name>")
+                .with_detail("field", "source")
+                .with_detail("value", self.source.as_str())
+        })
+    }
+
+    fn count_mode(&self) -> Result<CountMode> {
+        match self.count_mode.as_str() {
+            "chars" => Ok(CountMode::Chars),
+            "graphemes" => Ok(CountMode::Graphemes),
+            "bytes" => Ok(CountMode::Bytes),
+            other => Err(
+                PluginError::config("count_mode must be chars, graphemes or bytes")
+                    .with_detail("field", "count_mode")
+                    .with_detail("value", other),
+            ),
+        }
+    }
+
+    /// Checks the options that only make sense for text against
+    /// `count_mode`; returns whether the body is base64 to decode first.
+    fn check_bytes(&self, count_mode: CountMode, normalization: Option<TextForm>) -> Result<bool> {
+        let bytes = matches!(count_mode, CountMode::Bytes);
+        if let (true, Some(text_form)) = (bytes, normalization) {
+            let field = match text_form.form {
+                Some(_) => "normalize",
+                None => "strip_diacritics",
+            };
+            return Err(PluginError::config(format!(
+                "{} does not apply to count_mode bytes",
+                field
+            ))
+            .with_detail("field", field));
+        }
+        match self.body_encoding.as_str() {
+            "text" => Ok(false),
+            "base64" if bytes => Ok(true),
+            "base64" => Err(
+                PluginError::config("body_encoding base64 needs count_mode bytes")
+                    .with_detail("field", "body_encoding"),
+            ),
+            other => Err(PluginError::config("body_encoding must be text or base64")
+                .with_detail("field", "body_encoding")
+                .with_detail("value", other)),
+        }
+    }
+
+    /// The `normalize` form and `strip_diacritics` together; `None` when
+    /// the text is matched as it is.
+    fn normalization(&self) -> Result<Option<TextForm>> {
+        let form = match &self.normalize {
+            None => None,
+            Some(name) => Some(Normalization::parse(name).ok_or_else(|| {
+                PluginError::config("normalize must be nfc, nfd or nfkc")
+                    .with_detail("field", "normalize")
+                    .with_detail("value", name.as_str())
+            })?),
+        };
+        Ok(
+            (form.is_some() || self.strip_diacritics).then_some(TextForm {
+                form,
+                strip_diacritics: self.strip_diacritics,
+            }),
+        )
+    }
+
+    fn syntax(&self) -> Result<Syntax> {
+        match self.search_syntax.as_str() {
+            "literal" => Ok(Syntax::Literal),
+            "ranges" => Ok(Syntax::Ranges),
+            other => Err(
+                PluginError::config("search_syntax must be literal or ranges")
+                    .with_detail("field", "search_syntax")
+                    .with_detail("value", other),
+            ),
+        }
+    }
+
+    /// The compiled `search_pattern`, if one is configured. The pattern is
+    /// normalized like the body, so literal characters in it match.
+    fn pattern(
+        &self,
+        count_mode: &CountMode,
+        normalization: Option<TextForm>,
+    ) -> Result<Option<Regex>> {
+        let Some(pattern) = &self.search_pattern else {
+            return Ok(None);
+        };
+        let source = match normalization {
+            Some(form) => Cow::Owned(form.apply(pattern)),
+            None => Cow::Borrowed(pattern.as_str()),
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(!self.case_sensitive)
+            .size_limit(MAX_PATTERN_BYTES)
+            .build()
+            .map_err(|e| {
+                PluginError::config("search_pattern is not a valid regular expression")
+                    .with_detail("field", "search_pattern")
+                    .with_detail("value", pattern.as_str())
+                    .with_detail("reason", e.to_string())
+            })?;
+        if !matches!(count_mode, CountMode::Chars) {
+            return Err(
+                PluginError::config("count_mode does not apply to search_pattern")
+                    .with_detail("field", "count_mode"),
+            );
+        }
+        Ok(Some(regex))
+    }
+
+    /// Warnings about `search_characters` that is accepted but unlikely to
+    /// count what was meant.
+    fn check_characters(
+        &self,
+        syntax: Syntax,
+        count_mode: Option<CountMode>,
+        explicit_case: bool,
+        lint: &mut Lint,
+    ) {
+        let set = match self.search_set(syntax) {
+            Ok(set) => set,
+            Err(e) => return lint.reject(&e),
+        };
+        let chars = &set.literals;
+        if chars.trim().is_empty() && !set.has_classes() {
+            lint.warn(
+                "search_characters",
+                LintKind::Suspicious,
+                "search_characters is only whitespace",
+            );
+        }
+        // Classes are assumed to hold cased characters.
+        let has_cased =
+            set.has_classes() || chars.chars().any(|c| c.to_lowercase().ne(c.to_uppercase()));
+        if explicit_case && self.case_sensitive && !has_cased {
+            lint.warn(
+                "case_sensitive",
+                LintKind::Suspicious,
+                "case_sensitive has no effect: search_characters has no cased characters",
+            );
+        }
+        // A cluster such as a ZWJ emoji sequence is one search character
+        // in graphemes mode, so its repeated parts are not duplicates.
+        let graphemes = matches!(count_mode, Some(CountMode::Graphemes));
+        let units: Vec<&str> = if graphemes {
+            chars.graphemes(true).collect()
+        } else {
+            chars.split_inclusive(|_: char| true).collect()
+        };
+        let mut seen = HashSet::new();
+        let mut duplicates: Vec<&str> = Vec::new();
+        for unit in units {
+            if !seen.insert(unit) && !duplicates.contains(&unit) {
+                duplicates.push(unit);
+            }
+        }
+        if !duplicates.is_empty() {
+            lint.warn(
+                "search_characters",
+                LintKind::Suspicious,
+                format!(
+                    "search_characters repeats {:?}; each character is counted once",
+                    duplicates.concat()
+                ),
+            );
+        }
+        // Judged after normalize, which may compose the cluster.
+        let normalized = match self.normalization() {
+            Ok(Some(form)) => Cow::Owned(form.apply(chars)),
+            _ => Cow::Borrowed(chars.as_str()),
+        };
+        if matches!(count_mode, Some(CountMode::Chars)) {
+            if let Some(cluster) = normalized
+                .graphemes(true)
+                .find(|g| g.chars().nth(1).is_some())
+            {
+                lint.warn(
+                    "count_mode",
+                    LintKind::Suspicious,
+                    format!(
+                        "search_characters has {:?}, several characters that count_mode = \"chars\" \
+                         matches one at a time; use count_mode = \"graphemes\" to match it whole",
+                        cluster
+                    ),
+                );
+            }
+        }
+    }
+
+    /// `search_characters` with its classes and ranges expanded.
+    fn search_set(&self, syntax: Syntax) -> Result<SearchSet> {
+        SearchSet::parse(&self.search_characters, syntax, !self.case_sensitive).map_err(
+            |(part, reason)| {
+                PluginError::config("search_characters is not a valid character set")
+                    .with_detail("field", "search_characters")
+                    .with_detail("value", part)
+                    .with_detail("reason", reason)
+            },
+        )
+    }
+
+    /// The named `search_sets` in name order, parsed like
+    /// `search_characters`; `None` when the key is absent.
+    fn search_sets(&self, syntax: Syntax) -> Result<Option<Vec<(String, SearchSet)>>> {
+        let Some(sets) = &self.search_sets else {
+            return Ok(None);
+        };
+        if self.search_pattern.is_some() {
+            return Err(
+                PluginError::config("search_sets cannot be combined with search_pattern")
+                    .with_detail("field", "search_sets"),
+            );
+        }
+        if sets.is_empty() {
+            return Err(
+                PluginError::config("search_sets must name at least one set")
+                    .with_detail("field", "search_sets"),
+            );
+        }
+        let mut parsed = Vec::with_capacity(sets.len());
+        for (name, spec) in sets {
+            let spec = spec.as_str().filter(|s| !s.is_empty()).ok_or_else(|| {
+                PluginError::config("search_sets values must be non-empty strings")
+                    .with_detail("field", "search_sets")
+                    .with_detail("set", name.as_str())
+            })?;
+            let set = SearchSet::parse(spec, syntax, !self.case_sensitive).map_err(
+                |(part, reason)| {
+                    PluginError::config("search_sets has an invalid character set")
+                        .with_detail("field", "search_sets")
+                        .with_detail("set", name.as_str())
+                        .with_detail("value", part)
+                        .with_detail("reason", reason)
+                },
+            )?;
+            parsed.push((name.clone(), set));
+        }
+        Ok(Some(parsed))
+    }
+
+    /// Checks `report_version` and `max_positions`; returns whether the
+    /// caller asked for version 2.
+    fn wants_v2(&self) -> Result<bool> {
+        if !REPORT_VERSIONS.contains(&self.report_version) {
+            return Err(PluginError::config("report_version is not supported")
+                .with_detail("field", "report_version")
+                .with_detail("value", self.report_version)
+                .with_detail("supported", REPORT_VERSIONS.to_vec()));
+        }
+        if self.max_positions < 0 {
+            return Err(PluginError::config("max_positions cannot be negative")
+                .with_detail("field", "max_positions")
+                .with_detail("value", self.max_positions));
+        }
+        Ok(self.report_version == 2)
+    }
+
+    /// Whether to count the characters outside the set. A pattern or
+    /// several sets have no single set to invert.
+    fn invert(&self) -> Result<bool> {
+        let other = if !self.invert {
+            return Ok(false);
+        } else if self.search_pattern.is_some() {
+            "search_pattern"
+        } else if self.search_sets.is_some() {
+            "search_sets"
+        } else {
+            return Ok(true);
+        };
+        Err(
+            PluginError::config(format!("invert cannot be combined with {}", other))
+                .with_detail("field", "invert"),
+        )
+    }
+
+    /// The parsed `match_description`; `None` when the route has none.
+    fn description(&self) -> Result<Option<Description>> {
+        let Some(template) = &self.match_description else {
+            return Ok(None);
+        };
+        Description::parse(template).map(Some).map_err(|reason| {
+            PluginError::config("match_description is not a valid template")
+                .with_detail("field", "match_description")
+                .with_detail("value", template.as_str())
+                .with_detail("reason", reason)
+                .with_detail("placeholders", description::PLACEHOLDERS.to_vec())
+        })
+    }
+
+    /// The largest body accepted, in bytes as sent; `None` is no limit.
+    fn max_body_bytes(&self) -> Result<Option<u64>> {
+        match self.max_body_bytes {
+            Some(n) if n < 1 => Err(PluginError::config("max_body_bytes must be at least 1")
+                .with_detail("field", "max_body_bytes")
+                .with_detail("value", n)),
+            n => Ok(n.map(|n| n as u64)),
+        }
+    }
+
+    /// The whitespace clean-up to apply after `normalize`; `None` when
+    /// `preprocess` lists no step. A base64 body is binary, with no
+    /// whitespace to clean up.
+    fn preprocess(&self) -> Result<Option<Preprocess>> {
+        let Some(names) = &self.preprocess else {
+            return Ok(None);
+        };
+        let steps = Preprocess::parse(names).map_err(|name| {
+            PluginError::config("preprocess lists an unknown step")
+                .with_detail("field", "preprocess")
+                .with_detail("value", name)
+                .with_detail("known", preprocess::STEPS.to_vec())
+        })?;
+        if steps.is_some() && self.body_encoding == "base64" {
+            return Err(
+                PluginError::config("preprocess does not apply to a base64 body")
+                    .with_detail("field", "preprocess"),
+            );
+        }
+        Ok(steps)
+    }
+
+    /// Whether to strip markup first. A base64 body is binary, not HTML.
+    fn strip_html(&self) -> Result<bool> {
+        if self.strip_html && self.body_encoding == "base64" {
+            return Err(
+                PluginError::config("strip_html does not apply to a base64 body")
+                    .with_detail("field", "strip_html"),
+            );
+        }
+        Ok(self.strip_html)
+    }
+
+    /// An empty tally for the report `wants_v2` picked; checks `top_n`.
+    fn tally(&self, v2: bool) -> Result<Tally> {
+        let top_n = match self.top_n {
+            Some(n) if n < 1 => {
+                return Err(PluginError::config("top_n must be at least 1")
+                    .with_detail("field", "top_n")
+                    .with_detail("value", n))
+            }
+            Some(n) => usize::try_from(n).unwrap_or(usize::MAX),
+            None => 0,
+        };
+        Ok(Tally {
+            stats: self.include_stats,
+            locate: v2 || self.include_positions,
+            ..Tally::new(
+                self.include_histogram,
+                top_n,
+                v2,
+                self.max_positions as usize,
+            )
+        })
+    }
+}
+
+/// Upper bound on a compiled `search_pattern`; a larger pattern (such as a
+/// big counted repetition) is a config error rather than an allocation that
+/// exhausts the instance's memory.
+const MAX_PATTERN_BYTES: usize = 1 << 20;
+
+/// Accumulates matches. Per-character counts are kept for a version 2
+/// report, a histogram or `top_n`; unit totals for version 2 or
+/// `include_stats`, and positions for version 2 or `include_positions`.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Tally {
+    per_key: bool,
+    histogram: bool,
+    /// How many of the most frequent keys to report; 0 for none.
+    top_n: usize,
+    detailed: bool,
+    /// Whether `include_stats` asked for totals and ratios.
+    stats: bool,
+    /// Whether the first `max_positions` match positions are kept.
+    locate: bool,
+    max_positions: usize,
+    count: usize,
+    units: usize,
+    counts: BTreeMap<String, i32>,
+    positions: Vec<Position>,
+    /// Matches per search set, indexed like the sets passed to the counter.
+    per_set: Vec<i32>,
+}
+
+impl Tally {
+    fn new(histogram: bool, top_n: usize, detailed: bool, max_positions: usize) -> Tally {
+        Tally {
+            per_key: histogram || top_n > 0 || detailed,
+            histogram,
+            top_n,
+            detailed,
+            locate: detailed,
+            max_positions,
+            ..Tally::default()
+        }
+    }
+
+    /// Whether the units scanned are counted.
+    fn counts_units(&self) -> bool {
+        self.detailed || self.stats
+    }
+
+    /// `n` as a fraction of the units scanned, 0 when there were none.
+    fn ratio(&self, n: usize) -> f64 {
+        if self.units == 0 {
+            0.0
+        } else {
+            n as f64 / self.units as f64
+        }
+    }
+
+    /// Records that a match is in the `set`th search set. A match can be in
+    /// several sets; `hit` counts it once.
+    fn set_hit(&mut self, set: usize) {
+        if set >= self.per_set.len() {
+            self.per_set.resize(set + 1, 0);
+        }
+        self.per_set[set] += 1;
+    }
+
+    /// Records a match of `key` at `start..end` of the decoded body.
+    fn hit(&mut self, key: &str, start: usize, end: usize) {
+        self.count += 1;
+        if self.per_key {
+            match self.counts.get_mut(key) {
+                Some(n) => *n += 1,
+                None => {
+                    self.counts.insert(key.to_string(), 1);
+                }
+            }
+        }
+        if self.locate && self.positions.len() < self.max_positions {
+            self.positions.push(Position {
+                start: start as u64,
+                end: end as u64,
+            });
+        }
+    }
+
+    /// The `top_n` keys with the most matches, most first; ties in key
+    /// order, so the list is the same on every call.
+    fn top(&self) -> Vec<TopCharacter> {
+        let mut keys: Vec<(&String, &i32)> = self.counts.iter().collect();
+        keys.sort_by(|a, b| b.1.cmp(a.1));
+        keys.truncate(self.top_n);
+        keys.into_iter()
+            .map(|(character, &count)| TopCharacter {
+                character: character.clone(),
+                count,
+            })
+            .collect()
+    }
+
+    /// `set_names` labels `per_set` when the sets were named.
+    fn into_report(
+        mut self,
+        v2: bool,
+        characters: String,
+        pattern: Option<String>,
+        set_names: Option<Vec<String>>,
+        bytes: usize,
+    ) -> Report {
+        let count = self.count as i32;
+        let top = (self.top_n > 0).then(|| self.top());
+        let density = self.ratio(self.count);
+        if let Some(names) = &set_names {
+            self.per_set.resize(names.len(), 0);
+        }
+        let set_percentages = set_names.as_ref().filter(|_| self.stats).map(|names| {
+            names
+                .iter()
+                .zip(&self.per_set)
+                .map(|(name, &n)| (name.clone(), self.ratio(n as usize) * 100.0))
+                .collect()
+        });
+        let sets = set_names.map(|names| names.into_iter().zip(self.per_set).collect());
+        let positions_truncated = self.count > self.positions.len();
+        if !v2 {
+            return Report::V1(CharacterReport {
+                count,
+                characters,
+                pattern,
+                histogram: self.histogram.then_some(self.counts),
+                sets,
+                top,
+                total_chars: self.stats.then_some(self.units as u64),
+                match_ratio: self.stats.then_some(density),
+                set_percentages,
+                positions: self.locate.then_some(self.positions),
+                positions_truncated: self.locate.then_some(positions_truncated),
+                description: None,
+            });
+        }
+        Report::V2(CharacterReportV2 {
+            report_version: 2,
+            count,
+            characters,
+            pattern,
+            counts: self.counts,
+            sets,
+            top,
+            set_percentages,
+            stats: ReportStats {
+                bytes: bytes as u64,
+                units: self.units as u64,
+                density,
+            },
+            positions_truncated,
+            positions: self.positions,
+            description: None,
+        })
+    }
+}
+
+/// Counts and renders the report in the format the `Accept` header asks
+/// for (see `render`).
+#[firelynx_plugin]
+fn count_characters(request: Request, static_data: StaticData) -> Result<Output<Report>> {
+    let config = Config::load(&static_data)?;
+    check_body_size(request.body.encoded_len() as u64, config.max_body_bytes()?)?;
+    let format = Format::negotiate(&request)?;
+    format.render(report(&request, config)?)
+}
+
+fn report(request: &Request, config: Config) -> Result<Report> {
+    let selected;
+    let body = match config.source()?.select(request) {
+        Some(text) => {
+            selected = Body::from(text);
+            &selected
+        }
+        None => &request.body,
     };
+    if config.search_pattern.is_some() {
+        return count_pattern(body, config);
+    }
+    let base64 = config.check_bytes(config.count_mode()?, config.normalization()?)?;
+    let count = CharacterCount::new(config)?;
+    if base64 {
+        let bytes = STANDARD
+            .decode(body.text().trim())
+            .map_err(|e| PluginError::invalid_input(format!("Invalid base64 body: {}", e)))?;
+        return Ok(count.finish_bytes(&bytes));
+    }
+    Ok(count.finish(body))
+}
+
+/// Fails with `PAYLOAD_TOO_LARGE` when `size` bytes of body, as sent, are
+/// more than `limit`. The size is known before the body is decoded, so an
+/// oversized body is turned away without being read.
+fn check_body_size(size: u64, limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(max) if size > max => Err(PluginError::too_large(format!(
+            "The body is larger than max_body_bytes ({} bytes)",
+            max
+        ))
+        .with_detail("max_body_bytes", max)
+        .with_detail("body_bytes", size)),
+        _ => Ok(()),
+    }
+}
+
+/// Counts `search_pattern` matches. Matches can span any chunk boundary, so
+/// the pattern runs over the whole text.
+fn count_pattern(body: &Body, config: Config) -> Result<Report> {
+    let count_mode = config.count_mode()?;
+    let v2 = config.wants_v2()?;
+    let normalization = config.normalization()?;
+    let description = config.description()?;
+    // Rejects search_sets and invert alongside the pattern.
+    config.search_sets(config.syntax()?)?;
+    config.invert()?;
+    let mut tally = config.tally(v2)?;
+    let pattern = config
+        .pattern(&count_mode, normalization)?
+        .expect("search_pattern is set");
+
+    let preprocess = config.preprocess()?;
+    let mut body = body.text();
+    if config.strip_html()? {
+        body = Cow::Owned(html::strip(&body));
+    }
+    if let Some(form) = normalization {
+        body = Cow::Owned(form.apply(&body));
+    }
+    if let Some(steps) = preprocess {
+        body = Cow::Owned(steps.apply(&body));
+    }
+    // Empty matches (e.g. from `a*`) are not occurrences.
+    let key = config.search_pattern.as_deref().unwrap_or_default();
+    for m in pattern.find_iter(&body).filter(|m| !m.is_empty()) {
+        tally.hit(key, m.start(), m.end());
+    }
+    if tally.counts_units() {
+        tally.units = body.chars().count();
+    }
+    let bytes = body.len();
+    let mut report = tally.into_report(v2, String::new(), config.search_pattern, None, bytes);
+    report.describe(description.as_ref());
+    Ok(report)
+}
+
+/// Name of the extism var holding the count `CountCharactersBegin` opened.
+const COUNT_VAR: &str = "char_counter:count";
+
+/// How far a chunked count has got. Matches `CountProgress` in `schema.yaml`.
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CountProgress {
+    /// Matches so far.
+    pub count: i32,
+    /// Decoded bytes scanned so far. Text the next chunk could still change
+    /// (the end of a grapheme cluster, or of a normalized sequence) is
+    /// scanned with that chunk.
+    pub bytes: u64,
+}
+
+/// Starts counting a body too large for one input buffer, which the host
+/// sends as this call's body followed by `CountCharactersChunk` calls and a
+/// final `CountCharactersEnd`. The route's `static_data` is read here and
+/// holds for the whole body. A count still open on this instance is
+/// dropped.
+#[firelynx_plugin]
+fn count_characters_begin(request: Request, static_data: StaticData) -> Result<CountProgress> {
+    CharacterCount::discard()?;
+    let config = Config::load(&static_data)?;
+    if config.search_pattern.is_some() {
+        return Err(
+            PluginError::config("search_pattern cannot be counted in chunks")
+                .with_detail("field", "search_pattern"),
+        );
+    }
+    if !matches!(config.source()?, Source::Body) {
+        return Err(
+            PluginError::config("only the body can be counted in chunks")
+                .with_detail("field", "source")
+                .with_detail("value", config.source.as_str()),
+        );
+    }
+    if config.body_encoding != "text" {
+        return Err(
+            PluginError::config("only a text body can be counted in chunks")
+                .with_detail("field", "body_encoding")
+                .with_detail("value", config.body_encoding.as_str()),
+        );
+    }
+    let mut count = CharacterCount::new(config)?;
+    count.receive(&request.body)?;
+    count.push(&request.body);
+    count.save()?;
+    Ok(count.progress())
+}
+
+/// Counts the next piece of the body opened by `CountCharactersBegin`. A
+/// piece that takes the body past `max_body_bytes` closes the count.
+#[firelynx_plugin]
+fn count_characters_chunk(request: Request, _static_data: StaticData) -> Result<CountProgress> {
+    let mut count = CharacterCount::load()?;
+    if let Err(e) = count.receive(&request.body) {
+        CharacterCount::discard()?;
+        return Err(e);
+    }
+    count.push(&request.body);
+    count.save()?;
+    Ok(count.progress())
+}
+
+/// Counts the last piece of the body and returns the report
+/// `CountCharacters` would have for the whole body. The count is closed.
+#[firelynx_plugin]
+fn count_characters_end(request: Request, _static_data: StaticData) -> Result<Report> {
+    let mut count = CharacterCount::load()?;
+    CharacterCount::discard()?;
+    count.receive(&request.body)?;
+    Ok(count.finish(&request.body))
+}
+
+/// Checks a `CountCharacters` route's `static_data` without counting
+/// anything, for the firelynx CLI to surface during config validation.
+/// Problems that would fail the route are `errors`; config that works but
+/// probably is not what was meant (unused keys, settings that have no
+/// effect) is `warnings`. The request is ignored.
+#[firelynx_plugin]
+fn lint_config(_request: Request, static_data: StaticData) -> Result<LintReport> {
+    Ok(Config::lint(&static_data))
+}
+
+/// A count of `search_characters` or `search_sets` matches, fed the body
+/// piece by piece. `CountCharacters` makes one per call; the chunked exports
+/// keep one in an extism var between calls.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CharacterCount {
+    /// Normalized and case-folded like the body.
+    targets: Vec<SearchSet>,
+    set_names: Option<Vec<String>>,
+    characters: String,
+    count_mode: CountMode,
+    /// How to fold case; `None` when `case_sensitive`.
+    fold: Option<Locale>,
+    /// Count what is outside `targets` instead.
+    invert: bool,
+    v2: bool,
+    max_body_bytes: Option<u64>,
+    /// Body bytes received so far, as sent.
+    received: u64,
+    html: Option<HtmlStripper>,
+    normalizer: Option<Normalizer>,
+    preprocessor: Option<Preprocessor>,
+    description: Option<Description>,
+    progress: Progress,
+    tally: Tally,
+}
+
+impl CharacterCount {
+    /// Checks `config` and sets up the count. A `search_pattern` is counted
+    /// by `count_pattern` instead.
+    fn new(config: Config) -> Result<CharacterCount> {
+        let count_mode = config.count_mode()?;
+        let v2 = config.wants_v2()?;
+        let normalization = config.normalization()?;
+        let syntax = config.syntax()?;
+        config.check_bytes(count_mode, normalization)?;
+        let fold = config.fold()?;
+        let invert = config.invert()?;
+        let max_body_bytes = config.max_body_bytes()?;
+        let preprocess = config.preprocess()?;
+        let strip_html = config.strip_html()?;
+        let description = config.description()?;
+        let (mut targets, set_names, characters) = match config.search_sets(syntax)? {
+            Some(sets) => {
+                let (names, targets) = sets.into_iter().unzip();
+                (targets, Some(names), String::new())
+            }
+            None => (
+                vec![config.search_set(syntax)?],
+                None,
+                config.search_characters.clone(),
+            ),
+        };
+        if matches!(count_mode, CountMode::Bytes) {
+            check_byte_literals(&targets, set_names.as_deref())?;
+        }
+        for target in &mut targets {
+            if let Some(form) = normalization {
+                target.literals = form.apply(&target.literals);
+            }
+            if let Some(locale) = fold {
+                target.literals = fold_text(&target.literals, locale);
+            }
+        }
+        Ok(CharacterCount {
+            targets,
+            set_names,
+            characters,
+            count_mode,
+            fold,
+            invert,
+            v2,
+            max_body_bytes,
+            received: 0,
+            html: strip_html.then(HtmlStripper::default),
+            normalizer: normalization.map(Normalizer::new),
+            preprocessor: preprocess.map(Preprocessor::new),
+            description,
+            progress: Progress::default(),
+            tally: config.tally(v2)?,
+        })
+    }
+
+    /// Adds `body` to the bytes received and checks the total against
+    /// `max_body_bytes`, for a body sent in pieces.
+    fn receive(&mut self, body: &Body) -> Result<()> {
+        self.received += body.encoded_len() as u64;
+        check_body_size(self.received, self.max_body_bytes)
+    }
+
+    /// Counts `body` as the next piece of the text, holding back what the
+    /// next piece could still change.
+    fn push(&mut self, body: &Body) {
+        self.scan(body, false);
+    }
+
+    /// Counts `body` as the last piece of the text and reports.
+    fn finish(mut self, body: &Body) -> Report {
+        let bytes = self.scan(body, true);
+        let mut report =
+            self.tally
+                .into_report(self.v2, self.characters, None, self.set_names, bytes);
+        report.describe(self.description.as_ref());
+        report
+    }
+
+    /// Counts `bytes`, decoded binary, as the whole text and reports.
+    fn finish_bytes(mut self, bytes: &[u8]) -> Report {
+        let matcher = Matcher::new(&self.targets, self.count_mode, self.fold, self.invert);
+        matcher.push_bytes(bytes, &mut self.progress, &mut self.tally);
+        let mut report =
+            self.tally
+                .into_report(self.v2, self.characters, None, self.set_names, bytes.len());
+        report.describe(self.description.as_ref());
+        report
+    }
 
-    let target_chars = if case_sensitive {
-        matching_chars.to_string()
+    /// Reads `body` in bounded chunks (borrowed from the input buffer unless
+    /// it has escapes, or is stripped, normalized or preprocessed), so
+    /// counting never copies the whole body. Returns the decoded bytes scanned so far.
+    fn scan(&mut self, body: &Body, last: bool) -> usize {
+        let matcher = Matcher::new(&self.targets, self.count_mode, self.fold, self.invert);
+        let mut chunks = NormalizedChunks::resume(
+            body.chunks(DEFAULT_CHUNK_BYTES),
+            self.html.take(),
+            self.normalizer.take(),
+            last,
+        );
+        let mut preprocessed = String::new();
+        while let Some(chunk) = chunks.next_chunk() {
+            let chunk = match &mut self.preprocessor {
+                Some(preprocessor) => {
+                    preprocessed.clear();
+                    preprocessor.push(chunk, &mut preprocessed);
+                    &preprocessed
+                }
+                None => chunk,
+            };
+            matcher.push(chunk, &mut self.progress, &mut self.tally);
+        }
+        (self.html, self.normalizer) = chunks.into_parts();
+        if last {
+            if let Some(preprocessor) = &mut self.preprocessor {
+                preprocessed.clear();
+                preprocessor.finish(&mut preprocessed);
+                matcher.push(&preprocessed, &mut self.progress, &mut self.tally);
+            }
+            matcher.finish(&mut self.progress, &mut self.tally);
+        }
+        self.progress.offset
+    }
+
+    fn progress(&self) -> CountProgress {
+        CountProgress {
+            count: self.tally.count as i32,
+            bytes: self.progress.offset as u64,
+        }
+    }
+
+    fn load() -> Result<CharacterCount> {
+        match extism_pdk::var::get::<Json<CharacterCount>>(COUNT_VAR) {
+            Ok(Some(Json(count))) => Ok(count),
+            Ok(None) => Err(PluginError::invalid_input(
+                "No chunked count is open; call CountCharactersBegin first",
+            )),
+            Err(e) => Err(PluginError::internal(format!(
+                "Failed to load the chunked count: {}",
+                e
+            ))),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        extism_pdk::var::set(COUNT_VAR, Json(self))
+            .map_err(|e| PluginError::internal(format!("Failed to save the chunked count: {}", e)))
+    }
+
+    fn discard() -> Result<()> {
+        extism_pdk::var::remove(COUNT_VAR).map_err(|e| {
+            PluginError::internal(format!("Failed to discard the chunked count: {}", e))
+        })
+    }
+}
+
+/// Where counting stopped, so it can go on with more of the body.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Progress {
+    /// Decoded bytes scanned; the next match position is relative to this.
+    offset: usize,
+    /// Graphemes mode: the last cluster, which more text could extend.
+    pending: String,
+}
+
+/// Matches body text against the search sets, each set's literals in a
+/// HashSet for O(1) lookups before its classes.
+struct Matcher<'t> {
+    targets: &'t [SearchSet],
+    chars: Vec<HashSet<char>>,
+    graphemes: Vec<HashSet<&'t str>>,
+    count_mode: CountMode,
+    fold: Option<Locale>,
+    invert: bool,
+}
+
+impl<'t> Matcher<'t> {
+    fn new(
+        targets: &'t [SearchSet],
+        count_mode: CountMode,
+        fold: Option<Locale>,
+        invert: bool,
+    ) -> Matcher<'t> {
+        let (chars, graphemes) = match count_mode {
+            CountMode::Chars | CountMode::Bytes => (
+                targets
+                    .iter()
+                    .map(|t| t.literals.chars().collect())
+                    .collect(),
+                Vec::new(),
+            ),
+            CountMode::Graphemes => (
+                Vec::new(),
+                targets
+                    .iter()
+                    .map(|t| t.literals.graphemes(true).collect())
+                    .collect(),
+            ),
+        };
+        Matcher {
+            targets,
+            chars,
+            graphemes,
+            count_mode,
+            fold,
+            invert,
+        }
+    }
+
+    /// Counts `chunk`, the text after `progress`.
+    fn push(&self, chunk: &str, progress: &mut Progress, tally: &mut Tally) {
+        match self.count_mode {
+            CountMode::Chars => {
+                let mut key = [0; 4];
+                for (i, c) in chunk.char_indices() {
+                    let (start, end) = (progress.offset + i, progress.offset + i + c.len_utf8());
+                    let folded = self.fold.map_or(c, |locale| fold_simple(c, locale));
+                    if self.wanted(c, folded, tally) != self.invert {
+                        tally.hit(folded.encode_utf8(&mut key), start, end);
+                    }
+                }
+                if tally.counts_units() {
+                    tally.units += chunk.chars().count();
+                }
+                progress.offset += chunk.len();
+            }
+            CountMode::Bytes => self.push_bytes(chunk.as_bytes(), progress, tally),
+            CountMode::Graphemes => {
+                // A cluster can straddle a chunk boundary, so the last
+                // cluster is held back and read again with the next chunk;
+                // the boundaries before it cannot change when more text
+                // follows.
+                progress.pending.push_str(chunk);
+                let last = progress
+                    .pending
+                    .grapheme_indices(true)
+                    .next_back()
+                    .map_or(0, |(i, _)| i);
+                for (i, g) in progress.pending[..last].grapheme_indices(true) {
+                    self.visit(g, progress.offset + i, tally);
+                }
+                progress.pending.drain(..last);
+                progress.offset += last;
+            }
+        }
+    }
+
+    /// Counts `bytes`, the bytes after `progress`.
+    fn push_bytes(&self, bytes: &[u8], progress: &mut Progress, tally: &mut Tally) {
+        for (i, &b) in bytes.iter().enumerate() {
+            let c = char::from(b);
+            let folded = self.fold.map_or(c, |locale| fold_simple(c, locale));
+            if self.wanted(c, folded, tally) != self.invert {
+                let start = progress.offset + i;
+                tally.hit(&byte_key(b, folded), start, start + 1);
+            }
+        }
+        if tally.counts_units() {
+            tally.units += bytes.len();
+        }
+        progress.offset += bytes.len();
+    }
+
+    /// Counts what `push` held back, at the end of the text.
+    fn finish(&self, progress: &mut Progress, tally: &mut Tally) {
+        let pending = std::mem::take(&mut progress.pending);
+        for (i, g) in pending.grapheme_indices(true) {
+            self.visit(g, progress.offset + i, tally);
+        }
+        progress.offset += pending.len();
+    }
+
+    /// Whether `c` is in any set, recording a hit for each set it is in.
+    /// Literals are compared `folded`; classes already hold both cases when
+    /// case-insensitive.
+    fn wanted(&self, c: char, folded: char, tally: &mut Tally) -> bool {
+        let mut found = false;
+        for (i, (target, literals)) in self.targets.iter().zip(&self.chars).enumerate() {
+            if literals.contains(&folded) || target.in_class(c) {
+                tally.set_hit(i);
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// Counts cluster `g` at `start` if it equals one of the literal
+    /// clusters of a set or starts with a character in one of its classes
+    /// (or, with `invert`, if it does neither).
+    /// Clusters are compared as written, so unless `normalize` is set a
+    /// precomposed "é" and "e" plus U+0301 are different targets.
+    fn visit(&self, g: &str, start: usize, tally: &mut Tally) {
+        if tally.counts_units() {
+            tally.units += 1;
+        }
+        let key = match self.fold {
+            Some(locale) => Cow::Owned(fold_text(g, locale)),
+            None => Cow::Borrowed(g),
+        };
+        // A cluster is in a class when its first character is, so "e" plus
+        // an accent is a letter.
+        let first = g.chars().next();
+        let mut found = false;
+        for (i, (target, literals)) in self.targets.iter().zip(&self.graphemes).enumerate() {
+            if literals.contains(key.as_ref()) || first.is_some_and(|c| target.in_class(c)) {
+                tally.set_hit(i);
+                found = true;
+            }
+        }
+        if found != self.invert {
+            tally.hit(&key, start, start + g.len());
+        }
+    }
+}
+
+/// A matched byte's key: the (folded) character for printable ASCII,
+/// `0x..` for any other byte.
+fn byte_key(b: u8, folded: char) -> String {
+    if b.is_ascii_graphic() || b == b' ' {
+        folded.to_string()
     } else {
-        matching_chars.to_lowercase()
+        format!("0x{:02x}", b)
+    }
+}
+
+/// In `count_mode = "bytes"` a literal stands for its byte value, so it
+/// must be one of the first 256 code points.
+fn check_byte_literals(targets: &[SearchSet], set_names: Option<&[String]>) -> Result<()> {
+    for (i, target) in targets.iter().enumerate() {
+        let Some(c) = target.literals.chars().find(|&c| u32::from(c) > 0xff) else {
+            continue;
+        };
+        let mut err = PluginError::config(
+            "with count_mode bytes, literal search characters must be U+0000 to U+00FF",
+        )
+        .with_detail("value", c.to_string());
+        err = match set_names {
+            Some(names) => err
+                .with_detail("field", "search_sets")
+                .with_detail("set", names[i].as_str()),
+            None => err.with_detail("field", "search_characters"),
+        };
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// `text` case-folded one character at a time, so it keeps its length in
+/// characters.
+fn fold_text(text: &str, locale: Locale) -> String {
+    text.chars().map(|c| fold_simple(c, locale)).collect()
+}
+
+/// The result of `CountWords`. Matches `WordReport` in `schema.yaml`.
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WordReport {
+    pub count: i32,
+    /// "unicode" or "delimiters", the segmentation that was used.
+    pub segmentation: String,
+}
+
+#[derive(serde::Deserialize)]
+struct WordsConfig {
+    word_delimiters: Option<String>,
+}
+
+impl ConfigSchema for WordsConfig {
+    const FIELDS: &'static [Field] = &[Field::string("word_delimiters").non_empty()];
+}
+
+/// Counts words with Unicode word boundaries, or as runs of characters
+/// outside `word_delimiters` when that is set.
+#[firelynx_plugin]
+fn count_words(request: Request, static_data: StaticData) -> Result<WordReport> {
+    let config = WordsConfig::from_static_data(&static_data)?;
+    let (words, segmentation) = match config.word_delimiters {
+        Some(delimiters) => (Words::Delimited(delimiters.chars().collect()), "delimiters"),
+        None => (Words::Unicode, "unicode"),
     };
+    let mut counter = WordCounter::new(words);
+    let mut chunks = request.body.chunks(DEFAULT_CHUNK_BYTES);
+    while let Some(chunk) = chunks.next_chunk() {
+        counter.push(chunk);
+    }
+    Ok(WordReport {
+        count: counter.finish() as i32,
+        segmentation: segmentation.to_string(),
+    })
+}
 
-    // Count matching characters using HashSet for O(1) lookups
-    let target_set: std::collections::HashSet<char> = target_chars.chars().collect();
-    let count = search_text
-        .chars()
-        .filter(|c| target_set.contains(c))
-        .count() as i32;
+/// The result of `CountLines`. Matches `LineReport` in `schema.yaml`.
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LineReport {
+    pub count: i32,
+    /// Lines that are empty or only whitespace.
+    pub blank: i32,
+}
 
-    Ok(types::CharacterReport {
-        count,
-        characters: matching_chars.to_string(),
+#[derive(serde::Deserialize)]
+struct LinesConfig {
+    line_terminator: Option<String>,
+}
+
+impl ConfigSchema for LinesConfig {
+    const FIELDS: &'static [Field] = &[Field::string("line_terminator").non_empty()];
+}
+
+/// Counts lines ended by "\n", "\r\n" or "\r", or by `line_terminator` when
+/// that is set.
+#[firelynx_plugin]
+fn count_lines(request: Request, static_data: StaticData) -> Result<LineReport> {
+    let config = LinesConfig::from_static_data(&static_data)?;
+    let mut counter = LineCounter::new(config.line_terminator);
+    let mut chunks = request.body.chunks(DEFAULT_CHUNK_BYTES);
+    while let Some(chunk) = chunks.next_chunk() {
+        counter.push(chunk);
+    }
+    let (count, blank) = counter.finish();
+    Ok(LineReport {
+        count: count as i32,
+        blank: blank as i32,
     })
 }
@@ -0,0 +1,230 @@
+//! Unicode normalization of the body, applied chunk by chunk.
+//!
+//! Normalizing changes how characters are spelled, not what they are: "é"
+//! can be one code point (U+00E9) or "e" plus a combining acute (U+0301).
+//! Without a shared form, a search for one spelling silently misses the
+//! other.
+//!
+//! `strip_diacritics` goes further and removes the marks, so "é" in either
+//! spelling is "e". That is what a reader of French or Spanish expects a
+//! vowel count to do. It removes every combining mark, including the vowel
+//! signs of scripts such as Devanagari, so it suits Latin, Greek and
+//! Cyrillic text.
+
+use firelynx_pdk::body::ChunkedReader;
+use unicode_normalization::char::{canonical_combining_class, is_combining_mark};
+use unicode_normalization::{
+    is_nfc_quick, is_nfd_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization,
+};
+
+use crate::html::HtmlStripper;
+
+/// The `normalize` forms, per UAX #15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Normalization {
+    /// Canonical composition: "e" + U+0301 becomes "é".
+    Nfc,
+    /// Canonical decomposition: "é" becomes "e" + U+0301.
+    Nfd,
+    /// Compatibility composition: also folds variants such as "ﬁ" to "fi"
+    /// and fullwidth "Ａ" to "A".
+    Nfkc,
+}
+
+impl Normalization {
+    pub fn parse(name: &str) -> Option<Normalization> {
+        match name {
+            "nfc" => Some(Normalization::Nfc),
+            "nfd" => Some(Normalization::Nfd),
+            "nfkc" => Some(Normalization::Nfkc),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        self.extend(text, &mut out);
+        out
+    }
+
+    fn extend(self, text: &str, out: &mut String) {
+        match self {
+            Normalization::Nfc => out.extend(text.nfc()),
+            Normalization::Nfd => out.extend(text.nfd()),
+            Normalization::Nfkc => out.extend(text.nfkc()),
+        }
+    }
+
+    /// Whether text can be split just before `c` and each side normalized
+    /// on its own: `c` is a starter that neither reorders nor composes with
+    /// what precedes it (UAX #15, "Stable Code Points").
+    fn is_stable(self, c: char) -> bool {
+        if c.is_ascii() {
+            return true;
+        }
+        if canonical_combining_class(c) != 0 {
+            return false;
+        }
+        let c = std::iter::once(c);
+        let quick = match self {
+            Normalization::Nfc => is_nfc_quick(c),
+            Normalization::Nfd => is_nfd_quick(c),
+            Normalization::Nfkc => is_nfkc_quick(c),
+        };
+        quick == IsNormalized::Yes
+    }
+}
+
+/// What text is put through before matching: a `normalize` form, and the
+/// combining marks removed with `strip_diacritics`. At least one is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TextForm {
+    pub form: Option<Normalization>,
+    pub strip_diacritics: bool,
+}
+
+impl TextForm {
+    pub fn apply(self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        self.extend(text, &mut out);
+        out
+    }
+
+    /// Stripping decomposes the text, drops the marks and composes it again
+    /// in the form asked for, NFC without one, so that Hangul syllables,
+    /// which decomposing splits into letters, come out whole.
+    fn extend(self, text: &str, out: &mut String) {
+        let unmarked = |c: &char| !is_combining_mark(*c);
+        match (self.form, self.strip_diacritics) {
+            (Some(form), false) => form.extend(text, out),
+            (None, false) => out.push_str(text),
+            (Some(Normalization::Nfd), true) => out.extend(text.nfd().filter(unmarked)),
+            (Some(Normalization::Nfkc), true) => out.extend(text.nfkd().filter(unmarked).nfkc()),
+            (Some(Normalization::Nfc) | None, true) => {
+                out.extend(text.nfd().filter(unmarked).nfc())
+            }
+        }
+    }
+
+    fn is_stable(self, c: char) -> bool {
+        self.form.unwrap_or(Normalization::Nfc).is_stable(c)
+    }
+}
+
+/// Normalizes text that arrives in pieces. Each piece's output holds back
+/// the text after its last stable character, which the next piece could
+/// still combine with.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Normalizer {
+    form: TextForm,
+    pending: String,
+}
+
+impl Normalizer {
+    pub fn new(form: TextForm) -> Normalizer {
+        Normalizer {
+            form,
+            pending: String::new(),
+        }
+    }
+
+    /// Appends to `out` the normalized text that `text` settles.
+    pub fn push(&mut self, text: &str, out: &mut String) {
+        self.pending.push_str(text);
+        let form = self.form;
+        let split = self
+            .pending
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| form.is_stable(c))
+            .map_or(0, |(i, _)| i);
+        form.extend(&self.pending[..split], out);
+        self.pending.drain(..split);
+    }
+
+    /// Appends the normalized rest of the text to `out`.
+    pub fn finish(&mut self, out: &mut String) {
+        self.form.extend(&self.pending, out);
+        self.pending.clear();
+    }
+}
+
+/// A body's decoded chunks, with markup stripped (`strip_html`) and
+/// normalized when those are set.
+pub struct NormalizedChunks<'b> {
+    reader: ChunkedReader<'b>,
+    html: Option<HtmlStripper>,
+    normalizer: Option<Normalizer>,
+    /// Stripped text on its way to the normalizer.
+    stripped: String,
+    out: String,
+    done: bool,
+    /// Whether the body ends the text, so nothing is held back at its end.
+    last: bool,
+}
+
+impl<'b> NormalizedChunks<'b> {
+    /// Strips and normalizes with `html` and `normalizer`, which may have
+    /// seen the start of the text in an earlier body. Unless `last`, the end
+    /// of this body stays in them, and `into_parts` hands them back for the
+    /// next.
+    pub fn resume(
+        reader: ChunkedReader<'b>,
+        html: Option<HtmlStripper>,
+        normalizer: Option<Normalizer>,
+        last: bool,
+    ) -> NormalizedChunks<'b> {
+        NormalizedChunks {
+            reader,
+            html,
+            normalizer,
+            stripped: String::new(),
+            out: String::new(),
+            done: false,
+            last,
+        }
+    }
+
+    pub fn into_parts(self) -> (Option<HtmlStripper>, Option<Normalizer>) {
+        (self.html, self.normalizer)
+    }
+
+    pub fn next_chunk(&mut self) -> Option<&str> {
+        if self.html.is_none() && self.normalizer.is_none() {
+            return self.reader.next_chunk();
+        }
+        loop {
+            if self.done {
+                return None;
+            }
+            self.out.clear();
+            let chunk = self.reader.next_chunk();
+            self.done = chunk.is_none();
+            let text = match &mut self.html {
+                Some(html) => {
+                    self.stripped.clear();
+                    match chunk {
+                        Some(chunk) => html.push(chunk, &mut self.stripped),
+                        None if self.last => html.finish(&mut self.stripped),
+                        None => {}
+                    }
+                    self.stripped.as_str()
+                }
+                None => chunk.unwrap_or_default(),
+            };
+            match &mut self.normalizer {
+                Some(normalizer) => {
+                    normalizer.push(text, &mut self.out);
+                    if self.done && self.last {
+                        normalizer.finish(&mut self.out);
+                    }
+                }
+                None => self.out.push_str(text),
+            }
+            if !self.out.is_empty() {
+                break;
+            }
+        }
+        Some(&self.out)
+    }
+}
@@ -0,0 +1,94 @@
+//! Whitespace clean-up of the text before it is counted.
+//!
+//! Text submitted through a form often carries padding: a trailing newline,
+//! indentation, runs of spaces from a pasted block. `trim` drops the
+//! whitespace at the start and end, and `collapse_whitespace` turns every
+//! run of whitespace into one space, so padding neither matches a search
+//! for whitespace nor inflates `stats`.
+
+/// The `preprocess` step names.
+pub const STEPS: [&str; 2] = ["trim", "collapse_whitespace"];
+
+/// The steps a route asked for. Their order in the list does not matter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Preprocess {
+    pub trim: bool,
+    pub collapse_whitespace: bool,
+}
+
+impl Preprocess {
+    /// Reads the step names, returning the first unknown one as the error.
+    /// `None` when no step is listed.
+    pub fn parse(names: &[String]) -> Result<Option<Preprocess>, &str> {
+        let mut steps = Preprocess::default();
+        for name in names {
+            match name.as_str() {
+                "trim" => steps.trim = true,
+                "collapse_whitespace" => steps.collapse_whitespace = true,
+                other => return Err(other),
+            }
+        }
+        Ok((steps != Preprocess::default()).then_some(steps))
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        let mut preprocessor = Preprocessor::new(self);
+        let mut out = String::with_capacity(text.len());
+        preprocessor.push(text, &mut out);
+        preprocessor.finish(&mut out);
+        out
+    }
+}
+
+/// Preprocesses text that arrives in pieces. A run of whitespace is held
+/// back until the next character, since only that shows whether the run is
+/// trailing.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Preprocessor {
+    steps: Preprocess,
+    /// Whether anything other than whitespace has been seen.
+    started: bool,
+    /// The run of whitespace held back; a single space when collapsing.
+    pending: String,
+}
+
+impl Preprocessor {
+    pub fn new(steps: Preprocess) -> Preprocessor {
+        Preprocessor {
+            steps,
+            started: false,
+            pending: String::new(),
+        }
+    }
+
+    /// Appends to `out` the preprocessed text that `text` settles.
+    pub fn push(&mut self, text: &str, out: &mut String) {
+        for c in text.chars() {
+            if !c.is_whitespace() {
+                out.push_str(&self.pending);
+                self.pending.clear();
+                out.push(c);
+                self.started = true;
+                continue;
+            }
+            let leading = self.steps.trim && !self.started;
+            if leading || (self.steps.collapse_whitespace && !self.pending.is_empty()) {
+                continue;
+            }
+            self.pending.push(if self.steps.collapse_whitespace {
+                ' '
+            } else {
+                c
+            });
+        }
+    }
+
+    /// Appends the rest of the text to `out`: the whitespace held back,
+    /// unless it is trimmed.
+    pub fn finish(&mut self, out: &mut String) {
+        if !self.steps.trim {
+            out.push_str(&self.pending);
+        }
+        self.pending.clear();
+    }
+}
@@ -0,0 +1,143 @@
+//! The report rendered in the format the client's `Accept` header asks for.
+//!
+//! JSON is the report as the schema describes it and is picked when the
+//! client has no preference. Plain text and CSV carry the same numbers for
+//! people and spreadsheets: the total, then the per-character counts (a
+//! histogram, or version 2 `counts`) and the per-set counts when the report
+//! has them.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use firelynx_pdk::prelude::*;
+
+use crate::Report;
+
+/// Media types in server preference order; ties go to JSON.
+const OFFERS: [&str; 3] = ["application/json", "text/plain", "text/csv"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Text,
+    Csv,
+}
+
+impl Format {
+    /// The format for `request`'s `Accept` header (every value of it). No
+    /// acceptable format is `INVALID_INPUT` listing the ones there are.
+    pub fn negotiate(request: &Request) -> Result<Format> {
+        let header = request
+            .header_values("accept")
+            .map(|values| values.join(", "));
+        match negotiate(header.as_deref(), &OFFERS) {
+            Some("text/plain") => Ok(Format::Text),
+            Some("text/csv") => Ok(Format::Csv),
+            Some(_) => Ok(Format::Json),
+            None => Err(
+                PluginError::invalid_input("None of the report formats is acceptable")
+                    .with_detail("accept", header.unwrap_or_default())
+                    .with_detail("supported", OFFERS.to_vec()),
+            ),
+        }
+    }
+
+    /// The handler output for `report`: the report itself for JSON, which
+    /// the envelope codec encodes, or the rendered text written as-is.
+    pub fn render(self, report: Report) -> Result<Output<Report>> {
+        let text = match self {
+            Format::Json => return Ok(Output::Encoded(report)),
+            Format::Text => text(&report),
+            Format::Csv => csv(&report),
+        };
+        let mut out = OutputStream::with_capacity(text.len());
+        out.emit(text)?;
+        Ok(Output::Raw(out))
+    }
+}
+
+/// The numbers both text formats show, and the description plain text
+/// shows.
+struct Summary<'r> {
+    count: i32,
+    characters: &'r str,
+    pattern: Option<&'r str>,
+    description: Option<&'r str>,
+    per_key: Option<&'r BTreeMap<String, i32>>,
+    sets: Option<&'r BTreeMap<String, i32>>,
+}
+
+impl<'r> Summary<'r> {
+    fn of(report: &'r Report) -> Summary<'r> {
+        match report {
+            Report::V1(r) => Summary {
+                count: r.count,
+                characters: &r.characters,
+                pattern: r.pattern.as_deref(),
+                description: r.description.as_deref(),
+                per_key: r.histogram.as_ref(),
+                sets: r.sets.as_ref(),
+            },
+            Report::V2(r) => Summary {
+                count: r.count,
+                characters: &r.characters,
+                pattern: r.pattern.as_deref(),
+                description: r.description.as_deref(),
+                per_key: Some(&r.counts),
+                sets: r.sets.as_ref(),
+            },
+        }
+    }
+}
+
+/// One `name: value` line per number, with control characters in keys
+/// escaped so each entry stays on its line.
+pub fn text(report: &Report) -> String {
+    let summary = Summary::of(report);
+    let mut out = format!("count: {}\n", summary.count);
+    if let Some(pattern) = summary.pattern {
+        writeln!(out, "pattern: {}", pattern.escape_debug())
+            .expect("writing to a String cannot fail");
+    } else if !summary.characters.is_empty() {
+        writeln!(out, "characters: {}", summary.characters.escape_debug())
+            .expect("writing to a String cannot fail");
+    }
+    if let Some(description) = summary.description {
+        writeln!(out, "description: {}", description.escape_debug())
+            .expect("writing to a String cannot fail");
+    }
+    for (key, n) in summary.per_key.into_iter().flatten() {
+        writeln!(out, "character {}: {}", key.escape_debug(), n)
+            .expect("writing to a String cannot fail");
+    }
+    for (name, n) in summary.sets.into_iter().flatten() {
+        writeln!(out, "set {}: {}", name.escape_debug(), n)
+            .expect("writing to a String cannot fail");
+    }
+    out
+}
+
+/// RFC 4180 rows of `kind,key,count`: a `total` row, then a `character`
+/// row per counted character and a `set` row per named set.
+pub fn csv(report: &Report) -> String {
+    let summary = Summary::of(report);
+    let mut out = String::from("kind,key,count\r\n");
+    let total_key = summary.pattern.unwrap_or(summary.characters);
+    row(&mut out, "total", total_key, summary.count);
+    for (key, &n) in summary.per_key.into_iter().flatten() {
+        row(&mut out, "character", key, n);
+    }
+    for (name, &n) in summary.sets.into_iter().flatten() {
+        row(&mut out, "set", name, n);
+    }
+    out
+}
+
+fn row(out: &mut String, kind: &str, key: &str, count: i32) {
+    let key = if key.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", key.replace('"', "\"\""))
+    } else {
+        key.to_string()
+    };
+    write!(out, "{},{},{}\r\n", kind, key, count).expect("writing to a String cannot fail");
+}
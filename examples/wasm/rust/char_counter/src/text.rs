@@ -0,0 +1,180 @@
+//! Word and line counting over a body read in chunks.
+//!
+//! Each counter is fed the decoded body piece by piece and holds back only
+//! what a later chunk could still change, so neither needs the whole body in
+//! memory.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How a body is split into words.
+pub enum Words {
+    /// Unicode word boundaries (UAX #29); a word is a segment with at least
+    /// one letter or digit, so punctuation and spaces never count.
+    Unicode,
+    /// A word is a maximal run of characters not in the set.
+    Delimited(Vec<char>),
+}
+
+/// Counts words across chunks.
+pub struct WordCounter {
+    words: Words,
+    count: usize,
+    /// Unicode mode: text whose segmentation may still change.
+    pending: String,
+    /// Delimited mode: whether the last character seen was inside a word.
+    in_word: bool,
+}
+
+impl WordCounter {
+    pub fn new(words: Words) -> WordCounter {
+        WordCounter {
+            words,
+            count: 0,
+            pending: String::new(),
+            in_word: false,
+        }
+    }
+
+    pub fn push(&mut self, chunk: &str) {
+        match &self.words {
+            Words::Unicode => {
+                self.pending.push_str(chunk);
+                // A boundary can depend on the character after the next
+                // segment (e.g. "a.b" is one word), so the last two segments
+                // wait for more text.
+                let starts: Vec<usize> = self
+                    .pending
+                    .split_word_bound_indices()
+                    .map(|(i, _)| i)
+                    .collect();
+                if starts.len() > 2 {
+                    let settled = starts[starts.len() - 2];
+                    self.count += count_unicode_words(&self.pending[..settled]);
+                    self.pending.drain(..settled);
+                }
+            }
+            Words::Delimited(delimiters) => {
+                for c in chunk.chars() {
+                    let in_word = !delimiters.contains(&c);
+                    if in_word && !self.in_word {
+                        self.count += 1;
+                    }
+                    self.in_word = in_word;
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> usize {
+        self.count + count_unicode_words(&std::mem::take(&mut self.pending))
+    }
+}
+
+fn count_unicode_words(text: &str) -> usize {
+    text.split_word_bounds()
+        .filter(|segment| segment.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// Counts lines across chunks. A line ends at a terminator or at the end of
+/// the body; a body that ends with a terminator has no extra empty line,
+/// and an empty body has no lines.
+pub struct LineCounter {
+    terminator: Option<String>,
+    lines: usize,
+    blank: usize,
+    /// Whether the current line has any non-whitespace character.
+    has_content: bool,
+    /// Whether anything follows the last terminator.
+    open: bool,
+    /// Default terminators: the previous chunk ended in '\r', so a leading
+    /// '\n' completes that "\r\n" rather than ending another line.
+    after_cr: bool,
+    /// Custom terminator: the tail of the last chunk that could be the start
+    /// of a terminator split across chunks.
+    carry: String,
+}
+
+impl LineCounter {
+    /// With `None`, "\n", "\r\n" and a lone "\r" each end a line.
+    pub fn new(terminator: Option<String>) -> LineCounter {
+        LineCounter {
+            terminator,
+            lines: 0,
+            blank: 0,
+            has_content: false,
+            open: false,
+            after_cr: false,
+            carry: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &str) {
+        let Some(terminator) = self.terminator.take() else {
+            self.push_default(chunk);
+            return;
+        };
+        let mut text = std::mem::take(&mut self.carry);
+        text.push_str(chunk);
+        let mut rest = text.as_str();
+        while let Some(at) = rest.find(terminator.as_str()) {
+            self.content(&rest[..at]);
+            self.end_line();
+            rest = &rest[at + terminator.len()..];
+        }
+        // Keep the longest tail that is a proper prefix of the terminator.
+        let keep = (1..terminator.len())
+            .rev()
+            .find(|&n| {
+                n <= rest.len()
+                    && rest.is_char_boundary(rest.len() - n)
+                    && terminator.starts_with(&rest[rest.len() - n..])
+            })
+            .unwrap_or(0);
+        let (line, tail) = rest.split_at(rest.len() - keep);
+        self.content(line);
+        self.carry = tail.to_string();
+        self.terminator = Some(terminator);
+    }
+
+    fn push_default(&mut self, chunk: &str) {
+        for c in chunk.chars() {
+            let after_cr = std::mem::replace(&mut self.after_cr, c == '\r');
+            match c {
+                '\n' if after_cr => {}
+                '\n' | '\r' => self.end_line(),
+                c => {
+                    self.open = true;
+                    self.has_content |= !c.is_whitespace();
+                }
+            }
+        }
+    }
+
+    fn content(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.open = true;
+            self.has_content |= text.chars().any(|c| !c.is_whitespace());
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.lines += 1;
+        if !self.has_content {
+            self.blank += 1;
+        }
+        self.has_content = false;
+        self.open = false;
+    }
+
+    /// Returns the number of lines and how many of them are blank (empty or
+    /// whitespace only).
+    pub fn finish(mut self) -> (usize, usize) {
+        let carry = std::mem::take(&mut self.carry);
+        self.content(&carry);
+        if self.open {
+            self.end_line();
+        }
+        (self.lines, self.blank)
+    }
+}
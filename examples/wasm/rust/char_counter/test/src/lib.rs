@@ -1,60 +1,47 @@
 use extism_pdk::*;
-use xtp_test;
-use serde_json::json;
+use firelynx_fixture::RequestFixture;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct CharacterReport {
     count: i32,
     characters: String,
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(default)]
+    histogram: Option<std::collections::BTreeMap<String, i32>>,
+    #[serde(default)]
+    sets: Option<std::collections::BTreeMap<String, i32>>,
+    #[serde(default)]
+    top: Option<Vec<TopCharacter>>,
+    #[serde(default)]
+    total_chars: Option<u64>,
+    #[serde(default)]
+    match_ratio: Option<f64>,
+    #[serde(default)]
+    set_percentages: Option<std::collections::BTreeMap<String, f64>>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TopCharacter {
+    character: String,
+    count: i32,
 }
 
-// Helper function to create realistic test input matching go-polyscript format
 fn create_test_input(body: &str) -> String {
-    create_test_input_with_config(body, None, None)
+    RequestFixture::new(body).empty_static_data().to_json()
 }
 
-// Helper function to create test input with static_data configuration
 fn create_test_input_with_config(body: &str, search_chars: Option<&str>, case_sensitive: Option<bool>) -> String {
-    let mut input = json!({
-        "request": {
-            "Body": body,
-            "Headers": {
-                "Content-Type": ["application/json"],
-                "User-Agent": ["xtp-test/1.0"]
-            },
-            "QueryParams": {},
-            "Method": "POST",
-            "Proto": "HTTP/1.1",
-            "Host": "localhost:8080",
-            "RemoteAddr": "[::1]:12345",
-            "ContentLength": body.len(),
-            "URL": {
-                "Scheme": "http",
-                "Path": "/api/demo",
-                "Host": "localhost:8080",
-                "RawQuery": "",
-                "Fragment": ""
-            },
-            "URL_Path": "/api/demo",
-            "URL_Scheme": "http",
-            "URL_Host": "localhost:8080",
-            "URL_String": "/api/demo"
-        }
-    });
-
-    // Add static_data if configuration is provided
-    if search_chars.is_some() || case_sensitive.is_some() {
-        let mut static_data = json!({});
-        if let Some(chars) = search_chars {
-            static_data["search_characters"] = json!(chars);
-        }
-        if let Some(case_sens) = case_sensitive {
-            static_data["case_sensitive"] = json!(case_sens);
-        }
-        input["static_data"] = static_data;
+    let mut fixture = RequestFixture::new(body).empty_static_data();
+    if let Some(chars) = search_chars {
+        fixture = fixture.static_data("search_characters", chars);
     }
-
-    input.to_string()
+    if let Some(case_sens) = case_sensitive {
+        fixture = fixture.static_data("case_sensitive", case_sens);
+    }
+    fixture.to_json()
 }
 
 #[plugin_fn]
@@ -71,6 +58,15 @@ pub fn test() -> FnResult<()> {
     xtp_test::assert_eq!("Hello World has 3 vowels", result.count, 3);
     xtp_test::assert_eq!("Uses default vowel set", &result.characters, "aeiouAEIOU");
 
+    // A route without static_data is an error, not a silent fallback to vowels
+    let unconfigured = RequestFixture::new("Hello World").to_json();
+    xtp_test::assert!(
+        "missing static_data is rejected",
+        xtp_test::call::<Json<CharacterReport>>("CountCharacters", &unconfigured).is_err()
+    );
+    let Json(words): Json<serde_json::Value> = xtp_test::call("CountWords", &unconfigured)?;
+    xtp_test::assert_eq!("CountWords still defaults", &words["count"], &serde_json::json!(2));
+
     // Edge case: empty input
     let empty_input = create_test_input("");
     let Json(empty_result): Json<CharacterReport> = xtp_test::call("CountCharacters", &empty_input)?;
@@ -123,7 +119,8 @@ pub fn test() -> FnResult<()> {
         // Unicode content
         let unicode_input = create_test_input("café naïve résumé");
         let Json(unicode_result): Json<CharacterReport> = xtp_test::call("CountCharacters", &unicode_input)?;
-        xtp_test::assert_gt!("unicode content has vowels", unicode_result.count, 0);
+        // Only the ASCII vowels are in the default set: a, a, e, u.
+        xtp_test::assert_eq!("unicode content has 4 ASCII vowels", unicode_result.count, 4);
 
         Ok(())
     })?;
@@ -156,5 +153,808 @@ pub fn test() -> FnResult<()> {
         Ok(())
     })?;
 
+    // count_mode = "graphemes" counts what a reader sees as one character
+    xtp_test::group("grapheme count mode tests", || {
+        let graphemes = |body: &str, chars: &str| {
+            RequestFixture::new(body)
+                .static_data("search_characters", chars)
+                .static_data("count_mode", "graphemes")
+                .to_json()
+        };
+
+        let accented = graphemes("café naïve résumé", "aeiouéï");
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &accented)?;
+        xtp_test::assert_eq!("accented vowels count once each", result.count, 8);
+
+        // "e" + U+0301 is one cluster, and it is not a plain "e"
+        let decomposed = "cafe\u{301}";
+        let input = create_test_input_with_config(decomposed, Some("e"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("chars mode counts the base e", result.count, 1);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &graphemes(decomposed, "e"))?;
+        xtp_test::assert_eq!("graphemes mode does not", result.count, 0);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &graphemes(decomposed, "e\u{301}"))?;
+        xtp_test::assert_eq!("decomposed target matches the cluster", result.count, 1);
+
+        // A thumbs up with a skin tone modifier is two scalars, one cluster
+        let thumbs = "\u{1f44d}\u{1f3fd} ok \u{1f44d}\u{1f3fd}";
+        let input = create_test_input_with_config(thumbs, Some("\u{1f44d}\u{1f3fd}"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("chars mode counts each scalar", result.count, 4);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &graphemes(thumbs, "\u{1f44d}\u{1f3fd}"))?;
+        xtp_test::assert_eq!("graphemes mode counts each emoji once", result.count, 2);
+
+        // ZWJ sequences and flags are one cluster each and can be searched for as written
+        let family = "\u{1f469}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let flag = "\u{1f1ef}\u{1f1f5}";
+        let emoji = format!("{family} and {flag}{flag}");
+        let input = create_test_input_with_config(&emoji, Some(family), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("chars mode counts people and joiners", result.count, 5);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &graphemes(&emoji, family))?;
+        xtp_test::assert_eq!("a family is one character", result.count, 1);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &graphemes(&emoji, flag))?;
+        xtp_test::assert_eq!("a flag is one character", result.count, 2);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &graphemes(&emoji, "\\p{emoji}"))?;
+        xtp_test::assert_eq!("the emoji alias counts clusters", result.count, 3);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &graphemes(&emoji.repeat(5_000), "\\p{emoji}"))?;
+        xtp_test::assert_eq!("emoji across chunks", result.count, 15_000);
+
+        // Clusters spanning the 64 KiB chunk boundary are still whole
+        let long = "e\u{301}".repeat(30_000);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &graphemes(&long, "E\u{301}"))?;
+        xtp_test::assert_eq!("clusters across chunks", result.count, 30_000);
+
+        Ok(())
+    })?;
+
+    // search_characters can name Unicode classes
+    xtp_test::group("character class tests", || {
+        let input = create_test_input_with_config("Order 66: ship 3 items!", Some("\\p{digits}"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("digits alias", result.count, 3);
+        xtp_test::assert_eq!("characters as configured", result.characters, "\\p{digits}");
+
+        let input = create_test_input_with_config("a1-b2+c٣", Some("\\p{Nd}+-"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("classes mix with literals, non-ASCII digits count", result.count, 5);
+
+        let input = create_test_input_with_config("Hi, there. Ok?", Some("\\p{punctuation}\\p{whitespace}"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("punctuation and whitespace", result.count, 5);
+
+        let input = create_test_input_with_config("Hello World", Some("\\p{Lu}"), Some(true));
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("case sensitive class", result.count, 2);
+        let input = create_test_input_with_config("Hello World", Some("\\p{Lu}"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("case insensitive class matches both cases", result.count, 10);
+
+        let input = create_test_input_with_config("αβγ abc", Some("\\P{Greek}"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("negated script", result.count, 4);
+
+        let input = create_test_input_with_config("a\\b", Some("\\"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("a lone backslash is literal", result.count, 1);
+
+        let input = RequestFixture::new("cafe\u{301} naïve")
+            .static_data("search_characters", "\\p{letters}")
+            .static_data("count_mode", "graphemes")
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("clusters by their first character", result.count, 9);
+
+        let unknown = create_test_input_with_config("x", Some("\\p{Vowels}"), None);
+        xtp_test::assert!(
+            "unknown classes are rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &unknown).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // search_syntax = "ranges" reads search_characters as ranges
+    xtp_test::group("character range tests", || {
+        let ranges = |body: &str, chars: &str| {
+            RequestFixture::new(body)
+                .static_data("search_characters", chars)
+                .static_data("search_syntax", "ranges")
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &ranges("Room 101, floor B", "a-z,0-9"))?;
+        xtp_test::assert_eq!("letters and digits, either case", result.count, 13);
+        xtp_test::assert_eq!("characters as configured", result.characters, "a-z,0-9");
+
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &ranges("1-2, 3+4", "\\-\\,+"))?;
+        xtp_test::assert_eq!("escaped dash and comma", result.count, 3);
+
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &ranges("a1-b2", "\\p{digits},a-a"))?;
+        xtp_test::assert_eq!("ranges mix with classes", result.count, 3);
+
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &create_test_input_with_config("a-z, b", Some("a-z,"), None))?;
+        xtp_test::assert_eq!("literal syntax by default", result.count, 4);
+
+        for bad in ["z-a", "a-", "-", "a\\"] {
+            xtp_test::assert!(
+                &format!("{:?} is rejected", bad),
+                xtp_test::call::<Json<CharacterReport>>("CountCharacters", &ranges("x", bad)).is_err()
+            );
+        }
+
+        Ok(())
+    })?;
+
+    // search_sets counts several named sets in one call
+    xtp_test::group("named set tests", || {
+        let input = RequestFixture::new("Room 101, floor B")
+            .static_data("search_sets", serde_json::json!({"vowels": "aeiou", "digits": "0-9", "signs": "+\\-"}))
+            .static_data("search_syntax", "ranges")
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        let sets = result.sets.unwrap_or_default();
+        xtp_test::assert_eq!("vowels", sets.get("vowels").copied(), Some(4));
+        xtp_test::assert_eq!("digits", sets.get("digits").copied(), Some(3));
+        xtp_test::assert_eq!("sets without matches are listed", sets.get("signs").copied(), Some(0));
+        xtp_test::assert_eq!("count covers every set", result.count, 7);
+        xtp_test::assert_eq!("characters is empty", result.characters, "");
+
+        let input = RequestFixture::new("abc")
+            .static_data("search_sets", serde_json::json!({"first": "ab", "second": "bc"}))
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("overlapping sets count a character once", result.count, 3);
+        xtp_test::assert_eq!("and in each set", result.sets.unwrap_or_default().get("second").copied(), Some(2));
+
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &create_test_input("abc"))?;
+        xtp_test::assert!("no sets without search_sets", result.sets.is_none());
+
+        let with_pattern = RequestFixture::new("abc")
+            .static_data("search_sets", serde_json::json!({"a": "a"}))
+            .static_data("search_pattern", "a")
+            .to_json();
+        xtp_test::assert!(
+            "search_sets and search_pattern are rejected together",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &with_pattern).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // source counts another part of the request than the body
+    xtp_test::group("source tests", || {
+        let request = |source: &str| {
+            RequestFixture::new("aaaa")
+                .header("X-Note", "one")
+                .header("X-Note", "two")
+                .query("q", "rust")
+                .path("/api/items")
+                .static_data("source", source)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("body"))?;
+        xtp_test::assert_eq!("body by default", result.count, 4);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("path"))?;
+        xtp_test::assert_eq!("path", result.count, 3);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("header:x-note"))?;
+        xtp_test::assert_eq!("every header value", result.count, 3);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("query:q"))?;
+        xtp_test::assert_eq!("query parameter", result.count, 1);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("query:missing"))?;
+        xtp_test::assert_eq!("a missing parameter is empty", result.count, 0);
+        xtp_test::assert!(
+            "unknown sources are rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &request("cookie:id")).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // Case folding per character, with language-specific rules under locale
+    xtp_test::group("case folding tests", || {
+        let request = |body: &str, search: &str, locale: Option<&str>| {
+            let fixture = RequestFixture::new(body).static_data("search_characters", search);
+            match locale {
+                Some(locale) => fixture.static_data("locale", locale),
+                None => fixture,
+            }
+            .to_json()
+        };
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &request("ſ\u{212a}Σς", "sσk", None))?;
+        xtp_test::assert_eq!("long s, Kelvin sign and final sigma fold", result.count, 4);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &request("İi", "i", None))?;
+        xtp_test::assert_eq!("dotted capital I is one character", result.count, 1);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &request("IİıiK", "i", Some("tr")))?;
+        xtp_test::assert_eq!("Turkish I is dotless", result.count, 2);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &request("IİıiK", "i", Some("en-US")))?;
+        xtp_test::assert_eq!("other locales fold like the default", result.count, 2);
+        xtp_test::assert!(
+            "malformed locales are rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &request("a", "a", Some("t r"))).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // CountCharactersBegin/Chunk/End count one body sent in pieces
+    xtp_test::group("chunked count tests", || {
+        let Json(progress): Json<serde_json::Value> =
+            xtp_test::call("CountCharactersBegin", create_test_input("Hello "))?;
+        xtp_test::assert_eq!("begin counts the first piece", progress["count"].as_i64(), Some(2));
+        let Json(progress): Json<serde_json::Value> =
+            xtp_test::call("CountCharactersChunk", create_test_input("World"))?;
+        xtp_test::assert_eq!("chunks add up", progress["count"].as_i64(), Some(3));
+        xtp_test::assert_eq!("bytes so far", progress["bytes"].as_i64(), Some(11));
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharactersEnd", create_test_input(", goodbye"))?;
+        let Json(whole): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", create_test_input("Hello World, goodbye"))?;
+        xtp_test::assert_eq!("end reports the whole body", result.count, whole.count);
+        xtp_test::assert!(
+            "end closes the count",
+            xtp_test::call::<Json<serde_json::Value>>("CountCharactersChunk", create_test_input("a")).is_err()
+        );
+
+        // The cluster split between calls is still one match.
+        let begin = RequestFixture::new("e")
+            .static_data("count_mode", "graphemes")
+            .static_data("search_characters", "e\u{301}")
+            .to_json();
+        xtp_test::call::<Json<serde_json::Value>>("CountCharactersBegin", &begin)?;
+        xtp_test::call::<Json<serde_json::Value>>("CountCharactersChunk", create_test_input("\u{301}x"))?;
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharactersEnd", create_test_input(""))?;
+        xtp_test::assert_eq!("a cluster can span calls", result.count, 1);
+
+        let with_pattern = RequestFixture::new("abc").static_data("search_pattern", "a").to_json();
+        xtp_test::assert!(
+            "search_pattern cannot be chunked",
+            xtp_test::call::<Json<serde_json::Value>>("CountCharactersBegin", &with_pattern).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // normalize puts body and search set in one form before matching
+    xtp_test::group("normalization tests", || {
+        let normalized = |body: &str, chars: &str, form: &str| {
+            RequestFixture::new(body)
+                .static_data("search_characters", chars)
+                .static_data("normalize", form)
+                .to_json()
+        };
+
+        // One precomposed and one decomposed "é"
+        let mixed = "caf\u{e9} cafe\u{301}";
+        let input = create_test_input_with_config(mixed, Some("\u{e9}"), None);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("without normalize the decomposed form is missed", result.count, 1);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &normalized(mixed, "\u{e9}", "nfc"))?;
+        xtp_test::assert_eq!("nfc matches both spellings", result.count, 2);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &normalized(mixed, "e\u{301}", "nfc"))?;
+        xtp_test::assert_eq!("the search set is normalized too", result.count, 2);
+        xtp_test::assert_eq!("characters are reported as configured", result.characters, "e\u{301}");
+
+        let input = RequestFixture::new(mixed)
+            .static_data("search_characters", "\u{e9}")
+            .static_data("normalize", "nfd")
+            .static_data("count_mode", "graphemes")
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("nfd with graphemes", result.count, 2);
+
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &normalized("\u{fb01}le \u{ff21}", "fa", "nfkc"))?;
+        xtp_test::assert_eq!("nfkc folds compatibility variants", result.count, 2);
+
+        let input = RequestFixture::new(mixed)
+            .static_data("search_pattern", "caf\u{e9}")
+            .static_data("normalize", "nfc")
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("patterns are normalized", result.count, 2);
+
+        // Marks split from their letter by the 64 KiB chunk boundary
+        let long = "e\u{301}".repeat(30_000);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &normalized(&long, "\u{e9}", "nfc"))?;
+        xtp_test::assert_eq!("composition across chunks", result.count, 30_000);
+
+        let unknown = normalized(mixed, "e", "nfkd");
+        xtp_test::assert!(
+            "unknown forms are rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &unknown).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // strip_diacritics counts accented letters as their base letter
+    xtp_test::group("diacritics tests", || {
+        let spanish = "Él está aquí, cañón";
+        let Json(plain): Json<CharacterReport> = xtp_test::call("CountCharacters", create_test_input(spanish))?;
+        xtp_test::assert_eq!("accented vowels are missed by default", plain.count, 4);
+
+        let stripped = |chars: &str| {
+            RequestFixture::new(spanish)
+                .static_data("search_characters", chars)
+                .static_data("strip_diacritics", true)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &stripped("aeiouAEIOU"))?;
+        xtp_test::assert_eq!("every vowel counts", result.count, 8);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &stripped("\u{f1}"))?;
+        xtp_test::assert_eq!("the search set is stripped too", result.count, 2);
+
+        let decomposed = RequestFixture::new("cafe\u{301} cafe\u{300}")
+            .static_data("search_characters", "e")
+            .static_data("strip_diacritics", true)
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &decomposed)?;
+        xtp_test::assert_eq!("decomposed marks are removed", result.count, 2);
+
+        let bytes = RequestFixture::new(spanish)
+            .static_data("strip_diacritics", true)
+            .static_data("count_mode", "bytes")
+            .to_json();
+        xtp_test::assert!(
+            "bytes mode is rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &bytes).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // strip_html counts the text of an HTML body, not its markup
+    xtp_test::group("strip_html tests", || {
+        let page = "<p class=\"intro\">Caf&eacute; <b>open</b></p><script>var audio = 1;</script><!-- a note -->";
+        let Json(plain): Json<CharacterReport> = xtp_test::call("CountCharacters", create_test_input(page))?;
+        xtp_test::assert_eq!("markup is counted by default", plain.count, 20);
+
+        let stripped = |chars: &str| {
+            RequestFixture::new(page)
+                .static_data("search_characters", chars)
+                .static_data("strip_html", true)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &stripped("aeiouAEIOU"))?;
+        xtp_test::assert_eq!("only the text's vowels", result.count, 3);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &stripped("\u{e9}"))?;
+        xtp_test::assert_eq!("references are decoded", result.count, 1);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &stripped("<>"))?;
+        xtp_test::assert_eq!("no tag characters left", result.count, 0);
+
+        let base64 = RequestFixture::new("PGI+")
+            .static_data("strip_html", true)
+            .static_data("count_mode", "bytes")
+            .static_data("body_encoding", "base64")
+            .to_json();
+        xtp_test::assert!(
+            "a base64 body is rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &base64).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // preprocess cleans up whitespace before counting
+    xtp_test::group("preprocess tests", || {
+        let padded = |steps: serde_json::Value| {
+            RequestFixture::new("  Hello \n\n world  ")
+                .static_data("search_characters", " \n")
+                .static_data("preprocess", steps)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", padded(serde_json::json!([])))?;
+        xtp_test::assert_eq!("no steps counts every whitespace", result.count, 8);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", padded(serde_json::json!(["trim"])))?;
+        xtp_test::assert_eq!("trim keeps the inner run", result.count, 4);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", padded(serde_json::json!(["collapse_whitespace"])))?;
+        xtp_test::assert_eq!("collapse keeps one per run", result.count, 3);
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", padded(serde_json::json!(["trim", "collapse_whitespace"])))?;
+        xtp_test::assert_eq!("both leave a single space", result.count, 1);
+        xtp_test::assert!(
+            "unknown steps are rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", padded(serde_json::json!(["squash"]))).is_err()
+        );
+
+        // Trailing whitespace is only known at the end of a chunked body
+        let begin = RequestFixture::new(" Hello ")
+            .static_data("search_characters", " ")
+            .static_data("preprocess", serde_json::json!(["trim"]))
+            .to_json();
+        xtp_test::call::<Json<serde_json::Value>>("CountCharactersBegin", &begin)?;
+        xtp_test::call::<Json<serde_json::Value>>("CountCharactersChunk", create_test_input(" world "))?;
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharactersEnd", create_test_input("  "))?;
+        xtp_test::assert_eq!("trimmed across pieces", result.count, 2);
+
+        Ok(())
+    })?;
+
+    // search_pattern counts regex matches instead of characters
+    xtp_test::group("search_pattern tests", || {
+        let pattern = |body: &str, pattern: &str| {
+            RequestFixture::new(body)
+                .static_data("search_pattern", pattern)
+                .to_json()
+        };
+
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &pattern("the cat sat on The mat", r"\bthe\b"))?;
+        xtp_test::assert_eq!("word matches, case insensitive", result.count, 2);
+        xtp_test::assert_eq!("reports the pattern", result.pattern.as_deref(), Some(r"\bthe\b"));
+        xtp_test::assert_eq!("no character set", &result.characters, "");
+
+        let case_input = RequestFixture::new("the cat sat on The mat")
+            .static_data("search_pattern", r"\bthe\b")
+            .static_data("case_sensitive", true)
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &case_input)?;
+        xtp_test::assert_eq!("word matches, case sensitive", result.count, 1);
+
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &pattern("order 12, order 345", r"\d+"))?;
+        xtp_test::assert_eq!("matches do not overlap", result.count, 2);
+
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &pattern("bab", "a*"))?;
+        xtp_test::assert_eq!("empty matches are not counted", result.count, 1);
+
+        let invalid = xtp_test::call::<Json<CharacterReport>>("CountCharacters", pattern("x", "(unclosed"));
+        xtp_test::assert!("invalid pattern is rejected", invalid.is_err());
+
+        Ok(())
+    })?;
+
+    // report_version selects the output shape; version 1 stays the default
+    xtp_test::group("report version tests", || {
+        // Old consumers see exactly the bytes they always did.
+        let v1 = r#"{"count":3,"characters":"aeiouAEIOU"}"#;
+        let default_output: String = xtp_test::call("CountCharacters", create_test_input("Hello World"))?;
+        xtp_test::assert_eq!("default output is byte-identical to v1", &default_output, v1);
+        let explicit = RequestFixture::new("Hello World").static_data("report_version", 1).to_json();
+        let explicit_output: String = xtp_test::call("CountCharacters", &explicit)?;
+        xtp_test::assert_eq!("report_version 1 is the same", &explicit_output, v1);
+
+        let v2_input = RequestFixture::new("Hello World")
+            .static_data("report_version", 2)
+            .static_data("max_positions", 2)
+            .to_json();
+        let Json(v2): Json<serde_json::Value> = xtp_test::call("CountCharacters", &v2_input)?;
+        xtp_test::assert_eq!("v2 is tagged", &v2["report_version"], &serde_json::json!(2));
+        xtp_test::assert_eq!("v2 keeps the count", &v2["count"], &serde_json::json!(3));
+        xtp_test::assert_eq!("per-character counts", &v2["counts"], &serde_json::json!({"e": 1, "o": 2}));
+        xtp_test::assert_eq!("body bytes", &v2["stats"]["bytes"], &serde_json::json!(11));
+        xtp_test::assert_eq!("units scanned", &v2["stats"]["units"], &serde_json::json!(11));
+        xtp_test::assert_eq!(
+            "first positions",
+            &v2["positions"],
+            &serde_json::json!([{"start": 1, "end": 2}, {"start": 4, "end": 5}])
+        );
+        xtp_test::assert_eq!("positions were capped", &v2["positions_truncated"], &serde_json::json!(true));
+
+        let unknown = RequestFixture::new("x").static_data("report_version", 3).to_json();
+        xtp_test::assert!(
+            "unknown report_version is rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &unknown).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // include_histogram adds per-character counts to the version 1 report
+    xtp_test::group("histogram tests", || {
+        let Json(plain): Json<CharacterReport> = xtp_test::call("CountCharacters", create_test_input("Hello World"))?;
+        xtp_test::assert!("no histogram by default", plain.histogram.is_none());
+
+        let input = RequestFixture::new("Hello World, EVERYONE")
+            .static_data("include_histogram", true)
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        let expected: std::collections::BTreeMap<String, i32> =
+            [("e", 4), ("o", 3)].map(|(c, n)| (c.to_string(), n)).into_iter().collect();
+        xtp_test::assert_eq!("histogram folds case", result.histogram, Some(expected));
+        xtp_test::assert_eq!("total is unchanged", result.count, 7);
+
+        let input = RequestFixture::new("Hello World")
+            .static_data("include_histogram", true)
+            .static_data("search_characters", "xyz")
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("no matches, empty histogram", result.histogram, Some(Default::default()));
+
+        Ok(())
+    })?;
+
+    // top_n lists the most frequent matched characters
+    xtp_test::group("top characters tests", || {
+        let request = |top_n: i64| {
+            RequestFixture::new("Hello World, EVERYONE")
+                .static_data("top_n", top_n)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request(1))?;
+        let top = result.top.unwrap_or_default();
+        xtp_test::assert_eq!("one entry", top.len(), 1);
+        xtp_test::assert_eq!("most frequent first", &top[0].character, "e");
+        xtp_test::assert_eq!("with its count", top[0].count, 4);
+        xtp_test::assert!("no histogram unless asked", result.histogram.is_none());
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request(5))?;
+        xtp_test::assert_eq!("only matched characters", result.top.map(|top| top.len()), Some(2));
+        xtp_test::assert!(
+            "top_n must be positive",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &request(0)).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // invert counts the characters outside the set
+    xtp_test::group("invert tests", || {
+        let request = |search: &str| {
+            RequestFixture::new("Héllo wörld")
+                .static_data("search_characters", search)
+                .static_data("invert", true)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("aeiou"))?;
+        xtp_test::assert_eq!("everything but the vowels", result.count, 10);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("\\p{ASCII}"))?;
+        xtp_test::assert_eq!("non-ASCII characters", result.count, 2);
+        let with_pattern = RequestFixture::new("abc")
+            .static_data("invert", true)
+            .static_data("search_pattern", "b")
+            .to_json();
+        xtp_test::assert!(
+            "a pattern cannot be inverted",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &with_pattern).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // count_mode = "bytes" counts bytes of the text or of a base64 body
+    xtp_test::group("byte count tests", || {
+        let input = RequestFixture::new("Héllo")
+            .static_data("count_mode", "bytes")
+            .static_data("search_characters", "\\P{ASCII}")
+            .static_data("include_stats", true)
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("both bytes of é", result.count, 2);
+        xtp_test::assert_eq!("byte total", result.total_chars, Some(6));
+
+        let binary = RequestFixture::new("AAEC/w==")
+            .static_data("count_mode", "bytes")
+            .static_data("body_encoding", "base64")
+            .static_data("search_characters", "\\p{Cc}")
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &binary)?;
+        xtp_test::assert_eq!("control bytes of the decoded body", result.count, 3);
+        let text_mode = RequestFixture::new("AAEC/w==")
+            .static_data("body_encoding", "base64")
+            .to_json();
+        xtp_test::assert!(
+            "base64 needs bytes mode",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &text_mode).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // include_stats adds the text length and match ratios
+    xtp_test::group("stats tests", || {
+        let Json(plain): Json<CharacterReport> = xtp_test::call("CountCharacters", create_test_input("Hello"))?;
+        xtp_test::assert!("no stats by default", plain.total_chars.is_none() && plain.match_ratio.is_none());
+
+        let input = RequestFixture::new("Hello")
+            .static_data("include_stats", true)
+            .static_data("search_sets", serde_json::json!({"vowels": "aeiou", "l": "l"}))
+            .to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("total characters", result.total_chars, Some(5));
+        xtp_test::assert_eq!("ratio of matches", result.match_ratio, Some(0.8));
+        let percentages = result.set_percentages.unwrap_or_default();
+        xtp_test::assert_eq!("vowel percentage", percentages.get("vowels"), Some(&40.0));
+        xtp_test::assert_eq!("l percentage", percentages.get("l"), Some(&40.0));
+
+        Ok(())
+    })?;
+
+    // include_positions adds match byte ranges to the version 1 report
+    xtp_test::group("position tests", || {
+        let Json(plain): Json<serde_json::Value> = xtp_test::call("CountCharacters", create_test_input("Hello"))?;
+        xtp_test::assert!("no positions by default", plain.get("positions").is_none());
+
+        let input = RequestFixture::new("Héllo World")
+            .static_data("include_positions", true)
+            .static_data("max_positions", 2)
+            .to_json();
+        let Json(result): Json<serde_json::Value> = xtp_test::call("CountCharacters", &input)?;
+        xtp_test::assert_eq!("count is unchanged", &result["count"], &serde_json::json!(2));
+        xtp_test::assert_eq!(
+            "byte ranges",
+            &result["positions"],
+            &serde_json::json!([{"start": 5, "end": 6}, {"start": 8, "end": 9}])
+        );
+        xtp_test::assert_eq!("all listed", &result["positions_truncated"], &serde_json::json!(false));
+        xtp_test::assert!("still version 1", result.get("report_version").is_none());
+
+        Ok(())
+    })?;
+
+    // match_description becomes the report's description
+    xtp_test::group("description tests", || {
+        let Json(plain): Json<CharacterReport> = xtp_test::call("CountCharacters", create_test_input("Hello World"))?;
+        xtp_test::assert!("no description by default", plain.description.is_none());
+
+        let described = |template: &str| {
+            RequestFixture::new("Hello World")
+                .static_data("match_description", template)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &described("vowels"))?;
+        xtp_test::assert_eq!("plain text is kept", result.description.as_deref(), Some("vowels"));
+        let Json(result): Json<CharacterReport> =
+            xtp_test::call("CountCharacters", &described("{count} of {characters} {{sic}}"))?;
+        xtp_test::assert_eq!(
+            "placeholders are filled in",
+            result.description.as_deref(),
+            Some("3 of aeiouAEIOU {sic}")
+        );
+        xtp_test::assert!(
+            "unknown placeholders are rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", &described("{total} vowels")).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // The Accept header picks JSON, plain text or CSV
+    xtp_test::group("response format tests", || {
+        let request = |accept: &str| {
+            RequestFixture::new("Hello, world")
+                .header("Accept", accept)
+                .static_data("include_histogram", true)
+                .to_json()
+        };
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("application/json"))?;
+        xtp_test::assert_eq!("JSON when asked for", result.count, 3);
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", &request("*/*"))?;
+        xtp_test::assert_eq!("JSON for any type", result.count, 3);
+
+        let text: String = xtp_test::call("CountCharacters", &request("text/plain"))?;
+        xtp_test::assert_eq!(
+            "text lines",
+            text,
+            "count: 3\ncharacters: aeiouAEIOU\ncharacter e: 1\ncharacter o: 2\n"
+        );
+        let csv: String = xtp_test::call("CountCharacters", &request("text/csv, text/plain;q=0.5"))?;
+        xtp_test::assert_eq!(
+            "CSV rows",
+            csv,
+            "kind,key,count\r\ntotal,aeiouAEIOU,3\r\ncharacter,e,1\r\ncharacter,o,2\r\n"
+        );
+        xtp_test::assert!(
+            "unsupported types are rejected",
+            xtp_test::call::<String>("CountCharacters", &request("image/png")).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // max_body_bytes turns away larger bodies, whole or in pieces
+    xtp_test::group("body size limit tests", || {
+        let limited = |body: &str| RequestFixture::new(body).static_data("max_body_bytes", 8).to_json();
+        let Json(result): Json<CharacterReport> = xtp_test::call("CountCharacters", limited("Hello"))?;
+        xtp_test::assert_eq!("a body within the limit is counted", result.count, 2);
+        xtp_test::assert!(
+            "a larger body is rejected",
+            xtp_test::call::<Json<CharacterReport>>("CountCharacters", limited("Hello World")).is_err()
+        );
+
+        xtp_test::call::<Json<serde_json::Value>>("CountCharactersBegin", limited("Hello"))?;
+        xtp_test::assert!(
+            "the pieces count together",
+            xtp_test::call::<Json<serde_json::Value>>("CountCharactersChunk", create_test_input(" World")).is_err()
+        );
+        xtp_test::assert!(
+            "the rejected piece closes the count",
+            xtp_test::call::<Json<CharacterReport>>("CountCharactersEnd", create_test_input("")).is_err()
+        );
+
+        Ok(())
+    })?;
+
+    // CountWords and CountLines share the envelope and chunked body reading
+    xtp_test::group("word and line count tests", || {
+        let Json(words): Json<serde_json::Value> =
+            xtp_test::call("CountWords", create_test_input("Hello, world! It's 3.14 e.g. naïve"))?;
+        xtp_test::assert_eq!("unicode words", &words["count"], &serde_json::json!(6));
+        xtp_test::assert_eq!("unicode segmentation", &words["segmentation"], &serde_json::json!("unicode"));
+
+        let delimited = RequestFixture::new("a,b,,c d").static_data("word_delimiters", ", ").to_json();
+        let Json(words): Json<serde_json::Value> = xtp_test::call("CountWords", &delimited)?;
+        xtp_test::assert_eq!("delimited words", &words["count"], &serde_json::json!(4));
+
+        let Json(words): Json<serde_json::Value> = xtp_test::call("CountWords", create_test_input("  ...  "))?;
+        xtp_test::assert_eq!("punctuation is not a word", &words["count"], &serde_json::json!(0));
+
+        let Json(lines): Json<serde_json::Value> = xtp_test::call("CountLines", create_test_input("a\r\n\nb\r  \rc"))?;
+        xtp_test::assert_eq!("mixed terminators", &lines["count"], &serde_json::json!(5));
+        xtp_test::assert_eq!("blank lines", &lines["blank"], &serde_json::json!(2));
+
+        let Json(lines): Json<serde_json::Value> = xtp_test::call("CountLines", create_test_input("one\ntwo\n"))?;
+        xtp_test::assert_eq!("trailing newline adds no line", &lines["count"], &serde_json::json!(2));
+
+        let Json(lines): Json<serde_json::Value> = xtp_test::call("CountLines", create_test_input(""))?;
+        xtp_test::assert_eq!("empty body has no lines", &lines["count"], &serde_json::json!(0));
+
+        let custom = RequestFixture::new("a;;b;;;;c\nd").static_data("line_terminator", ";;").to_json();
+        let Json(lines): Json<serde_json::Value> = xtp_test::call("CountLines", &custom)?;
+        xtp_test::assert_eq!("custom terminator", &lines["count"], &serde_json::json!(4));
+        xtp_test::assert_eq!("custom terminator blank", &lines["blank"], &serde_json::json!(1));
+
+        // Words and terminators split across the 64 KiB chunk boundary
+        let long = "words;;".repeat(30_000);
+        let Json(words): Json<serde_json::Value> = xtp_test::call("CountWords", create_test_input(&long))?;
+        xtp_test::assert_eq!("words across chunks", &words["count"], &serde_json::json!(30_000));
+        let custom = RequestFixture::new(long.as_str()).static_data("line_terminator", ";;").to_json();
+        let Json(lines): Json<serde_json::Value> = xtp_test::call("CountLines", &custom)?;
+        xtp_test::assert_eq!("terminators across chunks", &lines["count"], &serde_json::json!(30_000));
+
+        Ok(())
+    })?;
+
+    // LintConfig reports config problems without failing the call
+    xtp_test::group("lint config tests", || {
+        let Json(report): Json<serde_json::Value> = xtp_test::call("LintConfig", create_test_input(""))?;
+        xtp_test::assert_eq!("defaults are clean", &report, &serde_json::json!({"valid": true, "errors": [], "warnings": []}));
+
+        let input = RequestFixture::new("")
+            .static_data("search_characters", "123")
+            .static_data("case_sensitive", true)
+            .static_data("match_description", "digits")
+            .static_data("serch_pattern", "x")
+            .to_json();
+        let Json(report): Json<serde_json::Value> = xtp_test::call("LintConfig", &input)?;
+        xtp_test::assert_eq!("warnings keep the config valid", &report["valid"], &serde_json::json!(true));
+        let warnings: Vec<(String, String)> = report["warnings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|w| (w["field"].as_str().unwrap().to_string(), w["kind"].as_str().unwrap().to_string()))
+            .collect();
+        xtp_test::assert_eq!(
+            "unused key and uncased case_sensitive charset",
+            warnings,
+            vec![
+                ("serch_pattern".to_string(), "unused_key".to_string()),
+                ("case_sensitive".to_string(), "suspicious".to_string()),
+            ]
+        );
+
+        let input = RequestFixture::new("")
+            .static_data("count_mode", "words")
+            .static_data("search_pattern", "(")
+            .static_data("report_version", 3)
+            .to_json();
+        let Json(report): Json<serde_json::Value> = xtp_test::call("LintConfig", &input)?;
+        xtp_test::assert_eq!("errors make it invalid", &report["valid"], &serde_json::json!(false));
+        let fields: Vec<&str> = report["errors"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+        xtp_test::assert_eq!("every error is reported", fields, vec!["count_mode", "report_version"]);
+
+        let absent = RequestFixture::new("").to_json();
+        let Json(report): Json<serde_json::Value> = xtp_test::call("LintConfig", &absent)?;
+        xtp_test::assert_eq!("absent static_data", &report["errors"][0]["field"], &serde_json::json!("static_data"));
+
+        Ok(())
+    })?;
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,512 @@
+mod reader;
+mod writer;
+
+use std::{fmt, io, mem};
+
+use firelynx_pdk::body::{ChunkedReader, DEFAULT_CHUNK_BYTES};
+use firelynx_pdk::limits::ParseLimits;
+use firelynx_pdk::prelude::*;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+
+use reader::{Dialect, RecordReader};
+use writer::{QuoteStyle, RecordWriter};
+
+/// Output gathered before it is handed to the `OutputStream`, so a long
+/// file becomes a few large blocks rather than one per row.
+const FLUSH_BYTES: usize = 64 * 1024;
+
+/// Whether the first CSV record names the columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Header {
+    /// Decided from the first record: a header when every field is a
+    /// distinct, non-empty name that is not a number.
+    Auto,
+    Present,
+    Absent,
+}
+
+impl Header {
+    const NAMES: [&'static str; 3] = ["auto", "present", "absent"];
+
+    fn parse(name: &str) -> Option<Header> {
+        match name {
+            "auto" => Some(Header::Auto),
+            "present" => Some(Header::Present),
+            "absent" => Some(Header::Absent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReadConfig {
+    delimiter: String,
+    quote: String,
+    header: String,
+    infer_types: bool,
+    max_record_bytes: u64,
+}
+
+impl ConfigSchema for ReadConfig {
+    const FIELDS: &'static [Field] = &[
+        Field::string("delimiter").default(DefaultValue::Str(",")),
+        Field::string("quote").default(DefaultValue::Str("\"")),
+        Field::string("header").default(DefaultValue::Str("auto")),
+        Field::bool("infer_types").default(DefaultValue::Bool(false)),
+        Field::integer("max_record_bytes").default(DefaultValue::Int(64 * 1024)),
+    ];
+}
+
+impl ReadConfig {
+    fn header(&self) -> Result<Header> {
+        Header::parse(&self.header).ok_or_else(|| {
+            PluginError::config("header must be auto, present or absent")
+                .with_detail("field", "header")
+                .with_detail("value", self.header.as_str())
+                .with_detail("supported", Header::NAMES.to_vec())
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct WriteConfig {
+    delimiter: String,
+    quote: String,
+    #[serde(default)]
+    columns: Option<Vec<String>>,
+    header_row: bool,
+    quote_style: String,
+}
+
+impl ConfigSchema for WriteConfig {
+    const FIELDS: &'static [Field] = &[
+        Field::string("delimiter").default(DefaultValue::Str(",")),
+        Field::string("quote").default(DefaultValue::Str("\"")),
+        Field::string_list("columns").non_empty(),
+        Field::bool("header_row").default(DefaultValue::Bool(true)),
+        Field::string("quote_style").default(DefaultValue::Str("necessary")),
+    ];
+}
+
+impl WriteConfig {
+    fn writer(&self) -> Result<RecordWriter> {
+        let dialect = dialect(&self.delimiter, &self.quote)?;
+        let style = QuoteStyle::parse(&self.quote_style).ok_or_else(|| {
+            PluginError::config("quote_style must be necessary or always")
+                .with_detail("field", "quote_style")
+                .with_detail("value", self.quote_style.as_str())
+                .with_detail("supported", QuoteStyle::NAMES.to_vec())
+        })?;
+        if style == QuoteStyle::Always && dialect.quote.is_none() {
+            return Err(PluginError::config("quote_style always needs a quote")
+                .with_detail("field", "quote_style"));
+        }
+        Ok(RecordWriter::new(dialect, style))
+    }
+}
+
+/// The dialect named by the `delimiter` and `quote` keys: one character
+/// each, other than a line break, and not the same one. An empty `quote`
+/// turns quoting off.
+fn dialect(delimiter: &str, quote: &str) -> Result<Dialect> {
+    let usable = |c: char| c != '\r' && c != '\n';
+    let delimiter = single_char(delimiter)
+        .filter(|&c| usable(c))
+        .ok_or_else(|| {
+            PluginError::config("delimiter must be a single character other than a line break")
+                .with_detail("field", "delimiter")
+                .with_detail("value", delimiter)
+        })?;
+    let quote = match quote {
+        "" => None,
+        text => Some(
+            single_char(text)
+                .filter(|&c| usable(c) && c != delimiter)
+                .ok_or_else(|| {
+                    PluginError::config(
+                        "quote must be empty or a single character other than a line break or the delimiter",
+                    )
+                    .with_detail("field", "quote")
+                    .with_detail("value", text)
+                })?,
+        ),
+    };
+    Ok(Dialect { delimiter, quote })
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Bytes for an `OutputStream`, emitted in blocks of `FLUSH_BYTES`.
+struct Sink {
+    out: OutputStream,
+    buf: Vec<u8>,
+}
+
+impl Sink {
+    fn new() -> Sink {
+        Sink {
+            out: OutputStream::new(),
+            buf: Vec::with_capacity(FLUSH_BYTES),
+        }
+    }
+
+    /// Emits the buffer once it is full; call after each row.
+    fn flush_if_full(&mut self) -> Result<()> {
+        if self.buf.len() >= FLUSH_BYTES {
+            self.out.emit(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<OutputStream> {
+        self.out.emit(&self.buf)?;
+        Ok(self.out)
+    }
+}
+
+#[firelynx_plugin]
+fn csv_to_json(request: Request, static_data: StaticData) -> Result<OutputStream> {
+    let config = ReadConfig::from_static_data(&static_data)?;
+    let dialect = dialect(&config.delimiter, &config.quote)?;
+    let max_record_bytes = usize::try_from(config.max_record_bytes).unwrap_or(usize::MAX);
+    let mut rows = JsonRows {
+        sink: Sink::new(),
+        header: config.header()?,
+        names: None,
+        infer_types: config.infer_types,
+        first: true,
+        count: 0,
+    };
+
+    let mut reader = RecordReader::new(dialect, max_record_bytes);
+    let mut emit = |record: &[String], line: u64| rows.record(record, line);
+    let mut chunks = request.body.chunks(DEFAULT_CHUNK_BYTES);
+    while let Some(chunk) = chunks.next_chunk() {
+        reader.feed(chunk, &mut emit)?;
+    }
+    reader.finish(&mut emit)?;
+
+    log_debug!("converted CSV to JSON", rows = rows.count);
+    rows.finish()
+}
+
+/// CSV records written as the elements of a JSON array: objects keyed by
+/// the header's names, or arrays when there is no header.
+struct JsonRows {
+    sink: Sink,
+    header: Header,
+    names: Option<Vec<String>>,
+    infer_types: bool,
+    first: bool,
+    count: u64,
+}
+
+impl JsonRows {
+    fn record(&mut self, record: &[String], line: u64) -> Result<()> {
+        if mem::take(&mut self.first) {
+            let is_header = match self.header {
+                Header::Auto => looks_like_header(record),
+                Header::Present => true,
+                Header::Absent => false,
+            };
+            if is_header {
+                if let Some(name) = repeated(record) {
+                    return Err(
+                        PluginError::invalid_input("The header names a column twice")
+                            .with_detail("column", name)
+                            .with_detail("line", line),
+                    );
+                }
+                self.names = Some(record.to_vec());
+                return Ok(());
+            }
+        }
+
+        let buf = &mut self.sink.buf;
+        buf.push(if self.count == 0 { b'[' } else { b',' });
+        match &self.names {
+            Some(names) => {
+                if record.len() != names.len() {
+                    return Err(PluginError::invalid_input(
+                        "A record does not have a field for every header column",
+                    )
+                    .with_detail("line", line)
+                    .with_detail("fields", record.len())
+                    .with_detail("columns", names.len()));
+                }
+                buf.push(b'{');
+                for (i, (name, field)) in names.iter().zip(record).enumerate() {
+                    if i > 0 {
+                        buf.push(b',');
+                    }
+                    write_string(buf, name)?;
+                    buf.push(b':');
+                    write_value(buf, field, self.infer_types)?;
+                }
+                buf.push(b'}');
+            }
+            None => {
+                buf.push(b'[');
+                for (i, field) in record.iter().enumerate() {
+                    if i > 0 {
+                        buf.push(b',');
+                    }
+                    write_value(buf, field, self.infer_types)?;
+                }
+                buf.push(b']');
+            }
+        }
+        self.count += 1;
+        self.sink.flush_if_full()
+    }
+
+    fn finish(mut self) -> Result<OutputStream> {
+        if self.count == 0 {
+            self.sink.buf.push(b'[');
+        }
+        self.sink.buf.push(b']');
+        self.sink.finish()
+    }
+}
+
+fn looks_like_header(record: &[String]) -> bool {
+    record
+        .iter()
+        .all(|field| !field.trim().is_empty() && !is_number(field))
+        && repeated(record).is_none()
+}
+
+fn repeated(record: &[String]) -> Option<&str> {
+    record
+        .iter()
+        .enumerate()
+        .find(|(i, name)| record[..*i].contains(name))
+        .map(|(_, name)| name.as_str())
+}
+
+/// Whether `field` is a JSON number as written, so `007` and ` 1` stay
+/// strings.
+fn is_number(field: &str) -> bool {
+    field.trim() == field && serde_json::from_str::<serde_json::Number>(field).is_ok()
+}
+
+fn write_value(buf: &mut Vec<u8>, field: &str, infer_types: bool) -> Result<()> {
+    if infer_types && (field == "true" || field == "false" || is_number(field)) {
+        buf.extend_from_slice(field.as_bytes());
+        Ok(())
+    } else {
+        write_string(buf, field)
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, text: &str) -> Result<()> {
+    serde_json::to_writer(buf, text)
+        .map_err(|e| PluginError::internal(format!("Failed to write JSON string: {}", e)))
+}
+
+#[firelynx_plugin]
+fn json_to_csv(request: Request, static_data: StaticData) -> Result<OutputStream> {
+    let config = WriteConfig::from_static_data(&static_data)?;
+    let mut rows = CsvRows {
+        sink: Sink::new(),
+        writer: config.writer()?,
+        fixed_columns: config.columns.is_some(),
+        columns: config.columns,
+        header_row: config.header_row,
+        objects: None,
+        count: 0,
+    };
+
+    // The body is read a chunk at a time and only the current row is
+    // parsed into a `Value`, however long the array.
+    let mut failed = None;
+    let mut de = serde_json::Deserializer::from_reader(BodyReader::new(&request.body));
+    let parsed = (&mut de)
+        .deserialize_seq(RowVisitor {
+            rows: &mut rows,
+            failed: &mut failed,
+        })
+        .and_then(|()| de.end());
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    parsed.map_err(|e| {
+        PluginError::invalid_input(format!("Invalid JSON body: {}", e))
+            .with_detail("line", e.line())
+            .with_detail("column", e.column())
+    })?;
+
+    log_debug!("converted JSON to CSV", rows = rows.count);
+    rows.finish()
+}
+
+/// JSON rows written as CSV records. Rows are all objects, whose values
+/// are looked up by column name, or all arrays, written as they are.
+struct CsvRows {
+    sink: Sink,
+    writer: RecordWriter,
+    /// The `columns` key, or the first object's keys.
+    columns: Option<Vec<String>>,
+    /// Whether `columns` came from the config, which lets rows have keys
+    /// that are not written.
+    fixed_columns: bool,
+    header_row: bool,
+    /// Whether the rows are objects, once the first is read.
+    objects: Option<bool>,
+    count: u64,
+}
+
+impl CsvRows {
+    fn row(&mut self, row: Value) -> Result<()> {
+        let index = self.count;
+        ParseLimits::active().check_value(&row)?;
+        let is_object = match &row {
+            Value::Object(_) => true,
+            Value::Array(_) => false,
+            _ => {
+                return Err(
+                    PluginError::invalid_input("A row is not an object or an array")
+                        .with_detail("index", index),
+                )
+            }
+        };
+        match self.objects {
+            None => {
+                self.objects = Some(is_object);
+                if let (Value::Object(first), None) = (&row, &self.columns) {
+                    self.columns = Some(first.keys().cloned().collect());
+                }
+                self.write_header()?;
+            }
+            Some(objects) if objects != is_object => {
+                return Err(
+                    PluginError::invalid_input("Rows must be all objects or all arrays")
+                        .with_detail("index", index),
+                );
+            }
+            Some(_) => {}
+        }
+
+        let cells: Vec<String> = match row {
+            Value::Object(mut object) => {
+                let columns = self.columns.as_deref().unwrap_or_default();
+                let cells = columns
+                    .iter()
+                    .map(|name| object.remove(name).map(cell).unwrap_or_default())
+                    .collect();
+                if let Some(key) = object.keys().next().filter(|_| !self.fixed_columns) {
+                    return Err(PluginError::invalid_input(
+                        "A row has a key the first row does not; list every column in columns",
+                    )
+                    .with_detail("index", index)
+                    .with_detail("key", key.as_str()));
+                }
+                cells
+            }
+            Value::Array(values) => values.into_iter().map(cell).collect(),
+            _ => unreachable!("checked above"),
+        };
+        self.writer
+            .write(&mut self.sink.buf, cells.iter().map(String::as_str))
+            .map_err(|e| e.with_detail("index", index))?;
+        self.count += 1;
+        self.sink.flush_if_full()
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        match &self.columns {
+            Some(columns) if self.header_row => self
+                .writer
+                .write(&mut self.sink.buf, columns.iter().map(String::as_str)),
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(mut self) -> Result<OutputStream> {
+        // An empty array still gets the configured header.
+        if self.objects.is_none() {
+            self.write_header()?;
+        }
+        self.sink.finish()
+    }
+}
+
+/// A CSV field for a JSON value: strings as they are, `null` empty, and
+/// nested objects and arrays as JSON text.
+fn cell(value: Value) -> String {
+    match value {
+        Value::String(text) => text,
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Hands each element of the body's top-level array to `CsvRows` as soon
+/// as it is parsed. A row's error is kept in `failed`, since serde would
+/// reduce it to a message.
+struct RowVisitor<'a> {
+    rows: &'a mut CsvRows,
+    failed: &'a mut Option<PluginError>,
+}
+
+impl<'de> Visitor<'de> for RowVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of rows")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(row) = seq.next_element::<Value>()? {
+            if let Err(err) = self.rows.row(row) {
+                *self.failed = Some(err);
+                return Err(de::Error::custom("row rejected"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The body as `io::Read`, decoded a chunk at a time.
+struct BodyReader<'b> {
+    chunks: ChunkedReader<'b>,
+    chunk: String,
+    pos: usize,
+}
+
+impl<'b> BodyReader<'b> {
+    fn new(body: &'b Body) -> BodyReader<'b> {
+        BodyReader {
+            chunks: body.chunks(DEFAULT_CHUNK_BYTES),
+            chunk: String::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl io::Read for BodyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            let Some(next) = self.chunks.next_chunk() else {
+                return Ok(0);
+            };
+            self.chunk.clear();
+            self.chunk.push_str(next);
+            self.pos = 0;
+        }
+        let rest = &self.chunk.as_bytes()[self.pos..];
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,151 @@
+//! An incremental CSV parser: text goes in a chunk at a time and each
+//! record comes out as soon as it ends, so only one record is held at once.
+
+use std::mem;
+
+use firelynx_pdk::prelude::*;
+
+/// The characters that give a CSV file its structure.
+#[derive(Debug, Clone, Copy)]
+pub struct Dialect {
+    pub delimiter: char,
+    /// `None` treats quote characters as ordinary text.
+    pub quote: Option<char>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    FieldStart,
+    Unquoted,
+    Quoted,
+    /// Just after a quote inside a quoted field: the end of the field, or
+    /// the first of a doubled quote.
+    QuoteInQuoted,
+}
+
+pub struct RecordReader {
+    dialect: Dialect,
+    max_record_bytes: usize,
+    state: State,
+    field: String,
+    record: Vec<String>,
+    record_bytes: usize,
+    /// The last character read was a CR, so an LF now completes a CRLF.
+    after_cr: bool,
+    /// The line being read, from 1.
+    line: u64,
+    /// The line the current record started on.
+    record_line: u64,
+}
+
+impl RecordReader {
+    pub fn new(dialect: Dialect, max_record_bytes: usize) -> RecordReader {
+        RecordReader {
+            dialect,
+            max_record_bytes,
+            state: State::FieldStart,
+            field: String::new(),
+            record: Vec::new(),
+            record_bytes: 0,
+            after_cr: false,
+            line: 1,
+            record_line: 1,
+        }
+    }
+
+    /// Reads the next piece of the text, calling `emit` with each record it
+    /// completes and the line that record started on. Blank lines are
+    /// skipped.
+    pub fn feed<F>(&mut self, text: &str, emit: &mut F) -> Result<()>
+    where
+        F: FnMut(&[String], u64) -> Result<()>,
+    {
+        for c in text.chars() {
+            let crlf = c == '\n' && self.after_cr;
+            self.after_cr = c == '\r';
+            // The CR already ended the record and the line.
+            if crlf && self.state != State::Quoted {
+                continue;
+            }
+            self.read(c, emit)?;
+            if c == '\r' || (c == '\n' && !crlf) {
+                self.line += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the text. The last record does not need a line break after it.
+    pub fn finish<F>(mut self, emit: &mut F) -> Result<()>
+    where
+        F: FnMut(&[String], u64) -> Result<()>,
+    {
+        if self.state == State::Quoted {
+            return Err(PluginError::invalid_input("A quoted field is not closed")
+                .with_detail("line", self.record_line));
+        }
+        self.end_record(emit)
+    }
+
+    fn read<F>(&mut self, c: char, emit: &mut F) -> Result<()>
+    where
+        F: FnMut(&[String], u64) -> Result<()>,
+    {
+        let is_quote = self.dialect.quote == Some(c);
+        let is_line_break = c == '\n' || c == '\r';
+        if self.state == State::FieldStart && self.record.is_empty() {
+            self.record_line = self.line;
+        }
+        // Delimiters count too, so a record of empty fields is bounded.
+        self.record_bytes += c.len_utf8();
+        if self.record_bytes > self.max_record_bytes {
+            return Err(PluginError::too_large(format!(
+                "A record is longer than max_record_bytes ({} bytes)",
+                self.max_record_bytes
+            ))
+            .with_detail("max_record_bytes", self.max_record_bytes)
+            .with_detail("line", self.record_line));
+        }
+        match self.state {
+            State::Quoted if is_quote => self.state = State::QuoteInQuoted,
+            State::Quoted => self.field.push(c),
+            State::QuoteInQuoted if is_quote => {
+                self.field.push(c);
+                self.state = State::Quoted;
+            }
+            State::QuoteInQuoted if c != self.dialect.delimiter && !is_line_break => {
+                return Err(PluginError::invalid_input(
+                    "A quoted field has text after its closing quote",
+                )
+                .with_detail("line", self.line));
+            }
+            State::FieldStart if is_quote => self.state = State::Quoted,
+            _ if c == self.dialect.delimiter => self.end_field(),
+            _ if is_line_break => self.end_record(emit)?,
+            _ => {
+                self.field.push(c);
+                self.state = State::Unquoted;
+            }
+        }
+        Ok(())
+    }
+
+    fn end_field(&mut self) {
+        self.record.push(mem::take(&mut self.field));
+        self.state = State::FieldStart;
+    }
+
+    fn end_record<F>(&mut self, emit: &mut F) -> Result<()>
+    where
+        F: FnMut(&[String], u64) -> Result<()>,
+    {
+        let blank = self.state == State::FieldStart && self.record.is_empty();
+        self.record_bytes = 0;
+        if !blank {
+            self.end_field();
+            emit(&self.record, self.record_line)?;
+            self.record.clear();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+//! CSV output, a record at a time.
+
+use firelynx_pdk::prelude::*;
+
+use crate::reader::Dialect;
+
+/// Which fields are put in quotes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+    /// Only fields holding the delimiter, the quote or a line break.
+    Necessary,
+    Always,
+}
+
+impl QuoteStyle {
+    pub const NAMES: [&'static str; 2] = ["necessary", "always"];
+
+    pub fn parse(name: &str) -> Option<QuoteStyle> {
+        match name {
+            "necessary" => Some(QuoteStyle::Necessary),
+            "always" => Some(QuoteStyle::Always),
+            _ => None,
+        }
+    }
+}
+
+pub struct RecordWriter {
+    dialect: Dialect,
+    style: QuoteStyle,
+}
+
+impl RecordWriter {
+    pub fn new(dialect: Dialect, style: QuoteStyle) -> RecordWriter {
+        RecordWriter { dialect, style }
+    }
+
+    /// Appends `fields` to `out` as one record ending in CRLF. A field
+    /// that needs quoting when `quote` is empty is an `INVALID_INPUT`
+    /// whose message names it; the caller adds where it came from.
+    pub fn write<'f>(
+        &self,
+        out: &mut Vec<u8>,
+        fields: impl IntoIterator<Item = &'f str>,
+    ) -> Result<()> {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                push_char(out, self.dialect.delimiter);
+            }
+            let needs_quotes = self.style == QuoteStyle::Always
+                || field.contains(|c| {
+                    c == self.dialect.delimiter
+                        || c == '\r'
+                        || c == '\n'
+                        || Some(c) == self.dialect.quote
+                });
+            match self.dialect.quote {
+                Some(quote) if needs_quotes => {
+                    push_char(out, quote);
+                    for c in field.chars() {
+                        if c == quote {
+                            push_char(out, quote);
+                        }
+                        push_char(out, c);
+                    }
+                    push_char(out, quote);
+                }
+                None if needs_quotes => {
+                    return Err(PluginError::invalid_input(
+                        "A value holds the delimiter or a line break and quote is empty",
+                    )
+                    .with_detail("value", field));
+                }
+                _ => out.extend_from_slice(field.as_bytes()),
+            }
+        }
+        out.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
@@ -0,0 +1,117 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn csv(body: &str) -> RequestFixture {
+    RequestFixture::new(body)
+        .without_header("Content-Type")
+        .header("Content-Type", "text/csv")
+}
+
+fn to_json(input: &str) -> Result<Value, Error> {
+    let Json(rows): Json<Value> = xtp_test::call("CsvToJson", input)?;
+    Ok(rows)
+}
+
+fn to_csv(input: &str) -> Result<String, Error> {
+    xtp_test::call("JsonToCsv", input)
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("CSV to JSON", || {
+        xtp_test::assert_eq!(
+            "a header names the columns",
+            to_json(&csv("name,born\r\nAda,1815\r\nGrace,1906\r\n").to_json())?,
+            json!([{"name": "Ada", "born": "1815"}, {"name": "Grace", "born": "1906"}])
+        );
+        xtp_test::assert_eq!(
+            "a numeric first record is not a header",
+            to_json(&csv("1,2\n3,4").to_json())?,
+            json!([["1", "2"], ["3", "4"]])
+        );
+        xtp_test::assert_eq!(
+            "quoted fields hold delimiters, quotes and line breaks",
+            to_json(&csv("a,b\n\"x, \"\"y\"\"\",\"one\ntwo\"\n").to_json())?,
+            json!([{"a": "x, \"y\"", "b": "one\ntwo"}])
+        );
+
+        let input = csv("id;active;zip\n7;true;007")
+            .static_data("delimiter", ";")
+            .static_data("infer_types", true)
+            .to_json();
+        xtp_test::assert_eq!(
+            "types are inferred on request",
+            to_json(&input)?,
+            json!([{"id": 7, "active": true, "zip": "007"}])
+        );
+
+        let input = csv("x\n1").static_data("header", "absent").to_json();
+        xtp_test::assert_eq!(
+            "the header can be declared absent",
+            to_json(&input)?,
+            json!([["x"], ["1"]])
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("JSON to CSV", || {
+        let input = RequestFixture::new(
+            r#"[{"name": "Ada", "note": "a,b"}, {"name": "Grace", "note": null}]"#,
+        )
+        .to_json();
+        xtp_test::assert_eq!(
+            "objects get a header from the first row",
+            to_csv(&input)?,
+            "name,note\r\nAda,\"a,b\"\r\nGrace,\r\n".to_string()
+        );
+
+        let input = RequestFixture::new(r#"[{"id": 1, "secret": "x"}]"#)
+            .static_data("columns", json!(["id"]))
+            .static_data("header_row", false)
+            .to_json();
+        xtp_test::assert_eq!("columns select keys", to_csv(&input)?, "1\r\n".to_string());
+
+        let input = RequestFixture::new(r#"[[1, "a"], [2, "b"]]"#)
+            .static_data("delimiter", "\t")
+            .static_data("quote_style", "always")
+            .to_json();
+        xtp_test::assert_eq!(
+            "arrays are written as they are",
+            to_csv(&input)?,
+            "\"1\"\t\"a\"\r\n\"2\"\t\"b\"\r\n".to_string()
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("errors", || {
+        xtp_test::assert!(
+            "a short record is rejected",
+            xtp_test::call::<Json<Value>>("CsvToJson", csv("a,b\n1").to_json()).is_err()
+        );
+        xtp_test::assert!(
+            "an unclosed quote is rejected",
+            xtp_test::call::<Json<Value>>("CsvToJson", csv("a\n\"1").to_json()).is_err()
+        );
+        xtp_test::assert!(
+            "a body that is not an array is rejected",
+            to_csv(&RequestFixture::new(r#"{"id": 1}"#).to_json()).is_err()
+        );
+        xtp_test::assert!(
+            "mixed rows are rejected",
+            to_csv(&RequestFixture::new(r#"[{"id": 1}, [2]]"#).to_json()).is_err()
+        );
+        xtp_test::assert!(
+            "a two-character delimiter is rejected",
+            to_csv(
+                &RequestFixture::new("[]")
+                    .static_data("delimiter", "::")
+                    .to_json()
+            )
+            .is_err()
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! Times the `CountCharacters` export of char_counter builds that differ only
+//! in their JSON envelope parser, over a matrix of body shapes and sizes.
+//!
+//! ```text
+//! envelope-bench serde_json=path/to/plugin.wasm simd-json=path/to/plugin.wasm
+//! ```
+//!
+//! Prints a Markdown table of the median time per call for each build.
+
+use std::time::{Duration, Instant};
+
+use extism::{Manifest, Plugin, Wasm};
+use firelynx_fixture::RequestFixture;
+
+const FUNCTION: &str = "CountCharacters";
+
+/// Bytes of body pushed through each build per case; smaller bodies get
+/// more calls.
+const BYTES_PER_CASE: usize = 64 * 1024 * 1024;
+const MIN_CALLS: usize = 20;
+const MAX_CALLS: usize = 5_000;
+const WARMUP_CALLS: usize = 5;
+
+const SIZES: [(&str, usize); 3] = [
+    ("1 KiB", 1024),
+    ("64 KiB", 64 * 1024),
+    ("1 MiB", 1024 * 1024),
+];
+
+/// Body generators, each repeating a fragment to the wanted size.
+const SHAPES: [(&str, &str); 3] = [
+    (
+        "ascii text",
+        "The quick brown fox jumps over the lazy dog. ",
+    ),
+    (
+        "escaped JSON",
+        r#"{"id": 17, "tags": ["a", "b"], "note": "line\nbreak \"quoted\""}, "#,
+    ),
+    ("non-ASCII text", "Grüße, 世界! Ça va? 😀 "),
+];
+
+fn main() {
+    let builds: Vec<(String, Plugin)> = std::env::args()
+        .skip(1)
+        .map(|arg| {
+            let (label, path) = arg
+                .split_once('=')
+                .unwrap_or_else(|| panic!("expected label=path.wasm, got {}", arg));
+            let manifest = Manifest::new([Wasm::file(path)]);
+            let plugin = Plugin::new(&manifest, [], true)
+                .unwrap_or_else(|e| panic!("failed to load {}: {}", path, e));
+            (label.to_string(), plugin)
+        })
+        .collect();
+    if builds.is_empty() {
+        eprintln!("usage: envelope-bench label=plugin.wasm [label=plugin.wasm ...]");
+        std::process::exit(2);
+    }
+
+    let labels: Vec<&str> = builds.iter().map(|(label, _)| label.as_str()).collect();
+    println!("| body | size | {} |", labels.join(" | "));
+    println!("|---|---|{}", "---:|".repeat(labels.len()));
+
+    let mut builds = builds;
+    for (shape, fragment) in SHAPES {
+        for (size_label, size) in SIZES {
+            let body = fragment.repeat(size / fragment.len() + 1);
+            let body = truncate_to_char_boundary(&body, size);
+            let input = RequestFixture::new(body).empty_static_data().to_json();
+            let calls = (BYTES_PER_CASE / size).clamp(MIN_CALLS, MAX_CALLS);
+
+            let mut cells = Vec::new();
+            for (label, plugin) in &mut builds {
+                let median = median_call(plugin, input.as_bytes(), calls)
+                    .unwrap_or_else(|e| panic!("{} failed on {}: {}", label, shape, e));
+                cells.push(format_duration(median));
+            }
+            println!("| {} | {} | {} |", shape, size_label, cells.join(" | "));
+        }
+    }
+}
+
+fn median_call(plugin: &mut Plugin, input: &[u8], calls: usize) -> Result<Duration, extism::Error> {
+    for _ in 0..WARMUP_CALLS {
+        plugin.call::<&[u8], &[u8]>(FUNCTION, input)?;
+    }
+    let mut times = Vec::with_capacity(calls);
+    for _ in 0..calls {
+        let started = Instant::now();
+        plugin.call::<&[u8], &[u8]>(FUNCTION, input)?;
+        times.push(started.elapsed());
+    }
+    times.sort();
+    Ok(times[times.len() / 2])
+}
+
+fn truncate_to_char_boundary(s: &str, max: usize) -> &str {
+    let mut end = max.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+fn format_duration(d: Duration) -> String {
+    let us = d.as_secs_f64() * 1e6;
+    if us >= 1000.0 {
+        format!("{:.2} ms", us / 1000.0)
+    } else {
+        format!("{:.1} µs", us)
+    }
+}
@@ -0,0 +1,216 @@
+//! Builds the go-polyscript input envelope that the firelynx host sends to
+//! WASM plugins, so plugin tests, xtp-test crates and the SDK's own tests all
+//! construct the same shape.
+//!
+//! ```ignore
+//! let input = RequestFixture::new("Hello World")
+//!     .method("GET")
+//!     .query("page", "2")
+//!     .static_data("case_sensitive", true)
+//!     .to_json();
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+/// A request envelope under construction. Defaults mirror a JSON `POST` to
+/// `http://localhost:8080/api/demo` from `[::1]:12345`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestFixture {
+    body: String,
+    method: String,
+    headers: BTreeMap<String, Vec<String>>,
+    query: Vec<(String, String)>,
+    scheme: String,
+    host: String,
+    path: String,
+    remote_addr: String,
+    static_data: Option<Map<String, Value>>,
+}
+
+impl RequestFixture {
+    pub fn new(body: impl Into<String>) -> Self {
+        let mut headers = BTreeMap::new();
+        headers.insert(
+            "Content-Type".to_string(),
+            vec!["application/json".to_string()],
+        );
+        headers.insert("User-Agent".to_string(), vec!["xtp-test/1.0".to_string()]);
+        RequestFixture {
+            body: body.into(),
+            method: "POST".to_string(),
+            headers,
+            query: Vec::new(),
+            scheme: "http".to_string(),
+            host: "localhost:8080".to_string(),
+            path: "/api/demo".to_string(),
+            remote_addr: "[::1]:12345".to_string(),
+            static_data: None,
+        }
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.method = method.into();
+        self
+    }
+
+    /// Adds a header value. Repeated calls with the same name append, as
+    /// repeated header lines do.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers
+            .entry(name.into())
+            .or_default()
+            .push(value.into());
+        self
+    }
+
+    /// Removes all values of a header, including the defaults.
+    pub fn without_header(mut self, name: &str) -> Self {
+        self.headers.remove(name);
+        self
+    }
+
+    /// Adds a query parameter. Values are used verbatim in `RawQuery`, so pass
+    /// them already percent-encoded where needed.
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.scheme = scheme.into();
+        self
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn remote_addr(mut self, remote_addr: impl Into<String>) -> Self {
+        self.remote_addr = remote_addr.into();
+        self
+    }
+
+    /// Sets a `static_data` key. The `static_data` object is only emitted once
+    /// at least one key is set, as the host omits it for routes without any.
+    pub fn static_data(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.static_data
+            .get_or_insert_with(Map::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Emits `static_data` even if no key is set, as `{}`: a route that
+    /// configures the table but takes every default.
+    pub fn empty_static_data(mut self) -> Self {
+        self.static_data.get_or_insert_with(Map::new);
+        self
+    }
+
+    pub fn to_value(&self) -> Value {
+        let raw_query = self
+            .query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut query_params: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (k, v) in &self.query {
+            query_params.entry(k).or_default().push(v);
+        }
+        let url_string = if raw_query.is_empty() {
+            self.path.clone()
+        } else {
+            format!("{}?{}", self.path, raw_query)
+        };
+
+        let mut envelope = json!({
+            "request": {
+                "Body": self.body,
+                "Headers": self.headers,
+                "QueryParams": query_params,
+                "Method": self.method,
+                "Proto": "HTTP/1.1",
+                "Host": self.host,
+                "RemoteAddr": self.remote_addr,
+                "ContentLength": self.body.len(),
+                "URL": {
+                    "Scheme": self.scheme,
+                    "Path": self.path,
+                    "Host": self.host,
+                    "RawQuery": raw_query,
+                    "Fragment": ""
+                },
+                "URL_Path": self.path,
+                "URL_Scheme": self.scheme,
+                "URL_Host": self.host,
+                "URL_String": url_string
+            }
+        });
+        if let Some(static_data) = &self.static_data {
+            envelope["static_data"] = Value::Object(static_data.clone());
+        }
+        envelope
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_a_host_envelope() {
+        let envelope = RequestFixture::new("Hello").to_value();
+        let request = &envelope["request"];
+        assert_eq!(request["Body"], "Hello");
+        assert_eq!(request["Method"], "POST");
+        assert_eq!(request["ContentLength"], 5);
+        assert_eq!(
+            request["Headers"]["Content-Type"],
+            json!(["application/json"])
+        );
+        assert_eq!(request["URL"]["Path"], "/api/demo");
+        assert_eq!(request["URL_String"], "/api/demo");
+        assert!(envelope.get("static_data").is_none());
+
+        let envelope = RequestFixture::new("Hello").empty_static_data().to_value();
+        assert_eq!(envelope["static_data"], json!({}));
+    }
+
+    #[test]
+    fn builder_sets_query_headers_and_static_data() {
+        let envelope = RequestFixture::new("")
+            .method("GET")
+            .path("/search")
+            .query("q", "a")
+            .query("q", "b")
+            .header("Accept", "text/html")
+            .header("Accept", "*/*")
+            .without_header("User-Agent")
+            .static_data("search_characters", "xyz")
+            .static_data("case_sensitive", true)
+            .to_value();
+        let request = &envelope["request"];
+        assert_eq!(request["Method"], "GET");
+        assert_eq!(request["QueryParams"]["q"], json!(["a", "b"]));
+        assert_eq!(request["URL"]["RawQuery"], "q=a&q=b");
+        assert_eq!(request["URL_String"], "/search?q=a&q=b");
+        assert_eq!(request["Headers"]["Accept"], json!(["text/html", "*/*"]));
+        assert!(request["Headers"].get("User-Agent").is_none());
+        assert_eq!(
+            envelope["static_data"],
+            json!({"search_characters": "xyz", "case_sensitive": true})
+        );
+    }
+}
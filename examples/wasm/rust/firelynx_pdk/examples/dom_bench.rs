@@ -0,0 +1,129 @@
+//! Compares `Document` with `serde_json::Value` on a parse, rewrite and
+//! serialize round trip: every record's `email` is redacted and a field is
+//! added, as a transformation plugin would.
+//!
+//! ```text
+//! cargo run --release --example dom_bench
+//! ```
+//!
+//! Prints a Markdown table of the median time and the allocations per round
+//! trip. Runs natively; the allocation counts carry over to wasm32, the
+//! times only roughly.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use firelynx_pdk::dom::Document;
+use serde_json::{json, Value};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const RECORDS: [usize; 3] = [10, 1_000, 50_000];
+const RUNS: usize = 21;
+
+fn main() {
+    println!("| records | body | Value | Document | Value allocs | Document allocs |");
+    println!("|---:|---:|---:|---:|---:|---:|");
+    for records in RECORDS {
+        let body = body(records);
+        let (value_time, value_allocs) = measure(|| with_value(&body));
+        let (doc_time, doc_allocs) = measure(|| with_document(&body));
+        assert_eq!(
+            serde_json::from_str::<Value>(&with_value(&body)).unwrap(),
+            serde_json::from_str::<Value>(&with_document(&body)).unwrap()
+        );
+        println!(
+            "| {} | {} KiB | {} | {} | {} | {} |",
+            records,
+            body.len() / 1024,
+            format_duration(value_time),
+            format_duration(doc_time),
+            value_allocs,
+            doc_allocs
+        );
+    }
+}
+
+fn with_value(body: &str) -> String {
+    let mut value: Value = serde_json::from_str(body).unwrap();
+    for record in value["records"].as_array_mut().unwrap() {
+        record["email"] = Value::String("[redacted]".to_string());
+        record["reviewed"] = Value::Bool(true);
+    }
+    serde_json::to_string(&value).unwrap()
+}
+
+fn with_document(body: &str) -> String {
+    let mut doc = Document::parse(body).unwrap();
+    let records = doc.pointer("/records").unwrap();
+    let ids: Vec<_> = doc.children(records).collect();
+    for record in ids {
+        let redacted = doc.add_str("[redacted]");
+        doc.insert(record, "email", redacted);
+        let reviewed = doc.add(&Value::Bool(true));
+        doc.insert(record, "reviewed", reviewed);
+    }
+    doc.to_json()
+}
+
+/// Median time and allocation count of `run`.
+fn measure(mut run: impl FnMut() -> String) -> (Duration, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    run();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            run();
+            started.elapsed()
+        })
+        .collect();
+    times.sort();
+    (times[RUNS / 2], allocs)
+}
+
+fn body(records: usize) -> String {
+    let records: Vec<Value> = (0..records)
+        .map(|i| {
+            json!({
+                "id": i,
+                "name": format!("user {}", i),
+                "email": format!("user{}@example.com", i),
+                "roles": ["reader", "writer"],
+                "profile": {"bio": "Likes \"quoted\" text\nand newlines", "score": i as f64 / 3.0},
+            })
+        })
+        .collect();
+    json!({ "records": records }).to_string()
+}
+
+fn format_duration(d: Duration) -> String {
+    let us = d.as_secs_f64() * 1e6;
+    if us >= 1000.0 {
+        format!("{:.2} ms", us / 1000.0)
+    } else {
+        format!("{:.1} µs", us)
+    }
+}
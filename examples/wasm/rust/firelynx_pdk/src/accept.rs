@@ -0,0 +1,218 @@
+//! `Accept` header parsing and content negotiation (RFC 9110, section 12.5.1).
+
+/// A single media range from an `Accept` header, e.g. `text/*;q=0.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    pub type_: String,
+    pub subtype: String,
+    /// Parameters other than `q`, lowercased names in header order.
+    pub params: Vec<(String, String)>,
+    /// Relative weight in the range 0.0..=1.0 (defaults to 1.0).
+    pub q: f32,
+}
+
+impl MediaRange {
+    /// Parses one comma-separated element of an `Accept` header.
+    /// Returns `None` for elements that are not a valid `type/subtype`.
+    pub fn parse(s: &str) -> Option<MediaRange> {
+        let mut parts = s.split(';');
+        let (type_, subtype) = parts.next()?.trim().split_once('/')?;
+        let (type_, subtype) = (type_.trim(), subtype.trim());
+        if type_.is_empty() || subtype.is_empty() || (type_ == "*" && subtype != "*") {
+            return None;
+        }
+
+        let mut params = Vec::new();
+        let mut q = 1.0;
+        for param in parts {
+            let Some((name, value)) = param.split_once('=') else {
+                continue;
+            };
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().trim_matches('"');
+            if name == "q" {
+                // Drop ranges with a malformed weight rather than silently
+                // promoting them to q=1.
+                q = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|q| (0.0..=1.0).contains(q))?;
+            } else {
+                params.push((name, value.to_string()));
+            }
+        }
+
+        Some(MediaRange {
+            type_: type_.to_ascii_lowercase(),
+            subtype: subtype.to_ascii_lowercase(),
+            params,
+            q,
+        })
+    }
+
+    /// Returns the specificity of this range when it matches `media_type`,
+    /// or `None` when it does not match. Higher is more specific:
+    /// `*/*` < `type/*` < `type/subtype` < `type/subtype;param=...`.
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        let mut parts = media_type.split(';');
+        let (type_, subtype) = parts.next()?.trim().split_once('/')?;
+        let offered: Vec<(String, String)> = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(n, v)| {
+                (
+                    n.trim().to_ascii_lowercase(),
+                    v.trim().trim_matches('"').to_string(),
+                )
+            })
+            .collect();
+
+        if self.type_ == "*" {
+            return Some(0);
+        }
+        if !self.type_.eq_ignore_ascii_case(type_.trim()) {
+            return None;
+        }
+        if self.subtype == "*" {
+            return Some(1);
+        }
+        if !self.subtype.eq_ignore_ascii_case(subtype.trim()) {
+            return None;
+        }
+        if self.params.is_empty() {
+            return Some(2);
+        }
+        let all_present = self.params.iter().all(|(name, value)| {
+            offered
+                .iter()
+                .any(|(n, v)| n == name && v.eq_ignore_ascii_case(value))
+        });
+        all_present.then_some(3)
+    }
+}
+
+/// A parsed `Accept` header.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Accept {
+    /// Media ranges sorted by descending `q`; ties keep header order.
+    pub ranges: Vec<MediaRange>,
+}
+
+impl Accept {
+    /// Parses an `Accept` header value. Invalid elements are skipped.
+    pub fn parse(header: &str) -> Accept {
+        let mut ranges: Vec<MediaRange> = header.split(',').filter_map(MediaRange::parse).collect();
+        // sort_by is stable, so equal weights keep the client's ordering.
+        ranges.sort_by(|a, b| b.q.total_cmp(&a.q));
+        Accept { ranges }
+    }
+
+    /// Returns the quality the client assigns to `media_type`, using the most
+    /// specific matching range; among equally specific ranges the highest
+    /// `q` wins. Returns 0.0 when nothing matches.
+    pub fn quality(&self, media_type: &str) -> f32 {
+        if self.ranges.is_empty() {
+            // No (or an unusable) Accept header means any media type is acceptable.
+            return 1.0;
+        }
+        // `ranges` is sorted by descending q and max_by_key returns the last
+        // of equal maxima, so walk it backwards to keep the first.
+        self.ranges
+            .iter()
+            .rev()
+            .filter_map(|r| r.specificity(media_type).map(|s| (s, r.q)))
+            .max_by_key(|(s, _)| *s)
+            .map(|(_, q)| q)
+            .unwrap_or(0.0)
+    }
+
+    /// Picks the best of the server's `offers` for this client. Offers are
+    /// listed in server preference order, which breaks ties between equal
+    /// weights. Returns `None` when no offer is acceptable (HTTP 406).
+    pub fn negotiate<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&'a str, f32)> = None;
+        for offer in offers {
+            let q = self.quality(offer);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((offer, q));
+            }
+        }
+        best.map(|(offer, _)| offer)
+    }
+}
+
+/// Convenience wrapper for `Accept::parse(header).negotiate(offers)`.
+/// A missing header accepts the first offer.
+pub fn negotiate<'a>(header: Option<&str>, offers: &[&'a str]) -> Option<&'a str> {
+    match header {
+        Some(h) => Accept::parse(h).negotiate(offers),
+        None => offers.first().copied(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_orders_by_q_and_keeps_header_order_for_ties() {
+        let accept = Accept::parse("text/plain;q=0.5, application/json, text/html");
+        let types: Vec<_> = accept.ranges.iter().map(|r| r.subtype.as_str()).collect();
+        assert_eq!(types, ["json", "html", "plain"]);
+    }
+
+    #[test]
+    fn parse_skips_invalid_elements() {
+        let accept = Accept::parse("garbage, */json, text/plain;q=2, text/html;q=0.8");
+        assert_eq!(accept.ranges.len(), 1);
+        assert_eq!(accept.ranges[0].subtype, "html");
+        assert_eq!(accept.ranges[0].q, 0.8);
+    }
+
+    #[test]
+    fn most_specific_range_wins() {
+        let accept = Accept::parse("text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.1");
+        assert_eq!(accept.quality("text/html;level=1"), 1.0);
+        assert_eq!(accept.quality("text/html"), 0.7);
+        assert_eq!(accept.quality("text/plain"), 0.3);
+        assert_eq!(accept.quality("image/png"), 0.1);
+    }
+
+    #[test]
+    fn equally_specific_ranges_use_the_highest_q() {
+        let accept = Accept::parse("text/html;q=0.2, text/html, text/*;q=0.4, text/*;q=0.9");
+        assert_eq!(accept.quality("text/html"), 1.0);
+        assert_eq!(accept.quality("text/plain"), 0.9);
+    }
+
+    #[test]
+    fn negotiate_picks_highest_weight_then_server_order() {
+        let accept = Accept::parse("text/*;q=0.5, application/json");
+        assert_eq!(
+            accept.negotiate(&["text/plain", "application/json"]),
+            Some("application/json")
+        );
+        assert_eq!(
+            accept.negotiate(&["text/csv", "text/plain"]),
+            Some("text/csv")
+        );
+    }
+
+    #[test]
+    fn negotiate_rejects_q_zero() {
+        let accept = Accept::parse("application/json;q=0, */*");
+        assert_eq!(
+            accept.negotiate(&["application/json", "text/plain"]),
+            Some("text/plain")
+        );
+        assert_eq!(accept.negotiate(&["application/json"]), None);
+    }
+
+    #[test]
+    fn missing_or_empty_header_accepts_first_offer() {
+        assert_eq!(
+            negotiate(None, &["application/json", "text/plain"]),
+            Some("application/json")
+        );
+        assert_eq!(negotiate(Some(""), &["text/plain"]), Some("text/plain"));
+    }
+}
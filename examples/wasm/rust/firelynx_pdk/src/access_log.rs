@@ -0,0 +1,224 @@
+//! Access-log records in the shape of the host's logger middleware.
+//!
+//! A middleware plugin that takes over a request (answering from a cache,
+//! say) can write the record the host's logger middleware would have,
+//! through the log binding, so both land in one consistent stream:
+//!
+//! ```ignore
+//! AccessLogEntry::from_request(&request)
+//!     .client_ip(request.client_ip(&trusted)?)
+//!     .route("api-cache")
+//!     .status(304)
+//!     .response_bytes(0)
+//!     .duration(started.elapsed())
+//!     .emit();
+//! ```
+//!
+//! Keys match the host's console logger: `method`, `path`, `client_ip`,
+//! `query`, `protocol`, `host`, `scheme`, `status`, `duration` (in
+//! nanoseconds, as its JSON handler writes it) and `body_size` under
+//! `request` and `response`. A 5xx is logged at warn, anything else at
+//! info, as the host does. `route` has no host counterpart and is only
+//! written when set.
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::input::Request;
+use crate::log::{self, Level};
+
+/// One access-log record. Fields left unset are omitted from the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry {
+    message: String,
+    method: String,
+    path: String,
+    client_ip: String,
+    query: String,
+    protocol: String,
+    host: String,
+    scheme: String,
+    status: u16,
+    duration: Option<Duration>,
+    route: Option<String>,
+    request_bytes: Option<u64>,
+    response_bytes: Option<u64>,
+}
+
+impl AccessLogEntry {
+    /// Starts a record for `request` with status 200. The client IP is the
+    /// direct peer; pass the result of `Request::client_ip` to
+    /// `client_ip` when the plugin sits behind proxies it trusts.
+    pub fn from_request(request: &Request) -> AccessLogEntry {
+        let client_ip = match request.peer() {
+            Ok(peer) => peer.ip.to_string(),
+            Err(_) => request.remote_addr.clone(),
+        };
+        let scheme = match request.url.scheme.as_str() {
+            "" => "http".to_string(),
+            scheme => scheme.to_ascii_lowercase(),
+        };
+        AccessLogEntry {
+            message: "access".to_string(),
+            method: request.method.clone(),
+            path: request.url.path.clone(),
+            client_ip,
+            query: request.url.raw_query.clone(),
+            protocol: request.proto.clone(),
+            host: request.host.clone(),
+            scheme,
+            status: 200,
+            duration: None,
+            route: None,
+            request_bytes: u64::try_from(request.content_length).ok(),
+            response_bytes: None,
+        }
+    }
+
+    /// The log message. The host uses the logger middleware's id; set the
+    /// same id to have plugin records grouped with the host's.
+    pub fn message(mut self, message: impl Into<String>) -> AccessLogEntry {
+        self.message = message.into();
+        self
+    }
+
+    pub fn client_ip(mut self, ip: impl ToString) -> AccessLogEntry {
+        self.client_ip = ip.to_string();
+        self
+    }
+
+    pub fn route(mut self, route: impl Into<String>) -> AccessLogEntry {
+        self.route = Some(route.into());
+        self
+    }
+
+    pub fn status(mut self, status: u16) -> AccessLogEntry {
+        self.status = status;
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> AccessLogEntry {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Size of the response body sent to the client.
+    pub fn response_bytes(mut self, bytes: u64) -> AccessLogEntry {
+        self.response_bytes = Some(bytes);
+        self
+    }
+
+    /// Warn for a 5xx, info otherwise.
+    pub fn level(&self) -> Level {
+        if self.status >= 500 {
+            Level::Warn
+        } else {
+            Level::Info
+        }
+    }
+
+    /// The record's fields in the host's order.
+    pub fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut fields = vec![
+            ("method", json!(self.method)),
+            ("path", json!(self.path)),
+            ("client_ip", json!(self.client_ip)),
+        ];
+        if !self.query.is_empty() {
+            fields.push(("query", json!(self.query)));
+        }
+        fields.push(("protocol", json!(self.protocol)));
+        fields.push(("host", json!(self.host)));
+        fields.push(("scheme", json!(self.scheme)));
+        if let Some(route) = &self.route {
+            fields.push(("route", json!(route)));
+        }
+        fields.push(("status", json!(self.status)));
+        if let Some(duration) = self.duration {
+            // Saturates after ~584 years, like Go's time.Duration.
+            let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+            fields.push(("duration", json!(nanos)));
+        }
+        if let Some(bytes) = self.request_bytes {
+            fields.push(("request", json!({ "body_size": bytes })));
+        }
+        if let Some(bytes) = self.response_bytes {
+            fields.push(("response", json!({ "body_size": bytes })));
+        }
+        fields
+    }
+
+    /// Writes the record through the log binding.
+    pub fn emit(&self) {
+        let level = self.level();
+        if log::enabled(level) {
+            log::write(level, &self.message, &self.fields());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> Request {
+        let mut req = Request {
+            method: "GET".to_string(),
+            proto: "HTTP/1.1".to_string(),
+            host: "api.example.com".to_string(),
+            remote_addr: "10.0.0.7:51234".to_string(),
+            content_length: 0,
+            ..Request::default()
+        };
+        req.url.path = "/v1/items".to_string();
+        req.url.raw_query = "page=2".to_string();
+        req
+    }
+
+    #[test]
+    fn mirrors_the_host_record() {
+        let entry = AccessLogEntry::from_request(&request())
+            .status(503)
+            .duration(Duration::from_micros(1500))
+            .response_bytes(42);
+        assert!(matches!(entry.level(), Level::Warn));
+        let line = log::record("access", &entry.fields());
+        assert_eq!(
+            line,
+            concat!(
+                r#"{"msg":"access","method":"GET","path":"/v1/items","client_ip":"10.0.0.7","#,
+                r#""query":"page=2","protocol":"HTTP/1.1","host":"api.example.com","#,
+                r#""scheme":"http","status":503,"duration":1500000,"#,
+                r#""request":{"body_size":0},"response":{"body_size":42}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn omits_what_is_unknown() {
+        let mut req = request();
+        req.url.raw_query.clear();
+        req.content_length = -1;
+        req.remote_addr = "@".to_string();
+        let entry = AccessLogEntry::from_request(&req)
+            .route("cache")
+            .client_ip("203.0.113.9".parse::<std::net::IpAddr>().unwrap());
+        assert!(matches!(entry.level(), Level::Info));
+        let keys: Vec<_> = entry.fields().into_iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            [
+                "method",
+                "path",
+                "client_ip",
+                "protocol",
+                "host",
+                "scheme",
+                "route",
+                "status"
+            ]
+        );
+        assert_eq!(entry.fields()[2].1, "203.0.113.9");
+    }
+}
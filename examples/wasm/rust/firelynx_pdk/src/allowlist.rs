@@ -0,0 +1,260 @@
+//! Destination allow-list checks for outbound calls.
+//!
+//! The firelynx host enforces its own allow-list for extism HTTP calls; this is
+//! a second, plugin-side check so a misconfigured host does not turn a plugin
+//! into an SSRF proxy. Rules usually come from the route's `static_data`:
+//!
+//! ```toml
+//! [endpoints.routes.static_data.outbound]
+//! hosts = ["api.example.com", "*.internal.example.com"]
+//! schemes = ["https"]
+//! ports = [443]
+//! ```
+
+use serde::Deserialize;
+
+use crate::PluginError;
+
+/// Allowed outbound destinations. An empty `hosts` list allows nothing.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AllowList {
+    /// Exact host names, or `*.suffix` patterns matching any subdomain of `suffix`.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Allowed URL schemes. Empty means `http` and `https`.
+    #[serde(default)]
+    pub schemes: Vec<String>,
+    /// Allowed ports. Empty means the scheme's default port only.
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// Allow literal IP addresses as hosts. Off by default so rules can only
+    /// name DNS hosts, never a raw address an attacker picked.
+    #[serde(default)]
+    pub allow_ip_literals: bool,
+}
+
+/// The parts of a URL the allow-list looks at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Destination {
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Destination {
+    /// Parses the scheme, host and port of an absolute URL.
+    pub fn parse(url: &str) -> Result<Destination, PluginError> {
+        let invalid = || PluginError::invalid_input("Invalid outbound URL").with_detail("url", url);
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let scheme = scheme.to_ascii_lowercase();
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        if authority.contains('@') {
+            // Userinfo is a classic way to make "https://trusted@evil" look trusted.
+            return Err(invalid().with_detail("reason", "userinfo is not allowed"));
+        }
+
+        let (host, port) = if let Some(v6) = authority.strip_prefix('[') {
+            let (host, after) = v6.split_once(']').ok_or_else(invalid)?;
+            (host, after.strip_prefix(':'))
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            }
+        };
+        let port = match port {
+            Some(p) => p.parse::<u16>().map_err(|_| invalid())?,
+            None => default_port(&scheme).ok_or_else(invalid)?,
+        };
+
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Destination { scheme, host, port })
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        _ => None,
+    }
+}
+
+impl AllowList {
+    /// Reads the allow-list from the `outbound` key of `static_data`.
+    /// A missing key yields an empty list, which denies every destination.
+    pub fn from_static_data(
+        static_data: Option<&serde_json::Value>,
+    ) -> Result<AllowList, PluginError> {
+        match static_data.and_then(|sd| sd.get("outbound")) {
+            Some(v) => serde_json::from_value(v.clone()).map_err(|e| {
+                PluginError::config(format!("Invalid outbound allow-list: {}", e))
+                    .with_detail("field", "outbound")
+            }),
+            None => Ok(AllowList::default()),
+        }
+    }
+
+    /// Checks `url` against the list, returning a `PolicyViolation` error when
+    /// the destination is not allowed.
+    pub fn check(&self, url: &str) -> Result<Destination, PluginError> {
+        let dest = Destination::parse(url)?;
+        let deny = |reason: &str| {
+            PluginError::policy("Outbound destination not allowed")
+                .with_detail("url", url)
+                .with_detail("reason", reason)
+        };
+
+        let scheme_ok = if self.schemes.is_empty() {
+            default_port(&dest.scheme).is_some()
+        } else {
+            self.schemes
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(&dest.scheme))
+        };
+        if !scheme_ok {
+            return Err(deny("scheme"));
+        }
+
+        let port_ok = if self.ports.is_empty() {
+            default_port(&dest.scheme) == Some(dest.port)
+        } else {
+            self.ports.contains(&dest.port)
+        };
+        if !port_ok {
+            return Err(deny("port"));
+        }
+
+        if !self.allow_ip_literals && dest.host.parse::<std::net::IpAddr>().is_ok() {
+            return Err(deny("ip literal"));
+        }
+        if !self
+            .hosts
+            .iter()
+            .any(|pattern| host_matches(pattern, &dest.host))
+        {
+            return Err(deny("host"));
+        }
+        Ok(dest)
+    }
+}
+
+/// Matches `host` against an exact name or a `*.suffix` wildcard. The wildcard
+/// requires at least one extra label, so `*.example.com` does not match
+/// `example.com` itself.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(hosts: &[&str]) -> AllowList {
+        AllowList {
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn reason(err: PluginError) -> String {
+        assert_eq!(err.code(), "POLICY_VIOLATION");
+        err.details()["reason"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_destination() {
+        let dest = Destination::parse("HTTPS://API.Example.com./v1?x=1").unwrap();
+        assert_eq!(dest.scheme, "https");
+        assert_eq!(dest.host, "api.example.com");
+        assert_eq!(dest.port, 443);
+
+        let dest = Destination::parse("http://[::1]:8080/").unwrap();
+        assert_eq!(dest.host, "::1");
+        assert_eq!(dest.port, 8080);
+    }
+
+    #[test]
+    fn parse_rejects_userinfo_and_bad_ports() {
+        let err = Destination::parse("https://api.example.com@evil.test/").unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert!(Destination::parse("https://api.example.com:99999/").is_err());
+        assert!(Destination::parse("ftp://files.example.com/").is_err());
+        assert!(Destination::parse("not a url").is_err());
+    }
+
+    #[test]
+    fn exact_and_wildcard_hosts() {
+        let allow = list(&["api.example.com", "*.internal.example.com"]);
+        assert!(allow.check("https://api.example.com/x").is_ok());
+        assert!(allow.check("https://a.b.internal.example.com/").is_ok());
+        assert_eq!(
+            reason(allow.check("https://internal.example.com/").unwrap_err()),
+            "host"
+        );
+        assert_eq!(
+            reason(allow.check("https://evilapi.example.com/").unwrap_err()),
+            "host"
+        );
+        assert_eq!(
+            reason(allow.check("https://xinternal.example.com/").unwrap_err()),
+            "host"
+        );
+    }
+
+    #[test]
+    fn empty_list_denies_everything() {
+        let allow = AllowList::from_static_data(None).unwrap();
+        assert_eq!(
+            reason(allow.check("https://example.com/").unwrap_err()),
+            "host"
+        );
+    }
+
+    #[test]
+    fn schemes_ports_and_ip_literals() {
+        let mut allow = list(&["api.example.com", "10.0.0.1"]);
+        assert!(allow.check("http://api.example.com/").is_ok());
+        assert_eq!(
+            reason(allow.check("https://api.example.com:8443/").unwrap_err()),
+            "port"
+        );
+        assert_eq!(
+            reason(allow.check("https://10.0.0.1/").unwrap_err()),
+            "ip literal"
+        );
+
+        allow.schemes = vec!["https".into()];
+        allow.ports = vec![443, 8443];
+        allow.allow_ip_literals = true;
+        assert_eq!(
+            reason(allow.check("http://api.example.com/").unwrap_err()),
+            "scheme"
+        );
+        assert!(allow.check("https://api.example.com:8443/").is_ok());
+        assert!(allow.check("https://10.0.0.1/").is_ok());
+    }
+
+    #[test]
+    fn from_static_data() {
+        let sd = serde_json::json!({
+            "outbound": {"hosts": ["*.example.com"], "schemes": ["https"], "ports": [443]}
+        });
+        let allow = AllowList::from_static_data(Some(&sd)).unwrap();
+        assert!(allow.check("https://api.example.com/").is_ok());
+
+        let bad = serde_json::json!({"outbound": {"ports": ["https"]}});
+        let err = AllowList::from_static_data(Some(&bad)).unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+    }
+}
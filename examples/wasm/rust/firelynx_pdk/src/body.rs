@@ -0,0 +1,395 @@
+//! The request body, decoded only when a handler reads it.
+//!
+//! For JSON envelopes the body is not copied out of the input buffer while
+//! parsing: `Body` keeps a shared handle to the whole envelope text and the
+//! byte range of the `Body` string literal inside it. Handlers that only look
+//! at headers or the URL never pay for unescaping or copying a large body.
+//! With the `simd-json` feature the parser unescapes the body in place, and
+//! `Body` keeps the range of the decoded text instead. Other envelope codecs
+//! decode the body eagerly.
+//!
+//! `chunks()` reads the body in pieces of bounded size instead. An escaped
+//! body is then unescaped into one reused buffer rather than a full copy, so
+//! a handler that streams over a large body never holds more than the
+//! envelope plus one chunk.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer};
+
+#[derive(Clone)]
+pub struct Body {
+    repr: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    /// A JSON string literal, quotes included, at `range` in `envelope`.
+    Raw {
+        envelope: Arc<String>,
+        range: Range<usize>,
+    },
+    /// Already unescaped UTF-8 text at `range` in `envelope`. The buffer as a
+    /// whole is not guaranteed to be UTF-8 after in-place unescaping.
+    #[cfg(feature = "simd-json")]
+    Decoded {
+        envelope: Arc<Vec<u8>>,
+        range: Range<usize>,
+    },
+    Text(String),
+}
+
+impl Body {
+    /// Wraps the JSON string literal at `range` in `envelope`. The literal
+    /// must already have been validated by the JSON parser.
+    pub(crate) fn raw(envelope: Arc<String>, range: Range<usize>) -> Body {
+        debug_assert!(envelope[range.clone()].starts_with('"'));
+        Body {
+            repr: Repr::Raw { envelope, range },
+        }
+    }
+
+    /// Wraps the decoded text at `range` in `envelope`.
+    #[cfg(feature = "simd-json")]
+    pub(crate) fn decoded(envelope: Arc<Vec<u8>>, range: Range<usize>) -> Body {
+        Body {
+            repr: Repr::Decoded { envelope, range },
+        }
+    }
+
+    /// The body text. Borrowed from the envelope unless the literal contains
+    /// escape sequences, in which case it is decoded into a new string.
+    pub fn text(&self) -> Cow<'_, str> {
+        match &self.repr {
+            Repr::Text(s) => Cow::Borrowed(s),
+            Repr::Raw { envelope, range } => {
+                let literal = &envelope[range.clone()];
+                if !literal.contains('\\') {
+                    return Cow::Borrowed(&literal[1..literal.len() - 1]);
+                }
+                // The parser already accepted the literal, so decoding it
+                // again cannot fail.
+                Cow::Owned(serde_json::from_str(literal).unwrap_or_default())
+            }
+            // The parser validated the whole input as UTF-8 and writes only
+            // complete characters when unescaping.
+            #[cfg(feature = "simd-json")]
+            Repr::Decoded { envelope, range } => {
+                Cow::Borrowed(std::str::from_utf8(&envelope[range.clone()]).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Consumes the body, returning its text.
+    pub fn into_string(self) -> String {
+        match self.repr {
+            Repr::Text(s) => s,
+            _ => self.text().into_owned(),
+        }
+    }
+
+    /// The size of the body as sent, without decoding it: the length of the
+    /// JSON string literal between its quotes for a lazily held body, else
+    /// the text length. Never less than `text().len()`, so it is a safe
+    /// bound for size limits.
+    pub fn encoded_len(&self) -> usize {
+        match &self.repr {
+            Repr::Text(s) => s.len(),
+            Repr::Raw { range, .. } => range.len() - 2,
+            #[cfg(feature = "simd-json")]
+            Repr::Decoded { range, .. } => range.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.encoded_len() == 0
+    }
+
+    /// Reads the body in chunks of at most `max_bytes` bytes, split on
+    /// character boundaries. A chunk is only longer when a single character
+    /// does not fit. The chunks concatenate to `text()`.
+    pub fn chunks(&self, max_bytes: usize) -> ChunkedReader<'_> {
+        let source = match &self.repr {
+            Repr::Text(s) => Source::Plain(s),
+            Repr::Raw { envelope, range } => {
+                let literal = &envelope[range.clone()];
+                let inner = &literal[1..literal.len() - 1];
+                if inner.contains('\\') {
+                    Source::Escaped(inner)
+                } else {
+                    Source::Plain(inner)
+                }
+            }
+            #[cfg(feature = "simd-json")]
+            Repr::Decoded { envelope, range } => {
+                Source::Plain(std::str::from_utf8(&envelope[range.clone()]).unwrap_or_default())
+            }
+        };
+        ChunkedReader {
+            source,
+            max_bytes: max_bytes.max(1),
+            buf: String::new(),
+        }
+    }
+}
+
+/// Chunk size for `Body::chunks` when a plugin has no tighter memory budget.
+pub const DEFAULT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// The chunks of a body; see `Body::chunks`.
+///
+/// ```ignore
+/// let mut chunks = request.body.chunks(DEFAULT_CHUNK_BYTES);
+/// while let Some(chunk) = chunks.next_chunk() {
+///     count += chunk.chars().filter(|c| set.contains(c)).count();
+/// }
+/// ```
+pub struct ChunkedReader<'b> {
+    source: Source<'b>,
+    max_bytes: usize,
+    /// Unescaped text of the current chunk, for escaped bodies.
+    buf: String,
+}
+
+/// The part of the body not read yet.
+enum Source<'b> {
+    /// Text that needs no decoding.
+    Plain(&'b str),
+    /// The inside of a JSON string literal with escape sequences.
+    Escaped(&'b str),
+}
+
+impl ChunkedReader<'_> {
+    /// The next chunk, or `None` once the body is exhausted.
+    pub fn next_chunk(&mut self) -> Option<&str> {
+        match &mut self.source {
+            Source::Plain(rest) => {
+                if rest.is_empty() {
+                    return None;
+                }
+                let (chunk, tail) = rest.split_at(chunk_end(rest, self.max_bytes));
+                *rest = tail;
+                Some(chunk)
+            }
+            Source::Escaped(rest) => {
+                if rest.is_empty() {
+                    return None;
+                }
+                self.buf.clear();
+                while !rest.is_empty() && self.buf.len() < self.max_bytes {
+                    if let Some(escape) = rest.strip_prefix('\\') {
+                        let (c, len) = unescape(escape);
+                        if !self.buf.is_empty() && self.buf.len() + c.len_utf8() > self.max_bytes {
+                            break;
+                        }
+                        self.buf.push(c);
+                        *rest = &escape[len..];
+                    } else {
+                        let run = rest.find('\\').unwrap_or(rest.len());
+                        let room = self.max_bytes - self.buf.len();
+                        let end = if self.buf.is_empty() {
+                            chunk_end(&rest[..run], room)
+                        } else {
+                            floor_char_boundary(rest, run.min(room))
+                        };
+                        if end == 0 {
+                            break;
+                        }
+                        self.buf.push_str(&rest[..end]);
+                        *rest = &rest[end..];
+                    }
+                }
+                Some(&self.buf)
+            }
+        }
+    }
+}
+
+/// Where a chunk of at most `max_bytes` of `s` ends: on a character
+/// boundary, and after at least one character.
+fn chunk_end(s: &str, max_bytes: usize) -> usize {
+    match floor_char_boundary(s, max_bytes) {
+        0 => s.chars().next().map_or(0, char::len_utf8),
+        end => end,
+    }
+}
+
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    let mut end = index.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    end
+}
+
+/// Decodes the escape sequence at the start of `escape` (the text after the
+/// backslash), returning the character and how many bytes it used. The JSON
+/// parser has already validated the literal; a lone surrogate becomes
+/// U+FFFD.
+fn unescape(escape: &str) -> (char, usize) {
+    let c = match escape.as_bytes().first() {
+        Some(b'"') => '"',
+        Some(b'\\') => '\\',
+        Some(b'/') => '/',
+        Some(b'b') => '\u{8}',
+        Some(b'f') => '\u{c}',
+        Some(b'n') => '\n',
+        Some(b'r') => '\r',
+        Some(b't') => '\t',
+        Some(b'u') => {
+            let Some(unit) = hex4(escape.get(1..5)) else {
+                return (char::REPLACEMENT_CHARACTER, 1);
+            };
+            if (0xD800..0xDC00).contains(&unit) && escape.get(5..7) == Some("\\u") {
+                if let Some(low @ 0xDC00..=0xDFFF) = hex4(escape.get(7..11)) {
+                    let c = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                    return (char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER), 11);
+                }
+            }
+            return (
+                char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER),
+                5,
+            );
+        }
+        _ => return (char::REPLACEMENT_CHARACTER, 0),
+    };
+    (c, 1)
+}
+
+fn hex4(digits: Option<&str>) -> Option<u32> {
+    let digits = digits?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+impl Default for Body {
+    fn default() -> Body {
+        Body::from(String::new())
+    }
+}
+
+impl From<String> for Body {
+    fn from(s: String) -> Body {
+        Body {
+            repr: Repr::Text(s),
+        }
+    }
+}
+
+impl From<&str> for Body {
+    fn from(s: &str) -> Body {
+        Body::from(s.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Body {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Body, D::Error> {
+        String::deserialize(deserializer).map(Body::from)
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.text(), f)
+    }
+}
+
+impl PartialEq for Body {
+    fn eq(&self, other: &Body) -> bool {
+        self.text() == other.text()
+    }
+}
+
+impl PartialEq<str> for Body {
+    fn eq(&self, other: &str) -> bool {
+        self.text() == other
+    }
+}
+
+impl PartialEq<&str> for Body {
+    fn eq(&self, other: &&str) -> bool {
+        self.text() == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(literal: &str) -> Body {
+        let envelope = format!("{{\"Body\": {}}}", literal);
+        let start = envelope.find('"').unwrap() + "\"Body\": ".len();
+        let range = start..start + literal.len();
+        Body::raw(Arc::new(envelope), range)
+    }
+
+    #[test]
+    fn unescaped_text_is_borrowed() {
+        let body = raw(r#""Hello World""#);
+        assert!(matches!(body.text(), Cow::Borrowed("Hello World")));
+        assert_eq!(body.encoded_len(), 11);
+    }
+
+    #[test]
+    fn escaped_text_is_decoded() {
+        let body = raw(r#""line\ncafé""#);
+        assert!(matches!(body.text(), Cow::Owned(_)));
+        assert_eq!(body, "line\ncaf\u{e9}");
+        assert!(body.encoded_len() >= body.text().len());
+        assert_eq!(body.into_string(), "line\ncaf\u{e9}");
+    }
+
+    fn chunks(body: &Body, max_bytes: usize) -> Vec<String> {
+        let mut reader = body.chunks(max_bytes);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = reader.next_chunk() {
+            chunks.push(chunk.to_string());
+        }
+        chunks
+    }
+
+    #[test]
+    fn chunks_concatenate_to_the_text() {
+        let bodies = [
+            raw(r#""Hello World""#),
+            raw(r#""line\ncaf\u00e9 \"q\" \\ \/ \ud83d\ude00 😀 \t end""#),
+            raw(r#""\n\n\n\n\n""#),
+            raw(r#""日本語のテキスト""#),
+            Body::from("plain é 😀 text"),
+        ];
+        for body in &bodies {
+            let text = body.text();
+            for max_bytes in [1, 2, 3, 4, 5, 7, 64] {
+                let chunks = chunks(body, max_bytes);
+                assert_eq!(chunks.concat(), text, "max_bytes {}", max_bytes);
+                for chunk in &chunks {
+                    assert!(
+                        chunk.len() <= max_bytes || chunk.chars().count() == 1,
+                        "{:?} over {} bytes",
+                        chunk,
+                        max_bytes
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn escaped_chunks_fill_up_to_the_budget() {
+        let body = raw(r#""ab\ncd\tef""#);
+        assert_eq!(chunks(&body, 3), ["ab\n", "cd\t", "ef"]);
+        assert!(chunks(&raw(r#""""#), 8).is_empty());
+    }
+
+    #[test]
+    fn empty_bodies() {
+        assert!(raw(r#""""#).is_empty());
+        assert!(Body::default().is_empty());
+        assert_eq!(Body::default(), raw(r#""""#));
+    }
+}
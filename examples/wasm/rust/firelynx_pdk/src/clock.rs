@@ -0,0 +1,174 @@
+//! Wall-clock time that tests can pin.
+//!
+//! Plugins read the time through `clock::now()` instead of
+//! `SystemTime::now()`. In production that is the host's wall clock, which
+//! the extism runtime serves through the WASI `clock_time_get` host
+//! function. A test host freezes it by setting the `clock_fixed_unix_ms`
+//! extism config var, read once per plugin instance by the
+//! `#[firelynx_plugin]` shim, so timestamps in output are reproducible:
+//!
+//! ```ignore
+//! let report = Report {
+//!     generated_at_ms: clock::unix_millis(),
+//!     ..
+//! };
+//! ```
+//!
+//! Native unit tests can instead install any `Clock` for the current thread
+//! with `clock::set`.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::config::PluginConfig;
+use crate::schema::Field;
+use crate::PluginError;
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+
+    /// Milliseconds since the Unix epoch; 0 for times before it.
+    fn unix_millis(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    }
+}
+
+/// The host's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(SystemTime);
+
+impl FixedClock {
+    pub fn new(at: SystemTime) -> FixedClock {
+        FixedClock(at)
+    }
+
+    pub fn from_unix_millis(ms: u64) -> FixedClock {
+        FixedClock(UNIX_EPOCH + Duration::from_millis(ms))
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        self.0 += by;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+#[derive(Deserialize)]
+struct ClockConfig {
+    clock_fixed_unix_ms: Option<i64>,
+}
+
+impl PluginConfig for ClockConfig {
+    const FIELDS: &'static [Field] = &[Field::integer("clock_fixed_unix_ms")];
+}
+
+/// The instance's clock from the host's config; `None` until the shim has
+/// read it or when the config does not pin the time.
+static CONFIGURED: OnceLock<Option<FixedClock>> = OnceLock::new();
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Box<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Reads `clock_fixed_unix_ms` from the host's config on the first call. An
+/// invalid value is a `CONFIG_ERROR` on every call until fixed.
+pub(crate) fn init() -> Result<(), PluginError> {
+    if CONFIGURED.get().is_none() {
+        let config = ClockConfig::load()?;
+        let fixed = config
+            .clock_fixed_unix_ms
+            .map(|ms| {
+                u64::try_from(ms)
+                    .map(FixedClock::from_unix_millis)
+                    .map_err(|_| {
+                        PluginError::config("clock_fixed_unix_ms must not be negative")
+                            .with_detail("field", "clock_fixed_unix_ms")
+                    })
+            })
+            .transpose()?;
+        let _ = CONFIGURED.set(fixed);
+    }
+    Ok(())
+}
+
+/// Makes `clock` the clock for this thread until `reset` is called. Takes
+/// precedence over the configured clock.
+pub fn set(clock: impl Clock + 'static) {
+    OVERRIDE.with(|o| *o.borrow_mut() = Some(Box::new(clock)));
+}
+
+/// Removes a clock installed with `set`.
+pub fn reset() {
+    OVERRIDE.with(|o| *o.borrow_mut() = None);
+}
+
+/// The current time from the active clock.
+pub fn now() -> SystemTime {
+    if let Some(now) = OVERRIDE.with(|o| o.borrow().as_ref().map(|clock| clock.now())) {
+        return now;
+    }
+    match CONFIGURED.get() {
+        Some(Some(fixed)) => fixed.now(),
+        _ => SystemClock.now(),
+    }
+}
+
+/// `now()` in milliseconds since the Unix epoch.
+pub fn unix_millis() -> u64 {
+    FixedClock::new(now()).unix_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_moves_only_when_advanced() {
+        let mut clock = FixedClock::from_unix_millis(1_700_000_000_000);
+        assert_eq!(clock.unix_millis(), 1_700_000_000_000);
+        assert_eq!(clock.now(), clock.now());
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(clock.unix_millis(), 1_700_000_002_000);
+    }
+
+    #[test]
+    fn installed_clock_overrides_the_system_clock() {
+        set(FixedClock::from_unix_millis(42));
+        assert_eq!(unix_millis(), 42);
+        reset();
+        assert!(unix_millis() > 42);
+    }
+
+    #[test]
+    fn reads_the_fixed_time_from_config_vars() {
+        let config =
+            ClockConfig::from_vars(|key| (key == "clock_fixed_unix_ms").then(|| "1000".into()))
+                .unwrap();
+        assert_eq!(config.clock_fixed_unix_ms, Some(1000));
+        let config = ClockConfig::from_vars(|_| None).unwrap();
+        assert_eq!(config.clock_fixed_unix_ms, None);
+        let err = ClockConfig::from_vars(|_| Some("soon".into()))
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+    }
+}
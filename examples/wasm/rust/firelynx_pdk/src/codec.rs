@@ -0,0 +1,329 @@
+//! Wire encodings for the plugin envelope.
+//!
+//! JSON is the default. A host that sets the extism config var
+//! `envelope_codec = "msgpack"` sends the input envelope as MessagePack and
+//! expects handler results back in MessagePack, which avoids the cost of
+//! JSON text for large bodies. The `#[firelynx_plugin]` shim picks the codec
+//! per call, so handlers are unchanged.
+//!
+//! With the `cbor` cargo feature, `envelope_codec = "cbor"` selects CBOR.
+//! CBOR byte strings are accepted wherever the envelope has a string (such as
+//! `Body`), so a host can send binary-safe text without base64; they must
+//! still be valid UTF-8.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::limits::ParseLimits;
+use crate::PluginError;
+
+/// The extism config var naming the envelope codec.
+pub const CONFIG_KEY: &str = "envelope_codec";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    #[default]
+    Json,
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Codec {
+    /// Reads the codec from the host's extism config, defaulting to JSON.
+    pub fn from_config() -> Result<Codec, PluginError> {
+        match extism_pdk::config::get(CONFIG_KEY) {
+            Ok(Some(name)) => name.parse(),
+            Ok(None) => Ok(Codec::Json),
+            Err(e) => Err(
+                PluginError::internal(format!("Failed to read config: {}", e))
+                    .with_detail("field", CONFIG_KEY),
+            ),
+        }
+    }
+
+    /// The name used in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "JSON",
+            Codec::MessagePack => "MessagePack",
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => "CBOR",
+        }
+    }
+
+    /// Decodes a whole envelope into a JSON value tree, which envelope
+    /// migration then works on regardless of the wire format.
+    /// Input that breaks the active `ParseLimits` is rejected.
+    pub fn decode(self, bytes: &[u8]) -> Result<Value, PluginError> {
+        let limits = ParseLimits::active();
+        if self == Codec::Json {
+            limits.check_json(bytes)?;
+        }
+        let decoded = match self {
+            Codec::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Codec::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => ciborium::from_reader(bytes)
+                .map_err(|e| e.to_string())
+                .and_then(cbor::to_json),
+        };
+        let value = decoded.map_err(|e| {
+            PluginError::invalid_input(format!("Invalid {} input: {}", self.name(), e))
+        })?;
+        limits.check_value(&value)?;
+        Ok(value)
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, PluginError> {
+        let mut buf = Vec::new();
+        self.encode_into(value, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Like `encode`, appending to `buf` so callers can reuse one buffer.
+    pub fn encode_into<T: Serialize>(
+        self,
+        value: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), PluginError> {
+        #[cfg(feature = "arbitrary-precision")]
+        if self != Codec::Json {
+            // Exact numbers serialize as a private marker struct that only
+            // serde_json understands; give the binary codecs plain ones.
+            let value = serde_json::to_value(value).map_err(|e| {
+                PluginError::internal(format!("Failed to encode {} output: {}", self.name(), e))
+            })?;
+            return self.encode_plain(&plain::Plain(&value), buf);
+        }
+        self.encode_plain(value, buf)
+    }
+
+    fn encode_plain<T: Serialize>(self, value: &T, buf: &mut Vec<u8>) -> Result<(), PluginError> {
+        let encoded = match self {
+            Codec::Json => serde_json::to_writer(&mut *buf, value).map_err(|e| e.to_string()),
+            // Named fields, so structs arrive as maps rather than positional
+            // arrays and decode the same way JSON objects do.
+            Codec::MessagePack => {
+                rmp_serde::encode::write_named(buf, value).map_err(|e| e.to_string())
+            }
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => ciborium::into_writer(value, &mut *buf).map_err(|e| e.to_string()),
+        };
+        encoded.map_err(|e| {
+            PluginError::internal(format!("Failed to encode {} output: {}", self.name(), e))
+        })
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = PluginError;
+
+    fn from_str(s: &str) -> Result<Codec, PluginError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "json" => Ok(Codec::Json),
+            "msgpack" | "messagepack" => Ok(Codec::MessagePack),
+            #[cfg(feature = "cbor")]
+            "cbor" => Ok(Codec::Cbor),
+            #[cfg(not(feature = "cbor"))]
+            "cbor" => Err(PluginError::config(
+                "envelope_codec cbor requires firelynx-pdk's cbor feature",
+            )
+            .with_detail("field", CONFIG_KEY)
+            .with_detail("value", s)),
+            _ => Err(
+                PluginError::config("envelope_codec must be json, msgpack or cbor")
+                    .with_detail("field", CONFIG_KEY)
+                    .with_detail("value", s),
+            ),
+        }
+    }
+}
+
+/// A `Value` whose numbers serialize as 64-bit integers or floats, for the
+/// codecs that have no exact decimal type.
+#[cfg(feature = "arbitrary-precision")]
+mod plain {
+    use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+    use serde_json::Value;
+
+    pub(super) struct Plain<'a>(pub &'a Value);
+
+    impl Serialize for Plain<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.0 {
+                Value::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+                    (Some(i), _, _) => serializer.serialize_i64(i),
+                    (_, Some(u), _) => serializer.serialize_u64(u),
+                    (_, _, Some(f)) => serializer.serialize_f64(f),
+                    _ => serializer.serialize_str(n.as_str()),
+                },
+                Value::Array(items) => {
+                    let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                    for item in items {
+                        seq.serialize_element(&Plain(item))?;
+                    }
+                    seq.end()
+                }
+                Value::Object(fields) => {
+                    let mut map = serializer.serialize_map(Some(fields.len()))?;
+                    for (k, v) in fields {
+                        map.serialize_entry(k, &Plain(v))?;
+                    }
+                    map.end()
+                }
+                other => other.serialize(serializer),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cbor")]
+mod cbor {
+    use ciborium::Value as Cbor;
+    use serde_json::{Map, Number, Value};
+
+    /// Converts a decoded CBOR item to the JSON value tree. Tags are
+    /// dropped, byte strings become (UTF-8) strings, and map keys must be
+    /// text.
+    pub(super) fn to_json(value: Cbor) -> Result<Value, String> {
+        Ok(match value {
+            Cbor::Null => Value::Null,
+            Cbor::Bool(b) => Value::Bool(b),
+            Cbor::Integer(i) => {
+                let i = i128::from(i);
+                match (i64::try_from(i), u64::try_from(i)) {
+                    (Ok(i), _) => i.into(),
+                    (_, Ok(u)) => u.into(),
+                    _ => return Err(format!("integer {} is out of range", i)),
+                }
+            }
+            Cbor::Float(f) => Number::from_f64(f)
+                .map(Value::Number)
+                .ok_or_else(|| format!("float {} has no JSON representation", f))?,
+            Cbor::Text(s) => Value::String(s),
+            Cbor::Bytes(b) => Value::String(
+                String::from_utf8(b).map_err(|_| "byte string is not valid UTF-8".to_string())?,
+            ),
+            Cbor::Array(items) => {
+                Value::Array(items.into_iter().map(to_json).collect::<Result<_, _>>()?)
+            }
+            Cbor::Map(entries) => {
+                let mut map = Map::new();
+                for (k, v) in entries {
+                    let Cbor::Text(k) = k else {
+                        return Err("map keys must be text".to_string());
+                    };
+                    map.insert(k, to_json(v)?);
+                }
+                Value::Object(map)
+            }
+            Cbor::Tag(_, inner) => to_json(*inner)?,
+            other => return Err(format!("unsupported CBOR item {:?}", other)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codecs() -> Vec<Codec> {
+        vec![
+            Codec::Json,
+            Codec::MessagePack,
+            #[cfg(feature = "cbor")]
+            Codec::Cbor,
+        ]
+    }
+
+    #[test]
+    fn parses_config_values() {
+        assert_eq!("json".parse::<Codec>().unwrap(), Codec::Json);
+        assert_eq!("".parse::<Codec>().unwrap(), Codec::Json);
+        assert_eq!(" MsgPack ".parse::<Codec>().unwrap(), Codec::MessagePack);
+        let err = "xml".parse::<Codec>().unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+        assert_eq!(err.details()["value"], "xml");
+    }
+
+    #[test]
+    fn round_trips_values() {
+        let value = serde_json::json!({"request": {"Body": "hi", "ContentLength": 2}});
+        for codec in codecs() {
+            let bytes = codec.encode(&value).unwrap();
+            assert_eq!(codec.decode(&bytes).unwrap(), value, "{:?}", codec);
+        }
+    }
+
+    #[test]
+    fn encodes_structs_as_maps() {
+        #[derive(Serialize)]
+        struct Report {
+            count: i32,
+        }
+        let bytes = Codec::MessagePack.encode(&Report { count: 3 }).unwrap();
+        assert_eq!(
+            Codec::MessagePack.decode(&bytes).unwrap(),
+            serde_json::json!({"count": 3})
+        );
+    }
+
+    #[test]
+    fn decode_errors_name_the_codec() {
+        let err = Codec::MessagePack.decode(&[0xc1]).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert!(err.message().starts_with("Invalid MessagePack input"));
+    }
+
+    #[test]
+    fn round_trips_fixture_envelopes() {
+        let fixtures = [
+            firelynx_fixture::RequestFixture::new("Hello World"),
+            firelynx_fixture::RequestFixture::new("")
+                .method("GET")
+                .query("q", "a")
+                .header("Accept", "text/html")
+                .static_data("limit", 10)
+                .static_data("ratio", 0.5)
+                .static_data("tags", serde_json::json!(["a", "b"])),
+        ];
+        for fixture in fixtures {
+            let json = fixture.to_value();
+            for codec in codecs() {
+                let bytes = codec.encode(&json).unwrap();
+                assert_eq!(codec.decode(&bytes).unwrap(), json, "{:?}", codec);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "cbor"))]
+    #[test]
+    fn cbor_needs_the_feature() {
+        let err = "cbor".parse::<Codec>().unwrap_err();
+        assert!(err.message().contains("cbor feature"));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_byte_strings_decode_as_text() {
+        use ciborium::Value as Cbor;
+
+        let envelope = Cbor::Map(vec![(
+            Cbor::Text("request".into()),
+            Cbor::Map(vec![(
+                Cbor::Text("Body".into()),
+                Cbor::Bytes(b"caf\xc3\xa9".to_vec()),
+            )]),
+        )]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&envelope, &mut bytes).unwrap();
+        let input: crate::input::Input = crate::input::Input::decode(Codec::Cbor, bytes).unwrap();
+        assert_eq!(input.request.body, "caf\u{e9}");
+
+        let invalid = Cbor::Bytes(vec![0xff]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&invalid, &mut bytes).unwrap();
+        assert!(Codec::Cbor.decode(&bytes).is_err());
+    }
+}
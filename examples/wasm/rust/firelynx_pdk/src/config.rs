@@ -0,0 +1,195 @@
+//! Typed deployment settings from extism config vars.
+//!
+//! `static_data` travels with every request and is set per route. Settings
+//! that belong to the plugin deployment instead (log level, limits, upstream
+//! base URLs) come from the host's extism `config` map, which is fixed for
+//! the lifetime of the plugin instance. `PluginConfig` declares those keys
+//! with the same `Field` builders as `ConfigSchema`:
+//!
+//! ```ignore
+//! #[derive(serde::Deserialize)]
+//! struct Settings {
+//!     log_level: String,
+//!     max_items: i64,
+//! }
+//!
+//! impl PluginConfig for Settings {
+//!     const FIELDS: &'static [Field] = &[
+//!         Field::string("log_level").default(DefaultValue::Str("info")),
+//!         Field::integer("max_items").default(DefaultValue::Int(100)),
+//!     ];
+//! }
+//!
+//! // Read once per plugin instance rather than on every call.
+//! static SETTINGS: OnceLock<Settings> = OnceLock::new();
+//!
+//! fn settings() -> Result<&'static Settings> {
+//!     if let Some(settings) = SETTINGS.get() {
+//!         return Ok(settings);
+//!     }
+//!     let settings = Settings::load()?;
+//!     Ok(SETTINGS.get_or_init(|| settings))
+//! }
+//! ```
+//!
+//! Config values are always strings on the wire; each is converted to its
+//! declared type before validation. Booleans are `true`/`false`, string lists
+//! are comma-separated, and tables are JSON objects.
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::schema::{self, Field, FieldType};
+use crate::PluginError;
+
+/// Implemented by a plugin's deployment settings to declare the extism
+/// config keys it reads.
+pub trait PluginConfig: DeserializeOwned {
+    const FIELDS: &'static [Field];
+
+    /// Reads every declared key from the host's extism config.
+    fn load() -> Result<Self, PluginError> {
+        let mut lookup_error = None;
+        let config = Self::from_vars(|key| {
+            extism_pdk::config::get(key).unwrap_or_else(|e| {
+                lookup_error.get_or_insert_with(|| {
+                    PluginError::internal(format!("Failed to read config: {}", e))
+                        .with_detail("field", key)
+                });
+                None
+            })
+        });
+        match lookup_error {
+            Some(err) => Err(err),
+            None => config,
+        }
+    }
+
+    /// Builds the settings from `lookup`, which returns the raw string for a
+    /// key. Validation and defaults work as in `ConfigSchema`; undeclared
+    /// keys are never looked up.
+    fn from_vars(mut lookup: impl FnMut(&str) -> Option<String>) -> Result<Self, PluginError> {
+        let mut values = Map::new();
+        for field in Self::FIELDS {
+            if let Some(raw) = lookup(field.name) {
+                values.insert(field.name.to_string(), convert(field.ty, raw));
+            }
+        }
+        let values = schema::validate(Self::FIELDS, values)?;
+        serde_json::from_value(Value::Object(values))
+            .map_err(|e| PluginError::config(format!("Invalid plugin config: {}", e)))
+    }
+}
+
+/// Converts a raw config string to `ty`. Unparseable values are kept as
+/// strings so validation reports them with the usual "must be" message.
+fn convert(ty: FieldType, raw: String) -> Value {
+    let parsed = match ty {
+        FieldType::String => None,
+        FieldType::Bool => raw.trim().parse::<bool>().ok().map(Value::from),
+        FieldType::Integer => raw.trim().parse::<i64>().ok().map(Value::from),
+        FieldType::Number => raw.trim().parse::<f64>().ok().map(Value::from),
+        FieldType::StringList => Some(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(Value::from)
+                .collect(),
+        ),
+        FieldType::Table => serde_json::from_str::<Value>(&raw)
+            .ok()
+            .filter(Value::is_object),
+    };
+    parsed.unwrap_or(Value::String(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::schema::DefaultValue;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Settings {
+        log_level: String,
+        max_items: i64,
+        debug: bool,
+        ratio: f64,
+        allowed: Vec<String>,
+        upstream: Option<Map<String, Value>>,
+    }
+
+    impl PluginConfig for Settings {
+        const FIELDS: &'static [Field] = &[
+            Field::string("log_level").default(DefaultValue::Str("info")),
+            Field::integer("max_items").default(DefaultValue::Int(100)),
+            Field::bool("debug").default(DefaultValue::Bool(false)),
+            Field::number("ratio").default(DefaultValue::Float(0.5)),
+            Field::string_list("allowed").required(),
+            Field::table("upstream"),
+        ];
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> impl FnMut(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn converts_strings_to_declared_types() {
+        let settings = Settings::from_vars(vars(&[
+            ("max_items", " 25 "),
+            ("debug", "true"),
+            ("ratio", "0.25"),
+            ("allowed", "a, b,,c"),
+            ("upstream", r#"{"url": "https://example.com"}"#),
+        ]))
+        .unwrap();
+        assert_eq!(settings.log_level, "info");
+        assert_eq!(settings.max_items, 25);
+        assert!(settings.debug);
+        assert_eq!(settings.ratio, 0.25);
+        assert_eq!(settings.allowed, ["a", "b", "c"]);
+        assert_eq!(settings.upstream.unwrap()["url"], "https://example.com");
+    }
+
+    #[test]
+    fn reports_unparseable_and_missing_values() {
+        let err =
+            Settings::from_vars(vars(&[("max_items", "lots"), ("debug", "yes")])).unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+        assert_eq!(err.details()["field"], "max_items");
+        assert_eq!(
+            err.details()["problems"],
+            serde_json::json!([
+                "max_items must be an integer",
+                "debug must be a boolean",
+                "allowed is required",
+            ])
+        );
+    }
+
+    #[test]
+    fn only_declared_keys_are_looked_up() {
+        let mut seen = Vec::new();
+        let _ = Settings::from_vars(|key| {
+            seen.push(key.to_string());
+            None
+        });
+        assert_eq!(
+            seen,
+            [
+                "log_level",
+                "max_items",
+                "debug",
+                "ratio",
+                "allowed",
+                "upstream"
+            ]
+        );
+    }
+}
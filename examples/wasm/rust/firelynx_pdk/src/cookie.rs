@@ -0,0 +1,123 @@
+//! Request cookie parsing and `Set-Cookie` construction.
+
+use std::fmt;
+
+use crate::input::Request;
+
+impl Request {
+    /// Returns the value of the first cookie called `name` across all
+    /// `Cookie` headers.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.header_values("cookie")?
+            .iter()
+            .flat_map(|header| header.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.trim_matches('"'))
+    }
+}
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+/// A `Set-Cookie` header value. Defaults to `Path=/; HttpOnly; Secure;
+/// SameSite=Lax`, the right choice for session cookies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub path: String,
+    pub domain: Option<String>,
+    pub max_age: Option<u64>,
+    pub http_only: bool,
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+impl SetCookie {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> SetCookie {
+        SetCookie {
+            name: name.into(),
+            value: value.into(),
+            path: "/".to_string(),
+            domain: None,
+            max_age: None,
+            http_only: true,
+            secure: true,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    /// A cookie that tells the browser to delete `name`.
+    pub fn removal(name: impl Into<String>) -> SetCookie {
+        SetCookie {
+            max_age: Some(0),
+            ..SetCookie::new(name, "")
+        }
+    }
+}
+
+impl fmt::Display for SetCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}; Path={}", self.name, self.value, self.path)?;
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        match self.same_site {
+            SameSite::Strict => f.write_str("; SameSite=Strict"),
+            SameSite::Lax => f.write_str("; SameSite=Lax"),
+            SameSite::None => f.write_str("; SameSite=None"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_cookie_across_headers() {
+        let mut req = Request::default();
+        req.headers.insert(
+            "Cookie".to_string(),
+            vec!["a=1; theme=dark".to_string(), "sid=\"abc=\"".to_string()],
+        );
+        assert_eq!(req.cookie("theme"), Some("dark"));
+        assert_eq!(req.cookie("sid"), Some("abc="));
+        assert_eq!(req.cookie("missing"), None);
+        assert_eq!(req.cookie("the"), None);
+    }
+
+    #[test]
+    fn formats_set_cookie() {
+        let cookie = SetCookie {
+            max_age: Some(60),
+            domain: Some("example.com".to_string()),
+            same_site: SameSite::Strict,
+            ..SetCookie::new("sid", "v")
+        };
+        assert_eq!(
+            cookie.to_string(),
+            "sid=v; Path=/; Domain=example.com; Max-Age=60; HttpOnly; Secure; SameSite=Strict"
+        );
+        assert_eq!(
+            SetCookie::removal("sid").to_string(),
+            "sid=; Path=/; Max-Age=0; HttpOnly; Secure; SameSite=Lax"
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Request bodies decoded by their `Content-Type`.
+//!
+//! `Request::decode` picks a decoder by the request's media type and
+//! deserializes the body into any `serde` type, so an endpoint that accepts
+//! several formats needs no dispatch of its own:
+//!
+//! ```ignore
+//! #[derive(serde::Deserialize)]
+//! struct Order {
+//!     sku: String,
+//!     quantity: u32,
+//! }
+//!
+//! let order: Order = request.decode()?;
+//! ```
+//!
+//! Built in: JSON (`application/json` and any `+json` type), HTML forms
+//! (`application/x-www-form-urlencoded`), and with the `xml` feature XML
+//! (`application/xml`, `text/xml` and any `+xml` type). Other formats are
+//! registered per instance, typically at the top of the handler:
+//!
+//! ```ignore
+//! decode::register("application/x-protobuf", |request| {
+//!     let bytes = STANDARD
+//!         .decode(request.body.text().as_bytes())
+//!         .map_err(|e| PluginError::invalid_input(format!("Invalid protobuf body: {}", e)))?;
+//!     let order = proto::Order::decode(bytes.as_slice())
+//!         .map_err(|e| PluginError::invalid_input(format!("Invalid protobuf body: {}", e)))?;
+//!     Ok(serde_json::to_value(order).expect("protobuf messages serialize"))
+//! });
+//! ```
+//!
+//! A registered decoder returns a `serde_json::Value`, which is checked
+//! against `ParseLimits` and then deserialized into the handler's type. It
+//! takes precedence over a built-in for the same media type. Bodies reach
+//! the plugin as text in the envelope, so a binary format needs the host and
+//! the decoder to agree on an encoding such as base64.
+//!
+//! A missing or unsupported `Content-Type` is `INVALID_INPUT` with the media
+//! types that would work under `details.supported`; `Request::decode_as`
+//! names the type for endpoints that have a default.
+
+use std::cell::RefCell;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::accept::MediaRange;
+use crate::input::Request;
+use crate::limits::{Limit, ParseLimits};
+use crate::PluginError;
+
+/// A registered decoder: the request in, the decoded body as a JSON tree.
+pub type DecodeFn = fn(&Request) -> Result<Value, PluginError>;
+
+thread_local! {
+    static REGISTERED: RefCell<Vec<(String, DecodeFn)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Registers `decoder` for `media_type` (parameters are ignored), replacing
+/// an earlier registration for the same type.
+pub fn register(media_type: &str, decoder: DecodeFn) {
+    let Some(essence) = essence(media_type) else {
+        panic!("not a media type: {:?}", media_type);
+    };
+    REGISTERED.with(|r| {
+        let mut registered = r.borrow_mut();
+        registered.retain(|(t, _)| *t != essence);
+        registered.push((essence, decoder));
+    });
+}
+
+/// The media types `decode` accepts, registered ones first.
+pub fn supported() -> Vec<String> {
+    let mut types: Vec<String> =
+        REGISTERED.with(|r| r.borrow().iter().map(|(t, _)| t.clone()).collect());
+    for built_in in BUILT_IN {
+        if !types.iter().any(|t| t == built_in) {
+            types.push(built_in.to_string());
+        }
+    }
+    types
+}
+
+const BUILT_IN: &[&str] = &[
+    "application/json",
+    "application/*+json",
+    "application/x-www-form-urlencoded",
+    #[cfg(feature = "xml")]
+    "application/xml",
+    #[cfg(feature = "xml")]
+    "text/xml",
+    #[cfg(feature = "xml")]
+    "application/*+xml",
+];
+
+enum Decoder {
+    Json,
+    Form,
+    #[cfg(feature = "xml")]
+    Xml,
+    Registered(DecodeFn),
+}
+
+impl Decoder {
+    fn find(essence: &str) -> Option<Decoder> {
+        let registered = REGISTERED.with(|r| {
+            r.borrow()
+                .iter()
+                .find(|(t, _)| t == essence)
+                .map(|&(_, decoder)| decoder)
+        });
+        if let Some(decoder) = registered {
+            return Some(Decoder::Registered(decoder));
+        }
+        let (_, subtype) = essence.split_once('/')?;
+        match essence {
+            "application/json" => Some(Decoder::Json),
+            "application/x-www-form-urlencoded" => Some(Decoder::Form),
+            #[cfg(feature = "xml")]
+            "application/xml" | "text/xml" => Some(Decoder::Xml),
+            _ if subtype.ends_with("+json") => Some(Decoder::Json),
+            #[cfg(feature = "xml")]
+            _ if subtype.ends_with("+xml") => Some(Decoder::Xml),
+            _ => None,
+        }
+    }
+}
+
+/// `type/subtype` of a media type, lowercased, without parameters.
+fn essence(media_type: &str) -> Option<String> {
+    let range = MediaRange::parse(media_type)?;
+    (range.type_ != "*" && range.subtype != "*")
+        .then(|| format!("{}/{}", range.type_, range.subtype))
+}
+
+impl Request {
+    /// Decodes the body by its `Content-Type`.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, PluginError> {
+        let Some(content_type) = self.header("content-type") else {
+            return Err(
+                PluginError::invalid_input("Content-Type is required to decode the body")
+                    .with_detail("supported", supported()),
+            );
+        };
+        self.decode_as(content_type)
+    }
+
+    /// Decodes the body as `content_type`, whatever the request says.
+    pub fn decode_as<T: DeserializeOwned>(&self, content_type: &str) -> Result<T, PluginError> {
+        let Some(decoder) = essence(content_type).and_then(|e| Decoder::find(&e)) else {
+            return Err(PluginError::invalid_input(format!(
+                "Unsupported Content-Type: {}",
+                content_type
+            ))
+            .with_detail("content_type", content_type)
+            .with_detail("supported", supported()));
+        };
+        let text = self.body.text();
+        let limits = ParseLimits::active();
+        match decoder {
+            Decoder::Json => {
+                limits.check_json(text.as_bytes())?;
+                serde_json::from_str(&text)
+                    .map_err(|e| PluginError::invalid_input(format!("Invalid JSON body: {}", e)))
+            }
+            Decoder::Form => {
+                check_form(limits, &text)?;
+                serde_urlencoded::from_str(&text)
+                    .map_err(|e| PluginError::invalid_input(format!("Invalid form body: {}", e)))
+            }
+            #[cfg(feature = "xml")]
+            Decoder::Xml => {
+                limits.check_xml(text.as_bytes())?;
+                quick_xml::de::from_str(&text)
+                    .map_err(|e| PluginError::invalid_input(format!("Invalid XML body: {}", e)))
+            }
+            Decoder::Registered(decode) => {
+                let value = decode(self)?;
+                limits.check_value(&value)?;
+                serde_json::from_value(value).map_err(|e| {
+                    PluginError::invalid_input(format!("Invalid {} body: {}", content_type, e))
+                        .with_detail("content_type", content_type)
+                })
+            }
+        }
+    }
+}
+
+/// A form is one flat object: each pair is a member, and a pair is held to
+/// the string limit.
+fn check_form(limits: &ParseLimits, text: &str) -> Result<(), PluginError> {
+    let mut offset = 0;
+    for (i, pair) in text.split('&').enumerate() {
+        if i >= limits.max_object_members {
+            return Err(Limit::ObjectMembers.exceeded(limits.max_object_members, offset));
+        }
+        if pair.len() > limits.max_string_bytes {
+            return Err(Limit::StringBytes.exceeded(limits.max_string_bytes, offset));
+        }
+        offset += pair.len() + 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Order {
+        sku: String,
+        quantity: u32,
+    }
+
+    fn request(content_type: Option<&str>, body: &str) -> Request {
+        let mut req = Request::default();
+        if let Some(content_type) = content_type {
+            req.headers
+                .insert("Content-Type".to_string(), vec![content_type.to_string()]);
+        }
+        req.body = Body::from(body);
+        req
+    }
+
+    fn order() -> Order {
+        Order {
+            sku: "a b".to_string(),
+            quantity: 2,
+        }
+    }
+
+    #[test]
+    fn dispatches_on_content_type() {
+        let json = request(
+            Some("application/json; charset=utf-8"),
+            r#"{"sku":"a b","quantity":2}"#,
+        );
+        assert_eq!(json.decode::<Order>().unwrap(), order());
+        let vendor = request(
+            Some("application/vnd.shop.order+JSON"),
+            r#"{"sku":"a b","quantity":2}"#,
+        );
+        assert_eq!(vendor.decode::<Order>().unwrap(), order());
+        let form = request(
+            Some("application/x-www-form-urlencoded"),
+            "sku=a+b&quantity=2",
+        );
+        assert_eq!(form.decode::<Order>().unwrap(), order());
+    }
+
+    #[test]
+    fn unsupported_or_missing_type_lists_supported() {
+        let err = request(Some("text/csv"), "a,b")
+            .decode::<Order>()
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert_eq!(err.details()["content_type"], "text/csv");
+        assert_eq!(err.details()["supported"][0], "application/json");
+
+        let err = request(None, "{}").decode::<Order>().unwrap_err();
+        assert_eq!(err.message(), "Content-Type is required to decode the body");
+
+        let req = request(None, "sku=a+b&quantity=2");
+        assert_eq!(
+            req.decode_as::<Order>("application/x-www-form-urlencoded")
+                .unwrap(),
+            order()
+        );
+    }
+
+    #[test]
+    fn malformed_bodies_are_invalid_input() {
+        let err = request(Some("application/json"), "{")
+            .decode::<Order>()
+            .unwrap_err();
+        assert!(err.message().starts_with("Invalid JSON body: "));
+        let err = request(
+            Some("application/x-www-form-urlencoded"),
+            "sku=x&quantity=many",
+        )
+        .decode::<Order>()
+        .unwrap_err();
+        assert!(err.message().starts_with("Invalid form body: "));
+
+        let deep = format!("{}{}", "[".repeat(100), "]".repeat(100));
+        let err = request(Some("application/json"), &deep)
+            .decode::<Value>()
+            .unwrap_err();
+        assert_eq!(Limit::of(&err), Some(Limit::Depth));
+    }
+
+    #[test]
+    fn registered_decoders_win_and_are_checked() {
+        register("text/x-order; version=1", |req| {
+            let text = req.body.text();
+            let (sku, quantity) = text.split_once(':').ok_or_else(|| {
+                PluginError::invalid_input("Invalid order body: expected sku:quantity")
+            })?;
+            Ok(serde_json::json!({"sku": sku, "quantity": quantity.parse::<u32>().ok()}))
+        });
+        assert_eq!(supported()[0], "text/x-order");
+        assert_eq!(
+            request(Some("text/x-order"), "a b:2")
+                .decode::<Order>()
+                .unwrap(),
+            order()
+        );
+
+        let err = request(Some("text/x-order"), "a b")
+            .decode::<Order>()
+            .unwrap_err();
+        assert_eq!(err.message(), "Invalid order body: expected sku:quantity");
+        let err = request(Some("text/x-order"), "a b:x")
+            .decode::<Order>()
+            .unwrap_err();
+        assert!(err.message().starts_with("Invalid text/x-order body: "));
+
+        register("application/json", |_| {
+            Ok(serde_json::json!({"sku": "a b", "quantity": 2}))
+        });
+        assert_eq!(
+            request(Some("application/json"), "not json")
+                .decode::<Order>()
+                .unwrap(),
+            order()
+        );
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn decodes_xml() {
+        let xml = request(
+            Some("application/order+xml"),
+            "<order><sku>a b</sku><quantity>2</quantity></order>",
+        );
+        assert_eq!(xml.decode::<Order>().unwrap(), order());
+        let err = request(Some("text/xml"), "<order><sku>")
+            .decode::<Order>()
+            .unwrap_err();
+        assert!(err.message().starts_with("Invalid XML body: "));
+    }
+}
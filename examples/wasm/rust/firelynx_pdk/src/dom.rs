@@ -0,0 +1,673 @@
+//! An arena-allocated JSON document for plugins that rewrite large bodies.
+//!
+//! `serde_json::Value` allocates every string, array and object on its own,
+//! so parsing a large body to change a few fields costs an allocation per
+//! node, and as many frees when the value is dropped. A `Document` keeps all
+//! of its nodes in one `Vec` and the text of all of its strings and keys in
+//! one `String`; nodes refer to each other by `NodeId`. Parsing only grows
+//! those buffers, and dropping the document frees them at once. `parse_into`
+//! reuses them for the next call.
+//!
+//! ```ignore
+//! let mut doc = Document::parse(&request.body.text())?;
+//! if let Some(email) = doc.pointer("/user/email") {
+//!     let redacted = doc.add_str("[redacted]");
+//!     doc.replace(email, redacted);
+//! }
+//! Ok(doc)
+//! ```
+//!
+//! Members of an object keep their document order, repeated keys included;
+//! `get` returns the last one, as serde_json would. Object lookups are linear
+//! scans. Nodes that are replaced or removed stay in the arena until the
+//! document is dropped or cleared, so a `Document` is meant to live for one
+//! call.
+
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_json::{Number, Value};
+
+use crate::extract::{is_index, parse_pointer, Reader};
+use crate::limits::ParseLimits;
+use crate::PluginError;
+
+/// A node of one `Document`. Ids are only meaningful to the document that
+/// created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+/// A borrowed view of one node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeRef<'d> {
+    Null,
+    Bool(bool),
+    Number(&'d Number),
+    String(&'d str),
+    /// An array and its length.
+    Array(usize),
+    /// An object and its member count.
+    Object(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    nodes: Vec<Node>,
+    /// Text of every string value and object key, back to back.
+    text: String,
+    root: NodeId,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    kind: Kind,
+    /// The member name, for a node in an object.
+    key: Option<Span>,
+    next: Option<NodeId>,
+    attached: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(Span),
+    Array(Children),
+    Object(Children),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: u32,
+    len: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Children {
+    first: Option<NodeId>,
+    last: Option<NodeId>,
+    len: u32,
+}
+
+impl Document {
+    /// An empty document whose root is `null`.
+    pub fn new() -> Document {
+        let mut doc = Document {
+            nodes: Vec::new(),
+            text: String::new(),
+            root: NodeId(0),
+        };
+        doc.clear();
+        doc
+    }
+
+    /// Parses `json`. Malformed JSON, or JSON breaking the active
+    /// `ParseLimits`, is `INVALID_INPUT`.
+    pub fn parse(json: &str) -> Result<Document, PluginError> {
+        let mut doc = Document {
+            nodes: Vec::with_capacity(json.len() / 8),
+            text: String::with_capacity(json.len() / 2),
+            root: NodeId(0),
+        };
+        doc.parse_into(json)?;
+        Ok(doc)
+    }
+
+    /// Replaces the document with `json`, reusing its buffers. On error the
+    /// document is cleared.
+    pub fn parse_into(&mut self, json: &str) -> Result<(), PluginError> {
+        if let Err(e) = ParseLimits::active().check_json(json.as_bytes()) {
+            self.clear();
+            return Err(e);
+        }
+        self.nodes.clear();
+        self.text.clear();
+        let mut reader = Reader { text: json, pos: 0 };
+        let result = self.parse_value(&mut reader).and_then(|root| {
+            reader.skip_ws();
+            match reader.peek() {
+                None => Ok(root),
+                Some(_) => Err("trailing characters".to_string()),
+            }
+        });
+        match result {
+            Ok(root) => {
+                self.attach(root);
+                self.root = root;
+                Ok(())
+            }
+            Err(message) => {
+                self.clear();
+                Err(
+                    PluginError::invalid_input(format!("Invalid JSON body: {}", message))
+                        .with_detail("offset", reader.pos),
+                )
+            }
+        }
+    }
+
+    /// Drops every node, keeping the allocated capacity. The root becomes
+    /// `null`.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.text.clear();
+        self.root = self.push_node(Kind::Null);
+        self.nodes[0].attached = true;
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Makes the detached `node` the root; the old root is discarded.
+    pub fn set_root(&mut self, node: NodeId) {
+        self.attach(node);
+        self.nodes[self.root.0 as usize].attached = false;
+        self.root = node;
+    }
+
+    /// Nodes in the arena, including discarded ones.
+    pub fn arena_len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, id: NodeId) -> NodeRef<'_> {
+        match &self.nodes[id.0 as usize].kind {
+            Kind::Null => NodeRef::Null,
+            Kind::Bool(b) => NodeRef::Bool(*b),
+            Kind::Number(n) => NodeRef::Number(n),
+            Kind::String(span) => NodeRef::String(self.span(*span)),
+            Kind::Array(children) => NodeRef::Array(children.len as usize),
+            Kind::Object(children) => NodeRef::Object(children.len as usize),
+        }
+    }
+
+    /// The elements of an array or the member values of an object, in
+    /// order. Empty for scalars.
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let first = match &self.nodes[id.0 as usize].kind {
+            Kind::Array(children) | Kind::Object(children) => children.first,
+            _ => None,
+        };
+        std::iter::successors(first, move |child| self.nodes[child.0 as usize].next)
+    }
+
+    /// The members of an object as `(key, value)`, in document order.
+    pub fn members(&self, id: NodeId) -> impl Iterator<Item = (&str, NodeId)> + '_ {
+        self.children(id)
+            .filter_map(move |child| Some((self.key(child)?, child)))
+    }
+
+    /// The member name of a node inside an object.
+    pub fn key(&self, id: NodeId) -> Option<&str> {
+        self.nodes[id.0 as usize].key.map(|span| self.span(span))
+    }
+
+    /// The last member of object `id` named `key`.
+    pub fn get(&self, id: NodeId, key: &str) -> Option<NodeId> {
+        self.members(id)
+            .filter(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+            .last()
+    }
+
+    /// Element `index` of array `id`.
+    pub fn index(&self, id: NodeId, index: usize) -> Option<NodeId> {
+        match self.nodes[id.0 as usize].kind {
+            Kind::Array(_) => self.children(id).nth(index),
+            _ => None,
+        }
+    }
+
+    /// Looks up a JSON Pointer (RFC 6901) from the root, like
+    /// `Value::pointer`.
+    pub fn pointer(&self, pointer: &str) -> Option<NodeId> {
+        parse_pointer(pointer)
+            .ok()?
+            .iter()
+            .try_fold(self.root, |id, token| {
+                match self.nodes[id.0 as usize].kind {
+                    Kind::Object(_) => self.get(id, token),
+                    Kind::Array(_) => token
+                        .parse()
+                        .ok()
+                        .filter(|&i| is_index(token, i))
+                        .and_then(|i| self.index(id, i)),
+                    _ => None,
+                }
+            })
+    }
+
+    /// Copies `value` into the arena as a detached node.
+    pub fn add(&mut self, value: &Value) -> NodeId {
+        match value {
+            Value::Null => self.push_node(Kind::Null),
+            Value::Bool(b) => self.push_node(Kind::Bool(*b)),
+            Value::Number(n) => self.push_node(Kind::Number(n.clone())),
+            Value::String(s) => self.add_str(s),
+            Value::Array(items) => {
+                let array = self.add_array();
+                for item in items {
+                    let item = self.add(item);
+                    self.push(array, item);
+                }
+                array
+            }
+            Value::Object(members) => {
+                let object = self.add_object();
+                for (key, value) in members {
+                    let value = self.add(value);
+                    self.append_member(object, key, value);
+                }
+                object
+            }
+        }
+    }
+
+    /// Adds a detached string node.
+    pub fn add_str(&mut self, s: &str) -> NodeId {
+        let span = self.push_text(s);
+        self.push_node(Kind::String(span))
+    }
+
+    /// Adds a detached, empty array.
+    pub fn add_array(&mut self) -> NodeId {
+        self.push_node(Kind::Array(Children::default()))
+    }
+
+    /// Adds a detached, empty object.
+    pub fn add_object(&mut self) -> NodeId {
+        self.push_node(Kind::Object(Children::default()))
+    }
+
+    /// Gives `target` the content of the detached node `with`, keeping
+    /// `target`'s place (and key) in its parent.
+    ///
+    /// Panics if `with` is already attached.
+    pub fn replace(&mut self, target: NodeId, with: NodeId) {
+        self.attach(with);
+        let kind = std::mem::replace(&mut self.nodes[with.0 as usize].kind, Kind::Null);
+        self.nodes[target.0 as usize].kind = kind;
+    }
+
+    /// Appends the detached node `item` to array `array`.
+    ///
+    /// Panics if `array` is not an array or `item` is already attached.
+    pub fn push(&mut self, array: NodeId, item: NodeId) {
+        assert!(
+            matches!(self.nodes[array.0 as usize].kind, Kind::Array(_)),
+            "Document::push on a node that is not an array"
+        );
+        self.append(array, item);
+    }
+
+    /// Sets member `key` of `object` to the detached node `value`, replacing
+    /// the value `get` would return or appending a new member.
+    ///
+    /// Panics if `object` is not an object or `value` is already attached.
+    pub fn insert(&mut self, object: NodeId, key: &str, value: NodeId) {
+        assert!(
+            matches!(self.nodes[object.0 as usize].kind, Kind::Object(_)),
+            "Document::insert on a node that is not an object"
+        );
+        match self.get(object, key) {
+            Some(existing) => self.replace(existing, value),
+            None => self.append_member(object, key, value),
+        }
+    }
+
+    /// Removes every member of `object` named `key` and returns the value
+    /// `get` would have returned, now detached.
+    pub fn remove(&mut self, object: NodeId, key: &str) -> Option<NodeId> {
+        if !matches!(self.nodes[object.0 as usize].kind, Kind::Object(_)) {
+            return None;
+        }
+        let mut removed = None;
+        self.retain_children(object, |doc, child| {
+            if doc.key(child) == Some(key) {
+                removed = Some(child);
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    /// Removes element `index` of array `array` and returns it, now
+    /// detached.
+    pub fn remove_at(&mut self, array: NodeId, index: usize) -> Option<NodeId> {
+        if !matches!(self.nodes[array.0 as usize].kind, Kind::Array(_)) {
+            return None;
+        }
+        let mut removed = None;
+        let mut position = 0;
+        self.retain_children(array, |_, child| {
+            let keep = position != index;
+            if !keep {
+                removed = Some(child);
+            }
+            position += 1;
+            keep
+        });
+        removed
+    }
+
+    /// Copies node `id` and everything under it out into a `Value`.
+    pub fn to_value(&self, id: NodeId) -> Value {
+        match self.node(id) {
+            NodeRef::Null => Value::Null,
+            NodeRef::Bool(b) => Value::Bool(b),
+            NodeRef::Number(n) => Value::Number(n.clone()),
+            NodeRef::String(s) => Value::String(s.to_string()),
+            NodeRef::Array(_) => self.children(id).map(|c| self.to_value(c)).collect(),
+            NodeRef::Object(_) => Value::Object(
+                self.members(id)
+                    .map(|(k, v)| (k.to_string(), self.to_value(v)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Serializes the document as compact JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a Document always serializes")
+    }
+
+    fn parse_value(&mut self, reader: &mut Reader<'_>) -> Result<NodeId, String> {
+        reader.skip_ws();
+        match reader.peek() {
+            Some(b'{') => {
+                reader.pos += 1;
+                let object = self.add_object();
+                reader.skip_ws();
+                if reader.eat(b'}') {
+                    return Ok(object);
+                }
+                loop {
+                    reader.skip_ws();
+                    let key = reader.string()?;
+                    reader.skip_ws();
+                    reader.expect(b':')?;
+                    let value = self.parse_value(reader)?;
+                    self.append_member(object, &key, value);
+                    if !reader.separator(b'}')? {
+                        return Ok(object);
+                    }
+                }
+            }
+            Some(b'[') => {
+                reader.pos += 1;
+                let array = self.add_array();
+                reader.skip_ws();
+                if reader.eat(b']') {
+                    return Ok(array);
+                }
+                loop {
+                    let item = self.parse_value(reader)?;
+                    self.append(array, item);
+                    if !reader.separator(b']')? {
+                        return Ok(array);
+                    }
+                }
+            }
+            Some(b'"') => {
+                let s = reader.string()?;
+                Ok(self.add_str(&s))
+            }
+            _ => {
+                let start = reader.pos;
+                reader.skip_value()?;
+                let kind = match &reader.text[start..reader.pos] {
+                    "null" => Kind::Null,
+                    "true" => Kind::Bool(true),
+                    "false" => Kind::Bool(false),
+                    literal => match literal.parse::<Number>() {
+                        Ok(n) => Kind::Number(n),
+                        Err(_) => {
+                            reader.pos = start;
+                            return Err("expected a value".to_string());
+                        }
+                    },
+                };
+                Ok(self.push_node(kind))
+            }
+        }
+    }
+
+    fn push_node(&mut self, kind: Kind) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(Node {
+            kind,
+            key: None,
+            next: None,
+            attached: false,
+        });
+        id
+    }
+
+    fn push_text(&mut self, s: &str) -> Span {
+        let start = self.text.len() as u32;
+        self.text.push_str(s);
+        Span {
+            start,
+            len: s.len() as u32,
+        }
+    }
+
+    fn span(&self, span: Span) -> &str {
+        &self.text[span.start as usize..(span.start + span.len) as usize]
+    }
+
+    fn attach(&mut self, node: NodeId) {
+        let node = &mut self.nodes[node.0 as usize];
+        assert!(!node.attached, "node is already part of the document");
+        node.attached = true;
+    }
+
+    fn append_member(&mut self, object: NodeId, key: &str, value: NodeId) {
+        let key = self.push_text(key);
+        self.append(object, value);
+        self.nodes[value.0 as usize].key = Some(key);
+    }
+
+    /// Links `child` after the last child of the container `parent`.
+    fn append(&mut self, parent: NodeId, child: NodeId) {
+        self.attach(child);
+        let last = match &mut self.nodes[parent.0 as usize].kind {
+            Kind::Array(children) | Kind::Object(children) => {
+                let last = children.last.replace(child);
+                if last.is_none() {
+                    children.first = Some(child);
+                }
+                children.len += 1;
+                last
+            }
+            _ => unreachable!("append to a scalar"),
+        };
+        if let Some(last) = last {
+            self.nodes[last.0 as usize].next = Some(child);
+        }
+    }
+
+    /// Unlinks the children of `parent` for which `keep` is false; they
+    /// become detached.
+    fn retain_children(&mut self, parent: NodeId, mut keep: impl FnMut(&Document, NodeId) -> bool) {
+        let ids: Vec<NodeId> = self.children(parent).collect();
+        let mut kept = Children::default();
+        for id in ids {
+            if keep(self, id) {
+                match kept.last {
+                    Some(last) => self.nodes[last.0 as usize].next = Some(id),
+                    None => kept.first = Some(id),
+                }
+                kept.last = Some(id);
+                kept.len += 1;
+            } else {
+                let node = &mut self.nodes[id.0 as usize];
+                node.key = None;
+                node.attached = false;
+            }
+            self.nodes[id.0 as usize].next = None;
+        }
+        match &mut self.nodes[parent.0 as usize].kind {
+            Kind::Array(children) | Kind::Object(children) => *children = kept,
+            _ => unreachable!("retain on a scalar"),
+        }
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document::new()
+    }
+}
+
+impl Serialize for Document {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        At(self, self.root).serialize(serializer)
+    }
+}
+
+/// One node of a document, for `Serialize`.
+struct At<'d>(&'d Document, NodeId);
+
+impl Serialize for At<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let At(doc, id) = *self;
+        match doc.node(id) {
+            NodeRef::Null => serializer.serialize_unit(),
+            NodeRef::Bool(b) => serializer.serialize_bool(b),
+            NodeRef::Number(n) => n.serialize(serializer),
+            NodeRef::String(s) => serializer.serialize_str(s),
+            NodeRef::Array(len) => {
+                let mut seq = serializer.serialize_seq(Some(len))?;
+                for child in doc.children(id) {
+                    seq.serialize_element(&At(doc, child))?;
+                }
+                seq.end()
+            }
+            NodeRef::Object(len) => {
+                let mut map = serializer.serialize_map(Some(len))?;
+                for (key, value) in doc.members(id) {
+                    map.serialize_entry(key, &At(doc, value))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const BODY: &str = r#"{
+        "user": {"name": "Ada", "email": "ada@example.com", "tags": ["a", "b\"c"]},
+        "items": [1, -2.5, 18446744073709551615, true, null, {}],
+        "a/b": "slash"
+    }"#;
+
+    #[test]
+    fn round_trips_like_serde_json() {
+        let doc = Document::parse(BODY).unwrap();
+        let expected: Value = serde_json::from_str(BODY).unwrap();
+        assert_eq!(doc.to_value(doc.root()), expected);
+        assert_eq!(
+            serde_json::from_str::<Value>(&doc.to_json()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn reads_nodes_by_pointer() {
+        let doc = Document::parse(BODY).unwrap();
+        let email = doc.pointer("/user/email").unwrap();
+        assert_eq!(doc.node(email), NodeRef::String("ada@example.com"));
+        assert_eq!(doc.key(email), Some("email"));
+        let tag = doc.pointer("/user/tags/1").unwrap();
+        assert_eq!(doc.node(tag), NodeRef::String("b\"c"));
+        assert_eq!(doc.node(doc.pointer("/items").unwrap()), NodeRef::Array(6));
+        assert!(doc.pointer("/a~1b").is_some());
+        assert_eq!(doc.pointer(""), Some(doc.root()));
+        assert_eq!(doc.pointer("/items/01"), None);
+        assert_eq!(doc.pointer("/user/name/x"), None);
+        assert_eq!(doc.pointer("user"), None);
+    }
+
+    #[test]
+    fn rewrites_in_place() {
+        let mut doc = Document::parse(BODY).unwrap();
+        let email = doc.pointer("/user/email").unwrap();
+        let redacted = doc.add_str("[redacted]");
+        doc.replace(email, redacted);
+
+        let user = doc.pointer("/user").unwrap();
+        let seen = doc.add(&json!({"at": 17}));
+        doc.insert(user, "seen", seen);
+        let name = doc.add_str("Lovelace");
+        doc.insert(user, "name", name);
+        assert!(doc.remove(user, "tags").is_some());
+        assert_eq!(doc.remove(user, "tags"), None);
+
+        let items = doc.pointer("/items").unwrap();
+        assert!(doc.remove_at(items, 0).is_some());
+        let last = doc.add(&json!("end"));
+        doc.push(items, last);
+
+        assert_eq!(
+            doc.to_value(user),
+            json!({"name": "Lovelace", "email": "[redacted]", "seen": {"at": 17}})
+        );
+        assert_eq!(
+            doc.to_value(items),
+            json!([-2.5, 18446744073709551615u64, true, null, {}, "end"])
+        );
+    }
+
+    #[test]
+    fn repeated_keys_keep_order_and_last_wins() {
+        let doc = Document::parse(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+        assert_eq!(doc.to_json(), r#"{"a":1,"b":2,"a":3}"#);
+        let a = doc.get(doc.root(), "a").unwrap();
+        assert_eq!(doc.to_value(a), json!(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "already part of the document")]
+    fn attaching_a_node_twice_panics() {
+        let mut doc = Document::parse("[]").unwrap();
+        let item = doc.add_str("x");
+        doc.push(doc.root(), item);
+        doc.push(doc.root(), item);
+    }
+
+    #[test]
+    fn parse_into_reuses_the_arena() {
+        let mut doc = Document::parse(BODY).unwrap();
+        doc.parse_into(r#"{"k": "v"}"#).unwrap();
+        assert_eq!(doc.to_json(), r#"{"k":"v"}"#);
+        assert_eq!(doc.arena_len(), 2);
+    }
+
+    #[test]
+    fn malformed_bodies_are_invalid_input() {
+        for json in [
+            "",
+            "{",
+            r#"{"a" 1}"#,
+            "[1, 2,]",
+            "[tru]",
+            "[01]",
+            r#"{"a": "unterminated}"#,
+            "[1] [2]",
+        ] {
+            let err = Document::parse(json).unwrap_err();
+            assert_eq!(err.code(), "INVALID_INPUT", "{}", json);
+        }
+        let deep = "[".repeat(1_000);
+        let err = Document::parse(&deep).unwrap_err();
+        assert_eq!(err.details()["limit"], "max_depth");
+    }
+}
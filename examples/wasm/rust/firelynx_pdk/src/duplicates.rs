@@ -0,0 +1,255 @@
+//! Duplicate object keys in JSON bodies.
+//!
+//! JSON leaves duplicate keys undefined, and parsers disagree: serde_json and
+//! Go's `encoding/json` keep the last value, others keep the first or reject
+//! the document. A body like `{"role": "user", "role": "admin"}` can therefore
+//! pass validation in one component and mean something else in the next.
+//! `duplicate_keys` reports every such collision, including the near-misses
+//! that other parsers treat as the same key:
+//!
+//! - `duplicate`: identical after unescaping (`"a"` and `"\u0061"`).
+//! - `case_fold`: equal under Unicode case folding, which is how Go's
+//!   `encoding/json` matches struct fields (`"id"`, `"ID"`, and `"K"`
+//!   (KELVIN SIGN) against `"k"`).
+//! - `nul_truncated`: equal up to the first NUL, for parsers backed by C
+//!   strings.
+//!
+//! Keys with lone surrogate escapes are rejected as malformed rather than
+//! replaced, so they cannot collide by substitution.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::extract::Reader;
+use crate::fold;
+use crate::limits::ParseLimits;
+use crate::PluginError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Collision {
+    Duplicate,
+    CaseFold,
+    NulTruncated,
+}
+
+impl Collision {
+    pub fn label(self) -> &'static str {
+        match self {
+            Collision::Duplicate => "duplicate",
+            Collision::CaseFold => "case_fold",
+            Collision::NulTruncated => "nul_truncated",
+        }
+    }
+}
+
+/// A key that collides with an earlier key of the same object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateKey {
+    /// JSON Pointer of the later member.
+    pub pointer: String,
+    pub key: String,
+    pub collision: Collision,
+}
+
+/// Scans `json` for colliding keys, in document order. Malformed JSON, or
+/// JSON breaking the active `ParseLimits`, is `INVALID_INPUT`.
+pub fn duplicate_keys(json: &str) -> Result<Vec<DuplicateKey>, PluginError> {
+    ParseLimits::active().check_json(json.as_bytes())?;
+    let mut reader = Reader { text: json, pos: 0 };
+    let mut found = Vec::new();
+    let result = walk(&mut reader, &mut String::new(), &mut found).and_then(|()| {
+        reader.skip_ws();
+        match reader.peek() {
+            None => Ok(()),
+            Some(_) => Err("trailing characters".to_string()),
+        }
+    });
+    result.map_err(|message| {
+        PluginError::invalid_input(format!("Invalid JSON body: {}", message))
+            .with_detail("offset", reader.pos)
+    })?;
+    Ok(found)
+}
+
+/// Parses `json` like `serde_json::from_str`, but rejects bodies with any
+/// key collision as `INVALID_INPUT` naming the first one.
+pub fn from_str_unique<T: DeserializeOwned>(json: &str) -> Result<T, PluginError> {
+    if let Some(first) = duplicate_keys(json)?.into_iter().next() {
+        return Err(PluginError::invalid_input("JSON body has a duplicate key")
+            .with_detail("pointer", first.pointer)
+            .with_detail("key", first.key)
+            .with_detail("collision", first.collision.label()));
+    }
+    serde_json::from_str(json)
+        .map_err(|e| PluginError::invalid_input(format!("Invalid JSON body: {}", e)))
+}
+
+fn walk(
+    reader: &mut Reader<'_>,
+    pointer: &mut String,
+    found: &mut Vec<DuplicateKey>,
+) -> Result<(), String> {
+    reader.skip_ws();
+    let len = pointer.len();
+    match reader.peek() {
+        Some(b'{') => {
+            reader.pos += 1;
+            reader.skip_ws();
+            if reader.eat(b'}') {
+                return Ok(());
+            }
+            let mut exact = HashSet::new();
+            let mut folded = HashSet::new();
+            let mut truncated = HashSet::new();
+            loop {
+                reader.skip_ws();
+                let key = reader.string()?;
+                reader.skip_ws();
+                reader.expect(b':')?;
+
+                pointer.push('/');
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                let fold = fold::fold(&key).into_owned();
+                let nul = key.split('\0').next().unwrap_or_default().to_string();
+                let collision = if exact.contains(&key) {
+                    Some(Collision::Duplicate)
+                } else if folded.contains(&fold) {
+                    Some(Collision::CaseFold)
+                } else if truncated.contains(&nul) {
+                    Some(Collision::NulTruncated)
+                } else {
+                    None
+                };
+                if let Some(collision) = collision {
+                    found.push(DuplicateKey {
+                        pointer: pointer.clone(),
+                        key: key.to_string(),
+                        collision,
+                    });
+                }
+
+                walk(reader, pointer, found)?;
+                pointer.truncate(len);
+                exact.insert(key);
+                folded.insert(fold);
+                truncated.insert(nul);
+                if !reader.separator(b'}')? {
+                    return Ok(());
+                }
+            }
+        }
+        Some(b'[') => {
+            reader.pos += 1;
+            reader.skip_ws();
+            if reader.eat(b']') {
+                return Ok(());
+            }
+            for index in 0.. {
+                pointer.push('/');
+                pointer.push_str(&index.to_string());
+                walk(reader, pointer, found)?;
+                pointer.truncate(len);
+                if !reader.separator(b']')? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        Some(b'"') => reader.string().map(|_: Cow<'_, str>| ()),
+        _ => reader.skip_value(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn collisions(json: &str) -> Vec<(String, Collision)> {
+        duplicate_keys(json)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.pointer, d.collision))
+            .collect()
+    }
+
+    // Cases from published JSON interoperability research (key collision,
+    // escape and truncation tricks) and Go case-insensitive field matching.
+    #[test]
+    fn detects_known_collision_tricks() {
+        let cases: &[(&str, &str, Collision)] = &[
+            (r#"{"test": 1, "test": 2}"#, "/test", Collision::Duplicate),
+            (
+                r#"{"test": 1, "\u0074est": 2}"#,
+                "/test",
+                Collision::Duplicate,
+            ),
+            (
+                r#"{"role": "user", "ROLE": "admin"}"#,
+                "/ROLE",
+                Collision::CaseFold,
+            ),
+            (r#"{"k": 1, "K": 2}"#, "/\u{212a}", Collision::CaseFold),
+            (r#"{"s": 1, "ſ": 2}"#, "/\u{17f}", Collision::CaseFold),
+            (
+                r#"{"qty": 1, "qty\u0000": -1}"#,
+                "/qty\u{0}",
+                Collision::NulTruncated,
+            ),
+        ];
+        for (json, pointer, collision) in cases {
+            assert_eq!(
+                collisions(json),
+                vec![(pointer.to_string(), *collision)],
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn reports_nested_collisions_with_pointers() {
+        let json = r#"{"a/b": {"x": 1, "x": 2}, "list": [{"id": 1}, {"id": 2, "id": 3}]}"#;
+        assert_eq!(
+            collisions(json),
+            vec![
+                ("/a~1b/x".to_string(), Collision::Duplicate),
+                ("/list/1/id".to_string(), Collision::Duplicate),
+            ]
+        );
+    }
+
+    #[test]
+    fn distinct_keys_and_sibling_objects_are_fine() {
+        assert!(collisions(r#"[{"a": 1}, {"a": 2}, {"b": {"a": 3}}]"#).is_empty());
+        assert!(collisions(r#"{"a": "a", "b": ["a", "a"]}"#).is_empty());
+    }
+
+    #[test]
+    fn malformed_keys_are_invalid_input() {
+        for json in [
+            r#"{"test": 1, "te\st": 2}"#,
+            r#"{"test": 1, "test\ud800": 2}"#,
+            r#"{"test": 1, "test"": 2}"#,
+            r#"{"a": 1} {"a": 2}"#,
+        ] {
+            let err = duplicate_keys(json).unwrap_err();
+            assert_eq!(err.code(), "INVALID_INPUT", "{}", json);
+        }
+    }
+
+    #[test]
+    fn strict_parse_rejects_collisions() {
+        let err = from_str_unique::<Value>(r#"{"role": "user", "role": "admin"}"#).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert_eq!(err.details()["pointer"], "/role");
+        assert_eq!(err.details()["collision"], "duplicate");
+
+        let value: Value = from_str_unique(r#"{"role": "user"}"#).unwrap();
+        assert_eq!(value["role"], "user");
+    }
+}
@@ -0,0 +1,191 @@
+//! Structured plugin errors with machine-readable codes.
+//!
+//! A `PluginError` converts into an `extism_pdk::Error` whose message is a
+//! JSON envelope, so the firelynx host and xtp tests can match on `code`
+//! instead of on free-form message text:
+//!
+//! ```json
+//! {"code":"CONFIG_ERROR","message":"Character set cannot be empty","details":{"field":"search_characters"}}
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+
+/// Extra context attached to an error, serialized as the envelope's `details`.
+pub type Details = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginError {
+    /// The request envelope or body could not be used.
+    InvalidInput { message: String, details: Details },
+    /// The route's `static_data` or plugin config is invalid.
+    ConfigError { message: String, details: Details },
+    /// A call to an upstream service failed.
+    UpstreamError { message: String, details: Details },
+    /// The plugin refused an action its policy does not allow.
+    PolicyViolation { message: String, details: Details },
+    /// The request body is larger than the plugin accepts; the host may
+    /// answer it with HTTP 413.
+    PayloadTooLarge { message: String, details: Details },
+    /// A bug or unexpected state inside the plugin.
+    Internal { message: String, details: Details },
+}
+
+impl PluginError {
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        PluginError::InvalidInput {
+            message: message.into(),
+            details: Details::new(),
+        }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        PluginError::ConfigError {
+            message: message.into(),
+            details: Details::new(),
+        }
+    }
+
+    pub fn upstream(message: impl Into<String>) -> Self {
+        PluginError::UpstreamError {
+            message: message.into(),
+            details: Details::new(),
+        }
+    }
+
+    pub fn policy(message: impl Into<String>) -> Self {
+        PluginError::PolicyViolation {
+            message: message.into(),
+            details: Details::new(),
+        }
+    }
+
+    pub fn too_large(message: impl Into<String>) -> Self {
+        PluginError::PayloadTooLarge {
+            message: message.into(),
+            details: Details::new(),
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        PluginError::Internal {
+            message: message.into(),
+            details: Details::new(),
+        }
+    }
+
+    /// Adds a key to the `details` map, replacing any previous value.
+    pub fn with_detail(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.details_mut().insert(key.into(), value.into());
+        self
+    }
+
+    /// The stable, machine-readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PluginError::InvalidInput { .. } => "INVALID_INPUT",
+            PluginError::ConfigError { .. } => "CONFIG_ERROR",
+            PluginError::UpstreamError { .. } => "UPSTREAM_ERROR",
+            PluginError::PolicyViolation { .. } => "POLICY_VIOLATION",
+            PluginError::PayloadTooLarge { .. } => "PAYLOAD_TOO_LARGE",
+            PluginError::Internal { .. } => "INTERNAL",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            PluginError::InvalidInput { message, .. }
+            | PluginError::ConfigError { message, .. }
+            | PluginError::UpstreamError { message, .. }
+            | PluginError::PolicyViolation { message, .. }
+            | PluginError::PayloadTooLarge { message, .. }
+            | PluginError::Internal { message, .. } => message,
+        }
+    }
+
+    pub fn details(&self) -> &Details {
+        match self {
+            PluginError::InvalidInput { details, .. }
+            | PluginError::ConfigError { details, .. }
+            | PluginError::UpstreamError { details, .. }
+            | PluginError::PolicyViolation { details, .. }
+            | PluginError::PayloadTooLarge { details, .. }
+            | PluginError::Internal { details, .. } => details,
+        }
+    }
+
+    fn details_mut(&mut self) -> &mut Details {
+        match self {
+            PluginError::InvalidInput { details, .. }
+            | PluginError::ConfigError { details, .. }
+            | PluginError::UpstreamError { details, .. }
+            | PluginError::PolicyViolation { details, .. }
+            | PluginError::PayloadTooLarge { details, .. }
+            | PluginError::Internal { details, .. } => details,
+        }
+    }
+
+    /// Serializes the error as the JSON envelope reported through `error_set`.
+    /// During a traced call the envelope also has the call's `trace_id`.
+    pub fn to_json(&self) -> String {
+        let mut envelope = serde_json::json!({
+            "code": self.code(),
+            "message": self.message(),
+            "details": self.details(),
+        });
+        if let Some(ctx) = crate::trace::current() {
+            envelope["trace_id"] = ctx.trace_id_hex().into();
+        }
+        envelope.to_string()
+    }
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl From<PluginError> for extism_pdk::Error {
+    fn from(e: PluginError) -> Self {
+        extism_pdk::Error::msg(e.to_json())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_has_code_message_and_details() {
+        let err = PluginError::config("Character set cannot be empty")
+            .with_detail("field", "search_characters");
+        let envelope: Value = serde_json::from_str(&err.to_json()).unwrap();
+        assert_eq!(
+            envelope,
+            serde_json::json!({
+                "code": "CONFIG_ERROR",
+                "message": "Character set cannot be empty",
+                "details": {"field": "search_characters"},
+            })
+        );
+    }
+
+    #[test]
+    fn converts_into_extism_error_carrying_the_envelope() {
+        let err: extism_pdk::Error = PluginError::policy("denied").into();
+        let envelope: Value = serde_json::from_str(&err.to_string()).unwrap();
+        assert_eq!(envelope["code"], "POLICY_VIOLATION");
+        assert_eq!(envelope["details"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn too_large_has_its_own_code() {
+        let err = PluginError::too_large("Body too large").with_detail("max_body_bytes", 1024);
+        let envelope: Value = serde_json::from_str(&err.to_json()).unwrap();
+        assert_eq!(envelope["code"], "PAYLOAD_TOO_LARGE");
+        assert_eq!(envelope["details"]["max_body_bytes"], 1024);
+    }
+}
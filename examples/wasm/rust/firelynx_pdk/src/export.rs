@@ -0,0 +1,340 @@
+//! Runtime support for the `#[firelynx_plugin]` export shim.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::sync::Once;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::Codec;
+use crate::input::{Input, InputRef, Request, RequestRef};
+use crate::limits::ParseLimits;
+use crate::stream::OutputStream;
+use crate::{clock, metrics, result, rng, trace, PluginError};
+
+/// A handler return value the shim knows how to write as export output.
+/// Serializable values are encoded with the envelope codec, under `ok` when
+/// the call has result envelopes; an `OutputStream` is written as-is.
+/// Metrics recorded during the call are added to object outputs under
+/// `_meta.metrics`.
+#[doc(hidden)]
+pub trait IntoOutput {
+    fn write_output(self, codec: Codec) -> Result<(), extism_pdk::Error>;
+}
+
+impl<T: Serialize> IntoOutput for T {
+    fn write_output(self, codec: Codec) -> Result<(), extism_pdk::Error> {
+        let metrics = metrics::take();
+        OUTPUT.with(|output| {
+            let mut output = output.borrow_mut();
+            let bytes = if metrics.is_empty() {
+                output.encode_result(codec, &self)?
+            } else {
+                let mut value = serde_json::to_value(&self).map_err(|e| {
+                    PluginError::internal(format!(
+                        "Failed to encode {} output: {}",
+                        codec.name(),
+                        e
+                    ))
+                })?;
+                metrics::attach(&mut value, &metrics);
+                output.encode_result(codec, &value)?
+            };
+            extism_pdk::output(bytes)?;
+            output.trim();
+            Ok(())
+        })
+    }
+}
+
+/// Capacity the output buffer keeps between calls; a larger output is
+/// encoded into a buffer that is released once it has been written.
+const MAX_RETAINED_OUTPUT_BYTES: usize = 1024 * 1024;
+
+thread_local! {
+    static OUTPUT: RefCell<OutputBuffer> = const {
+        RefCell::new(OutputBuffer {
+            buf: Vec::new(),
+            last_len: 0,
+        })
+    };
+}
+
+/// The buffer outputs are encoded into before being copied to extism
+/// memory. It is reused across calls, and sized up front for the previous
+/// call's output, so a plugin returning similar outputs does not allocate
+/// or regrow a buffer per call.
+struct OutputBuffer {
+    buf: Vec<u8>,
+    last_len: usize,
+}
+
+impl OutputBuffer {
+    fn encode<T: Serialize>(&mut self, codec: Codec, value: &T) -> Result<&[u8], PluginError> {
+        self.buf.clear();
+        self.buf.reserve(self.last_len);
+        codec.encode_into(value, &mut self.buf)?;
+        self.last_len = self.buf.len();
+        Ok(&self.buf)
+    }
+
+    /// Encodes a handler's result: `value`, or `{"ok": value}` when the
+    /// call has result envelopes.
+    fn encode_result<T: Serialize>(
+        &mut self,
+        codec: Codec,
+        value: &T,
+    ) -> Result<&[u8], PluginError> {
+        match result::codec() {
+            Some(_) => self.encode(codec, &result::Succeeded { ok: value }),
+            None => self.encode(codec, value),
+        }
+    }
+
+    /// Releases a buffer grown past `MAX_RETAINED_OUTPUT_BYTES`, and caps
+    /// the size the next call reserves, so one large output does not make
+    /// every later call allocate that much again.
+    fn trim(&mut self) {
+        if self.buf.capacity() > MAX_RETAINED_OUTPUT_BYTES {
+            self.buf = Vec::new();
+        }
+        self.last_len = self.last_len.min(MAX_RETAINED_OUTPUT_BYTES);
+    }
+}
+
+impl IntoOutput for OutputStream {
+    fn write_output(self, _: Codec) -> Result<(), extism_pdk::Error> {
+        metrics::take();
+        self.finish();
+        Ok(())
+    }
+}
+
+/// Runs one export invocation: reads and parses the input envelope in the
+/// configured `Codec` under the configured `ParseLimits`, calls `handler` with the request and its typed
+/// `static_data`, and writes the result as output (see `IntoOutput`).
+/// Returns the extism status code.
+#[doc(hidden)]
+pub fn run<S, T, E>(handler: impl FnOnce(Request, S) -> Result<T, E>) -> i32
+where
+    S: DeserializeOwned + Default,
+    T: IntoOutput,
+    E: Into<extism_pdk::Error>,
+{
+    run_input(|input: Input<S>| {
+        handler(input.request, input.static_data.unwrap_or_default()).map_err(Into::into)
+    })
+}
+
+/// `run` for a handler that takes the whole parsed envelope.
+pub(crate) fn run_input<S, T>(handler: impl FnOnce(Input<S>) -> Result<T, extism_pdk::Error>) -> i32
+where
+    S: DeserializeOwned,
+    T: IntoOutput,
+{
+    run_raw(|codec, raw| {
+        let input = Input::<S>::decode(codec, raw)?;
+        trace::begin(input.request.trace_context());
+        handler(input)?.write_output(codec)
+    })
+}
+
+/// `run` for a handler that takes a `RequestRef`. The request borrows from
+/// the input text, so only JSON envelopes are accepted.
+#[doc(hidden)]
+pub fn run_ref<S, T, E>(handler: impl FnOnce(RequestRef<'_>, S) -> Result<T, E>) -> i32
+where
+    S: DeserializeOwned + Default,
+    T: IntoOutput,
+    E: Into<extism_pdk::Error>,
+{
+    run_raw(|codec, raw| {
+        if codec != Codec::Json {
+            return Err(
+                PluginError::invalid_input("This export only reads JSON envelopes")
+                    .with_detail("envelope_codec", codec.name())
+                    .into(),
+            );
+        }
+        let text = String::from_utf8(raw).map_err(|e| {
+            PluginError::invalid_input(format!("Invalid {} input: {}", codec.name(), e))
+        })?;
+        let input = InputRef::<S>::from_json(&text)?;
+        trace::begin(input.request.trace_context());
+        handler(input.request, input.static_data.unwrap_or_default())
+            .map_err(Into::into)?
+            .write_output(codec)
+    })
+}
+
+/// Sets up one export invocation and runs `call` on the raw input and the
+/// configured `Codec`, reporting its error or panic to the host.
+fn run_raw(call: impl FnOnce(Codec, Vec<u8>) -> Result<(), extism_pdk::Error>) -> i32 {
+    install_panic_hook();
+    // Drop anything left over from an earlier call that failed.
+    metrics::take();
+    result::begin(None);
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        extism_pdk::input::<Vec<u8>>().and_then(|raw| {
+            let codec = Codec::from_config();
+            if result::from_config()? {
+                // A bad codec is itself reported, in JSON.
+                result::begin(Some(codec.as_ref().copied().unwrap_or_default()));
+            }
+            let codec = codec?;
+            ParseLimits::init()?;
+            clock::init()?;
+            rng::init()?;
+            call(codec, raw)
+        })
+    }));
+
+    match outcome {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => match result::codec() {
+            Some(codec) => result::write_error(codec, e),
+            None => return_error(e),
+        },
+        // The panic hook has already reported the error.
+        Err(_) => -1,
+    }
+}
+
+/// Installs, once per instance, a panic hook that reports the panic message
+/// and location through `error_set` as an `INTERNAL` error. wasm32 builds
+/// abort on panic, so the instance still traps afterwards, but the host
+/// reads the error instead of an opaque `unreachable` trap.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        panic::set_hook(Box::new(|info| {
+            set_error(&panic_error(info.payload(), info.location()).to_json());
+        }));
+    });
+}
+
+/// Builds the error reported for a panic with `payload` raised at `location`.
+fn panic_error(payload: &dyn Any, location: Option<&Location<'_>>) -> PluginError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    match location {
+        Some(location) => {
+            PluginError::internal(format!("plugin panicked at {}: {}", location, message))
+                .with_detail("location", location.to_string())
+        }
+        None => PluginError::internal(format!("plugin panicked: {}", message)),
+    }
+}
+
+/// Reported when the error itself cannot be: it is short enough that its
+/// allocation may still succeed after a larger one failed.
+#[cfg(target_family = "wasm")]
+const UNREPORTABLE_ERROR: &str =
+    r#"{"code":"INTERNAL","message":"the plugin's error could not be reported","details":{}}"#;
+
+#[cfg(target_family = "wasm")]
+fn set_error(message: &str) {
+    // Allocation may be what failed; failing the fallback too, there is
+    // nothing left to report with.
+    let mem = extism_pdk::Memory::from_bytes(message)
+        .or_else(|_| extism_pdk::Memory::from_bytes(UNREPORTABLE_ERROR));
+    if let Ok(mem) = mem {
+        unsafe {
+            extism_pdk::extism::error_set(mem.offset());
+        }
+    }
+}
+
+/// Native builds (unit tests) have no host to report to.
+#[cfg(not(target_family = "wasm"))]
+fn set_error(_: &str) {}
+
+/// Reports `e` to the host through `error_set` and returns the failure status.
+/// Never panics: an error that cannot be allocated is reported as a fixed
+/// `INTERNAL` error, or not at all, and the status is the same.
+#[doc(hidden)]
+pub fn return_error(e: extism_pdk::Error) -> i32 {
+    set_error(&e.to_string());
+    -1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn output_buffer_does_not_grow_for_repeated_outputs() {
+        let mut output = OutputBuffer {
+            buf: Vec::new(),
+            last_len: 0,
+        };
+        let value = json!({"count": 3, "characters": "aeiou".repeat(100)});
+        let first = output.encode(Codec::Json, &value).unwrap().to_vec();
+        let (ptr, capacity) = (output.buf.as_ptr(), output.buf.capacity());
+        for _ in 0..10 {
+            assert_eq!(output.encode(Codec::Json, &value).unwrap(), first);
+            output.trim();
+        }
+        assert_eq!(output.buf.as_ptr(), ptr);
+        assert_eq!(output.buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn output_buffer_is_presized_and_released_after_large_outputs() {
+        let mut output = OutputBuffer {
+            buf: Vec::new(),
+            last_len: 0,
+        };
+        let large = "x".repeat(2 * MAX_RETAINED_OUTPUT_BYTES);
+        output.encode(Codec::Json, &large).unwrap();
+        output.trim();
+        assert_eq!(output.buf.capacity(), 0);
+        // The next call reserves up to the retained size, not the large
+        // output's, and the buffer stays bounded from then on.
+        output.encode(Codec::Json, &"small").unwrap();
+        output.trim();
+        let capacity = output.buf.capacity();
+        assert!(capacity >= MAX_RETAINED_OUTPUT_BYTES);
+        assert!(capacity < large.len());
+        output.encode(Codec::Json, &"small").unwrap();
+        output.trim();
+        assert_eq!(output.buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn panic_error_has_message_and_location() {
+        let location = Location::caller();
+        let payload: Box<dyn Any> =
+            Box::new("range end index 9 out of range for slice of length 4");
+        let err = panic_error(payload.as_ref(), Some(location));
+        assert_eq!(err.code(), "INTERNAL");
+        assert_eq!(
+            err.message(),
+            format!(
+                "plugin panicked at {}: range end index 9 out of range for slice of length 4",
+                location
+            )
+        );
+        assert_eq!(err.details()["location"], location.to_string());
+    }
+
+    #[test]
+    fn panic_error_reads_formatted_payloads() {
+        let payload: Box<dyn Any> = Box::new(format!("bad index {}", 3));
+        let err = panic_error(payload.as_ref(), None);
+        assert_eq!(err.message(), "plugin panicked: bad index 3");
+        assert!(err.details().is_empty());
+
+        let payload: Box<dyn Any> = Box::new(42);
+        assert_eq!(
+            panic_error(payload.as_ref(), None).message(),
+            "plugin panicked: Box<dyn Any>"
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! Pulls a few values out of a large JSON body without parsing all of it.
+//!
+//! An `Extractor` is built once from JSON Pointers (RFC 6901). `extract`
+//! walks the body byte by byte, descending only into objects and arrays on a
+//! requested path. Everything else is skipped by matching brackets and
+//! strings, without allocating, and the walk stops as soon as every pointer
+//! has been found. Only the matched values are parsed into `Value`s.
+//!
+//! ```ignore
+//! let extractor = Extractor::new(["/model", "/messages/0/role"])?;
+//! let [model, role] = extractor.extract(&request.body.text())?.try_into().unwrap();
+//! ```
+//!
+//! Skipped parts of the body are checked only for balanced brackets and
+//! terminated strings, and nothing after the last match is looked at, so a
+//! body that is malformed there can still yield values.
+
+use std::borrow::Cow;
+
+use serde_json::Value;
+
+use crate::limits::ParseLimits;
+use crate::PluginError;
+
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    pointers: Vec<String>,
+    /// Unescaped reference tokens of each pointer.
+    paths: Vec<Vec<String>>,
+}
+
+impl Extractor {
+    /// Compiles `pointers`. Each must be empty (the whole body) or start with
+    /// `/`; anything else is a `CONFIG_ERROR`.
+    pub fn new<I, S>(pointers: I) -> Result<Extractor, PluginError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let pointers: Vec<String> = pointers.into_iter().map(Into::into).collect();
+        let paths = pointers
+            .iter()
+            .map(|p| parse_pointer(p))
+            .collect::<Result<_, _>>()?;
+        Ok(Extractor { pointers, paths })
+    }
+
+    /// The pointers, in the order `extract` reports their values.
+    pub fn pointers(&self) -> &[String] {
+        &self.pointers
+    }
+
+    /// Returns the value at each pointer, `None` where the body has nothing
+    /// there. Malformed JSON on the way to a match is `INVALID_INPUT`, as is
+    /// a body breaking the active `ParseLimits` anywhere.
+    pub fn extract(&self, json: &str) -> Result<Vec<Option<Value>>, PluginError> {
+        ParseLimits::active().check_json(json.as_bytes())?;
+        let mut walk = Walk {
+            reader: Reader { text: json, pos: 0 },
+            paths: &self.paths,
+            found: vec![None; self.paths.len()],
+            done: vec![false; self.paths.len()],
+            remaining: self.paths.len(),
+        };
+        let all: Vec<usize> = (0..self.paths.len()).collect();
+        if !all.is_empty() {
+            walk.value(&all, 0).map_err(|message| {
+                PluginError::invalid_input(format!("Invalid JSON body: {}", message))
+                    .with_detail("offset", walk.reader.pos)
+            })?;
+        }
+        Ok(walk.found)
+    }
+}
+
+pub(crate) fn parse_pointer(pointer: &str) -> Result<Vec<String>, PluginError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(
+            PluginError::config("JSON pointer must be empty or start with '/'")
+                .with_detail("pointer", pointer),
+        );
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// True when `token` is the canonical array index `index` (no sign, no
+/// leading zeros).
+pub(crate) fn is_index(token: &str, index: usize) -> bool {
+    (token == "0" || !token.starts_with('0')) && token.parse() == Ok(index)
+}
+
+struct Walk<'a, 'p> {
+    reader: Reader<'a>,
+    paths: &'p [Vec<String>],
+    found: Vec<Option<Value>>,
+    /// Pointers already answered; with duplicate keys the first occurrence
+    /// wins.
+    done: Vec<bool>,
+    /// Pointers not found yet; the walk stops when this reaches zero.
+    remaining: usize,
+}
+
+impl Walk<'_, '_> {
+    /// Visits the value at the reader, which `candidates` (indexes into
+    /// `paths`) all lead to or through. `depth` tokens of each have matched.
+    fn value(&mut self, candidates: &[usize], depth: usize) -> Result<(), String> {
+        self.reader.skip_ws();
+        let start = self.reader.pos;
+        if candidates.iter().any(|&i| self.paths[i].len() == depth) {
+            // A pointer ends here: parse this value once and answer every
+            // candidate, including longer pointers, from it.
+            self.reader.skip_value()?;
+            let value: Value = serde_json::from_str(&self.reader.text[start..self.reader.pos])
+                .map_err(|e| e.to_string())?;
+            for &i in candidates {
+                if self.done[i] {
+                    continue;
+                }
+                self.found[i] = lookup(&value, &self.paths[i][depth..]).cloned();
+                self.done[i] = true;
+                self.remaining -= 1;
+            }
+            return Ok(());
+        }
+
+        match self.reader.peek() {
+            Some(b'{') => {
+                self.reader.pos += 1;
+                self.reader.skip_ws();
+                if self.reader.eat(b'}') {
+                    return Ok(());
+                }
+                loop {
+                    self.reader.skip_ws();
+                    let key = self.reader.string()?;
+                    self.reader.skip_ws();
+                    self.reader.expect(b':')?;
+                    let next: Vec<usize> = candidates
+                        .iter()
+                        .copied()
+                        .filter(|&i| self.paths[i][depth] == key)
+                        .collect();
+                    if next.is_empty() {
+                        self.reader.skip_ws();
+                        self.reader.skip_value()?;
+                    } else {
+                        self.value(&next, depth + 1)?;
+                    }
+                    if self.remaining == 0 || !self.reader.separator(b'}')? {
+                        return Ok(());
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.reader.pos += 1;
+                self.reader.skip_ws();
+                if self.reader.eat(b']') {
+                    return Ok(());
+                }
+                for index in 0.. {
+                    let next: Vec<usize> = candidates
+                        .iter()
+                        .copied()
+                        .filter(|&i| is_index(&self.paths[i][depth], index))
+                        .collect();
+                    if next.is_empty() {
+                        self.reader.skip_ws();
+                        self.reader.skip_value()?;
+                    } else {
+                        self.value(&next, depth + 1)?;
+                    }
+                    if self.remaining == 0 || !self.reader.separator(b']')? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            // A scalar cannot contain the rest of any candidate's path.
+            _ => self.reader.skip_value(),
+        }
+    }
+}
+
+fn lookup<'v>(value: &'v Value, tokens: &[String]) -> Option<&'v Value> {
+    tokens.iter().try_fold(value, |value, token| match value {
+        Value::Object(map) => map.get(token),
+        Value::Array(items) => token
+            .parse()
+            .ok()
+            .filter(|&i| is_index(token, i))
+            .and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// A byte-level JSON reader shared by the SDK's scanners and `Document`.
+pub(crate) struct Reader<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    pub(crate) fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    pub(crate) fn eat(&mut self, b: u8) -> bool {
+        let matched = self.peek() == Some(b);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    pub(crate) fn expect(&mut self, b: u8) -> Result<(), String> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", b as char))
+        }
+    }
+
+    /// After a member or element: true on `,`, false on `close`.
+    pub(crate) fn separator(&mut self, close: u8) -> Result<bool, String> {
+        self.skip_ws();
+        if self.eat(b',') {
+            Ok(true)
+        } else if self.eat(close) {
+            Ok(false)
+        } else {
+            Err(format!("expected ',' or '{}'", close as char))
+        }
+    }
+
+    /// Reads a string, borrowing it unless it has escapes.
+    pub(crate) fn string(&mut self) -> Result<Cow<'a, str>, String> {
+        let start = self.pos;
+        self.skip_string()?;
+        let literal = &self.text[start..self.pos];
+        if literal.contains('\\') {
+            serde_json::from_str(literal)
+                .map(Cow::Owned)
+                .map_err(|e| e.to_string())
+        } else {
+            Ok(Cow::Borrowed(&literal[1..literal.len() - 1]))
+        }
+    }
+
+    pub(crate) fn skip_string(&mut self) -> Result<(), String> {
+        self.expect(b'"')?;
+        let bytes = self.text.as_bytes();
+        while let Some(&b) = bytes.get(self.pos) {
+            self.pos += 1;
+            match b {
+                b'"' => return Ok(()),
+                b'\\' => self.pos += 1,
+                _ => {}
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    pub(crate) fn skip_value(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Some(b'"') => self.skip_string(),
+            Some(b'{' | b'[') => {
+                let mut depth = 0usize;
+                while let Some(b) = self.peek() {
+                    match b {
+                        b'"' => {
+                            self.skip_string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.pos += 1;
+                                return Ok(());
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                Err("unterminated object or array".to_string())
+            }
+            _ => {
+                let start = self.pos;
+                while !matches!(
+                    self.peek(),
+                    None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')
+                ) {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    Err("expected a value".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const BODY: &str = r#"{
+        "model": "gpt-4o",
+        "messages": [
+            {"role": "system", "content": "be brief"},
+            {"role": "user", "content": "hi \"there\"", "tags": ["a/b"]}
+        ],
+        "meta": {"a/b": 1, "t~x": [true, null]},
+        "tail": [1, 2, {"deep": "x"}]
+    }"#;
+
+    fn extract(pointers: &[&str], body: &str) -> Vec<Option<Value>> {
+        Extractor::new(pointers.iter().copied())
+            .unwrap()
+            .extract(body)
+            .unwrap()
+    }
+
+    #[test]
+    fn extracts_declared_paths() {
+        let found = extract(
+            &[
+                "/model",
+                "/messages/1/content",
+                "/messages/0",
+                "/meta/a~1b",
+                "/meta/t~0x/1",
+                "/missing",
+                "/messages/7",
+            ],
+            BODY,
+        );
+        assert_eq!(
+            found,
+            vec![
+                Some(json!("gpt-4o")),
+                Some(json!("hi \"there\"")),
+                Some(json!({"role": "system", "content": "be brief"})),
+                Some(json!(1)),
+                Some(Value::Null),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_pointer_under_a_matched_value() {
+        let found = extract(&["/messages", "/messages/1/tags/0"], BODY);
+        assert_eq!(found[0].as_ref().unwrap().as_array().unwrap().len(), 2);
+        assert_eq!(found[1], Some(json!("a/b")));
+    }
+
+    #[test]
+    fn whole_document_and_escaped_keys() {
+        assert_eq!(extract(&[""], "[1, 2]"), vec![Some(json!([1, 2]))]);
+        assert_eq!(
+            extract(&["/k\"ey"], r#"{"k\"ey": 3}"#),
+            vec![Some(json!(3))]
+        );
+    }
+
+    #[test]
+    fn stops_once_everything_is_found() {
+        // The garbage after "model" is never reached.
+        let found = extract(&["/model"], r#"{"model": "m", "rest": [}"#);
+        assert_eq!(found, vec![Some(json!("m"))]);
+    }
+
+    #[test]
+    fn first_duplicate_key_wins() {
+        let found = extract(&["/a", "/b"], r#"{"a": 1, "a": 2, "b": 3}"#);
+        assert_eq!(found, vec![Some(json!(1)), Some(json!(3))]);
+    }
+
+    #[test]
+    fn array_indices_are_canonical() {
+        assert_eq!(
+            extract(&["/01", "/1"], "[5, 6]"),
+            vec![None, Some(json!(6))]
+        );
+    }
+
+    #[test]
+    fn malformed_bodies_are_invalid_input() {
+        let extractor = Extractor::new(["/b"]).unwrap();
+        let err = extractor.extract(r#"{"a": "unterminated}"#).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        let err = extractor.extract(r#"{"a" 1}"#).unwrap_err();
+        assert_eq!(err.details()["offset"], 5);
+        assert!(extractor.extract(r#"{"b": tru}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_relative_pointers() {
+        let err = Extractor::new(["model"]).unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+    }
+}
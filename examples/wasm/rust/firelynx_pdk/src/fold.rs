@@ -0,0 +1,286 @@
+//! Case-insensitive text comparison with an ASCII fast path.
+//!
+//! Folding maps each character through `to_uppercase` and then
+//! `to_lowercase`, so characters that only meet after a round trip compare
+//! equal: `"K"` (KELVIN SIGN) and `"k"`, `"ſ"` and `"s"`, `"ς"` and `"σ"`.
+//! When both sides are ASCII, as keys and keywords nearly always are, the
+//! functions compare bytes with `eq_ignore_ascii_case` and do not allocate;
+//! anything else takes the per-character path.
+//!
+//! `fold_simple` folds one character to one character (Unicode simple case
+//! folding), for counting per character, and takes the language-specific
+//! rules of a `Locale`.
+//!
+//! HTTP header names, methods and media types stay on plain
+//! `eq_ignore_ascii_case`: they are ASCII by definition, and folding would
+//! let a non-ASCII name match a header no HTTP peer considers equal.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// The folded form of `s`, borrowed when it is ASCII without uppercase
+/// letters.
+pub fn fold(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        if s.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(s.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(s)
+        }
+    } else {
+        Cow::Owned(folded_chars(s).collect())
+    }
+}
+
+/// True when `a` and `b` fold to the same text.
+pub fn eq_fold(a: &str, b: &str) -> bool {
+    if a.is_ascii() && b.is_ascii() {
+        return a.eq_ignore_ascii_case(b);
+    }
+    eq_fold_chars(a, b)
+}
+
+/// The byte ranges in `haystack` whose text folds to `needle`, leftmost
+/// first and not overlapping. Ranges always fall on character boundaries
+/// of `haystack`.
+pub fn find_fold(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    if haystack.is_ascii() && needle.is_ascii() {
+        return find_fold_ascii(haystack.as_bytes(), needle.as_bytes());
+    }
+    find_fold_chars(haystack, needle)
+}
+
+/// Case-folding rules that depend on the language.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    /// The language-neutral rules of CaseFolding.txt.
+    #[default]
+    Root,
+    /// Turkish and Azerbaijani: "I" folds to dotless "ı" and "İ" to "i",
+    /// so the dotted and dotless letters stay apart.
+    Turkic,
+}
+
+impl Locale {
+    /// The rules for a BCP 47 language tag such as `"tr"` or `"az-Latn-AZ"`:
+    /// `Turkic` for Turkish and Azerbaijani, `Root` for every other
+    /// language. `None` when `tag` is not a well-formed tag.
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let mut subtags = tag.split(['-', '_']);
+        let language = subtags.next()?;
+        let well_formed = (2..=8).contains(&language.len())
+            && language.bytes().all(|b| b.is_ascii_alphabetic())
+            && subtags.all(|s| {
+                (1..=8).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric())
+            });
+        if !well_formed {
+            return None;
+        }
+        if language.eq_ignore_ascii_case("tr") || language.eq_ignore_ascii_case("az") {
+            Some(Locale::Turkic)
+        } else {
+            Some(Locale::Root)
+        }
+    }
+}
+
+/// `c` folded to a single character, so one character of text stays one
+/// counted character: "ſ" and "s", "ς" and "σ", "K" (KELVIN SIGN) and "k"
+/// fold together, while "ß" and "İ", which fold to two characters under
+/// full folding, stay as they are (outside `Locale::Turkic`, which folds
+/// "İ" to "i").
+pub fn fold_simple(c: char, locale: Locale) -> char {
+    if c.is_ascii() && locale == Locale::Root {
+        return c.to_ascii_lowercase();
+    }
+    match (c, locale) {
+        ('I', Locale::Turkic) => return 'ı',
+        ('İ', Locale::Turkic) => return 'i',
+        // Its uppercase is "I", whose lowercase is "i", but dotless "ı"
+        // has no folding of its own.
+        ('ı', _) => return 'ı',
+        _ => {}
+    }
+    // Round-tripping through uppercase joins variants that lowercase alone
+    // leaves apart; a case that maps to several characters is skipped.
+    single(c.to_uppercase())
+        .and_then(|upper| single(upper.to_lowercase()))
+        .or_else(|| single(c.to_lowercase()))
+        .unwrap_or(c)
+}
+
+/// The only item of `chars`, if it has exactly one.
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let first = chars.next()?;
+    chars.next().is_none().then_some(first)
+}
+
+fn fold_char(c: char) -> impl Iterator<Item = char> {
+    c.to_uppercase().flat_map(char::to_lowercase)
+}
+
+fn folded_chars(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().flat_map(fold_char)
+}
+
+fn eq_fold_chars(a: &str, b: &str) -> bool {
+    folded_chars(a).eq(folded_chars(b))
+}
+
+fn find_fold_ascii(haystack: &[u8], needle: &[u8]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= haystack.len() {
+        let end = i + needle.len();
+        if haystack[i].eq_ignore_ascii_case(&needle[0])
+            && haystack[i..end].eq_ignore_ascii_case(needle)
+        {
+            found.push(i..end);
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+fn find_fold_chars(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    let needle: Vec<char> = folded_chars(needle).collect();
+    let mut found = Vec::new();
+    let mut start = 0;
+    while let Some(c) = haystack[start..].chars().next() {
+        match match_len(&haystack[start..], &needle) {
+            Some(len) => {
+                found.push(start..start + len);
+                start += len;
+            }
+            None => start += c.len_utf8(),
+        }
+    }
+    found
+}
+
+/// The length of the prefix of `text` that folds to exactly `needle`.
+fn match_len(text: &str, needle: &[char]) -> Option<usize> {
+    let mut matched = 0;
+    for (i, c) in text.char_indices() {
+        for f in fold_char(c) {
+            if needle.get(matched) != Some(&f) {
+                return None;
+            }
+            matched += 1;
+        }
+        if matched == needle.len() {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pieces for generated strings: ASCII letters and the non-ASCII
+    /// characters that fold onto them or expand when folded.
+    const ALPHABET: &[&str] = &[
+        "a", "A", "k", "K", "s", "S", "i", "I", " ", "-", "\u{212a}", "ſ", "ß", "ẞ", "İ", "ı", "σ",
+        "ς", "Σ", "é", "É", "ǅ",
+    ];
+
+    /// Deterministic strings of up to `max_len` pieces (xorshift).
+    fn generated(count: usize, max_len: usize, ascii_only: bool) -> Vec<String> {
+        let pieces: Vec<&str> = ALPHABET
+            .iter()
+            .copied()
+            .filter(|p| !ascii_only || p.is_ascii())
+            .collect();
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        (0..count)
+            .map(|_| {
+                let len = next() % (max_len + 1);
+                (0..len).map(|_| pieces[next() % pieces.len()]).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ascii_fast_path_matches_the_slow_path() {
+        let strings = generated(300, 6, true);
+        for a in &strings {
+            assert_eq!(fold(a), folded_chars(a).collect::<String>(), "{:?}", a);
+            for b in strings.iter().take(60) {
+                assert_eq!(eq_fold(a, b), eq_fold_chars(a, b), "{:?} {:?}", a, b);
+                if !b.is_empty() {
+                    assert_eq!(find_fold(a, b), find_fold_chars(a, b), "{:?} in {:?}", b, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mixed_inputs_agree_with_folded_comparison() {
+        let strings = generated(300, 5, false);
+        for a in &strings {
+            for b in strings.iter().take(60) {
+                assert_eq!(eq_fold(a, b), fold(a) == fold(b), "{:?} {:?}", a, b);
+                for range in find_fold(a, b) {
+                    assert!(eq_fold(&a[range.clone()], b), "{:?} in {:?}", b, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn folds_across_scripts() {
+        assert!(eq_fold("Kelvin", "\u{212a}ELVIN"));
+        assert!(eq_fold("ſecret", "SECRET"));
+        assert!(eq_fold("ΟΔΟΣ", "οδος"));
+        assert!(!eq_fold("a", "á"));
+        assert!(matches!(fold("already lower"), Cow::Borrowed(_)));
+        assert_eq!(fold("Straße"), "strasse");
+    }
+
+    #[test]
+    fn simple_folding_keeps_one_character() {
+        let root =
+            |s: &str| -> String { s.chars().map(|c| fold_simple(c, Locale::Root)).collect() };
+        assert_eq!(root("ſ\u{212a}ΣςǅẞAb"), "skσσǆßab");
+        assert_eq!(root("Straße İı"), "straße İı");
+
+        let turkic =
+            |s: &str| -> String { s.chars().map(|c| fold_simple(c, Locale::Turkic)).collect() };
+        assert_eq!(turkic("IİıiK"), "ıiıik");
+    }
+
+    #[test]
+    fn locales_come_from_language_tags() {
+        assert_eq!(Locale::from_tag("tr"), Some(Locale::Turkic));
+        assert_eq!(Locale::from_tag("AZ-Latn-AZ"), Some(Locale::Turkic));
+        assert_eq!(Locale::from_tag("en_US"), Some(Locale::Root));
+        assert_eq!(Locale::from_tag("de"), Some(Locale::Root));
+        for bad in ["", "t", "tr-", "1r", "tr-TR!", "toolonglanguage"] {
+            assert_eq!(Locale::from_tag(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn finds_matches_by_original_offsets() {
+        assert_eq!(find_fold("Ignore IGNORE", "ignore"), vec![0..6, 7..13]);
+        let text = "é IGNORE \u{212a}ey";
+        assert_eq!(find_fold(text, "ignore"), vec![3..9]);
+        assert_eq!(find_fold(text, "key"), vec![10..15]);
+        assert_eq!(find_fold("aaa", "aa"), vec![0..2]);
+        assert!(find_fold("abc", "").is_empty());
+    }
+}
@@ -0,0 +1,723 @@
+//! Outbound HTTP layered over extism's HTTP host calls: `fetch` for typed
+//! requests and responses, and the limits and error taxonomy it applies.
+
+use std::time::{Duration, Instant};
+
+use extism_pdk::HttpResponse;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::allowlist::AllowList;
+use crate::{trace, PluginError};
+
+/// Default cap on how much of an upstream response body is copied into
+/// plugin memory.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Why an outbound call failed.
+///
+/// `label()` values are stable and meant for metrics labels and logs, so
+/// operators can tell a degraded upstream from a plugin bug when a route
+/// starts failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpstreamErrorKind {
+    /// The host call failed with a timeout, or the response arrived after
+    /// `Request::timeout`.
+    Timeout,
+    /// DNS, TCP or TLS setup failed.
+    Connect,
+    /// The upstream answered with a 4xx status.
+    ClientStatus,
+    /// The upstream answered with a 5xx status.
+    ServerStatus,
+    /// The response could not be used: wrong content type or unparseable body.
+    Decode,
+    /// The response body exceeded `ResponseLimits::max_body_bytes`.
+    TooLarge,
+}
+
+impl UpstreamErrorKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            UpstreamErrorKind::Timeout => "timeout",
+            UpstreamErrorKind::Connect => "connect",
+            UpstreamErrorKind::ClientStatus => "status_4xx",
+            UpstreamErrorKind::ServerStatus => "status_5xx",
+            UpstreamErrorKind::Decode => "decode",
+            UpstreamErrorKind::TooLarge => "too_large",
+        }
+    }
+
+    /// Whether the failure says the upstream itself is unhealthy (timeouts,
+    /// connection failures and 5xx statuses) rather than pointing at the
+    /// request the plugin built or at a contract mismatch (4xx statuses,
+    /// decode failures, oversized bodies). The SDK only classifies; a plugin
+    /// that keeps failure counts or alerts on upstream health can count just
+    /// these.
+    pub fn is_upstream_fault(self) -> bool {
+        matches!(
+            self,
+            UpstreamErrorKind::Timeout
+                | UpstreamErrorKind::Connect
+                | UpstreamErrorKind::ServerStatus
+        )
+    }
+
+    /// Classifies a response status; `None` for non-error statuses.
+    pub fn from_status(status: u16) -> Option<UpstreamErrorKind> {
+        match status {
+            400..=499 => Some(UpstreamErrorKind::ClientStatus),
+            500..=599 => Some(UpstreamErrorKind::ServerStatus),
+            _ => None,
+        }
+    }
+
+    /// Classifies an error returned by the extism HTTP host call. The host
+    /// only reports a message, so this matches the wording used by Go's
+    /// `net/http` and `context` packages, defaulting to `Connect`.
+    ///
+    /// The extism runtimes trap on transport failures rather than returning
+    /// them, so under those hosts the call fails as a whole and this is never
+    /// reached for a refused connection or a host-side timeout.
+    pub fn from_host_error(err: &extism_pdk::Error) -> UpstreamErrorKind {
+        let msg = err.to_string().to_ascii_lowercase();
+        if msg.contains("deadline exceeded") || msg.contains("timeout") || msg.contains("timed out")
+        {
+            UpstreamErrorKind::Timeout
+        } else {
+            UpstreamErrorKind::Connect
+        }
+    }
+
+    /// Builds an `UPSTREAM_ERROR` tagged with this kind under `details.kind`.
+    pub fn error(self, message: impl Into<String>) -> PluginError {
+        PluginError::upstream(message).with_detail("kind", self.label())
+    }
+
+    /// Reads the kind back from an error built by `error`.
+    pub fn of(err: &PluginError) -> Option<UpstreamErrorKind> {
+        if err.code() != "UPSTREAM_ERROR" {
+            return None;
+        }
+        let kind = err.details().get("kind")?.as_str()?;
+        ALL_KINDS.iter().copied().find(|k| k.label() == kind)
+    }
+}
+
+const ALL_KINDS: [UpstreamErrorKind; 6] = [
+    UpstreamErrorKind::Timeout,
+    UpstreamErrorKind::Connect,
+    UpstreamErrorKind::ClientStatus,
+    UpstreamErrorKind::ServerStatus,
+    UpstreamErrorKind::Decode,
+    UpstreamErrorKind::TooLarge,
+];
+
+/// What to do when an upstream response body exceeds `max_body_bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnOversize {
+    /// Fail the call with an `UPSTREAM_ERROR`.
+    #[default]
+    Error,
+    /// Keep the first `max_body_bytes` and mark the body as truncated.
+    Truncate,
+}
+
+/// Limits applied to upstream responses before their bodies are read.
+///
+/// The host hands the response body over as extism memory, so the size is
+/// known before anything is copied into the plugin's linear memory; an
+/// oversized body is never fully loaded.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ResponseLimits {
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Accepted media types, e.g. `application/json` or `text/*`. Empty accepts
+    /// any content type.
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    #[serde(default)]
+    pub on_oversize: OnOversize,
+}
+
+fn default_max_body_bytes() -> usize {
+    DEFAULT_MAX_BODY_BYTES
+}
+
+impl Default for ResponseLimits {
+    fn default() -> Self {
+        ResponseLimits {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            content_types: Vec::new(),
+            on_oversize: OnOversize::Error,
+        }
+    }
+}
+
+/// A response body read under `ResponseLimits`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitedBody {
+    pub bytes: Vec<u8>,
+    /// True when the upstream sent more than `max_body_bytes`.
+    pub truncated: bool,
+}
+
+impl ResponseLimits {
+    /// Checks the content type and size of a response and returns how many
+    /// bytes of the body may be read.
+    pub fn check(&self, content_type: Option<&str>, body_len: usize) -> Result<usize, PluginError> {
+        if !self.content_types.is_empty() {
+            let essence = content_type
+                .and_then(|ct| ct.split(';').next())
+                .map(|ct| ct.trim().to_ascii_lowercase())
+                .unwrap_or_default();
+            if !self
+                .content_types
+                .iter()
+                .any(|allowed| media_type_matches(allowed, &essence))
+            {
+                return Err(UpstreamErrorKind::Decode
+                    .error("Upstream response has an unexpected content type")
+                    .with_detail("content_type", content_type.unwrap_or_default())
+                    .with_detail("accepted", self.content_types.clone()));
+            }
+        }
+
+        if body_len <= self.max_body_bytes {
+            return Ok(body_len);
+        }
+        match self.on_oversize {
+            OnOversize::Truncate => Ok(self.max_body_bytes),
+            OnOversize::Error => Err(UpstreamErrorKind::TooLarge
+                .error("Upstream response body too large")
+                .with_detail("length", body_len)
+                .with_detail("max_body_bytes", self.max_body_bytes)),
+        }
+    }
+
+    /// Reads the body of `resp`, copying at most `max_body_bytes` out of host
+    /// memory.
+    pub fn read_body(&self, resp: &HttpResponse) -> Result<LimitedBody, PluginError> {
+        let memory = resp.as_memory();
+        let content_type = header(resp, "content-type");
+        let n = self.check(content_type, memory.len())?;
+
+        let mut bytes = vec![0; n];
+        // SAFETY: `memory` is a live host allocation of `memory.len()` bytes and
+        // `n <= memory.len()`, so the load stays in bounds.
+        unsafe { extism_pdk::extism::load(memory.offset(), &mut bytes) };
+        Ok(LimitedBody {
+            bytes,
+            truncated: n < memory.len(),
+        })
+    }
+}
+
+/// Case-insensitive response header lookup.
+pub fn header<'a>(resp: &'a HttpResponse, name: &str) -> Option<&'a str> {
+    resp.headers()
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Matches a media type essence against `type/subtype`, `type/*` or `*/*`.
+fn media_type_matches(allowed: &str, essence: &str) -> bool {
+    let allowed = allowed.trim().to_ascii_lowercase();
+    match allowed.strip_suffix("/*") {
+        Some("*") => true,
+        Some(type_) => essence.split_once('/').is_some_and(|(t, _)| t == type_),
+        None => allowed == essence,
+    }
+}
+
+/// An outbound request for `fetch`.
+///
+/// ```ignore
+/// let allow = AllowList::from_static_data(Some(&static_data))?;
+/// let resp = http::fetch(
+///     http::Request::post("https://auth.example.com/introspect")
+///         .header("accept", "application/json")
+///         .form(&[("token", token)])
+///         .timeout(Duration::from_secs(2)),
+///     &allow,
+/// )?
+/// .error_for_status()?;
+/// let claims: Claims = resp.json()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+    limits: ResponseLimits,
+}
+
+impl Request {
+    pub fn new(method: impl Into<String>, url: impl Into<String>) -> Request {
+        Request {
+            method: method.into().to_ascii_uppercase(),
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+            timeout: None,
+            limits: ResponseLimits::default(),
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Request {
+        Request::new("GET", url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Request {
+        Request::new("POST", url)
+    }
+
+    /// Sets a header, replacing any earlier value under the same name.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Request {
+        let name = name.into();
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(&name));
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn bearer_auth(self, token: &str) -> Request {
+        self.header("authorization", format!("Bearer {}", token))
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Request {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Serializes `value` as the JSON body and sets `content-type`.
+    pub fn json<T: Serialize>(self, value: &T) -> Result<Request, PluginError> {
+        let body = serde_json::to_vec(value)
+            .map_err(|e| PluginError::internal(format!("Failed to encode request body: {}", e)))?;
+        Ok(self.header("content-type", "application/json").body(body))
+    }
+
+    /// Sets an `application/x-www-form-urlencoded` body.
+    pub fn form<K: AsRef<str>, V: AsRef<str>>(self, fields: &[(K, V)]) -> Request {
+        let body = fields
+            .iter()
+            .map(|(k, v)| format!("{}={}", form_encode(k.as_ref()), form_encode(v.as_ref())))
+            .collect::<Vec<_>>()
+            .join("&");
+        self.header("content-type", "application/x-www-form-urlencoded")
+            .body(body)
+    }
+
+    /// The longest the response may take. This is checked after the host
+    /// call returns, not passed to the host: extism's HTTP request has no
+    /// timeout field and the plugin cannot interrupt a host call. A response
+    /// that took longer is discarded as a `timeout` error, so this bounds
+    /// what the plugin accepts, not how long it waits; only the host's own
+    /// HTTP timeout and the script app's `timeout` do that.
+    pub fn timeout(mut self, timeout: Duration) -> Request {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits on the response body; `ResponseLimits::default()` otherwise.
+    pub fn limits(mut self, limits: ResponseLimits) -> Request {
+        self.limits = limits;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Rejects header names and values that could smuggle extra headers or
+    /// split the request.
+    fn validate(&self) -> Result<(), PluginError> {
+        let is_token = |b: u8| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b);
+        if self.method.is_empty() || !self.method.bytes().all(is_token) {
+            return Err(PluginError::invalid_input("Invalid outbound HTTP method")
+                .with_detail("method", self.method.as_str()));
+        }
+        for (name, value) in &self.headers {
+            if name.is_empty() || !name.bytes().all(is_token) {
+                return Err(PluginError::invalid_input("Invalid outbound header name")
+                    .with_detail("header", name.as_str()));
+            }
+            if value.bytes().any(|b| matches!(b, b'\r' | b'\n' | 0)) {
+                return Err(PluginError::invalid_input("Invalid outbound header value")
+                    .with_detail("header", name.as_str()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A response from `fetch`, its body read under the request's
+/// `ResponseLimits`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: LimitedBody,
+}
+
+impl Response {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body.bytes
+    }
+
+    /// True when the body was cut at `max_body_bytes`.
+    pub fn truncated(&self) -> bool {
+        self.body.truncated
+    }
+
+    pub fn text(&self) -> Result<&str, PluginError> {
+        std::str::from_utf8(&self.body.bytes)
+            .map_err(|_| UpstreamErrorKind::Decode.error("Upstream response body is not UTF-8"))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, PluginError> {
+        serde_json::from_slice(&self.body.bytes).map_err(|e| {
+            UpstreamErrorKind::Decode
+                .error("Upstream response body is not the expected JSON")
+                .with_detail("reason", e.to_string())
+        })
+    }
+
+    /// Turns a 4xx or 5xx response into an `UPSTREAM_ERROR` carrying the
+    /// status.
+    pub fn error_for_status(self) -> Result<Response, PluginError> {
+        match UpstreamErrorKind::from_status(self.status) {
+            Some(kind) => Err(kind
+                .error("Upstream returned an error status")
+                .with_detail("status", self.status)),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Sends `request` through the host's HTTP capability after checking its
+/// URL against `allow`.
+///
+/// A denied destination is a `POLICY_VIOLATION`; transport failures,
+/// timeouts and responses rejected by the limits are `UPSTREAM_ERROR`s
+/// tagged with an `UpstreamErrorKind`. Error statuses are returned as
+/// responses; use `Response::error_for_status` to fail on them. When the
+/// call is traced, a `traceparent` header naming the current span is added
+/// unless the request sets one.
+pub fn fetch(request: Request, allow: &AllowList) -> Result<Response, PluginError> {
+    allow.check(&request.url)?;
+    let mut request = request;
+    if let Some(ctx) = trace::current() {
+        if !request
+            .headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("traceparent"))
+        {
+            request
+                .headers
+                .push(("traceparent".into(), ctx.traceparent()));
+        }
+    }
+    request.validate()?;
+
+    let started = Instant::now();
+    let response = send(&request)?;
+    if let Some(timeout) = request.timeout {
+        let elapsed = started.elapsed();
+        if elapsed > timeout {
+            return Err(UpstreamErrorKind::Timeout
+                .error("Upstream did not respond in time")
+                .with_detail("timeout_ms", timeout.as_millis() as u64)
+                .with_detail("elapsed_ms", elapsed.as_millis() as u64));
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(target_family = "wasm")]
+fn send(request: &Request) -> Result<Response, PluginError> {
+    let mut host_request = extism_pdk::HttpRequest::new(&request.url).with_method(&request.method);
+    for (name, value) in &request.headers {
+        host_request = host_request.with_header(name, value);
+    }
+    let resp = extism_pdk::http::request(&host_request, request.body.clone()).map_err(|e| {
+        UpstreamErrorKind::from_host_error(&e)
+            .error("Upstream request failed")
+            .with_detail("url", request.url.as_str())
+            .with_detail("reason", e.to_string())
+    })?;
+    read_then_free(
+        resp,
+        |resp| {
+            Ok(Response {
+                status: resp.status_code(),
+                headers: resp
+                    .headers()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+                body: request.limits.read_body(resp)?,
+            })
+        },
+        |resp| resp.into_memory().free(),
+    )
+}
+
+/// Runs `read` on a host response and then `free`s it, also when `read`
+/// fails, so a body rejected by the limits is not left in host memory for
+/// the rest of the call.
+#[cfg_attr(not(target_family = "wasm"), allow(dead_code))]
+fn read_then_free<R, T>(
+    resp: R,
+    read: impl FnOnce(&R) -> Result<T, PluginError>,
+    free: impl FnOnce(R),
+) -> Result<T, PluginError> {
+    let out = read(&resp);
+    free(resp);
+    out
+}
+
+/// Native builds (unit tests) have no host to send through.
+#[cfg(not(target_family = "wasm"))]
+fn send(request: &Request) -> Result<Response, PluginError> {
+    Err(UpstreamErrorKind::Connect
+        .error("Outbound HTTP needs the extism runtime")
+        .with_detail("url", request.url.as_str()))
+}
+
+/// Percent-encodes a form field; spaces become `+`.
+fn form_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'*' => {
+                out.push(b as char)
+            }
+            b' ' => out.push('+'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_only() -> ResponseLimits {
+        ResponseLimits {
+            max_body_bytes: 10,
+            content_types: vec!["application/json".into(), "text/*".into()],
+            on_oversize: OnOversize::Error,
+        }
+    }
+
+    #[test]
+    fn accepts_listed_content_types() {
+        let limits = json_only();
+        assert_eq!(
+            limits.check(Some("application/json; charset=utf-8"), 5),
+            Ok(5)
+        );
+        assert_eq!(limits.check(Some("Text/Plain"), 5), Ok(5));
+    }
+
+    #[test]
+    fn rejects_unlisted_or_missing_content_types() {
+        let limits = json_only();
+        let err = limits
+            .check(Some("application/octet-stream"), 5)
+            .unwrap_err();
+        assert_eq!(err.code(), "UPSTREAM_ERROR");
+        assert_eq!(err.details()["content_type"], "application/octet-stream");
+        assert_eq!(UpstreamErrorKind::of(&err), Some(UpstreamErrorKind::Decode));
+        assert!(limits.check(None, 5).is_err());
+        assert_eq!(ResponseLimits::default().check(None, 5), Ok(5));
+    }
+
+    #[test]
+    fn oversize_errors_or_truncates() {
+        let mut limits = json_only();
+        let err = limits.check(Some("application/json"), 11).unwrap_err();
+        assert_eq!(err.details()["length"], 11);
+        assert_eq!(err.details()["max_body_bytes"], 10);
+        assert_eq!(
+            UpstreamErrorKind::of(&err),
+            Some(UpstreamErrorKind::TooLarge)
+        );
+
+        limits.on_oversize = OnOversize::Truncate;
+        assert_eq!(limits.check(Some("application/json"), 11), Ok(10));
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        assert_eq!(UpstreamErrorKind::from_status(200), None);
+        assert_eq!(UpstreamErrorKind::from_status(304), None);
+        assert_eq!(
+            UpstreamErrorKind::from_status(404),
+            Some(UpstreamErrorKind::ClientStatus)
+        );
+        assert_eq!(
+            UpstreamErrorKind::from_status(503),
+            Some(UpstreamErrorKind::ServerStatus)
+        );
+    }
+
+    #[test]
+    fn classifies_host_errors() {
+        let timeout = extism_pdk::Error::msg("Get \"https://x\": context deadline exceeded");
+        assert_eq!(
+            UpstreamErrorKind::from_host_error(&timeout),
+            UpstreamErrorKind::Timeout
+        );
+        let refused = extism_pdk::Error::msg("dial tcp 10.0.0.1:443: connect: connection refused");
+        assert_eq!(
+            UpstreamErrorKind::from_host_error(&refused),
+            UpstreamErrorKind::Connect
+        );
+    }
+
+    #[test]
+    fn only_upstream_faults_count_against_the_upstream() {
+        let faults: Vec<_> = ALL_KINDS
+            .iter()
+            .filter(|k| k.is_upstream_fault())
+            .map(|k| k.label())
+            .collect();
+        assert_eq!(faults, ["timeout", "connect", "status_5xx"]);
+    }
+
+    #[test]
+    fn kind_round_trips_through_plugin_error() {
+        for kind in ALL_KINDS {
+            assert_eq!(UpstreamErrorKind::of(&kind.error("x")), Some(kind));
+        }
+        assert_eq!(UpstreamErrorKind::of(&PluginError::upstream("x")), None);
+        assert_eq!(UpstreamErrorKind::of(&PluginError::internal("x")), None);
+    }
+
+    #[test]
+    fn deserializes_with_defaults() {
+        let limits: ResponseLimits =
+            serde_json::from_value(serde_json::json!({"on_oversize": "truncate"})).unwrap();
+        assert_eq!(limits.max_body_bytes, DEFAULT_MAX_BODY_BYTES);
+        assert_eq!(limits.on_oversize, OnOversize::Truncate);
+        assert!(limits.content_types.is_empty());
+    }
+
+    fn response(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: LimitedBody {
+                bytes: body.as_bytes().to_vec(),
+                truncated: false,
+            },
+        }
+    }
+
+    #[test]
+    fn builds_requests() {
+        let req = Request::new("post", "https://api.example.com/")
+            .header("X-Id", "1")
+            .header("x-id", "2")
+            .form(&[("token", "a b&c"), ("hint", "é")]);
+        assert_eq!(req.method(), "POST");
+        assert_eq!(
+            req.headers,
+            [
+                ("x-id".to_string(), "2".to_string()),
+                (
+                    "content-type".to_string(),
+                    "application/x-www-form-urlencoded".to_string()
+                )
+            ]
+        );
+        assert_eq!(req.body.as_deref(), Some(&b"token=a+b%26c&hint=%C3%A9"[..]));
+
+        let req = Request::get("https://api.example.com/")
+            .json(&serde_json::json!({"a": 1}))
+            .unwrap();
+        assert_eq!(req.body.as_deref(), Some(&br#"{"a":1}"#[..]));
+    }
+
+    #[test]
+    fn rejects_header_injection() {
+        let req = Request::get("https://api.example.com/").header("x-a", "1\r\nhost: evil");
+        assert_eq!(req.validate().unwrap_err().code(), "INVALID_INPUT");
+        let req = Request::get("https://api.example.com/").header("bad name", "1");
+        assert!(req.validate().is_err());
+        assert!(Request::new("GE T", "https://x/").validate().is_err());
+    }
+
+    #[test]
+    fn fetch_checks_the_allow_list_first() {
+        let allow = AllowList {
+            hosts: vec!["api.example.com".into()],
+            ..Default::default()
+        };
+        let err = fetch(Request::get("https://evil.test/"), &allow).unwrap_err();
+        assert_eq!(err.code(), "POLICY_VIOLATION");
+
+        // Allowed, but there is no host to send through natively.
+        let err = fetch(Request::get("https://api.example.com/"), &allow).unwrap_err();
+        assert_eq!(
+            UpstreamErrorKind::of(&err),
+            Some(UpstreamErrorKind::Connect)
+        );
+    }
+
+    #[test]
+    fn host_responses_are_freed_whether_or_not_they_are_read() {
+        let mut freed = Vec::new();
+        let ok = read_then_free(1, |_| Ok("body"), |resp| freed.push(resp));
+        assert_eq!(ok, Ok("body"));
+        let err = read_then_free(
+            2,
+            |_| json_only().check(Some("image/png"), 5),
+            |resp| freed.push(resp),
+        );
+        assert!(err.is_err());
+        assert_eq!(freed, [1, 2]);
+    }
+
+    #[test]
+    fn reads_responses() {
+        let resp = response(200, r#"{"active": true}"#);
+        assert_eq!(resp.header("content-type"), Some("application/json"));
+        let value: serde_json::Value = resp.json().unwrap();
+        assert_eq!(value["active"], true);
+        let err = response(200, "<html>")
+            .json::<serde_json::Value>()
+            .unwrap_err();
+        assert_eq!(UpstreamErrorKind::of(&err), Some(UpstreamErrorKind::Decode));
+
+        assert!(response(204, "").error_for_status().is_ok());
+        let err = response(503, "").error_for_status().unwrap_err();
+        assert_eq!(err.details()["status"], 503);
+        assert_eq!(
+            UpstreamErrorKind::of(&err),
+            Some(UpstreamErrorKind::ServerStatus)
+        );
+    }
+}
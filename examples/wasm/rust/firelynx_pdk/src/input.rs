@@ -0,0 +1,897 @@
+//! The JSON envelope firelynx passes to extism plugins.
+//!
+//! go-polyscript serializes the incoming `http.Request` under `request` and
+//! the merged app/route configuration under `static_data`. Parsing is lenient
+//! by default: unknown envelope fields are ignored so a newer host keeps
+//! working with older plugins. Building with the `strict` feature adds
+//! `deny_unknown_fields` to the envelope structs so schema drift between the
+//! Go host and the Rust types fails loudly in CI instead of being dropped.
+//!
+//! `static_data` is route configuration rather than host wire format, so it is
+//! never subject to strict mode; plugins pick their own type for it.
+//!
+//! The envelope carries a `schema_version`. Envelopes without one are treated
+//! as version 1, the original go-polyscript shape, and are migrated step by
+//! step to `SCHEMA_VERSION` before deserialization, so plugins only ever see
+//! the canonical structs below.
+//!
+//! A JSON envelope is parsed without materializing `request.Body`; see
+//! `Body`. `InputRef` goes further and borrows every string of the request
+//! from the envelope text, for handlers that take a `RequestRef`.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+
+use crate::body::Body;
+use crate::codec::Codec;
+use crate::limits::ParseLimits;
+use crate::static_data::StaticData;
+use crate::PluginError;
+
+/// The envelope version the canonical structs describe.
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// The top-level plugin input.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct Input<S = StaticData> {
+    /// Always `SCHEMA_VERSION` after parsing.
+    pub schema_version: u64,
+    pub request: Request,
+    pub static_data: Option<S>,
+    /// The state a `resume::Step::Yield` returned, sent back by the host to
+    /// the export's `Resume` counterpart.
+    #[serde(default)]
+    pub resume: Option<Value>,
+}
+
+impl<S: DeserializeOwned> Input<S> {
+    /// Parses the raw plugin input, migrating older envelope versions.
+    /// Failures are reported as `INVALID_INPUT`.
+    pub fn from_json(input: &str) -> Result<Self, PluginError> {
+        Self::decode(Codec::Json, input.as_bytes().to_vec())
+    }
+
+    /// Like `from_json`, for an envelope in any supported wire encoding.
+    /// Takes the buffer by value so a JSON body can be kept in it unparsed.
+    pub fn decode(codec: Codec, input: Vec<u8>) -> Result<Self, PluginError> {
+        if codec == Codec::Json {
+            ParseLimits::active().check_json(&input)?;
+        }
+        let (mut envelope, body) = match codec {
+            #[cfg(feature = "simd-json")]
+            Codec::Json => simd::split_body(input)?,
+            #[cfg(not(feature = "simd-json"))]
+            Codec::Json => split_body(input)?,
+            _ => (codec.decode(&input)?, None),
+        };
+        migrate(&mut envelope)?;
+        let mut input: Self = serde_json::from_value(envelope).map_err(|e| {
+            PluginError::invalid_input(format!("Invalid {} input: {}", codec.name(), e))
+        })?;
+        if let Some(body) = body {
+            input.request.body = body;
+        }
+        Ok(input)
+    }
+}
+
+/// Parses a JSON envelope into a value tree, except for a string
+/// `request.Body`, which is left in the buffer and returned as a lazy `Body`.
+/// Its place in the tree holds an empty string so deserialization still sees
+/// the field.
+#[cfg_attr(feature = "simd-json", allow(dead_code))]
+fn split_body(input: Vec<u8>) -> Result<(Value, Option<Body>), PluginError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        PluginError::invalid_input(format!("Invalid {} input: {}", Codec::Json.name(), e))
+    };
+    let text = Arc::new(String::from_utf8(input).map_err(|e| invalid(&e))?);
+    let Ok(fields) = serde_json::from_str::<BTreeMap<String, &RawValue>>(&text) else {
+        // Not an object (or not JSON at all): let the eager path report it.
+        return Ok((Codec::Json.decode(text.as_bytes())?, None));
+    };
+
+    let parse = |raw: &RawValue| serde_json::from_str::<Value>(raw.get()).map_err(|e| invalid(&e));
+    let mut envelope = Map::new();
+    let mut body = None;
+    for (key, raw) in fields {
+        let request = match key.as_str() {
+            "request" => serde_json::from_str::<BTreeMap<String, &RawValue>>(raw.get()).ok(),
+            _ => None,
+        };
+        let Some(request) = request else {
+            envelope.insert(key, parse(raw)?);
+            continue;
+        };
+        let mut map = Map::new();
+        for (field, raw) in request {
+            if field == "Body" && raw.get().starts_with('"') {
+                let start = raw.get().as_ptr() as usize - text.as_ptr() as usize;
+                body = Some(Body::raw(text.clone(), start..start + raw.get().len()));
+                map.insert(field, Value::String(String::new()));
+            } else {
+                map.insert(field, parse(raw)?);
+            }
+        }
+        envelope.insert(key, Value::Object(map));
+    }
+    Ok((Value::Object(envelope), body))
+}
+
+/// `split_body` on simd-json. The parser unescapes strings in place, so the
+/// body is kept as a range of already-decoded text in the input buffer.
+#[cfg(feature = "simd-json")]
+mod simd {
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    use serde_json::{Map, Number, Value};
+    use simd_json::{BorrowedValue, StaticNode};
+
+    use crate::body::Body;
+    use crate::codec::Codec;
+    use crate::PluginError;
+
+    pub(super) fn split_body(mut input: Vec<u8>) -> Result<(Value, Option<Body>), PluginError> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            PluginError::invalid_input(format!("Invalid {} input: {}", Codec::Json.name(), e))
+        };
+        let base = input.as_ptr() as usize;
+        let (envelope, body) = {
+            let mut parsed = simd_json::to_borrowed_value(&mut input).map_err(|e| invalid(&e))?;
+            let mut body = None;
+            if let BorrowedValue::Object(fields) = &mut parsed {
+                if let Some(BorrowedValue::Object(request)) = fields.get_mut("request") {
+                    body = match request.get_mut("Body") {
+                        Some(BorrowedValue::String(Cow::Borrowed(s))) => {
+                            let start = s.as_ptr() as usize - base;
+                            Some(Err(start..start + s.len()))
+                        }
+                        Some(BorrowedValue::String(s)) => Some(Ok(std::mem::take(s).into_owned())),
+                        _ => None,
+                    };
+                    if body.is_some() {
+                        request.insert("Body".into(), BorrowedValue::String(Cow::Borrowed("")));
+                    }
+                }
+            }
+            (to_json(parsed).map_err(|e| invalid(&e))?, body)
+        };
+        let body = body.map(|body| match body {
+            Ok(text) => Body::from(text),
+            Err(range) => Body::decoded(Arc::new(input), range),
+        });
+        Ok((envelope, body))
+    }
+
+    fn to_json(value: BorrowedValue<'_>) -> Result<Value, String> {
+        Ok(match value {
+            BorrowedValue::Static(StaticNode::Null) => Value::Null,
+            BorrowedValue::Static(StaticNode::Bool(b)) => Value::Bool(b),
+            BorrowedValue::Static(StaticNode::I64(i)) => i.into(),
+            BorrowedValue::Static(StaticNode::U64(u)) => u.into(),
+            BorrowedValue::Static(StaticNode::F64(f)) => Number::from_f64(f)
+                .map(Value::Number)
+                .ok_or_else(|| format!("number {} is out of range", f))?,
+            BorrowedValue::String(s) => Value::String(s.into_owned()),
+            BorrowedValue::Array(items) => {
+                Value::Array(items.into_iter().map(to_json).collect::<Result<_, _>>()?)
+            }
+            BorrowedValue::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(k, v)| Ok((k.into_owned(), to_json(v)?)))
+                    .collect::<Result<Map<_, _>, String>>()?,
+            ),
+        })
+    }
+}
+
+/// Upgrades `envelope` in place to `SCHEMA_VERSION`.
+fn migrate(envelope: &mut Value) -> Result<(), PluginError> {
+    let Some(envelope) = envelope.as_object_mut() else {
+        return Err(PluginError::invalid_input(
+            "Input envelope must be a JSON object",
+        ));
+    };
+    let version = match envelope.get("schema_version") {
+        None => 1,
+        Some(v) => v.as_u64().filter(|v| *v >= 1).ok_or_else(|| {
+            PluginError::invalid_input("schema_version must be a positive integer")
+        })?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(
+            PluginError::invalid_input("Unsupported input schema_version")
+                .with_detail("schema_version", version)
+                .with_detail("supported", SCHEMA_VERSION),
+        );
+    }
+
+    if version < 2 {
+        if let Some(Value::Object(request)) = envelope.get_mut("request") {
+            migrate_v1_request(request);
+        }
+    }
+    envelope.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    Ok(())
+}
+
+/// Version 1 duplicated parts of the URL as flat `URL_*` request fields next
+/// to the nested `URL` object. Version 2 keeps only `URL`; flat values fill in
+/// any parts the nested object is missing. `URL_String` has no counterpart
+/// and is dropped.
+fn migrate_v1_request(request: &mut Map<String, Value>) {
+    let mut url = match request.remove("URL") {
+        Some(Value::Object(url)) => url,
+        _ => Map::new(),
+    };
+    for (flat, nested) in [
+        ("URL_Path", "Path"),
+        ("URL_Scheme", "Scheme"),
+        ("URL_Host", "Host"),
+    ] {
+        let Some(value) = request.remove(flat) else {
+            continue;
+        };
+        let missing = url
+            .get(nested)
+            .and_then(Value::as_str)
+            .is_none_or(str::is_empty);
+        if missing {
+            url.insert(nested.to_string(), value);
+        }
+    }
+    request.remove("URL_String");
+    request.insert("URL".to_string(), Value::Object(url));
+}
+
+/// The serialized `http.Request`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct Request {
+    #[serde(rename = "Body")]
+    pub body: Body,
+    #[serde(rename = "Headers", default)]
+    pub headers: HashMap<String, Vec<String>>,
+    #[serde(rename = "QueryParams", default)]
+    pub query_params: HashMap<String, Vec<String>>,
+    #[serde(rename = "Method", default)]
+    pub method: String,
+    #[serde(rename = "Proto", default)]
+    pub proto: String,
+    #[serde(rename = "Host", default)]
+    pub host: String,
+    #[serde(rename = "RemoteAddr", default)]
+    pub remote_addr: String,
+    #[serde(rename = "ContentLength", default)]
+    pub content_length: i64,
+    #[serde(rename = "URL", default)]
+    pub url: Url,
+}
+
+impl Request {
+    /// Returns every value of a header. Lookup is case-insensitive since the
+    /// host may not canonicalize names consistently.
+    pub fn header_values(&self, name: &str) -> Option<&[String]> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// Returns the first value of a header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.header_values(name)?.first().map(String::as_str)
+    }
+}
+
+/// The serialized `url.URL` of the request.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct Url {
+    #[serde(rename = "Scheme", default)]
+    pub scheme: String,
+    #[serde(rename = "Path", default)]
+    pub path: String,
+    #[serde(rename = "Host", default)]
+    pub host: String,
+    #[serde(rename = "RawQuery", default)]
+    pub raw_query: String,
+    #[serde(rename = "Fragment", default)]
+    pub fragment: String,
+}
+
+/// `Input` with the request's strings borrowed from the envelope text
+/// instead of copied into owned ones. Only JSON envelopes can be read this
+/// way. A string with JSON escapes in it is still decoded into an owned
+/// `Cow`; the rest point into the input buffer.
+#[derive(Debug, Clone)]
+pub struct InputRef<'a, S = StaticData> {
+    /// Always `SCHEMA_VERSION` after parsing.
+    pub schema_version: u64,
+    pub request: RequestRef<'a>,
+    pub static_data: Option<S>,
+}
+
+impl<'a, S: Deserialize<'a>> InputRef<'a, S> {
+    /// Parses a JSON envelope, migrating older versions as
+    /// `Input::from_json` does. Failures are reported as `INVALID_INPUT`.
+    pub fn from_json(input: &'a str) -> Result<Self, PluginError> {
+        ParseLimits::active().check_json(input.as_bytes())?;
+        let envelope: EnvelopeRef<'a, S> = serde_json::from_str(input).map_err(|e| {
+            PluginError::invalid_input(format!("Invalid {} input: {}", Codec::Json.name(), e))
+        })?;
+        let version = match envelope.schema_version {
+            None => 1,
+            Some(raw) => raw
+                .get()
+                .parse::<u64>()
+                .ok()
+                .filter(|v| *v >= 1)
+                .ok_or_else(|| {
+                    PluginError::invalid_input("schema_version must be a positive integer")
+                })?,
+        };
+        if version > SCHEMA_VERSION {
+            return Err(
+                PluginError::invalid_input("Unsupported input schema_version")
+                    .with_detail("schema_version", version)
+                    .with_detail("supported", SCHEMA_VERSION),
+            );
+        }
+        Ok(InputRef {
+            schema_version: SCHEMA_VERSION,
+            request: envelope.request.migrate(version)?,
+            static_data: envelope.static_data,
+        })
+    }
+}
+
+/// The envelope as sent, before migration.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+struct EnvelopeRef<'a, S> {
+    #[serde(borrow, default)]
+    schema_version: Option<&'a RawValue>,
+    #[serde(borrow)]
+    request: RawRequestRef<'a>,
+    static_data: Option<S>,
+    /// Resume state is only read through `Input`.
+    #[serde(default)]
+    #[allow(dead_code)]
+    resume: IgnoredAny,
+}
+
+/// `RequestRef` plus the flat `URL_*` fields of version 1 envelopes.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+struct RawRequestRef<'a> {
+    #[serde(rename = "Body", borrow)]
+    body: Cow<'a, str>,
+    #[serde(rename = "Headers", borrow, default, deserialize_with = "borrowed_map")]
+    headers: ValuesRef<'a>,
+    #[serde(
+        rename = "QueryParams",
+        borrow,
+        default,
+        deserialize_with = "borrowed_map"
+    )]
+    query_params: ValuesRef<'a>,
+    #[serde(rename = "Method", borrow, default)]
+    method: Cow<'a, str>,
+    #[serde(rename = "Proto", borrow, default)]
+    proto: Cow<'a, str>,
+    #[serde(rename = "Host", borrow, default)]
+    host: Cow<'a, str>,
+    #[serde(rename = "RemoteAddr", borrow, default)]
+    remote_addr: Cow<'a, str>,
+    #[serde(rename = "ContentLength", default)]
+    content_length: i64,
+    #[serde(rename = "URL", borrow, default)]
+    url: UrlRef<'a>,
+    #[serde(rename = "URL_Path", default)]
+    flat_path: Option<String>,
+    #[serde(rename = "URL_Scheme", default)]
+    flat_scheme: Option<String>,
+    #[serde(rename = "URL_Host", default)]
+    flat_host: Option<String>,
+    #[serde(rename = "URL_String", default)]
+    flat_string: Option<IgnoredAny>,
+}
+
+impl<'a> RawRequestRef<'a> {
+    /// `migrate_v1_request` for the borrowed form: flat values fill in the
+    /// URL parts the nested object is missing. Later versions have no flat
+    /// fields, so strict builds reject them there as unknown.
+    fn migrate(self, version: u64) -> Result<RequestRef<'a>, PluginError> {
+        let mut url = self.url;
+        let flat = [
+            (&mut url.path, self.flat_path, "URL_Path"),
+            (&mut url.scheme, self.flat_scheme, "URL_Scheme"),
+            (&mut url.host, self.flat_host, "URL_Host"),
+        ];
+        for (nested, value, name) in flat {
+            let Some(value) = value else {
+                continue;
+            };
+            if version >= 2 {
+                if cfg!(feature = "strict") {
+                    return Err(PluginError::invalid_input(format!(
+                        "Invalid {} input: unknown field `{}`",
+                        Codec::Json.name(),
+                        name
+                    )));
+                }
+            } else if nested.is_empty() {
+                *nested = Cow::Owned(value);
+            }
+        }
+        if cfg!(feature = "strict") && version >= 2 && self.flat_string.is_some() {
+            return Err(PluginError::invalid_input(format!(
+                "Invalid {} input: unknown field `URL_String`",
+                Codec::Json.name()
+            )));
+        }
+        Ok(RequestRef {
+            body: self.body,
+            headers: self.headers,
+            query_params: self.query_params,
+            method: self.method,
+            proto: self.proto,
+            host: self.host,
+            remote_addr: self.remote_addr,
+            content_length: self.content_length,
+            url,
+        })
+    }
+}
+
+/// Reads a header or query map, borrowing every name and value without
+/// escapes. serde borrows a `Cow` only when it is a field of its own, not
+/// inside a container, hence the wrapper.
+fn borrowed_map<'de: 'a, 'a, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ValuesRef<'a>, D::Error> {
+    #[derive(Deserialize, PartialEq, Eq, Hash)]
+    struct Str<'a>(#[serde(borrow)] Cow<'a, str>);
+
+    let map = HashMap::<Str<'a>, Vec<Str<'a>>>::deserialize(deserializer)?;
+    Ok(map
+        .into_iter()
+        .map(|(name, values)| (name.0, values.into_iter().map(|v| v.0).collect()))
+        .collect())
+}
+
+/// `Request` borrowing from the envelope text. Handlers take it in place of
+/// `Request` to skip copying headers, query parameters and the body:
+///
+/// ```ignore
+/// #[firelynx_plugin]
+/// fn estimate_tokens(request: RequestRef<'_>, static_data: StaticData) -> Result<TokenEstimate> {
+///     let tokens = estimate(&request.body);
+///     // ...
+/// }
+/// ```
+///
+/// Such exports read JSON envelopes only; another `envelope_codec` is an
+/// `INVALID_INPUT` error.
+#[derive(Debug, Clone, Default)]
+pub struct RequestRef<'a> {
+    pub body: Cow<'a, str>,
+    pub headers: ValuesRef<'a>,
+    pub query_params: ValuesRef<'a>,
+    pub method: Cow<'a, str>,
+    pub proto: Cow<'a, str>,
+    pub host: Cow<'a, str>,
+    pub remote_addr: Cow<'a, str>,
+    pub content_length: i64,
+    pub url: UrlRef<'a>,
+}
+
+impl<'a> RequestRef<'a> {
+    /// Returns every value of a header, matching the name case-insensitively
+    /// as `Request::header_values` does.
+    pub fn header_values(&self, name: &str) -> Option<&[Cow<'a, str>]> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// Returns the first value of a header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.header_values(name)?.first().map(|v| v.as_ref())
+    }
+
+    /// Copies the request into an owned `Request`, for the SDK helpers that
+    /// take one.
+    pub fn into_owned(self) -> Request {
+        let owned = |map: ValuesRef<'a>| {
+            map.into_iter()
+                .map(|(k, v)| (k.into_owned(), v.into_iter().map(Cow::into_owned).collect()))
+                .collect()
+        };
+        Request {
+            body: Body::from(self.body.into_owned()),
+            headers: owned(self.headers),
+            query_params: owned(self.query_params),
+            method: self.method.into_owned(),
+            proto: self.proto.into_owned(),
+            host: self.host.into_owned(),
+            remote_addr: self.remote_addr.into_owned(),
+            content_length: self.content_length,
+            url: Url {
+                scheme: self.url.scheme.into_owned(),
+                path: self.url.path.into_owned(),
+                host: self.url.host.into_owned(),
+                raw_query: self.url.raw_query.into_owned(),
+                fragment: self.url.fragment.into_owned(),
+            },
+        }
+    }
+}
+
+/// Header or query parameter names with all their values, borrowed from
+/// the envelope text where possible.
+pub type ValuesRef<'a> = HashMap<Cow<'a, str>, Vec<Cow<'a, str>>>;
+
+/// `Url` borrowing from the envelope text.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct UrlRef<'a> {
+    #[serde(rename = "Scheme", borrow, default)]
+    pub scheme: Cow<'a, str>,
+    #[serde(rename = "Path", borrow, default)]
+    pub path: Cow<'a, str>,
+    #[serde(rename = "Host", borrow, default)]
+    pub host: Cow<'a, str>,
+    #[serde(rename = "RawQuery", borrow, default)]
+    pub raw_query: Cow<'a, str>,
+    #[serde(rename = "Fragment", borrow, default)]
+    pub fragment: Cow<'a, str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limit;
+
+    const ENVELOPE: &str = r#"{
+        "request": {
+            "Body": "Hello World",
+            "Headers": {"Content-Type": ["application/json"]},
+            "QueryParams": {"q": ["1", "2"]},
+            "Method": "POST",
+            "Proto": "HTTP/1.1",
+            "Host": "localhost:8080",
+            "RemoteAddr": "[::1]:12345",
+            "ContentLength": 11,
+            "URL": {"Scheme": "http", "Path": "/api/demo", "Host": "localhost:8080", "RawQuery": "q=1&q=2", "Fragment": ""},
+            "URL_Path": "/api/demo",
+            "URL_Scheme": "http",
+            "URL_Host": "localhost:8080",
+            "URL_String": "/api/demo?q=1&q=2"
+        },
+        "static_data": {"search_characters": "xyz"}
+    }"#;
+
+    #[test]
+    fn parses_full_envelope() {
+        let input: Input = Input::from_json(ENVELOPE).unwrap();
+        assert_eq!(input.request.body, "Hello World");
+        assert_eq!(input.request.query_params["q"], ["1", "2"]);
+        assert_eq!(input.request.content_length, 11);
+        assert_eq!(
+            input.request.header("content-type"),
+            Some("application/json")
+        );
+        assert_eq!(input.request.header("Accept"), None);
+        assert_eq!(input.request.url.raw_query, "q=1&q=2");
+        assert_eq!(
+            input
+                .static_data
+                .unwrap()
+                .get_str("search_characters")
+                .unwrap(),
+            Some("xyz")
+        );
+        assert_eq!(input.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn parses_fixture_envelope() {
+        let json = firelynx_fixture::RequestFixture::new("Hi")
+            .method("GET")
+            .path("/search")
+            .query("q", "rust")
+            .static_data("case_sensitive", true)
+            .to_json();
+        let input: Input = Input::from_json(&json).unwrap();
+        assert_eq!(input.request.body, "Hi");
+        assert_eq!(input.request.method, "GET");
+        assert_eq!(input.request.url.path, "/search");
+        assert_eq!(input.request.url.raw_query, "q=rust");
+        assert_eq!(input.request.query_params["q"], ["rust"]);
+        assert_eq!(
+            input
+                .static_data
+                .unwrap()
+                .get_bool("case_sensitive")
+                .unwrap(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn decodes_msgpack_envelope() {
+        let envelope: Value = serde_json::from_str(ENVELOPE).unwrap();
+        let bytes = Codec::MessagePack.encode(&envelope).unwrap();
+        let input: Input = Input::decode(Codec::MessagePack, bytes).unwrap();
+        assert_eq!(input.request.body, "Hello World");
+        assert_eq!(input.request.url.path, "/api/demo");
+        assert_eq!(input.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn v1_flat_url_fields_migrate_into_url() {
+        let json = r#"{"request": {
+            "Body": "",
+            "URL_Path": "/flat",
+            "URL_Scheme": "https",
+            "URL_Host": "example.com",
+            "URL_String": "/flat"
+        }}"#;
+        let input: Input = Input::from_json(json).unwrap();
+        assert_eq!(input.request.url.path, "/flat");
+        assert_eq!(input.request.url.scheme, "https");
+        assert_eq!(input.request.url.host, "example.com");
+    }
+
+    #[test]
+    fn v1_nested_url_wins_over_flat_fields() {
+        let json = r#"{"request": {
+            "Body": "",
+            "URL": {"Path": "/nested", "Scheme": ""},
+            "URL_Path": "/flat",
+            "URL_Scheme": "http"
+        }}"#;
+        let input: Input = Input::from_json(json).unwrap();
+        assert_eq!(input.request.url.path, "/nested");
+        assert_eq!(input.request.url.scheme, "http");
+    }
+
+    #[test]
+    fn v2_envelope_is_not_migrated() {
+        let json = r#"{"schema_version": 2, "request": {"Body": "", "URL": {"Path": "/v2"}}}"#;
+        let input: Input = Input::from_json(json).unwrap();
+        assert_eq!(input.request.url.path, "/v2");
+    }
+
+    #[test]
+    fn rejects_unknown_or_invalid_versions() {
+        let err = Input::<Value>::from_json(r#"{"schema_version": 99, "request": {"Body": ""}}"#)
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert_eq!(err.details()["schema_version"], 99);
+
+        let err = Input::<Value>::from_json(r#"{"schema_version": "2", "request": {"Body": ""}}"#)
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+    }
+
+    #[test]
+    fn static_data_is_optional() {
+        let input: Input = Input::from_json(r#"{"request": {"Body": ""}}"#).unwrap();
+        assert!(input.static_data.is_none());
+        let input: Input =
+            Input::from_json(r#"{"request": {"Body": ""}, "static_data": {}}"#).unwrap();
+        assert!(input.static_data.unwrap().is_present());
+    }
+
+    #[test]
+    fn json_body_is_decoded_on_access() {
+        let json = r#"{"request": {"Body": "caf\u00e9 \"au lait\"", "Method": "GET"}}"#;
+        let input: Input = Input::from_json(json).unwrap();
+        assert_eq!(input.request.method, "GET");
+        assert_eq!(input.request.body, "caf\u{e9} \"au lait\"");
+    }
+
+    #[test]
+    fn input_ref_borrows_unescaped_strings() {
+        let input: InputRef = InputRef::from_json(ENVELOPE).unwrap();
+        let request = &input.request;
+        assert!(matches!(request.body, Cow::Borrowed("Hello World")));
+        assert!(matches!(request.method, Cow::Borrowed("POST")));
+        assert!(matches!(request.url.path, Cow::Borrowed("/api/demo")));
+        assert!(matches!(
+            request.header("content-type"),
+            Some("application/json")
+        ));
+        assert!(request
+            .headers
+            .iter()
+            .all(|(k, v)| matches!(k, Cow::Borrowed(_))
+                && v.iter().all(|v| matches!(v, Cow::Borrowed(_)))));
+        assert_eq!(request.query_params["q"], ["1", "2"]);
+        assert_eq!(request.content_length, 11);
+        assert_eq!(input.schema_version, SCHEMA_VERSION);
+        assert_eq!(
+            input
+                .static_data
+                .unwrap()
+                .get_str("search_characters")
+                .unwrap(),
+            Some("xyz")
+        );
+
+        let json = r#"{"request": {"Body": "caf\u00e9", "Headers": {"X-Q": ["a\"b"]}}}"#;
+        let input: InputRef = InputRef::from_json(json).unwrap();
+        assert!(matches!(input.request.body, Cow::Owned(_)));
+        assert_eq!(input.request.body, "caf\u{e9}");
+        assert_eq!(input.request.header("x-q"), Some("a\"b"));
+    }
+
+    #[test]
+    fn input_ref_matches_input() {
+        let owned: Input = Input::from_json(ENVELOPE).unwrap();
+        let borrowed: InputRef = InputRef::from_json(ENVELOPE).unwrap();
+        let converted = borrowed.request.into_owned();
+        assert_eq!(converted.body, owned.request.body.text().as_ref());
+        assert_eq!(converted.headers, owned.request.headers);
+        assert_eq!(converted.query_params, owned.request.query_params);
+        assert_eq!(converted.method, owned.request.method);
+        assert_eq!(converted.remote_addr, owned.request.remote_addr);
+        assert_eq!(converted.url.raw_query, owned.request.url.raw_query);
+    }
+
+    #[test]
+    fn input_ref_migrates_and_checks_versions() {
+        let json = r#"{"request": {
+            "Body": "",
+            "URL": {"Path": "/nested", "Scheme": ""},
+            "URL_Path": "/flat",
+            "URL_Scheme": "http",
+            "URL_Host": "example.com",
+            "URL_String": "/flat"
+        }}"#;
+        let input: InputRef = InputRef::from_json(json).unwrap();
+        assert_eq!(input.request.url.path, "/nested");
+        assert_eq!(input.request.url.scheme, "http");
+        assert_eq!(input.request.url.host, "example.com");
+
+        let err =
+            InputRef::<Value>::from_json(r#"{"schema_version": 99, "request": {"Body": ""}}"#)
+                .unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert_eq!(err.details()["schema_version"], 99);
+        let err =
+            InputRef::<Value>::from_json(r#"{"schema_version": "2", "request": {"Body": ""}}"#)
+                .unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert!(InputRef::<Value>::from_json(r#"{"request": {}}"#).is_err());
+    }
+
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn simd_json_unescapes_body_in_place() {
+        let json = r#"{"request": {"Body": "line\nca\u00e9", "Method": "PUT"}, "static_data": {"n": 1.5}}"#;
+        let input: Input<Value> = Input::from_json(json).unwrap();
+        assert!(matches!(
+            input.request.body.text(),
+            std::borrow::Cow::Borrowed("line\nca\u{e9}")
+        ));
+        assert_eq!(input.request.body.encoded_len(), "line\nca\u{e9}".len());
+        assert_eq!(input.request.method, "PUT");
+        assert_eq!(input.static_data.unwrap()["n"], 1.5);
+    }
+
+    /// Both JSON parsers must hand handlers the same envelope, so switching
+    /// the `simd-json` feature on is only a performance decision.
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn simd_json_matches_serde_json() {
+        use firelynx_fixture::RequestFixture;
+        use serde_json::json;
+
+        let corpus = [
+            RequestFixture::new("Hello World").to_json(),
+            RequestFixture::new("").method("GET").to_json(),
+            RequestFixture::new("quote \" slash \\ / tab \t nul \0 \u{7f} é 日本 😀").to_json(),
+            RequestFixture::new(r#"{"nested": ["json", {"in": "a body"}]}"#.repeat(2000))
+                .header("X-Trace", "a")
+                .header("X-Trace", "b")
+                .query("q", "ünïcode")
+                .to_json(),
+            RequestFixture::new("numbers")
+                .static_data("ints", json!([0, -1, i64::MIN, u64::MAX]))
+                .static_data(
+                    "floats",
+                    json!([0.1, -1.5, 1e300, 2.5e-8, 1.7976931348623157e308]),
+                )
+                .static_data("nested", json!({"ключ": [[[]], {}, null, true]}))
+                .to_json(),
+            r#"{"request": {"Body": "v1", "Method": "GET", "Path": "/old", "RawQuery": "a=1"}}"#
+                .to_string(),
+            r#"  {"request" : {"Body" : "spacedA😀" } , "static_data" : null }  "#.to_string(),
+            r#"{"request": {"Body": 1}}"#.to_string(),
+            r#"["request"]"#.to_string(),
+        ];
+        for json in &corpus {
+            let serde = split_body(json.as_bytes().to_vec()).unwrap();
+            let simd = simd::split_body(json.as_bytes().to_vec()).unwrap();
+            assert_eq!(serde.0, simd.0, "{}", json);
+            assert_eq!(serde.1, simd.1, "{}", json);
+        }
+
+        for malformed in [
+            "",
+            "{",
+            r#"{"request": {"Body": "unterminated}}"#,
+            r#"{"request": {"Body": "bad \x escape"}}"#,
+            r#"{"request": {"Body": "lone \ud800"}} trailing"#,
+            r#"{"a": 1,}"#,
+            "[1 2]",
+            "nan",
+        ] {
+            let serde = split_body(malformed.as_bytes().to_vec()).unwrap_err();
+            let simd = simd::split_body(malformed.as_bytes().to_vec()).unwrap_err();
+            assert_eq!(serde.code(), simd.code(), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn non_string_body_is_invalid_input() {
+        let err = Input::<Value>::from_json(r#"{"request": {"Body": 1}}"#).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        let err = Input::<Value>::from_json(r#"["request"]"#).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+    }
+
+    #[test]
+    fn envelopes_breaking_parse_limits_are_rejected() {
+        let deep = format!(
+            r#"{{"request": {{"Body": "", "Headers": {}0{}}}}}"#,
+            "[".repeat(100_000),
+            "]".repeat(100_000)
+        );
+        let err = Input::<Value>::from_json(&deep).unwrap_err();
+        assert_eq!(Limit::of(&err), Some(Limit::Depth));
+
+        let mut envelope: Value = serde_json::from_str(ENVELOPE).unwrap();
+        envelope["static_data"] = Value::from(vec![0; 100_001]);
+        let bytes = Codec::MessagePack.encode(&envelope).unwrap();
+        let err = Input::<Value>::decode(Codec::MessagePack, bytes).unwrap_err();
+        assert_eq!(Limit::of(&err), Some(Limit::ArrayItems));
+    }
+
+    #[test]
+    fn missing_body_is_invalid_input() {
+        let err = Input::<Value>::from_json(r#"{"request": {}}"#).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+    }
+
+    #[cfg(not(feature = "strict"))]
+    #[test]
+    fn lenient_mode_ignores_unknown_fields() {
+        let json = r#"{"request": {"Body": "x", "Trailer": {}}, "extra": 1}"#;
+        assert!(Input::<Value>::from_json(json).is_ok());
+    }
+
+    #[cfg(feature = "strict")]
+    #[test]
+    fn strict_mode_rejects_unknown_fields() {
+        let json = r#"{"request": {"Body": "x", "Trailer": {}}}"#;
+        let err = Input::<Value>::from_json(json).unwrap_err();
+        assert!(err.message().contains("Trailer"));
+
+        let json = r#"{"request": {"Body": "x"}, "extra": 1}"#;
+        assert!(Input::<Value>::from_json(json).is_err());
+    }
+}
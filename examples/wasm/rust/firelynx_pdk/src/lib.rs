@@ -0,0 +1,51 @@
+//! Shared helpers for firelynx WASM plugins written against the Extism PDK.
+//!
+//! Plugins in `examples/wasm/rust/` depend on this crate by path so that
+//! request handling details (header parsing, envelope shapes, etc.) are
+//! implemented once instead of in every plugin.
+
+// Lets macro output name `::firelynx_pdk` inside this crate's own tests.
+extern crate self as firelynx_pdk;
+
+pub mod accept;
+pub mod access_log;
+pub mod allowlist;
+pub mod body;
+pub mod clock;
+pub mod codec;
+pub mod config;
+pub mod cookie;
+pub mod decode;
+pub mod dom;
+pub mod duplicates;
+pub mod error;
+pub mod export;
+pub mod extract;
+pub mod fold;
+pub mod http;
+pub mod input;
+pub mod limits;
+pub mod lint;
+pub mod log;
+pub mod method;
+pub mod metrics;
+pub mod net;
+#[cfg(feature = "arbitrary-precision")]
+pub mod number;
+pub mod prelude;
+pub mod result;
+pub mod resume;
+pub mod rng;
+pub mod schema;
+pub mod session;
+pub mod static_data;
+pub mod stream;
+pub mod table;
+pub mod trace;
+
+pub use error::PluginError;
+pub use firelynx_pdk_macros::{char_class, firelynx_plugin, keywords};
+pub use static_data::StaticData;
+
+/// `Result` defaulting to `PluginError`, the error type handlers return.
+pub type Result<T, E = PluginError> = std::result::Result<T, E>;
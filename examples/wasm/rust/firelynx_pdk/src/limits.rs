@@ -0,0 +1,426 @@
+//! Resource limits shared by the SDK's parsers.
+//!
+//! A small request can still be expensive to parse: `[[[[...]]]]` nested
+//! a few hundred thousand levels deep exhausts the wasm stack of a recursive
+//! parser, and one array of millions of `0,` elements allocates far more than
+//! its text. Every parser in the SDK (the input envelope in every codec,
+//! `Extractor`, `duplicate_keys`, `Request::decode`) checks its input
+//! against `ParseLimits` first and reports a breach as `INVALID_INPUT` with
+//! the limit under `details.limit`:
+//!
+//! ```json
+//! {"code":"INVALID_INPUT","message":"Input exceeds the max_depth limit of 64","details":{"limit":"max_depth","max":64,"offset":64}}
+//! ```
+//!
+//! The defaults apply unless the host overrides them through extism config
+//! vars (`parse_max_depth`, `parse_max_string_bytes`, `parse_max_array_items`,
+//! `parse_max_object_members`, `parse_max_entity_expansions`), which the
+//! `#[firelynx_plugin]` shim reads once per plugin instance.
+//!
+//! XML bodies decoded with the `xml` feature (see `decode`) are checked by
+//! `check_xml`; `max_entity_expansions` is the budget for their entity and
+//! character references, and for any other XML parser built on the SDK.
+
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::config::PluginConfig;
+use crate::schema::{DefaultValue, Field};
+use crate::PluginError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ParseLimits {
+    /// Nesting of objects and arrays; the top-level value is depth 0.
+    #[serde(rename = "parse_max_depth")]
+    pub max_depth: usize,
+    /// Length of one string as sent, escapes included.
+    #[serde(rename = "parse_max_string_bytes")]
+    pub max_string_bytes: usize,
+    #[serde(rename = "parse_max_array_items")]
+    pub max_array_items: usize,
+    #[serde(rename = "parse_max_object_members")]
+    pub max_object_members: usize,
+    /// Entity references an XML parser may expand in one document.
+    #[serde(rename = "parse_max_entity_expansions")]
+    pub max_entity_expansions: usize,
+}
+
+impl ParseLimits {
+    pub const DEFAULT: ParseLimits = ParseLimits {
+        max_depth: 64,
+        max_string_bytes: 16 * 1024 * 1024,
+        max_array_items: 100_000,
+        max_object_members: 10_000,
+        max_entity_expansions: 10_000,
+    };
+
+    /// The limits in effect: the host's config once the shim has read it,
+    /// else `DEFAULT`.
+    pub fn active() -> &'static ParseLimits {
+        ACTIVE.get().unwrap_or(&ParseLimits::DEFAULT)
+    }
+
+    /// Reads the limits from the host's config on the first call. An
+    /// invalid value is a `CONFIG_ERROR` on every call until fixed.
+    pub(crate) fn init() -> Result<(), PluginError> {
+        if ACTIVE.get().is_none() {
+            let limits = ParseLimits::load()?;
+            let _ = ACTIVE.set(limits);
+        }
+        Ok(())
+    }
+
+    /// Checks JSON text without parsing it: one pass over the bytes, no
+    /// recursion and no allocation beyond one entry per open container.
+    /// Syntax errors are left for the parser to report.
+    pub fn check_json(&self, text: &[u8]) -> Result<(), PluginError> {
+        // Open containers: (is_array, elements seen).
+        let mut open: Vec<(bool, usize)> = Vec::new();
+        let mut pos = 0;
+        while let Some(&b) = text.get(pos) {
+            match b {
+                b' ' | b'\t' | b'\n' | b'\r' | b':' => {}
+                b',' => {
+                    if let Some((is_array, seen)) = open.last_mut() {
+                        *seen += 1;
+                        self.check_count(*is_array, *seen, pos)?;
+                    }
+                }
+                b'}' | b']' => {
+                    open.pop();
+                }
+                _ => {
+                    if let Some((_, seen)) = open.last_mut() {
+                        *seen = (*seen).max(1);
+                    }
+                    match b {
+                        b'{' | b'[' => {
+                            if open.len() >= self.max_depth {
+                                return Err(Limit::Depth.exceeded(self.max_depth, pos));
+                            }
+                            open.push((b == b'[', 0));
+                        }
+                        b'"' => {
+                            let start = pos;
+                            pos += 1;
+                            while let Some(&c) = text.get(pos) {
+                                match c {
+                                    b'"' => break,
+                                    b'\\' => pos += 2,
+                                    _ => pos += 1,
+                                }
+                            }
+                            let len = pos.min(text.len()) - start - 1;
+                            if len > self.max_string_bytes {
+                                return Err(
+                                    Limit::StringBytes.exceeded(self.max_string_bytes, start)
+                                );
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            pos += 1;
+        }
+        Ok(())
+    }
+
+    /// Checks XML text the way `check_json` checks JSON: element nesting
+    /// against `max_depth`, and entity and character references against
+    /// `max_entity_expansions`. Comments and CDATA sections are skipped;
+    /// syntax errors are left for the parser to report.
+    pub fn check_xml(&self, text: &[u8]) -> Result<(), PluginError> {
+        let references = text.iter().enumerate().filter(|&(_, &b)| b == b'&');
+        if let Some((pos, _)) = references.clone().nth(self.max_entity_expansions) {
+            return Err(Limit::EntityExpansions.exceeded(self.max_entity_expansions, pos));
+        }
+
+        let find = |from: usize, pattern: &[u8]| {
+            text[from..]
+                .windows(pattern.len())
+                .position(|w| w == pattern)
+                .map_or(text.len(), |i| from + i + pattern.len())
+        };
+        let mut depth = 0;
+        let mut pos = 0;
+        while let Some(&b) = text.get(pos) {
+            if b != b'<' {
+                pos += 1;
+                continue;
+            }
+            let rest = &text[pos..];
+            if rest.starts_with(b"<!--") {
+                pos = find(pos, b"-->");
+            } else if rest.starts_with(b"<![CDATA[") {
+                pos = find(pos, b"]]>");
+            } else if let Some(b'/' | b'?' | b'!') = rest.get(1) {
+                if rest[1] == b'/' {
+                    depth -= 1usize.min(depth);
+                }
+                pos = find(pos, b">");
+            } else {
+                // The tag ends at the first '>' outside a quoted attribute.
+                let mut quote = None;
+                let mut end = pos + 1;
+                while let Some(&c) = text.get(end) {
+                    match (quote, c) {
+                        (None, b'>') => break,
+                        (None, b'"' | b'\'') => quote = Some(c),
+                        (Some(q), c) if c == q => quote = None,
+                        _ => {}
+                    }
+                    end += 1;
+                }
+                if text[end - 1] != b'/' {
+                    depth += 1;
+                    if depth > self.max_depth {
+                        return Err(Limit::Depth.exceeded(self.max_depth, pos));
+                    }
+                }
+                pos = end + 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks an already decoded value tree, for codecs whose decoders
+    /// cannot be checked ahead of time.
+    pub fn check_value(&self, value: &Value) -> Result<(), PluginError> {
+        let mut pending = vec![(value, 0)];
+        while let Some((value, depth)) = pending.pop() {
+            match value {
+                Value::String(s) if s.len() > self.max_string_bytes => {
+                    return Err(Limit::StringBytes.exceeded_in_tree(self.max_string_bytes));
+                }
+                Value::Array(_) | Value::Object(_) if depth >= self.max_depth => {
+                    return Err(Limit::Depth.exceeded_in_tree(self.max_depth));
+                }
+                Value::Array(items) => {
+                    if items.len() > self.max_array_items {
+                        return Err(Limit::ArrayItems.exceeded_in_tree(self.max_array_items));
+                    }
+                    pending.extend(items.iter().map(|v| (v, depth + 1)));
+                }
+                Value::Object(members) => {
+                    if members.len() > self.max_object_members {
+                        return Err(Limit::ObjectMembers.exceeded_in_tree(self.max_object_members));
+                    }
+                    for (key, v) in members {
+                        if key.len() > self.max_string_bytes {
+                            return Err(Limit::StringBytes.exceeded_in_tree(self.max_string_bytes));
+                        }
+                        pending.push((v, depth + 1));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_count(&self, is_array: bool, seen: usize, pos: usize) -> Result<(), PluginError> {
+        let (limit, max) = if is_array {
+            (Limit::ArrayItems, self.max_array_items)
+        } else {
+            (Limit::ObjectMembers, self.max_object_members)
+        };
+        if seen > max {
+            return Err(limit.exceeded(max, pos));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits::DEFAULT
+    }
+}
+
+impl PluginConfig for ParseLimits {
+    const FIELDS: &'static [Field] = &[
+        Field::integer("parse_max_depth").default(DefaultValue::Int(64)),
+        Field::integer("parse_max_string_bytes").default(DefaultValue::Int(16 * 1024 * 1024)),
+        Field::integer("parse_max_array_items").default(DefaultValue::Int(100_000)),
+        Field::integer("parse_max_object_members").default(DefaultValue::Int(10_000)),
+        Field::integer("parse_max_entity_expansions").default(DefaultValue::Int(10_000)),
+    ];
+}
+
+static ACTIVE: OnceLock<ParseLimits> = OnceLock::new();
+
+/// The limit an input broke.
+///
+/// `label()` values are stable and name the `ParseLimits` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Depth,
+    StringBytes,
+    ArrayItems,
+    ObjectMembers,
+    EntityExpansions,
+}
+
+impl Limit {
+    pub fn label(self) -> &'static str {
+        match self {
+            Limit::Depth => "max_depth",
+            Limit::StringBytes => "max_string_bytes",
+            Limit::ArrayItems => "max_array_items",
+            Limit::ObjectMembers => "max_object_members",
+            Limit::EntityExpansions => "max_entity_expansions",
+        }
+    }
+
+    /// Builds the `INVALID_INPUT` for input that broke this limit at byte
+    /// `offset`.
+    pub fn exceeded(self, max: usize, offset: usize) -> PluginError {
+        self.exceeded_in_tree(max).with_detail("offset", offset)
+    }
+
+    /// Like `exceeded`, when there is no input offset to report.
+    pub fn exceeded_in_tree(self, max: usize) -> PluginError {
+        PluginError::invalid_input(format!(
+            "Input exceeds the {} limit of {}",
+            self.label(),
+            max
+        ))
+        .with_detail("limit", self.label())
+        .with_detail("max", max)
+    }
+
+    /// Reads the limit back from an error built by `exceeded`.
+    pub fn of(err: &PluginError) -> Option<Limit> {
+        if err.code() != "INVALID_INPUT" {
+            return None;
+        }
+        let label = err.details().get("limit")?.as_str()?;
+        ALL_LIMITS.iter().copied().find(|l| l.label() == label)
+    }
+}
+
+const ALL_LIMITS: [Limit; 5] = [
+    Limit::Depth,
+    Limit::StringBytes,
+    Limit::ArrayItems,
+    Limit::ObjectMembers,
+    Limit::EntityExpansions,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SMALL: ParseLimits = ParseLimits {
+        max_depth: 3,
+        max_string_bytes: 8,
+        max_array_items: 4,
+        max_object_members: 2,
+        max_entity_expansions: 0,
+    };
+
+    /// The limit `json` breaks, checking that the scan and the tree check
+    /// agree.
+    fn broken(json: &str) -> Option<Limit> {
+        let scanned = SMALL.check_json(json.as_bytes()).err();
+        let value: Value = serde_json::from_str(json).unwrap();
+        let tree = SMALL.check_value(&value).err();
+        let scanned = scanned.as_ref().and_then(Limit::of);
+        assert_eq!(tree.as_ref().and_then(Limit::of), scanned, "{}", json);
+        scanned
+    }
+
+    #[test]
+    fn inputs_within_limits_pass() {
+        for json in [
+            r#"{"a": [[1, 2, 3, 4]], "b": "12345678"}"#,
+            r#"["a\"b,c]", {}, [], ""]"#,
+            r#"{"k": "AB"}"#,
+            "17",
+        ] {
+            assert_eq!(broken(json), None, "{}", json);
+        }
+    }
+
+    #[test]
+    fn reports_the_broken_limit() {
+        assert_eq!(broken("[[[[1]]]]"), Some(Limit::Depth));
+        assert_eq!(broken(r#"{"a": {"b": {"c": {}}}}"#), Some(Limit::Depth));
+        assert_eq!(broken(r#"["123456789"]"#), Some(Limit::StringBytes));
+        assert_eq!(broken(r#"{"123456789": 1}"#), Some(Limit::StringBytes));
+        assert_eq!(broken("[1, 2, 3, 4, 5]"), Some(Limit::ArrayItems));
+        assert_eq!(
+            broken(r#"{"a": 1, "b": 2, "c": 3}"#),
+            Some(Limit::ObjectMembers)
+        );
+    }
+
+    #[test]
+    fn scan_survives_deep_and_malformed_input() {
+        let deep = "[".repeat(1_000_000);
+        let err = ParseLimits::DEFAULT
+            .check_json(deep.as_bytes())
+            .unwrap_err();
+        assert_eq!(Limit::of(&err), Some(Limit::Depth));
+        assert_eq!(err.details()["offset"], json!(64));
+
+        for junk in [r#""unterminated"#, "]]]}", r#""\"#, ",,,,"] {
+            let _ = SMALL.check_json(junk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn checks_xml_nesting_and_references() {
+        let limits = ParseLimits {
+            max_entity_expansions: 3,
+            ..SMALL
+        };
+        let xml = |text: &str| {
+            limits
+                .check_xml(text.as_bytes())
+                .err()
+                .map(|e| Limit::of(&e))
+        };
+        assert_eq!(xml("<a><b x='>'><c/><c></c></b></a>"), None);
+        assert_eq!(xml("<a><!-- <b><c><d> --><![CDATA[<b><c><d>]]></a>"), None);
+        assert_eq!(xml("<?xml version='1.0'?><a><b><c><d/></c></b></a>"), None);
+        assert_eq!(xml("<a><b><c><d>"), Some(Some(Limit::Depth)));
+        assert_eq!(xml("<a>&amp;&lt;&#65;</a>"), None);
+        assert_eq!(
+            xml("<a>&amp;&lt;&#65;&gt;</a>"),
+            Some(Some(Limit::EntityExpansions))
+        );
+        for junk in ["<", "<a", "<!--", "<a x='", "</"] {
+            let _ = limits.check_xml(junk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn limit_errors_round_trip() {
+        let err = Limit::ArrayItems.exceeded(4, 10);
+        assert_eq!(err.code(), "INVALID_INPUT");
+        assert_eq!(err.details()["max"], json!(4));
+        assert_eq!(Limit::of(&err), Some(Limit::ArrayItems));
+        assert_eq!(Limit::of(&PluginError::invalid_input("x")), None);
+    }
+
+    #[test]
+    fn reads_overrides_from_config_vars() {
+        let limits =
+            ParseLimits::from_vars(|key| (key == "parse_max_depth").then(|| "8".to_string()))
+                .unwrap();
+        assert_eq!(
+            limits,
+            ParseLimits {
+                max_depth: 8,
+                ..ParseLimits::DEFAULT
+            }
+        );
+        let err = ParseLimits::from_vars(|_| Some("-1".to_string())).unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+    }
+}
@@ -0,0 +1,132 @@
+//! Config linting: problems that stop a route from working, plus warnings
+//! about config that works but probably is not what was meant.
+//!
+//! `ConfigSchema::lint` checks `static_data` against the declared fields
+//! without failing on the first problem, so a plugin can offer a lint export
+//! for the firelynx CLI to call while validating a config file:
+//!
+//! ```ignore
+//! #[firelynx_plugin]
+//! fn lint_config(_request: Request, static_data: StaticData) -> Result<LintReport> {
+//!     Ok(Config::lint(&static_data))
+//! }
+//! ```
+//!
+//! Warnings come from three places: keys no field declares (and the schema
+//! does not list in `IGNORED_KEYS`), fields marked `deprecated`, and the
+//! plugin's own `ConfigSchema::check`, which sees the parsed config.
+
+use serde::Serialize;
+
+use crate::PluginError;
+
+/// Why an issue was reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    /// The config is rejected; calls on this route fail with `CONFIG_ERROR`.
+    Invalid,
+    /// A key the plugin does not read.
+    UnusedKey,
+    /// A key the plugin still reads but plans to drop.
+    Deprecated,
+    /// A value that is accepted but likely a mistake.
+    Suspicious,
+}
+
+/// One problem, tied to the `static_data` key it concerns.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LintIssue {
+    pub field: String,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+/// The result of linting one route's `static_data`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LintReport {
+    /// False when any error was found.
+    pub valid: bool,
+    pub errors: Vec<LintIssue>,
+    pub warnings: Vec<LintIssue>,
+}
+
+/// Collects issues while linting.
+#[derive(Debug, Default)]
+pub struct Lint {
+    errors: Vec<LintIssue>,
+    warnings: Vec<LintIssue>,
+}
+
+impl Lint {
+    pub fn new() -> Lint {
+        Lint::default()
+    }
+
+    /// Records an error under `field`.
+    pub fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(LintIssue {
+            field: field.to_string(),
+            kind: LintKind::Invalid,
+            message: message.into(),
+        });
+    }
+
+    /// Records a validation error the plugin would return at call time,
+    /// under its `details.field` (or `static_data` without one).
+    pub fn reject(&mut self, err: &PluginError) {
+        let field = err
+            .details()
+            .get("field")
+            .and_then(|v| v.as_str())
+            .unwrap_or("static_data");
+        self.error(field, err.message());
+    }
+
+    /// Records a warning. `kind` should not be `Invalid`; use `error`.
+    pub fn warn(&mut self, field: &str, kind: LintKind, message: impl Into<String>) {
+        self.warnings.push(LintIssue {
+            field: field.to_string(),
+            kind,
+            message: message.into(),
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    pub fn finish(self) -> LintReport {
+        LintReport {
+            valid: self.errors.is_empty(),
+            errors: self.errors,
+            warnings: self.warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_is_valid_without_errors() {
+        let mut lint = Lint::new();
+        lint.warn("x", LintKind::UnusedKey, "x is not used");
+        let report = lint.finish();
+        assert!(report.valid);
+        assert_eq!(report.warnings.len(), 1);
+
+        let mut lint = Lint::new();
+        lint.reject(&PluginError::config("mode must be a or b").with_detail("field", "mode"));
+        lint.reject(&PluginError::config("broken"));
+        let report = lint.finish();
+        assert!(!report.valid);
+        assert_eq!(report.errors[0].field, "mode");
+        assert_eq!(report.errors[1].field, "static_data");
+        assert_eq!(
+            serde_json::to_value(&report.errors[0]).unwrap(),
+            serde_json::json!({"field": "mode", "kind": "invalid", "message": "mode must be a or b"})
+        );
+    }
+}
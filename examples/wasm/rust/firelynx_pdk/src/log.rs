@@ -0,0 +1,143 @@
+//! Structured logging through the extism host.
+//!
+//! Each macro writes one JSON object per call to the extism log host
+//! function of its level, which the host forwards to its own logger:
+//!
+//! ```ignore
+//! log_warn!("upstream check failed", check = name, status = 503);
+//! // {"msg":"upstream check failed","check":"db","status":503}
+//! ```
+//!
+//! Lines logged during a traced call also carry `trace_id` and `span_id`
+//! (see `trace`). Field values are anything `Serialize`. Nothing is formatted or serialized
+//! when the host's log level filters the call out.
+
+use serde::Serialize;
+use serde_json::Value;
+
+pub use extism_pdk::LogLevel as Level;
+
+/// True when the host would keep a message at `level`.
+#[cfg(target_family = "wasm")]
+pub fn enabled(level: Level) -> bool {
+    let host = unsafe { extism_pdk::extism::get_log_level() };
+    host != i32::MAX && level.to_int() >= host
+}
+
+/// Native builds (unit tests) have no host to log to.
+#[cfg(not(target_family = "wasm"))]
+pub fn enabled(_: Level) -> bool {
+    false
+}
+
+/// Writes one log line, adding `trace_id` and `span_id` when the call is
+/// traced.
+#[doc(hidden)]
+pub fn write(level: Level, message: &str, fields: &[(&str, Value)]) {
+    let line = match crate::trace::current() {
+        Some(ctx) => {
+            let mut fields = fields.to_vec();
+            fields.push(("trace_id", ctx.trace_id_hex().into()));
+            fields.push(("span_id", ctx.span_id_hex().into()));
+            record(message, &fields)
+        }
+        None => record(message, fields),
+    };
+    emit(level, line);
+}
+
+#[cfg(target_family = "wasm")]
+fn emit(level: Level, line: String) {
+    if let Ok(memory) = extism_pdk::Memory::from_bytes(line) {
+        memory.log(level);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn emit(_: Level, _: String) {}
+
+/// Renders one log line: `msg` first, then the fields in call order.
+#[doc(hidden)]
+pub fn record(message: &str, fields: &[(&str, Value)]) -> String {
+    let mut line = String::from("{\"msg\":");
+    line.push_str(&Value::from(message).to_string());
+    for (key, value) in fields {
+        line.push(',');
+        line.push_str(&Value::from(*key).to_string());
+        line.push(':');
+        line.push_str(&value.to_string());
+    }
+    line.push('}');
+    line
+}
+
+/// Converts a field value, logging a placeholder rather than failing when it
+/// cannot be serialized.
+#[doc(hidden)]
+pub fn field<T: Serialize + ?Sized>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|e| format!("<unserializable: {}>", e).into())
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $msg:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write(
+                $level,
+                &$msg,
+                &[$((stringify!($key), $crate::log::field(&$value))),*],
+            );
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($args:tt)+) => { $crate::__log!($crate::log::Level::Debug, $($args)+) };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($args:tt)+) => { $crate::__log!($crate::log::Level::Info, $($args)+) };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($args:tt)+) => { $crate::__log!($crate::log::Level::Warn, $($args)+) };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($args:tt)+) => { $crate::__log!($crate::log::Level::Error, $($args)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_message_then_fields_in_order() {
+        let line = record(
+            "check \"db\" failed",
+            &[("zone", json!("eu")), ("status", json!(503))],
+        );
+        assert_eq!(
+            line,
+            r#"{"msg":"check \"db\" failed","zone":"eu","status":503}"#
+        );
+        assert!(serde_json::from_str::<Value>(&line).is_ok());
+    }
+
+    #[test]
+    fn fields_take_any_serializable_value() {
+        #[derive(Serialize)]
+        struct Peer {
+            ip: &'static str,
+        }
+        assert_eq!(field(&Peer { ip: "::1" }), json!({"ip": "::1"}));
+        assert_eq!(field("text"), json!("text"));
+        assert_eq!(field(&[1, 2]), json!([1, 2]));
+    }
+}
@@ -0,0 +1,134 @@
+//! HTTP request methods.
+
+use std::fmt;
+
+use crate::input::Request;
+
+/// An HTTP request method. Standard methods are matched case-insensitively;
+/// anything else is kept verbatim in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Options,
+    Connect,
+    Trace,
+    Other(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+            Method::Connect => "CONNECT",
+            Method::Trace => "TRACE",
+            Method::Other(m) => m,
+        }
+    }
+
+    /// Safe methods are read-only (RFC 9110, section 9.2.1).
+    pub fn is_safe(&self) -> bool {
+        matches!(
+            self,
+            Method::Get | Method::Head | Method::Options | Method::Trace
+        )
+    }
+
+    /// Idempotent methods can be retried without changing the outcome
+    /// (RFC 9110, section 9.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, Method::Put | Method::Delete)
+    }
+}
+
+impl From<&str> for Method {
+    fn from(s: &str) -> Method {
+        const STANDARD: [Method; 9] = [
+            Method::Get,
+            Method::Head,
+            Method::Post,
+            Method::Put,
+            Method::Patch,
+            Method::Delete,
+            Method::Options,
+            Method::Connect,
+            Method::Trace,
+        ];
+        STANDARD
+            .into_iter()
+            .find(|m| m.as_str().eq_ignore_ascii_case(s))
+            .unwrap_or_else(|| Method::Other(s.to_string()))
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Request {
+    /// The request method as a `Method`. An empty method, which Go's
+    /// `http.Request` treats as GET, maps to `Method::Get`.
+    pub fn method(&self) -> Method {
+        if self.method.is_empty() {
+            Method::Get
+        } else {
+            Method::from(self.method.as_str())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_standard_methods_case_insensitively() {
+        assert_eq!(Method::from("GET"), Method::Get);
+        assert_eq!(Method::from("post"), Method::Post);
+        assert_eq!(Method::from("Patch"), Method::Patch);
+        assert_eq!(
+            Method::from("PROPFIND"),
+            Method::Other("PROPFIND".to_string())
+        );
+        assert_eq!(Method::from("PROPFIND").to_string(), "PROPFIND");
+    }
+
+    #[test]
+    fn safety_and_idempotency() {
+        let safe: Vec<_> = ["GET", "HEAD", "OPTIONS", "TRACE"]
+            .map(Method::from)
+            .to_vec();
+        assert!(safe.iter().all(|m| m.is_safe() && m.is_idempotent()));
+
+        assert!(!Method::Put.is_safe() && Method::Put.is_idempotent());
+        assert!(!Method::Delete.is_safe() && Method::Delete.is_idempotent());
+        for m in [
+            Method::Post,
+            Method::Patch,
+            Method::Connect,
+            Method::from("X"),
+        ] {
+            assert!(!m.is_safe() && !m.is_idempotent(), "{m}");
+        }
+    }
+
+    #[test]
+    fn request_method_defaults_to_get() {
+        let mut req = Request::default();
+        assert_eq!(req.method(), Method::Get);
+        req.method = "delete".to_string();
+        assert_eq!(req.method(), Method::Delete);
+    }
+}
@@ -0,0 +1,231 @@
+//! Per-call plugin metrics, reported in the response under `_meta.metrics`.
+//!
+//! Handlers record into counters, histograms and timers while they run:
+//!
+//! ```ignore
+//! metrics::increment("rows");
+//! metrics::record("row_bytes", line.len() as f64);
+//! let _t = metrics::timer("parse_ms");
+//! ```
+//!
+//! When a `#[firelynx_plugin]` handler returns a value that serializes to an
+//! object and anything was recorded, the shim adds
+//!
+//! ```json
+//! "_meta": {"metrics": {
+//!   "counters": {"rows": 120},
+//!   "histograms": {"row_bytes": {"count": 120, "sum": 9800, "min": 12, "max": 311}},
+//!   "timers": {"parse_ms": {"count": 1, "sum": 0.42, "min": 0.42, "max": 0.42}}
+//! }}
+//! ```
+//!
+//! to it, for the host to scrape. Outputs that are not objects, and
+//! `OutputStream` outputs, are written unchanged and the metrics are dropped.
+//! Metrics are also dropped when the handler fails. Every call starts from
+//! an empty set.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// The output member the metrics are written under.
+pub const META_KEY: &str = "_meta";
+
+thread_local! {
+    static REGISTRY: RefCell<Metrics> = RefCell::new(Metrics::default());
+}
+
+/// Everything recorded during one call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Metrics {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub counters: BTreeMap<String, u64>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub histograms: BTreeMap<String, Summary>,
+    /// Elapsed milliseconds.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub timers: BTreeMap<String, Summary>,
+}
+
+impl Metrics {
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty() && self.histograms.is_empty() && self.timers.is_empty()
+    }
+}
+
+/// Count, sum and range of the values recorded under one name.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Summary {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Adds one to counter `name`.
+pub fn increment(name: &str) {
+    add(name, 1);
+}
+
+/// Adds `n` to counter `name`.
+pub fn add(name: &str, n: u64) {
+    REGISTRY.with(|r| {
+        let mut r = r.borrow_mut();
+        match r.counters.get_mut(name) {
+            Some(c) => *c = c.saturating_add(n),
+            None => {
+                r.counters.insert(name.to_string(), n);
+            }
+        }
+    });
+}
+
+/// Records `value` in histogram `name`. Non-finite values are ignored, as
+/// they cannot be written as JSON numbers.
+pub fn record(name: &str, value: f64) {
+    if value.is_finite() {
+        REGISTRY.with(|r| observe(&mut r.borrow_mut().histograms, name, value));
+    }
+}
+
+/// Starts timer `name`. The elapsed time is recorded when the returned guard
+/// is dropped or `stop` is called.
+pub fn timer(name: &str) -> Timer {
+    Timer {
+        name: name.to_string(),
+        started: Instant::now(),
+        stopped: false,
+    }
+}
+
+/// Times `f` under timer `name`.
+pub fn time<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let _timer = timer(name);
+    f()
+}
+
+fn observe(summaries: &mut BTreeMap<String, Summary>, name: &str, value: f64) {
+    match summaries.get_mut(name) {
+        Some(s) => {
+            s.count += 1;
+            s.sum += value;
+            s.min = s.min.min(value);
+            s.max = s.max.max(value);
+        }
+        None => {
+            let first = Summary {
+                count: 1,
+                sum: value,
+                min: value,
+                max: value,
+            };
+            summaries.insert(name.to_string(), first);
+        }
+    }
+}
+
+/// A running timer; see `timer`.
+#[must_use = "the timer records when dropped"]
+pub struct Timer {
+    name: String,
+    started: Instant,
+    stopped: bool,
+}
+
+impl Timer {
+    /// Records the elapsed time now and returns it in milliseconds.
+    pub fn stop(mut self) -> f64 {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> f64 {
+        self.stopped = true;
+        let ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        REGISTRY.with(|r| observe(&mut r.borrow_mut().timers, &self.name, ms));
+        ms
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if !self.stopped {
+            self.finish();
+        }
+    }
+}
+
+/// Returns what has been recorded so far and clears it.
+pub fn take() -> Metrics {
+    REGISTRY.with(|r| std::mem::take(&mut *r.borrow_mut()))
+}
+
+/// Adds `metrics` to `output` under `_meta.metrics`, keeping any other
+/// `_meta` members the handler set. Returns false, leaving `output`
+/// untouched, when it is not an object or its `_meta` is not one.
+pub fn attach(output: &mut Value, metrics: &Metrics) -> bool {
+    let Value::Object(fields) = output else {
+        return false;
+    };
+    let meta = fields
+        .entry(META_KEY)
+        .or_insert_with(|| Value::Object(Default::default()));
+    let Value::Object(meta) = meta else {
+        return false;
+    };
+    let metrics = serde_json::to_value(metrics).unwrap_or_default();
+    meta.insert("metrics".to_string(), metrics);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn records_counters_histograms_and_timers() {
+        take();
+        increment("rows");
+        add("rows", 2);
+        record("size", 10.0);
+        record("size", 4.0);
+        record("size", f64::NAN);
+        let elapsed = timer("parse").stop();
+        time("parse", || ());
+
+        let metrics = take();
+        assert_eq!(metrics.counters["rows"], 3);
+        assert_eq!(
+            metrics.histograms["size"],
+            Summary {
+                count: 2,
+                sum: 14.0,
+                min: 4.0,
+                max: 10.0
+            }
+        );
+        assert_eq!(metrics.timers["parse"].count, 2);
+        assert!(metrics.timers["parse"].sum >= elapsed);
+        assert!(take().is_empty());
+    }
+
+    #[test]
+    fn attaches_under_meta() {
+        let mut metrics = Metrics::default();
+        metrics.counters.insert("hits".to_string(), 1);
+
+        let mut output = json!({"count": 5, "_meta": {"version": 2}});
+        assert!(attach(&mut output, &metrics));
+        assert_eq!(
+            output,
+            json!({"count": 5, "_meta": {"version": 2, "metrics": {"counters": {"hits": 1}}}})
+        );
+
+        let mut output = json!([1, 2]);
+        assert!(!attach(&mut output, &metrics));
+        assert_eq!(output, json!([1, 2]));
+    }
+}
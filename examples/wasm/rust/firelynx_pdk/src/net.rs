@@ -0,0 +1,316 @@
+//! Client address parsing: `RemoteAddr`, CIDR ranges and forwarded headers.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::input::Request;
+use crate::PluginError;
+
+/// The transport-level peer of a request, parsed from `RemoteAddr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peer {
+    pub ip: IpAddr,
+    /// Absent when the host reports a bare address.
+    pub port: Option<u16>,
+}
+
+impl FromStr for Peer {
+    type Err = PluginError;
+
+    /// Accepts `1.2.3.4:5678`, `[::1]:12345`, and bare `1.2.3.4` / `::1`.
+    fn from_str(s: &str) -> Result<Peer, PluginError> {
+        parse_host_port(s).ok_or_else(|| {
+            PluginError::invalid_input("Invalid remote address").with_detail("remote_addr", s)
+        })
+    }
+}
+
+fn parse_host_port(s: &str) -> Option<Peer> {
+    let s = s.trim();
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return Some(Peer { ip, port: None });
+    }
+    if let Some(rest) = s.strip_prefix('[') {
+        let (ip, after) = rest.split_once(']')?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => Some(port.parse().ok()?),
+            None if after.is_empty() => None,
+            None => return None,
+        };
+        return Some(Peer {
+            ip: ip.parse().ok()?,
+            port,
+        });
+    }
+    let (ip, port) = s.rsplit_once(':')?;
+    Some(Peer {
+        ip: ip.parse::<std::net::Ipv4Addr>().ok()?.into(),
+        port: Some(port.parse().ok()?),
+    })
+}
+
+/// An IP network such as `10.0.0.0/8` or `2001:db8::/32`. A bare address is a
+/// single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Compare IPv4-mapped IPv6 addresses as IPv4.
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            v4 => v4,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_eq(u32::from(net).into(), u32::from(ip).into(), 32, self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_eq(u128::from(net), u128::from(ip), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_eq(net: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+    let shift = u32::from(bits - prefix);
+    shift >= 128 || (net >> shift) == (ip >> shift)
+}
+
+impl FromStr for Cidr {
+    type Err = PluginError;
+
+    fn from_str(s: &str) -> Result<Cidr, PluginError> {
+        let invalid = || PluginError::config("Invalid CIDR").with_detail("cidr", s);
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(invalid)?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Cidr, String> {
+        s.parse().map_err(|e: PluginError| e.to_string())
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Proxies whose forwarding headers are believed. Deserializes from a list of
+/// CIDR strings, e.g. `trusted_proxies = ["10.0.0.0/8", "::1"]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct TrustedProxies(pub Vec<Cidr>);
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+impl Request {
+    /// Parses `RemoteAddr` into the connecting peer's IP and port.
+    pub fn peer(&self) -> Result<Peer, PluginError> {
+        self.remote_addr.parse()
+    }
+
+    /// Resolves the originating client IP.
+    ///
+    /// Forwarding headers are only consulted when the direct peer is a
+    /// trusted proxy. The `Forwarded` header (RFC 7239) is preferred over
+    /// `X-Forwarded-For`; hops are walked right to left, skipping trusted
+    /// proxies, and the first untrusted address is the client. If every hop
+    /// is trusted, the left-most one is returned. An unparseable hop stops
+    /// the walk at the last address that could be verified.
+    pub fn client_ip(&self, trusted: &TrustedProxies) -> Result<IpAddr, PluginError> {
+        let peer = self.peer()?.ip;
+        if !trusted.contains(peer) {
+            return Ok(peer);
+        }
+
+        let hops: Vec<Option<IpAddr>> = if let Some(forwarded) = self.header_values("forwarded") {
+            forwarded.iter().flat_map(|v| forwarded_for(v)).collect()
+        } else if let Some(xff) = self.header_values("x-forwarded-for") {
+            xff.iter()
+                .flat_map(|v| v.split(','))
+                .map(|hop| parse_host_port(hop).map(|p| p.ip))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            let Some(ip) = hop else { break };
+            client = ip;
+            if !trusted.contains(ip) {
+                break;
+            }
+        }
+        Ok(client)
+    }
+}
+
+/// Extracts the `for=` node of each element of a `Forwarded` header value.
+/// Obfuscated or `unknown` nodes yield `None`.
+fn forwarded_for(value: &str) -> Vec<Option<IpAddr>> {
+    value
+        .split(',')
+        .map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, node) = pair.split_once('=')?;
+                if !name.trim().eq_ignore_ascii_case("for") {
+                    return None;
+                }
+                parse_host_port(node.trim().trim_matches('"')).map(|p| p.ip)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn request(remote_addr: &str, headers: &[(&str, &str)]) -> Request {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for (k, v) in headers {
+            map.entry(k.to_string()).or_default().push(v.to_string());
+        }
+        Request {
+            remote_addr: remote_addr.to_string(),
+            headers: map,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_remote_addr_forms() {
+        assert_eq!(
+            "[::1]:12345".parse::<Peer>().unwrap(),
+            Peer {
+                ip: ip("::1"),
+                port: Some(12345)
+            }
+        );
+        assert_eq!(
+            "192.0.2.1:80".parse::<Peer>().unwrap(),
+            Peer {
+                ip: ip("192.0.2.1"),
+                port: Some(80)
+            }
+        );
+        assert_eq!("2001:db8::1".parse::<Peer>().unwrap().port, None);
+        assert_eq!("[2001:db8::1]".parse::<Peer>().unwrap().port, None);
+
+        for bad in ["", "localhost:80", "[::1]x", "1.2.3.4:99999", "::1:80:"] {
+            assert!(bad.parse::<Peer>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let net: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(net.contains(ip("10.1.2.3")));
+        assert!(net.contains(ip("::ffff:10.1.2.3")));
+        assert!(!net.contains(ip("11.0.0.1")));
+        assert!(!net.contains(ip("::1")));
+
+        let v6: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8:ffff::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("203.0.113.9")));
+
+        let host: Cidr = "::1".parse().unwrap();
+        assert_eq!(host.to_string(), "::1/128");
+        assert!(host.contains(ip("::1")));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("example.com/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_forwarding_headers() {
+        let req = request("203.0.113.9:1234", &[("X-Forwarded-For", "198.51.100.1")]);
+        let trusted = TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]);
+        assert_eq!(req.client_ip(&trusted).unwrap(), ip("203.0.113.9"));
+        assert_eq!(
+            req.client_ip(&TrustedProxies::default()).unwrap(),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn x_forwarded_for_skips_trusted_hops() {
+        let trusted: TrustedProxies =
+            serde_json::from_value(serde_json::json!(["10.0.0.0/8", "::1"])).unwrap();
+        let req = request(
+            "[::1]:5000",
+            &[
+                ("X-Forwarded-For", "198.51.100.7, 203.0.113.9"),
+                ("X-Forwarded-For", "10.0.0.2"),
+            ],
+        );
+        assert_eq!(req.client_ip(&trusted).unwrap(), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn forwarded_header_is_preferred() {
+        let trusted = TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]);
+        let req = request(
+            "10.0.0.1:80",
+            &[
+                (
+                    "Forwarded",
+                    r#"for=192.0.2.60;proto=http, for="[2001:db8:cafe::17]:4711""#,
+                ),
+                ("X-Forwarded-For", "198.51.100.1"),
+            ],
+        );
+        assert_eq!(req.client_ip(&trusted).unwrap(), ip("2001:db8:cafe::17"));
+    }
+
+    #[test]
+    fn unparseable_hop_stops_at_last_verified_address() {
+        let trusted = TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]);
+        let req = request(
+            "10.0.0.1:80",
+            &[("Forwarded", "for=192.0.2.1, for=_hidden, for=10.0.0.5")],
+        );
+        assert_eq!(req.client_ip(&trusted).unwrap(), ip("10.0.0.5"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Exact JSON numbers, with the `arbitrary-precision` feature.
+//!
+//! serde_json normally parses every non-integer, and every integer beyond
+//! 64 bits, into an `f64`, so `12345678901234567890123` or `0.10000000000000001`
+//! silently become something else before a handler sees them. With the
+//! feature, `Number` keeps the text it was parsed from (`Number::as_str`) and
+//! these helpers let validation plugins find values that a consumer parsing
+//! into `f64` would get wrong.
+//!
+//! Only JSON carries exact numbers; the MessagePack and CBOR codecs write
+//! them as 64-bit integers or floats.
+
+use serde_json::{Number, Value};
+
+/// True when `n` comes back unchanged from a round trip through `f64`:
+/// parsing it and printing the shortest representation denotes the same
+/// decimal value. `0.1` qualifies; `9007199254740993` and `1e400` do not.
+pub fn survives_f64(n: &Number) -> bool {
+    if n.is_i64() || n.is_u64() {
+        return n
+            .as_f64()
+            .is_some_and(|f| f.abs() < 9007199254740992.0 || n.as_str() == format!("{}", f));
+    }
+    match n.as_str().parse::<f64>() {
+        Ok(f) if f.is_finite() => normalize(n.as_str()) == normalize(&format!("{}", f)),
+        _ => false,
+    }
+}
+
+/// JSON Pointers of every number in `value` that does not survive an `f64`
+/// round trip. Object members are visited in key order.
+pub fn lossy_numbers(value: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+    collect(value, &mut String::new(), &mut found);
+    found
+}
+
+fn collect(value: &Value, pointer: &mut String, found: &mut Vec<String>) {
+    let len = pointer.len();
+    match value {
+        Value::Number(n) if !survives_f64(n) => found.push(pointer.clone()),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                pointer.push('/');
+                pointer.push_str(&i.to_string());
+                collect(item, pointer, found);
+                pointer.truncate(len);
+            }
+        }
+        Value::Object(fields) => {
+            for (k, v) in fields {
+                pointer.push('/');
+                pointer.push_str(&k.replace('~', "~0").replace('/', "~1"));
+                collect(v, pointer, found);
+                pointer.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reduces a JSON number to sign, significant digits and the exponent of
+/// the first digit, so `1.50`, `15e-1` and `0.15E1` compare equal.
+fn normalize(s: &str) -> (bool, String, i64) {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], s[i + 1..].parse::<i64>().unwrap_or(0)),
+        None => (s, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac);
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    let significant = digits.trim_matches('0').to_string();
+    if significant.is_empty() {
+        return (false, String::new(), 0);
+    }
+    let point = int.len() as i64 - leading as i64 + exponent;
+    (negative, significant, point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn number(s: &str) -> Number {
+        serde_json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_original_text() {
+        let n = number("12345678901234567890123.4500");
+        assert_eq!(n.as_str(), "12345678901234567890123.4500");
+    }
+
+    #[test]
+    fn round_trip_check() {
+        for exact in [
+            "0",
+            "-0.0",
+            "0.1",
+            "1.50",
+            "15e-1",
+            "9007199254740992",
+            "-42",
+            "1e300",
+        ] {
+            assert!(survives_f64(&number(exact)), "{}", exact);
+        }
+        for lossy in [
+            "9007199254740993",
+            "12345678901234567890",
+            "0.10000000000000001",
+            "1e400",
+            "3.141592653589793238",
+        ] {
+            assert!(!survives_f64(&number(lossy)), "{}", lossy);
+        }
+    }
+
+    #[test]
+    fn points_at_lossy_numbers() {
+        let value: Value = serde_json::from_str(
+            r#"{"amount": 10.25, "ledger": [1, 123456789012345678901], "a/b": {"x": 0.30000000000000004441}}"#,
+        )
+        .unwrap();
+        assert_eq!(lossy_numbers(&value), vec!["/a~1b/x", "/ledger/1"]);
+        assert!(lossy_numbers(&json!({"n": 1})).is_empty());
+    }
+}
@@ -0,0 +1,23 @@
+//! Common imports for plugin authors.
+//!
+//! ```ignore
+//! use firelynx_pdk::prelude::*;
+//! ```
+
+pub use crate::accept::{negotiate, Accept};
+pub use crate::body::Body;
+pub use crate::config::PluginConfig;
+pub use crate::error::PluginError;
+pub use crate::extract::Extractor;
+pub use crate::input::{Input, InputRef, Request, RequestRef, Url, UrlRef};
+pub use crate::lint::{Lint, LintKind, LintReport};
+pub use crate::method::Method;
+pub use crate::net::{Cidr, Peer, TrustedProxies};
+pub use crate::resume::Step;
+pub use crate::schema::{ConfigSchema, DefaultValue, Field, WhenAbsent};
+pub use crate::static_data::StaticData;
+pub use crate::stream::{Output, OutputStream};
+pub use crate::{
+    clock, firelynx_plugin, log_debug, log_error, log_info, log_warn, metrics, rng, Result,
+};
+pub use extism_pdk::{plugin_fn, FnResult, Json};
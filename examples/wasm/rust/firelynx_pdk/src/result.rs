@@ -0,0 +1,144 @@
+//! Typed result envelopes, for hosts that branch on every outcome in one
+//! place.
+//!
+//! By default a call that fails returns a non-zero status and reports its
+//! error envelope through `error_set`, while a call that succeeds writes its
+//! output. With the `result_envelope` extism config var set to `true`, every
+//! call the shim can answer succeeds and writes one of:
+//!
+//! ```json
+//! {"ok": {"count": 3, "characters": "aeiouAEIOU"}}
+//! {"error": {"code": "CONFIG_ERROR", "message": "...", "details": {"field": "search_characters"}}}
+//! ```
+//!
+//! in the envelope codec, so the host reads the output and matches on the
+//! key, and on `error.code`, instead of on the status. `error` holds the same
+//! envelope `error_set` would have had, `trace_id` included. Failures before
+//! the codec is known, such as a bad `envelope_codec`, are written in JSON.
+//!
+//! Raw outputs (`OutputStream`, `Output::Raw`) are written as-is: they are a
+//! format the client asked for, not a value the envelope could hold. A panic
+//! still traps, since the instance cannot write output after one.
+
+use std::cell::Cell;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::codec::Codec;
+use crate::PluginError;
+
+/// The extism config var that turns result envelopes on.
+pub const CONFIG_KEY: &str = "result_envelope";
+
+thread_local! {
+    static ENVELOPE: Cell<Option<Codec>> = const { Cell::new(None) };
+}
+
+/// Reads whether result envelopes are on from the host's extism config.
+pub(crate) fn from_config() -> Result<bool, PluginError> {
+    match extism_pdk::config::get(CONFIG_KEY) {
+        Ok(None) => Ok(false),
+        Ok(Some(value)) => match value.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(PluginError::config(format!(
+                "Config var {} must be true or false",
+                CONFIG_KEY
+            ))
+            .with_detail("field", CONFIG_KEY)
+            .with_detail("value", value)),
+        },
+        Err(e) => Err(
+            PluginError::internal(format!("Failed to read config: {}", e))
+                .with_detail("field", CONFIG_KEY),
+        ),
+    }
+}
+
+/// Records how the current call reports its result: enveloped in `codec`,
+/// or with `None` the default way.
+pub(crate) fn begin(codec: Option<Codec>) {
+    ENVELOPE.with(|e| e.set(codec));
+}
+
+/// The codec the current call's envelopes are written in, when it has
+/// them.
+pub(crate) fn codec() -> Option<Codec> {
+    ENVELOPE.with(Cell::get)
+}
+
+#[derive(Serialize)]
+pub(crate) struct Succeeded<'a, T: ?Sized> {
+    pub ok: &'a T,
+}
+
+#[derive(Serialize)]
+struct Failed {
+    error: Value,
+}
+
+/// The `error` envelope for a failure. Errors raised as a `PluginError`
+/// carry their envelope in the message; any other error is `INTERNAL`.
+fn error_envelope(e: &extism_pdk::Error) -> Value {
+    let message = e.to_string();
+    match serde_json::from_str::<Value>(&message) {
+        Ok(envelope) if envelope.get("code").is_some_and(Value::is_string) => envelope,
+        _ => serde_json::from_str(&PluginError::internal(message).to_json())
+            .expect("an error envelope is JSON"),
+    }
+}
+
+/// Writes `e` as an `error` envelope in `codec`. Returns the export status:
+/// 0, or the failure status when the envelope cannot be written either.
+pub(crate) fn write_error(codec: Codec, e: extism_pdk::Error) -> i32 {
+    let failed = Failed {
+        error: error_envelope(&e),
+    };
+    let mut buf = Vec::new();
+    match codec.encode_into(&failed, &mut buf) {
+        Ok(()) if extism_pdk::output(buf).is_ok() => 0,
+        _ => crate::export::return_error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn plugin_errors_keep_their_envelope() {
+        let err: extism_pdk::Error = PluginError::config("Character set cannot be empty")
+            .with_detail("field", "search_characters")
+            .into();
+        assert_eq!(
+            error_envelope(&err),
+            json!({
+                "code": "CONFIG_ERROR",
+                "message": "Character set cannot be empty",
+                "details": {"field": "search_characters"},
+            })
+        );
+    }
+
+    #[test]
+    fn other_errors_are_internal() {
+        let err = extism_pdk::Error::msg("input unavailable");
+        let envelope = error_envelope(&err);
+        assert_eq!(envelope["code"], "INTERNAL");
+        assert_eq!(envelope["message"], "input unavailable");
+
+        let err = extism_pdk::Error::msg(r#"{"message": "not an envelope"}"#);
+        assert_eq!(error_envelope(&err)["code"], "INTERNAL");
+    }
+
+    #[test]
+    fn ok_wraps_the_output() {
+        let value = json!({"count": 3});
+        assert_eq!(
+            serde_json::to_value(Succeeded { ok: &value }).unwrap(),
+            json!({"ok": {"count": 3}})
+        );
+    }
+}
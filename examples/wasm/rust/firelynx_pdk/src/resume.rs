@@ -0,0 +1,141 @@
+//! Checkpoint and resume for work that does not fit in one call.
+//!
+//! A handler that takes a third parameter, the state an earlier call left
+//! off with, may stop partway and hand its progress to the host instead of
+//! a result:
+//!
+//! ```ignore
+//! #[derive(Default, Serialize, Deserialize)]
+//! struct Scan {
+//!     offset: usize,
+//!     rows: u64,
+//! }
+//!
+//! #[firelynx_plugin]
+//! fn count_rows(request: Request, config: Config, state: Option<Scan>) -> Result<Step<RowReport, Scan>> {
+//!     let mut scan = state.unwrap_or_default();
+//!     let text = request.body.text();
+//!     let rest = text.get(scan.offset..).ok_or_else(|| PluginError::invalid_input("Resume offset is out of range"))?;
+//!     for line in rest.split_inclusive('\n').take(config.batch_rows) {
+//!         scan.offset += line.len();
+//!         scan.rows += 1;
+//!     }
+//!     if scan.offset < text.len() {
+//!         return Ok(Step::Yield(scan));
+//!     }
+//!     Ok(Step::Done(RowReport { rows: scan.rows }))
+//! }
+//! ```
+//!
+//! `#[firelynx_plugin]` then generates two exports. `CountRows` calls the
+//! handler with `None`; `CountRowsResume` reads the state from the
+//! envelope's `resume` key and calls it with `Some(state)`. `Step::Done`
+//! writes the result as any handler's output, so a call that finishes at
+//! once looks exactly like a plain export. `Step::Yield` writes
+//! `{"_yield": {"state": ...}}`, and the host continues by calling the
+//! `Resume` export with the same envelope plus `"resume": <state>`, for as
+//! many calls as the work takes.
+//!
+//! The state travels through the host rather than living in the instance,
+//! so a resumed call may land on any instance, and nothing is left behind
+//! when the host gives up. It is also input like the request: check it
+//! before trusting offsets or counts in it, as above. Keep it small, since
+//! it is written and parsed once per call.
+
+use serde::de::DeserializeOwned;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::export::{self, IntoOutput};
+use crate::input::{Input, Request};
+use crate::PluginError;
+
+/// The output key marking a yield. Like `_meta`, it cannot clash with a
+/// plugin's own fields.
+pub const YIELD_KEY: &str = "_yield";
+
+/// What a resumable handler returns: its result, or the state to go on
+/// from in the next call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step<T, S> {
+    Done(T),
+    Yield(S),
+}
+
+impl<T: Serialize, S: Serialize> Serialize for Step<T, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        #[derive(Serialize)]
+        struct Yielded<'a, S> {
+            state: &'a S,
+        }
+        match self {
+            Step::Done(output) => output.serialize(serializer),
+            Step::Yield(state) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(YIELD_KEY, &Yielded { state })?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// Reads the state of a `Resume` call: `INVALID_INPUT` when the envelope
+/// has none or it does not deserialize into `S`.
+fn state<S: DeserializeOwned>(resume: Option<serde_json::Value>) -> Result<S, PluginError> {
+    let Some(state) = resume else {
+        return Err(PluginError::invalid_input(
+            "A Resume call needs the state of the yield it continues under resume",
+        ));
+    };
+    serde_json::from_value(state)
+        .map_err(|e| PluginError::invalid_input(format!("Invalid resume state: {}", e)))
+}
+
+/// Runs the `Resume` export of a resumable handler: like `export::run`,
+/// with the state from the envelope's `resume` key.
+#[doc(hidden)]
+pub fn run<C, S, T, E>(handler: impl FnOnce(Request, C, S) -> Result<T, E>) -> i32
+where
+    C: DeserializeOwned + Default,
+    S: DeserializeOwned,
+    T: IntoOutput,
+    E: Into<extism_pdk::Error>,
+{
+    export::run_input(|input: Input<C>| {
+        let state = state(input.resume)?;
+        handler(input.request, input.static_data.unwrap_or_default(), state).map_err(Into::into)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+    struct Scan {
+        offset: usize,
+    }
+
+    #[test]
+    fn done_is_the_plain_output_and_yield_is_marked() {
+        let done: Step<_, Scan> = Step::Done(json!({"rows": 2}));
+        assert_eq!(serde_json::to_value(&done).unwrap(), json!({"rows": 2}));
+        let yielded: Step<serde_json::Value, _> = Step::Yield(Scan { offset: 13 });
+        assert_eq!(
+            serde_json::to_value(&yielded).unwrap(),
+            json!({"_yield": {"state": {"offset": 13}}})
+        );
+    }
+
+    #[test]
+    fn state_is_required_and_checked() {
+        let scan: Scan = state(Some(json!({"offset": 13}))).unwrap();
+        assert_eq!(scan, Scan { offset: 13 });
+
+        let err = state::<Scan>(None).unwrap_err();
+        assert_eq!(err.code(), "INVALID_INPUT");
+        let err = state::<Scan>(Some(json!({"offset": -1}))).unwrap_err();
+        assert!(err.message().starts_with("Invalid resume state: "));
+    }
+}
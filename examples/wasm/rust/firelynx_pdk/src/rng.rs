@@ -0,0 +1,266 @@
+//! Random numbers that tests can reproduce.
+//!
+//! Plugins draw randomness through `rng::below()`, `rng::token()` and
+//! friends instead of pulling in their own RNG crate. In production the
+//! generator is keyed from the host's random source through `getrandom`
+//! (WASI `random_get` on `wasm32-wasip1`). A test host makes every call see the same
+//! sequence by setting the `rng_seed` extism config var, read once per
+//! plugin instance by the `#[firelynx_plugin]` shim, which then reseeds the
+//! generator at the start of each call:
+//!
+//! ```ignore
+//! let variant = if rng::below(100) < 10 { "b" } else { "a" };
+//! let csrf = rng::token(16);
+//! ```
+//!
+//! The generator is SHA-256 in counter mode, so tokens drawn from an
+//! unseeded instance are unpredictable. A seeded one is predictable by
+//! design: never set `rng_seed` in production. Native unit tests can install
+//! a generator for the current thread with `rng::set`.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::PluginConfig;
+use crate::schema::Field;
+use crate::PluginError;
+
+/// A deterministic stream of random bytes derived from a 256-bit key.
+#[derive(Clone)]
+pub struct Rng {
+    key: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    used: usize,
+}
+
+impl Rng {
+    /// A generator whose output depends only on `seed`.
+    pub fn from_seed(seed: u64) -> Rng {
+        let key = Sha256::new()
+            .chain_update(b"firelynx-rng")
+            .chain_update(seed.to_le_bytes())
+            .finalize();
+        Rng::from_key(key.into())
+    }
+
+    /// A generator keyed from the host's random source.
+    ///
+    /// # Panics
+    ///
+    /// If the host has no random source. Tokens drawn from a guessable key
+    /// would be worse than failing the call.
+    pub fn from_entropy() -> Rng {
+        let mut key = [0; 32];
+        getrandom::fill(&mut key).expect("host random source is unavailable");
+        Rng::from_key(key)
+    }
+
+    fn from_key(key: [u8; 32]) -> Rng {
+        Rng {
+            key,
+            counter: 0,
+            block: [0; 32],
+            used: 32,
+        }
+    }
+
+    pub fn fill(&mut self, out: &mut [u8]) {
+        let mut written = 0;
+        while written < out.len() {
+            if self.used == self.block.len() {
+                self.refill();
+            }
+            let n = (out.len() - written).min(self.block.len() - self.used);
+            out[written..written + n].copy_from_slice(&self.block[self.used..self.used + n]);
+            self.used += n;
+            written += n;
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// A uniformly distributed value in `0..n`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "Rng::below called with 0");
+        // Reject the top partial range so every residue is equally likely.
+        let zone = u64::MAX - (u64::MAX - n + 1) % n;
+        loop {
+            let x = self.next_u64();
+            if x <= zone {
+                return x % n;
+            }
+        }
+    }
+
+    /// `bytes` random bytes as unpadded base64url, for IDs and CSRF tokens.
+    pub fn token(&mut self, bytes: usize) -> String {
+        let mut buf = vec![0; bytes];
+        self.fill(&mut buf);
+        URL_SAFE_NO_PAD.encode(buf)
+    }
+
+    fn refill(&mut self) {
+        self.block = Sha256::new()
+            .chain_update(self.key)
+            .chain_update(self.counter.to_le_bytes())
+            .finalize()
+            .into();
+        self.counter += 1;
+        self.used = 0;
+    }
+}
+
+impl std::fmt::Debug for Rng {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The key is the generator's whole secret.
+        f.debug_struct("Rng").finish_non_exhaustive()
+    }
+}
+
+#[derive(Deserialize)]
+struct RngConfig {
+    rng_seed: Option<i64>,
+}
+
+impl PluginConfig for RngConfig {
+    const FIELDS: &'static [Field] = &[Field::integer("rng_seed")];
+}
+
+/// The instance's seed from the host's config; `None` until the shim has
+/// read it or when the config does not set one.
+static SEED: OnceLock<Option<u64>> = OnceLock::new();
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rng>> = const { RefCell::new(None) };
+}
+
+/// Reads `rng_seed` from the host's config on the first call, and reseeds
+/// the generator on every call when it is set.
+pub(crate) fn init() -> Result<(), PluginError> {
+    if SEED.get().is_none() {
+        let config = RngConfig::load()?;
+        // The seed is an opaque bit pattern, so negative values are fine.
+        let _ = SEED.set(config.rng_seed.map(|seed| seed as u64));
+    }
+    if let Some(Some(seed)) = SEED.get() {
+        set(Rng::from_seed(*seed));
+    }
+    Ok(())
+}
+
+/// Makes `rng` the generator for this thread. The shim replaces it at the
+/// start of the next call when `rng_seed` is configured.
+pub fn set(rng: Rng) {
+    CURRENT.with(|c| *c.borrow_mut() = Some(rng));
+}
+
+/// Runs `f` with this thread's generator, keying it from the host's random
+/// source on first use.
+pub fn with<R>(f: impl FnOnce(&mut Rng) -> R) -> R {
+    CURRENT.with(|c| f(c.borrow_mut().get_or_insert_with(Rng::from_entropy)))
+}
+
+pub fn fill(out: &mut [u8]) {
+    with(|rng| rng.fill(out))
+}
+
+pub fn next_u64() -> u64 {
+    with(Rng::next_u64)
+}
+
+/// See `Rng::below`.
+pub fn below(n: u64) -> u64 {
+    with(|rng| rng.below(n))
+}
+
+/// See `Rng::token`.
+pub fn token(bytes: usize) -> String {
+    with(|rng| rng.token(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_the_same_sequence() {
+        let mut a = Rng::from_seed(7);
+        let mut b = Rng::from_seed(7);
+        let a: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        let b: Vec<u64> = (0..10).map(|_| b.next_u64()).collect();
+        assert_eq!(a, b);
+        assert_ne!(Rng::from_seed(8).next_u64(), a[0]);
+        assert_ne!(
+            Rng::from_entropy().next_u64(),
+            Rng::from_entropy().next_u64()
+        );
+    }
+
+    #[test]
+    fn byte_stream_does_not_depend_on_read_sizes() {
+        let mut whole = [0u8; 80];
+        Rng::from_seed(1).fill(&mut whole);
+        let mut rng = Rng::from_seed(1);
+        let mut pieces = [0u8; 80];
+        for chunk in pieces.chunks_mut(7) {
+            rng.fill(chunk);
+        }
+        assert_eq!(whole, pieces);
+    }
+
+    #[test]
+    fn below_stays_in_range_and_covers_it() {
+        let mut rng = Rng::from_seed(3);
+        let mut seen = [false; 10];
+        for _ in 0..1000 {
+            let x = rng.below(10);
+            seen[x as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+        assert_eq!(rng.below(1), 0);
+        rng.below(u64::MAX);
+    }
+
+    #[test]
+    fn tokens_are_base64url() {
+        let token = Rng::from_seed(5).token(16);
+        assert_eq!(token.len(), 22);
+        assert!(token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+    }
+
+    #[test]
+    fn installed_generator_is_used_by_the_module_functions() {
+        set(Rng::from_seed(9));
+        let first = next_u64();
+        set(Rng::from_seed(9));
+        assert_eq!(next_u64(), first);
+    }
+
+    #[test]
+    fn reads_the_seed_from_config_vars() {
+        let config = RngConfig::from_vars(|key| (key == "rng_seed").then(|| "-1".into())).unwrap();
+        assert_eq!(config.rng_seed, Some(-1));
+        let config = RngConfig::from_vars(|_| None).unwrap();
+        assert_eq!(config.rng_seed, None);
+        let err = RngConfig::from_vars(|_| Some("lucky".into()))
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+    }
+}
@@ -0,0 +1,490 @@
+//! Declarative `static_data` validation.
+//!
+//! A plugin lists the keys it understands once, with their types and
+//! defaults, and gets a typed config struct plus actionable errors
+//! ("search_characters must be a non-empty string") instead of hand-rolled
+//! checks:
+//!
+//! ```ignore
+//! #[derive(serde::Deserialize)]
+//! struct Config {
+//!     search_characters: String,
+//!     case_sensitive: bool,
+//! }
+//!
+//! impl ConfigSchema for Config {
+//!     const FIELDS: &'static [Field] = &[
+//!         Field::string("search_characters")
+//!             .non_empty()
+//!             .default(DefaultValue::Str("aeiouAEIOU")),
+//!         Field::bool("case_sensitive").default(DefaultValue::Bool(false)),
+//!     ];
+//! }
+//!
+//! let config = Config::from_static_data(&static_data)?;
+//! ```
+//!
+//! A route that sends no `static_data` at all gets the defaults too. A
+//! plugin that cannot do anything sensible unconfigured sets
+//! `WHEN_ABSENT = WhenAbsent::Error`, so such a route fails with a
+//! `CONFIG_ERROR` instead of quietly running on defaults; `static_data = {}`
+//! still opts in to every default.
+//!
+//! `lint` checks the same table without stopping at the first problem and
+//! adds warnings for unused and deprecated keys; see `crate::lint`.
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::lint::{Lint, LintKind, LintReport};
+use crate::static_data::StaticData;
+use crate::PluginError;
+
+/// The JSON type a `static_data` key must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    Bool,
+    Integer,
+    Number,
+    StringList,
+    Table,
+}
+
+impl FieldType {
+    fn describe(self, non_empty: bool) -> &'static str {
+        match (self, non_empty) {
+            (FieldType::String, false) => "a string",
+            (FieldType::String, true) => "a non-empty string",
+            (FieldType::Bool, _) => "a boolean",
+            (FieldType::Integer, _) => "an integer",
+            (FieldType::Number, _) => "a number",
+            (FieldType::StringList, false) => "an array of strings",
+            (FieldType::StringList, true) => "a non-empty array of strings",
+            (FieldType::Table, _) => "a table",
+        }
+    }
+
+    fn matches(self, value: &Value, non_empty: bool) -> bool {
+        match self {
+            FieldType::String => value.as_str().is_some_and(|s| !non_empty || !s.is_empty()),
+            FieldType::Bool => value.is_boolean(),
+            FieldType::Integer => value.is_i64() || value.is_u64(),
+            FieldType::Number => value.is_number(),
+            FieldType::StringList => value.as_array().is_some_and(|items| {
+                (!non_empty || !items.is_empty()) && items.iter().all(Value::is_string)
+            }),
+            FieldType::Table => value.is_object(),
+        }
+    }
+}
+
+/// A default applied when a key is absent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultValue {
+    Str(&'static str),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+}
+
+impl DefaultValue {
+    fn to_value(self) -> Value {
+        match self {
+            DefaultValue::Str(s) => s.into(),
+            DefaultValue::Bool(b) => b.into(),
+            DefaultValue::Int(i) => i.into(),
+            DefaultValue::Float(f) => f.into(),
+        }
+    }
+}
+
+/// One declared `static_data` key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub ty: FieldType,
+    pub required: bool,
+    pub non_empty: bool,
+    pub default: Option<DefaultValue>,
+    /// Set by `deprecated`: what to use instead, reported by `lint`.
+    pub deprecated: Option<&'static str>,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, ty: FieldType) -> Field {
+        Field {
+            name,
+            ty,
+            required: false,
+            non_empty: false,
+            default: None,
+            deprecated: None,
+        }
+    }
+
+    pub const fn string(name: &'static str) -> Field {
+        Field::new(name, FieldType::String)
+    }
+
+    pub const fn bool(name: &'static str) -> Field {
+        Field::new(name, FieldType::Bool)
+    }
+
+    pub const fn integer(name: &'static str) -> Field {
+        Field::new(name, FieldType::Integer)
+    }
+
+    pub const fn number(name: &'static str) -> Field {
+        Field::new(name, FieldType::Number)
+    }
+
+    pub const fn string_list(name: &'static str) -> Field {
+        Field::new(name, FieldType::StringList)
+    }
+
+    pub const fn table(name: &'static str) -> Field {
+        Field::new(name, FieldType::Table)
+    }
+
+    /// The key must be present (and has no default).
+    pub const fn required(mut self) -> Field {
+        self.required = true;
+        self
+    }
+
+    /// Strings and lists must not be empty.
+    pub const fn non_empty(mut self) -> Field {
+        self.non_empty = true;
+        self
+    }
+
+    pub const fn default(mut self, value: DefaultValue) -> Field {
+        self.default = Some(value);
+        self
+    }
+
+    /// The key still works but `lint` warns about it, with `advice` such as
+    /// "use search_pattern instead".
+    pub const fn deprecated(mut self, advice: &'static str) -> Field {
+        self.deprecated = Some(advice);
+        self
+    }
+
+    fn check(&self, value: &Value) -> Option<String> {
+        if self.ty.matches(value, self.non_empty) {
+            None
+        } else {
+            Some(format!(
+                "{} must be {}",
+                self.name,
+                self.ty.describe(self.non_empty)
+            ))
+        }
+    }
+}
+
+/// What `from_static_data` does when the envelope has no `static_data`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenAbsent {
+    /// Treat it as empty: every key takes its default.
+    UseDefaults,
+    /// Fail with a `CONFIG_ERROR` for `field = "static_data"`.
+    Error,
+}
+
+/// Implemented by a plugin's typed configuration to declare its
+/// `static_data` keys.
+pub trait ConfigSchema: DeserializeOwned {
+    const FIELDS: &'static [Field];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::UseDefaults;
+
+    /// Keys the plugin does not read but expects to see, e.g. descriptive
+    /// keys shared by every route of an app; `lint` does not warn about them.
+    const IGNORED_KEYS: &'static [&'static str] = &[];
+
+    /// Validates `static_data` against `FIELDS`, fills in defaults and
+    /// deserializes the result. Every problem found is reported in a single
+    /// `CONFIG_ERROR` whose `details.problems` lists them all. Keys that are
+    /// not declared are passed through untouched.
+    fn from_static_data(static_data: &StaticData) -> Result<Self, PluginError> {
+        if !static_data.is_present() && Self::WHEN_ABSENT == WhenAbsent::Error {
+            return Err(
+                PluginError::config("static_data is required for this plugin")
+                    .with_detail("field", "static_data"),
+            );
+        }
+        let values = validate(Self::FIELDS, static_data.as_map().clone())?;
+        serde_json::from_value(Value::Object(values))
+            .map_err(|e| PluginError::config(format!("Invalid static_data: {}", e)))
+    }
+
+    /// Plugin-specific checks on a config that parsed, run by `lint`: report
+    /// values the handler would reject with `Lint::reject`, and accepted but
+    /// doubtful ones with `Lint::warn`. `static_data` tells explicit keys
+    /// from defaults.
+    fn check(&self, _static_data: &StaticData, _lint: &mut Lint) {}
+
+    /// Reports every problem with `static_data` instead of stopping at the
+    /// first: field errors, then unused and deprecated keys, then `check`
+    /// when the config parses.
+    fn lint(static_data: &StaticData) -> LintReport {
+        let mut lint = Lint::new();
+        if !static_data.is_present() && Self::WHEN_ABSENT == WhenAbsent::Error {
+            lint.error("static_data", "static_data is required for this plugin");
+            return lint.finish();
+        }
+
+        let mut values = static_data.as_map().clone();
+        for (field, problem) in check_fields(Self::FIELDS, &mut values) {
+            lint.error(field, problem);
+        }
+        for key in static_data.keys() {
+            match Self::FIELDS.iter().find(|f| f.name == key) {
+                Some(Field {
+                    deprecated: Some(advice),
+                    ..
+                }) => lint.warn(
+                    key,
+                    LintKind::Deprecated,
+                    format!("{} is deprecated; {}", key, advice),
+                ),
+                Some(_) => {}
+                None if Self::IGNORED_KEYS.contains(&key) => {}
+                None => lint.warn(
+                    key,
+                    LintKind::UnusedKey,
+                    format!("{} is not a setting of this plugin", key),
+                ),
+            }
+        }
+
+        if !lint.has_errors() {
+            match serde_json::from_value::<Self>(Value::Object(values)) {
+                Ok(config) => config.check(static_data, &mut lint),
+                Err(e) => lint.error("static_data", format!("Invalid static_data: {}", e)),
+            }
+        }
+        lint.finish()
+    }
+}
+
+/// Checks `values` against `fields` and fills in defaults, collecting every
+/// problem into one `CONFIG_ERROR`.
+pub(crate) fn validate(
+    fields: &[Field],
+    mut values: Map<String, Value>,
+) -> Result<Map<String, Value>, PluginError> {
+    let found = check_fields(fields, &mut values);
+    match found.first() {
+        Some((field, _)) => {
+            let problems: Vec<String> = found.iter().map(|(_, p)| p.clone()).collect();
+            Err(PluginError::config(problems.join("; "))
+                .with_detail("field", *field)
+                .with_detail("problems", problems))
+        }
+        None => Ok(values),
+    }
+}
+
+/// Fills in defaults and returns each field's problem, in `fields` order.
+fn check_fields(fields: &[Field], values: &mut Map<String, Value>) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    for field in fields {
+        let problem = match values.get(field.name) {
+            None | Some(Value::Null) => match field.default {
+                Some(default) => {
+                    values.insert(field.name.to_string(), default.to_value());
+                    None
+                }
+                None if field.required => Some(format!("{} is required", field.name)),
+                None => None,
+            },
+            Some(value) => field.check(value),
+        };
+        if let Some(problem) = problem {
+            problems.push((field.name, problem));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Config {
+        search_characters: String,
+        case_sensitive: bool,
+        limit: Option<u32>,
+        name: String,
+    }
+
+    impl ConfigSchema for Config {
+        const FIELDS: &'static [Field] = &[
+            Field::string("search_characters")
+                .non_empty()
+                .default(DefaultValue::Str("aeiou")),
+            Field::bool("case_sensitive").default(DefaultValue::Bool(false)),
+            Field::integer("limit"),
+            Field::string("name").required(),
+        ];
+    }
+
+    fn static_data(v: Value) -> StaticData {
+        serde_json::from_value(v).unwrap()
+    }
+
+    #[test]
+    fn applies_defaults() {
+        let config =
+            Config::from_static_data(&static_data(serde_json::json!({"name": "x"}))).unwrap();
+        assert_eq!(config.search_characters, "aeiou");
+        assert!(!config.case_sensitive);
+        assert_eq!(config.limit, None);
+        assert_eq!(config.name, "x");
+    }
+
+    #[test]
+    fn explicit_values_win() {
+        let sd = static_data(serde_json::json!({
+            "search_characters": "xyz",
+            "case_sensitive": true,
+            "limit": 3,
+            "name": "x",
+            "undeclared": [1, 2],
+        }));
+        let config = Config::from_static_data(&sd).unwrap();
+        assert_eq!(config.search_characters, "xyz");
+        assert!(config.case_sensitive);
+        assert_eq!(config.limit, Some(3));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let sd = static_data(serde_json::json!({
+            "search_characters": "",
+            "case_sensitive": "yes",
+        }));
+        let err = Config::from_static_data(&sd).unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+        assert_eq!(err.details()["field"], "search_characters");
+        assert_eq!(
+            err.details()["problems"],
+            serde_json::json!([
+                "search_characters must be a non-empty string",
+                "case_sensitive must be a boolean",
+                "name is required",
+            ])
+        );
+        assert!(err
+            .message()
+            .starts_with("search_characters must be a non-empty string; "));
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Strict {
+        mode: String,
+    }
+
+    impl ConfigSchema for Strict {
+        const FIELDS: &'static [Field] = &[Field::string("mode").default(DefaultValue::Str("a"))];
+        const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+    }
+
+    #[test]
+    fn absent_static_data_follows_when_absent() {
+        let absent = StaticData::default();
+        let err = Strict::from_static_data(&absent).unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+        assert_eq!(err.details()["field"], "static_data");
+
+        let empty = static_data(serde_json::json!({}));
+        assert!(empty.is_present());
+        assert_eq!(Strict::from_static_data(&empty).unwrap().mode, "a");
+
+        // The default policy validates absent static_data like `{}`.
+        let err = Config::from_static_data(&absent).unwrap_err();
+        assert_eq!(err.message(), "name is required");
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Linted {
+        mode: String,
+    }
+
+    impl ConfigSchema for Linted {
+        const FIELDS: &'static [Field] = &[
+            Field::string("mode").default(DefaultValue::Str("a")),
+            Field::bool("legacy").deprecated("use mode instead"),
+        ];
+        const IGNORED_KEYS: &'static [&'static str] = &["service_name"];
+
+        fn check(&self, _static_data: &StaticData, lint: &mut Lint) {
+            match self.mode.as_str() {
+                "a" => {}
+                "b" => lint.warn("mode", LintKind::Suspicious, "mode b is slow"),
+                other => lint.reject(
+                    &PluginError::config(format!("unknown mode {}", other))
+                        .with_detail("field", "mode"),
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn lint_reports_errors_and_warnings() {
+        let report = Linted::lint(&static_data(serde_json::json!({
+            "mode": "b",
+            "legacy": true,
+            "service_name": "x",
+            "typo": 1,
+        })));
+        assert!(report.valid);
+        let warnings: Vec<_> = report
+            .warnings
+            .iter()
+            .map(|w| (w.field.as_str(), w.kind))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                ("legacy", LintKind::Deprecated),
+                ("typo", LintKind::UnusedKey),
+                ("mode", LintKind::Suspicious),
+            ]
+        );
+        assert_eq!(
+            report.warnings[0].message,
+            "legacy is deprecated; use mode instead"
+        );
+
+        let report = Linted::lint(&static_data(serde_json::json!({"mode": "c"})));
+        assert!(!report.valid);
+        assert_eq!(report.errors[0].field, "mode");
+        assert_eq!(report.errors[0].message, "unknown mode c");
+    }
+
+    #[test]
+    fn lint_skips_check_when_fields_fail() {
+        let report = Config::lint(&static_data(serde_json::json!({
+            "search_characters": "",
+            "case_sensitive": "yes",
+        })));
+        let errors: Vec<_> = report.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(errors, ["search_characters", "case_sensitive", "name"]);
+
+        let report = Strict::lint(&StaticData::default());
+        assert_eq!(report.errors[0].field, "static_data");
+        assert!(Linted::lint(&StaticData::default()).valid);
+    }
+
+    #[test]
+    fn single_problem_message_is_the_problem() {
+        let sd = static_data(serde_json::json!({"name": "x", "limit": 1.5}));
+        let err = Config::from_static_data(&sd).unwrap_err();
+        assert_eq!(err.message(), "limit must be an integer");
+    }
+}
@@ -0,0 +1,311 @@
+//! HMAC-signed, stateless cookie sessions.
+//!
+//! The session lives entirely in the cookie as
+//! `base64url(json payload) "." base64url(HMAC-SHA256(payload))`, so no
+//! server-side storage is needed. Sessions carry both an idle and an absolute
+//! expiry; `issue` refreshes the idle window and should be called on every
+//! response that saw a valid session. Signing keys rotate by listing the new
+//! secret first: the first secret signs, all of them verify.
+//!
+//! Payloads are signed, not encrypted. Do not store secrets in `data`.
+//!
+//! There are no server-side sessions yet. Keeping sessions in KV, with
+//! rotation and idle/absolute expiry, is unfinished: it waits for the
+//! firelynx host to provide KV functions.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+use crate::cookie::{SameSite, SetCookie};
+use crate::input::Request;
+use crate::limits::ParseLimits;
+use crate::PluginError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Secrets shorter than this are rejected.
+pub const MIN_SECRET_LEN: usize = 32;
+
+/// Session settings, typically read from `static_data`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionConfig {
+    /// Signing secrets, newest first.
+    pub secrets: Vec<String>,
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    /// Seconds of inactivity after which a session expires.
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout: u64,
+    /// Seconds after issuance after which a session expires regardless of use.
+    #[serde(default = "default_absolute_timeout")]
+    pub absolute_timeout: u64,
+    #[serde(default)]
+    pub same_site: SameSite,
+    /// Only disable for plain-HTTP local development.
+    #[serde(default = "default_true")]
+    pub secure: bool,
+}
+
+fn default_cookie_name() -> String {
+    "firelynx_session".to_string()
+}
+
+fn default_idle_timeout() -> u64 {
+    30 * 60
+}
+
+fn default_absolute_timeout() -> u64 {
+    12 * 60 * 60
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Session state carried in the cookie.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub data: Map<String, Value>,
+    /// Unix seconds when the session was first issued.
+    #[serde(rename = "iat")]
+    pub issued_at: u64,
+    /// Unix seconds of the last request that refreshed the session.
+    #[serde(rename = "lat")]
+    pub last_seen: u64,
+}
+
+impl Session {
+    /// Starts a new session at `now`.
+    pub fn new(now: u64) -> Session {
+        Session {
+            data: Map::new(),
+            issued_at: now,
+            last_seen: now,
+        }
+    }
+}
+
+/// Loads and issues signed session cookies.
+#[derive(Debug, Clone)]
+pub struct SignedSessions {
+    config: SessionConfig,
+}
+
+impl SignedSessions {
+    pub fn new(config: SessionConfig) -> Result<SignedSessions, PluginError> {
+        if config.secrets.is_empty() {
+            return Err(
+                PluginError::config("At least one session secret is required")
+                    .with_detail("field", "secrets"),
+            );
+        }
+        if config.secrets.iter().any(|s| s.len() < MIN_SECRET_LEN) {
+            return Err(PluginError::config(format!(
+                "Session secrets must be at least {} bytes",
+                MIN_SECRET_LEN
+            ))
+            .with_detail("field", "secrets"));
+        }
+        Ok(SignedSessions { config })
+    }
+
+    /// Returns the request's session, or `None` when there is no cookie or it
+    /// is forged, malformed, or expired. Rejections are deliberately not
+    /// distinguished: the caller's only sensible reaction is a fresh login.
+    pub fn load(&self, request: &Request, now: u64) -> Option<Session> {
+        let cookie = request.cookie(&self.config.cookie_name)?;
+        let session = self.decode(cookie)?;
+        let idle_ok = now.saturating_sub(session.last_seen) < self.config.idle_timeout;
+        let absolute_ok = now.saturating_sub(session.issued_at) < self.config.absolute_timeout;
+        (idle_ok && absolute_ok).then_some(session)
+    }
+
+    /// Signs `session` with `last_seen` set to `now` and returns the
+    /// `Set-Cookie` header value.
+    pub fn issue(&self, session: &Session, now: u64) -> Result<String, PluginError> {
+        let session = Session {
+            last_seen: now,
+            ..session.clone()
+        };
+        let remaining = self
+            .config
+            .absolute_timeout
+            .saturating_sub(now.saturating_sub(session.issued_at));
+        let cookie = SetCookie {
+            max_age: Some(remaining.min(self.config.idle_timeout)),
+            secure: self.config.secure,
+            same_site: self.config.same_site,
+            ..SetCookie::new(&self.config.cookie_name, self.encode(&session)?)
+        };
+        Ok(cookie.to_string())
+    }
+
+    /// The `Set-Cookie` header value that ends the session (logout).
+    pub fn clear(&self) -> String {
+        SetCookie {
+            secure: self.config.secure,
+            same_site: self.config.same_site,
+            ..SetCookie::removal(&self.config.cookie_name)
+        }
+        .to_string()
+    }
+
+    fn encode(&self, session: &Session) -> Result<String, PluginError> {
+        let payload = serde_json::to_vec(session)
+            .map_err(|e| PluginError::internal(format!("Failed to encode session: {}", e)))?;
+        let payload = URL_SAFE_NO_PAD.encode(payload);
+        let signature = mac(&self.config.secrets[0], payload.as_bytes()).finalize();
+        Ok(format!(
+            "{}.{}",
+            payload,
+            URL_SAFE_NO_PAD.encode(signature.into_bytes())
+        ))
+    }
+
+    fn decode(&self, cookie: &str) -> Option<Session> {
+        let (payload, signature) = cookie.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let verified = self.config.secrets.iter().any(|secret| {
+            mac(secret, payload.as_bytes())
+                .verify_slice(&signature)
+                .is_ok()
+        });
+        if !verified {
+            return None;
+        }
+        let json = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        ParseLimits::active().check_json(&json).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+fn mac(secret: &str, payload: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+    const OLD_SECRET: &str = "fedcba9876543210fedcba9876543210";
+
+    fn sessions(secrets: &[&str]) -> SignedSessions {
+        SignedSessions::new(
+            serde_json::from_value(serde_json::json!({
+                "secrets": secrets,
+                "idle_timeout": 100,
+                "absolute_timeout": 1000,
+            }))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// Builds a request carrying the cookie from a `Set-Cookie` value.
+    fn request_with(set_cookie: &str) -> Request {
+        let pair = set_cookie.split(';').next().unwrap();
+        let mut req = Request::default();
+        req.headers
+            .insert("Cookie".to_string(), vec![pair.to_string()]);
+        req
+    }
+
+    fn logged_in(now: u64) -> Session {
+        let mut session = Session::new(now);
+        session.data.insert("user".to_string(), "alice".into());
+        session
+    }
+
+    #[test]
+    fn round_trips_and_refreshes_idle_window() {
+        let store = sessions(&[SECRET]);
+        let header = store.issue(&logged_in(1000), 1000).unwrap();
+        assert!(header.starts_with("firelynx_session="));
+        assert!(header.contains("Max-Age=100; HttpOnly; Secure; SameSite=Lax"));
+
+        let session = store.load(&request_with(&header), 1050).unwrap();
+        assert_eq!(session.data["user"], "alice");
+        assert_eq!(session.last_seen, 1000);
+
+        let refreshed = store.issue(&session, 1050).unwrap();
+        let session = store.load(&request_with(&refreshed), 1140).unwrap();
+        assert_eq!(session.last_seen, 1050);
+        assert_eq!(session.issued_at, 1000);
+    }
+
+    #[test]
+    fn expires_on_idle_and_absolute_timeouts() {
+        let store = sessions(&[SECRET]);
+        let header = store.issue(&logged_in(1000), 1000).unwrap();
+        assert!(store.load(&request_with(&header), 1100).is_none());
+
+        // Kept alive past the absolute limit by regular refreshes.
+        let header = store.issue(&logged_in(1000), 1950).unwrap();
+        assert!(header.contains("Max-Age=50;"));
+        assert!(store.load(&request_with(&header), 1990).is_some());
+        assert!(store.load(&request_with(&header), 2000).is_none());
+    }
+
+    #[test]
+    fn rejects_tampered_cookies() {
+        let store = sessions(&[SECRET]);
+        let header = store.issue(&logged_in(1000), 1000).unwrap();
+        let value = header.split(';').next().unwrap();
+        let (payload, signature) = value.split_once('.').unwrap();
+
+        let forged = URL_SAFE_NO_PAD.encode(
+            serde_json::to_vec(
+                &serde_json::json!({"data": {"user": "mallory"}, "iat": 1000, "lat": 1000}),
+            )
+            .unwrap(),
+        );
+        let forged = format!("firelynx_session={}.{}", forged, signature);
+        assert!(store.load(&request_with(&forged), 1001).is_none());
+        assert!(store.load(&request_with(payload), 1001).is_none());
+        assert!(sessions(&[OLD_SECRET])
+            .load(&request_with(&header), 1001)
+            .is_none());
+    }
+
+    #[test]
+    fn verifies_with_rotated_secrets() {
+        let old = sessions(&[OLD_SECRET]);
+        let header = old.issue(&logged_in(1000), 1000).unwrap();
+
+        let rotated = sessions(&[SECRET, OLD_SECRET]);
+        let session = rotated.load(&request_with(&header), 1001).unwrap();
+        let reissued = rotated.issue(&session, 1001).unwrap();
+        assert!(sessions(&[SECRET])
+            .load(&request_with(&reissued), 1002)
+            .is_some());
+    }
+
+    #[test]
+    fn rejects_weak_configuration() {
+        let config = |secrets: Vec<&str>| SessionConfig {
+            secrets: secrets.into_iter().map(String::from).collect(),
+            cookie_name: default_cookie_name(),
+            idle_timeout: 1,
+            absolute_timeout: 1,
+            same_site: SameSite::Lax,
+            secure: true,
+        };
+        assert!(SignedSessions::new(config(vec![])).is_err());
+        assert!(SignedSessions::new(config(vec![SECRET, "short"])).is_err());
+    }
+
+    #[test]
+    fn clear_expires_the_cookie() {
+        assert!(sessions(&[SECRET])
+            .clear()
+            .starts_with("firelynx_session=; Path=/; Max-Age=0;"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Typed accessors over a route's `static_data` table.
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::PluginError;
+
+/// The `static_data` object from the input envelope.
+///
+/// Accessors return `Ok(None)` for absent keys and a `CONFIG_ERROR` naming the
+/// key when a value has the wrong type, so plugins can apply their own
+/// defaults without hand-matching on `serde_json::Value`.
+///
+/// An envelope without `static_data` (or with `null`) gives the `Default`
+/// value, which is empty and not `is_present()`; `{}` is present but empty.
+/// The host omits `static_data` for routes that configure none, so the
+/// difference tells an unconfigured route from one that chose every default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(from = "Map<String, Value>")]
+pub struct StaticData {
+    values: Map<String, Value>,
+    present: bool,
+}
+
+impl From<Map<String, Value>> for StaticData {
+    fn from(values: Map<String, Value>) -> Self {
+        StaticData::new(values)
+    }
+}
+
+impl StaticData {
+    /// Present `static_data` with these values.
+    pub fn new(values: Map<String, Value>) -> Self {
+        StaticData {
+            values,
+            present: true,
+        }
+    }
+
+    /// Whether the envelope had a `static_data` object at all, even an empty
+    /// one.
+    pub fn is_present(&self) -> bool {
+        self.present
+    }
+
+    pub fn as_map(&self) -> &Map<String, Value> {
+        &self.values
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<&str>, PluginError> {
+        self.typed(key, "a string", Value::as_str)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, PluginError> {
+        self.typed(key, "a boolean", Value::as_bool)
+    }
+
+    pub fn get_i64(&self, key: &str) -> Result<Option<i64>, PluginError> {
+        self.typed(key, "an integer", Value::as_i64)
+    }
+
+    pub fn get_u64(&self, key: &str) -> Result<Option<u64>, PluginError> {
+        self.typed(key, "a non-negative integer", Value::as_u64)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Result<Option<f64>, PluginError> {
+        self.typed(key, "a number", Value::as_f64)
+    }
+
+    /// Reads an array of strings.
+    pub fn get_str_list(&self, key: &str) -> Result<Option<Vec<&str>>, PluginError> {
+        self.typed(key, "an array of strings", |v| {
+            v.as_array()?.iter().map(Value::as_str).collect()
+        })
+    }
+
+    /// Deserializes a value into any serde type, e.g. a nested table.
+    pub fn get_as<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, PluginError> {
+        match self.values.get(key) {
+            None => Ok(None),
+            Some(v) => serde_json::from_value(v.clone()).map(Some).map_err(|e| {
+                PluginError::config(format!("{} is invalid: {}", key, e)).with_detail("field", key)
+            }),
+        }
+    }
+
+    fn typed<'a, T>(
+        &'a self,
+        key: &str,
+        expected: &str,
+        convert: impl FnOnce(&'a Value) -> Option<T>,
+    ) -> Result<Option<T>, PluginError> {
+        match self.values.get(key) {
+            None => Ok(None),
+            Some(v) => convert(v).map(Some).ok_or_else(|| {
+                PluginError::config(format!("{} must be {}", key, expected))
+                    .with_detail("field", key)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> StaticData {
+        serde_json::from_value(serde_json::json!({
+            "search_characters": "aeiou",
+            "case_sensitive": true,
+            "limit": 10,
+            "ratio": 0.5,
+            "tags": ["a", "b"],
+            "nested": {"hosts": ["example.com"]},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn typed_accessors() {
+        let sd = data();
+        assert_eq!(sd.get_str("search_characters").unwrap(), Some("aeiou"));
+        assert_eq!(sd.get_bool("case_sensitive").unwrap(), Some(true));
+        assert_eq!(sd.get_i64("limit").unwrap(), Some(10));
+        assert_eq!(sd.get_u64("limit").unwrap(), Some(10));
+        assert_eq!(sd.get_f64("ratio").unwrap(), Some(0.5));
+        assert_eq!(sd.get_str_list("tags").unwrap(), Some(vec!["a", "b"]));
+
+        #[derive(Deserialize)]
+        struct Nested {
+            hosts: Vec<String>,
+        }
+        let nested: Nested = sd.get_as("nested").unwrap().unwrap();
+        assert_eq!(nested.hosts, ["example.com"]);
+    }
+
+    #[test]
+    fn absent_and_empty_are_distinct() {
+        assert!(!StaticData::default().is_present());
+        let empty: StaticData = serde_json::from_str("{}").unwrap();
+        assert!(empty.is_present() && empty.is_empty());
+        assert_ne!(empty, StaticData::default());
+    }
+
+    #[test]
+    fn absent_keys_are_none() {
+        let sd = StaticData::default();
+        assert_eq!(sd.get_str("missing").unwrap(), None);
+        assert_eq!(sd.get_as::<Vec<String>>("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn wrong_types_are_config_errors() {
+        let sd = data();
+        let err = sd.get_bool("search_characters").unwrap_err();
+        assert_eq!(err.code(), "CONFIG_ERROR");
+        assert_eq!(err.message(), "search_characters must be a boolean");
+        assert_eq!(err.details()["field"], "search_characters");
+
+        assert!(sd.get_u64("ratio").is_err());
+        assert!(sd.get_as::<u32>("tags").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Chunked plugin output.
+//!
+//! extism hands the host a single output block, so a response cannot leave
+//! the plugin before the export returns. What can be avoided is holding the
+//! whole response in the plugin's own linear memory: `OutputStream` copies
+//! each emitted chunk into extism (kernel) memory straight away, so a plugin
+//! generating a large report only ever holds the chunk it is working on.
+//!
+//! ```ignore
+//! #[firelynx_plugin]
+//! fn export_csv(request: Request) -> Result<OutputStream> {
+//!     let mut out = OutputStream::new();
+//!     for row in rows() {
+//!         out.emit(row.to_csv_line())?;
+//!     }
+//!     Ok(out)
+//! }
+//! ```
+//!
+//! Returning an `OutputStream` from a `#[firelynx_plugin]` handler writes the
+//! emitted bytes as-is instead of JSON-encoding them. A handler that picks
+//! per call, such as one negotiating a format from the `Accept` header,
+//! returns `Output`: `Output::Encoded` is written like any serializable
+//! result, in the envelope codec, and `Output::Raw` like an `OutputStream`.
+
+use extism_pdk::Memory;
+use serde::Serialize;
+
+use crate::codec::Codec;
+use crate::export::IntoOutput;
+use crate::PluginError;
+
+/// A handler result that is either encoded or written as-is.
+pub enum Output<T> {
+    Encoded(T),
+    Raw(OutputStream),
+}
+
+impl<T: Serialize> IntoOutput for Output<T> {
+    fn write_output(self, codec: Codec) -> Result<(), extism_pdk::Error> {
+        match self {
+            Output::Encoded(value) => value.write_output(codec),
+            Output::Raw(stream) => stream.write_output(codec),
+        }
+    }
+}
+
+/// Bytes copied through plugin memory at a time when joining chunks.
+const COPY_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Output assembled from chunks held in extism memory.
+pub struct OutputStream {
+    target: Target,
+    len: usize,
+}
+
+enum Target {
+    /// Chunks kept as separate blocks and joined by `finish`.
+    Chunks(Vec<Memory>),
+    /// A single block sized up front; chunks are written in place.
+    Preallocated(Memory),
+}
+
+impl OutputStream {
+    pub fn new() -> OutputStream {
+        OutputStream {
+            target: Target::Chunks(Vec::new()),
+            len: 0,
+        }
+    }
+
+    /// Allocates the output block up front. When the total size is known this
+    /// avoids the copy `finish` otherwise does to join chunks. Emitting more
+    /// than `capacity` bytes is an error; emitting fewer is fine.
+    pub fn with_capacity(capacity: usize) -> OutputStream {
+        let block = Memory(extism_pdk::memory::internal::memory_alloc(capacity as u64));
+        OutputStream {
+            target: Target::Preallocated(block),
+            len: 0,
+        }
+    }
+
+    /// Bytes emitted so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `bytes` to the output.
+    pub fn emit(&mut self, bytes: impl AsRef<[u8]>) -> Result<(), PluginError> {
+        let bytes = bytes.as_ref();
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        match &mut self.target {
+            Target::Chunks(chunks) => {
+                let chunk = Memory::from_bytes(bytes).map_err(|e| {
+                    PluginError::internal(format!("Failed to allocate output chunk: {}", e))
+                })?;
+                chunks.push(chunk);
+            }
+            Target::Preallocated(block) => {
+                if self.len + bytes.len() > block.len() {
+                    return Err(
+                        PluginError::internal("Output exceeds preallocated capacity")
+                            .with_detail("capacity", block.len())
+                            .with_detail("attempted", self.len + bytes.len()),
+                    );
+                }
+                // SAFETY: the block is a live allocation of `block.len()` bytes
+                // and the bounds check above keeps the write inside it.
+                unsafe { extism_pdk::extism::store(block.offset() + self.len as u64, bytes) };
+            }
+        }
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    /// Sets the emitted bytes as the export's output.
+    pub fn finish(self) {
+        let (offset, len) = match self.target {
+            Target::Preallocated(block) => (block.offset(), self.len),
+            Target::Chunks(chunks) if chunks.len() == 1 => (chunks[0].offset(), self.len),
+            Target::Chunks(chunks) => (join(chunks, self.len), self.len),
+        };
+        unsafe { extism_pdk::extism::output_set(offset, len as u64) };
+    }
+}
+
+impl Default for OutputStream {
+    fn default() -> Self {
+        OutputStream::new()
+    }
+}
+
+/// Copies `chunks` into one new block of `len` bytes, freeing each chunk once
+/// copied, and returns the block's offset.
+fn join(chunks: Vec<Memory>, len: usize) -> u64 {
+    let target = extism_pdk::memory::internal::memory_alloc(len as u64).offset;
+    let mut buf = vec![0u8; COPY_BUFFER_BYTES.min(len)];
+    let mut written = 0u64;
+    for chunk in chunks {
+        let mut copied = 0;
+        while copied < chunk.len() {
+            let n = buf.len().min(chunk.len() - copied);
+            // SAFETY: both ranges lie inside live allocations: `chunk` holds
+            // `chunk.len()` bytes and `target` was sized to the sum of them.
+            unsafe {
+                extism_pdk::extism::load(chunk.offset() + copied as u64, &mut buf[..n]);
+                extism_pdk::extism::store(target + written, &buf[..n]);
+            }
+            copied += n;
+            written += n as u64;
+        }
+        chunk.free();
+    }
+    target
+}
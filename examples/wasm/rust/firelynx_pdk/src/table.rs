@@ -0,0 +1,184 @@
+//! Lookup tables generated at compile time.
+//!
+//! When a plugin's character set or keyword list is fixed at build time,
+//! the `char_class!` and `keywords!` macros compute the tables during
+//! compilation and embed them as `static` data, so nothing is built per
+//! instance or per call:
+//!
+//! ```ignore
+//! static DIGITS: CharClass = char_class!("0123456789");
+//! static MARKERS: Keywords = keywords!(ignore_case; "ignore previous instructions", "[inst]");
+//!
+//! let digits = DIGITS.count(text);
+//! for (keyword, range) in MARKERS.find(text) { .. }
+//! ```
+//!
+//! Sets that come from `static_data` still need runtime matching, such as
+//! `fold::find_fold`; a plugin can keep a baked-in default and fall back to
+//! that only when the config overrides it.
+
+use std::ops::Range;
+
+use crate::fold::find_fold;
+
+/// A set of characters with a bitmap for ASCII. Build it with
+/// `char_class!`.
+#[derive(Debug, Clone, Copy)]
+pub struct CharClass {
+    ascii: [u64; 2],
+    /// Non-ASCII members, sorted.
+    other: &'static [char],
+}
+
+impl CharClass {
+    #[doc(hidden)]
+    pub const fn from_raw(ascii: [u64; 2], other: &'static [char]) -> CharClass {
+        CharClass { ascii, other }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        if c.is_ascii() {
+            self.ascii[c as usize / 64] & (1 << (c as u32 % 64)) != 0
+        } else {
+            self.other.binary_search(&c).is_ok()
+        }
+    }
+
+    /// The number of characters of `text` in the class.
+    pub fn count(&self, text: &str) -> usize {
+        if text.is_ascii() {
+            text.bytes().filter(|&b| self.contains(b as char)).count()
+        } else {
+            text.chars().filter(|&c| self.contains(c)).count()
+        }
+    }
+}
+
+/// A keyword matcher backed by an Aho-Corasick automaton. Build it with
+/// `keywords!`.
+#[derive(Debug, Clone, Copy)]
+pub struct Keywords {
+    patterns: &'static [&'static str],
+    ignore_case: bool,
+    classes: &'static [u8; 256],
+    stride: usize,
+    transitions: &'static [u16],
+    outputs: &'static [&'static [u16]],
+    lens: &'static [usize],
+}
+
+impl Keywords {
+    #[doc(hidden)]
+    pub const fn from_raw(
+        patterns: &'static [&'static str],
+        ignore_case: bool,
+        classes: &'static [u8; 256],
+        stride: usize,
+        transitions: &'static [u16],
+        outputs: &'static [&'static [u16]],
+        lens: &'static [usize],
+    ) -> Keywords {
+        Keywords {
+            patterns,
+            ignore_case,
+            classes,
+            stride,
+            transitions,
+            outputs,
+            lens,
+        }
+    }
+
+    /// The keywords as written in the macro call.
+    pub fn patterns(&self) -> &'static [&'static str] {
+        self.patterns
+    }
+
+    /// Every match as the keyword's index and its byte range, ordered by
+    /// start then index. Matches of one keyword do not overlap, as with
+    /// `find_fold`; matches of different keywords may.
+    pub fn find(&self, haystack: &str) -> Vec<(usize, Range<usize>)> {
+        let mut found = if self.ignore_case && !haystack.is_ascii() {
+            // The automaton folds ASCII only; Unicode folding can change
+            // lengths, so take the per-keyword path.
+            self.patterns
+                .iter()
+                .enumerate()
+                .flat_map(|(i, p)| find_fold(haystack, p).into_iter().map(move |r| (i, r)))
+                .collect()
+        } else {
+            self.run(haystack.as_bytes())
+        };
+        found.sort_by_key(|(i, range)| (range.start, *i));
+        found
+    }
+
+    fn run(&self, haystack: &[u8]) -> Vec<(usize, Range<usize>)> {
+        let mut last_end = vec![0; self.patterns.len()];
+        let mut found = Vec::new();
+        let mut state = 0;
+        for (i, &b) in haystack.iter().enumerate() {
+            let class = self.classes[b as usize] as usize;
+            state = self.transitions[state * self.stride + class] as usize;
+            for &p in self.outputs[state] {
+                let p = p as usize;
+                let end = i + 1;
+                let start = end - self.lens[p];
+                if start >= last_end[p] {
+                    last_end[p] = end;
+                    found.push((p, start..end));
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{char_class, keywords};
+
+    static VOWELS: CharClass = char_class!(ignore_case; "aeioué");
+    static MARKERS: Keywords =
+        keywords!(ignore_case; "ignore", "key", "ſecret", "[inst]", "aa", "straße");
+    static EXACT: Keywords = keywords!("he", "she", "hers", "é");
+
+    #[test]
+    fn char_class_matches_members_and_case_variants() {
+        assert!(VOWELS.contains('a') && VOWELS.contains('U') && VOWELS.contains('É'));
+        assert!(!VOWELS.contains('b') && !VOWELS.contains('ñ'));
+        assert_eq!(VOWELS.count("Hello wOrld"), 3);
+        assert_eq!(VOWELS.count("Été"), 2);
+    }
+
+    #[test]
+    fn keywords_agree_with_find_fold() {
+        let texts = [
+            "IGNORE the key, ignore it",
+            "no SECRET here [INST] aaa",
+            "\u{212a}EY and ſecret and Straße STRASSE",
+            "",
+            "aaaa",
+        ];
+        for text in texts {
+            let mut expected: Vec<(usize, Range<usize>)> = MARKERS
+                .patterns()
+                .iter()
+                .enumerate()
+                .flat_map(|(i, p)| find_fold(text, p).into_iter().map(move |r| (i, r)))
+                .collect();
+            expected.sort_by_key(|(i, range)| (range.start, *i));
+            assert_eq!(MARKERS.find(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn exact_keywords_overlap_across_patterns() {
+        assert_eq!(
+            EXACT.find("ushers café"),
+            vec![(1, 1..4), (0, 2..4), (2, 2..6), (3, 10..12)]
+        );
+        assert!(EXACT.find("HE").is_empty());
+    }
+}
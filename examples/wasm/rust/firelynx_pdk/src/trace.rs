@@ -0,0 +1,286 @@
+//! W3C Trace Context (`traceparent` / `tracestate`) propagation.
+//!
+//! When a request arrives with a valid `traceparent` header, the
+//! `#[firelynx_plugin]` shim starts a span for the call as its child and makes
+//! it current. While a span is current, log lines from the `log_*!` macros
+//! carry `trace_id` and `span_id` fields and error envelopes carry
+//! `trace_id`, so plugin output joins the trace of the route that invoked
+//! it. Requests without the header are not traced.
+//!
+//! Handlers can time parts of their work as nested spans, and pass the
+//! context on to upstream calls (`http::fetch` adds the header itself):
+//!
+//! ```ignore
+//! let span = trace::span("lookup");
+//! if let Some(ctx) = span.context() {
+//!     req = req.with_header("traceparent", ctx.traceparent());
+//! }
+//! ```
+//!
+//! Finished spans are logged at debug level; there is no exporter inside the
+//! plugin.
+
+use std::cell::RefCell;
+use std::time::Instant;
+
+use crate::input::{Request, RequestRef};
+use crate::rng;
+
+/// The only `traceparent` version this parser knows the layout of.
+const VERSION: &str = "00";
+
+/// The trace a call belongs to and the span it is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+    /// The span this one was started from, if it was started here.
+    pub parent_id: Option<u64>,
+    pub flags: u8,
+    /// The `tracestate` header, passed on unchanged.
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Parses a `traceparent` header (and the matching `tracestate`). The
+    /// result describes the caller's span. Returns `None` for malformed
+    /// headers, all-zero IDs and the forbidden version `ff`; headers from
+    /// later versions are read by their version 00 prefix, as the
+    /// specification asks.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<TraceContext> {
+        let header = traceparent.trim();
+        let version = header.get(..2)?;
+        if !is_lower_hex(version) || version == "ff" {
+            return None;
+        }
+        let fields = match (version, header.get(55..)) {
+            (VERSION, Some("")) => &header[..55],
+            (VERSION, _) => return None,
+            (_, Some(rest)) if rest.is_empty() || rest.starts_with('-') => &header[..55],
+            _ => return None,
+        };
+        let mut parts = fields.split('-').skip(1);
+        let (trace, span, flags) = (parts.next()?, parts.next()?, parts.next()?);
+        if ![(trace, 32), (span, 16), (flags, 2)]
+            .iter()
+            .all(|(part, len)| part.len() == *len && is_lower_hex(part))
+        {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(trace, 16).ok()?;
+        let span_id = u64::from_str_radix(span, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id,
+            span_id,
+            parent_id: None,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+            tracestate: tracestate
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    /// A new span in the same trace, parented to this one.
+    pub fn child(&self) -> TraceContext {
+        TraceContext {
+            trace_id: self.trace_id,
+            span_id: new_span_id(),
+            parent_id: Some(self.span_id),
+            flags: self.flags,
+            tracestate: self.tracestate.clone(),
+        }
+    }
+
+    /// Whether the caller recorded the trace (the `sampled` flag).
+    pub fn sampled(&self) -> bool {
+        self.flags & 1 == 1
+    }
+
+    /// The `traceparent` header naming this span as the parent.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "{}-{}-{}-{:02x}",
+            VERSION,
+            self.trace_id_hex(),
+            self.span_id_hex(),
+            self.flags
+        )
+    }
+
+    pub fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    pub fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+}
+
+impl Request {
+    /// The caller's trace context from the `traceparent` and `tracestate`
+    /// headers. Repeated `tracestate` headers are joined with commas.
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        let tracestate = self.header_values("tracestate").map(|v| v.join(","));
+        TraceContext::parse(self.header("traceparent")?, tracestate.as_deref())
+    }
+}
+
+impl RequestRef<'_> {
+    /// See `Request::trace_context`.
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        let tracestate = self.header_values("tracestate").map(|v| v.join(","));
+        TraceContext::parse(self.header("traceparent")?, tracestate.as_deref())
+    }
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// A random, non-zero span ID, reproducible when `rng_seed` is set.
+fn new_span_id() -> u64 {
+    loop {
+        let id = rng::next_u64();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+}
+
+/// The current span, if the call is traced.
+pub fn current() -> Option<TraceContext> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+/// Starts the span for one call under the `caller`'s context from the
+/// request headers, replacing whatever an earlier call left current.
+pub(crate) fn begin(caller: Option<TraceContext>) {
+    let span = caller.map(|caller| caller.child());
+    CURRENT.with(|c| *c.borrow_mut() = span);
+}
+
+/// Starts a span named `name` under the current one. It is current until
+/// the returned guard is dropped, which logs it at debug level. Without a
+/// current span nothing is traced and the guard does nothing.
+pub fn span(name: &str) -> Span {
+    let previous = current();
+    let context = previous.as_ref().map(TraceContext::child);
+    if context.is_some() {
+        CURRENT.with(|c| *c.borrow_mut() = context.clone());
+    }
+    Span {
+        name: name.to_string(),
+        context,
+        previous,
+        started: Instant::now(),
+    }
+}
+
+/// A running span; see `span`.
+#[must_use = "the span ends when dropped"]
+pub struct Span {
+    name: String,
+    context: Option<TraceContext>,
+    previous: Option<TraceContext>,
+    started: Instant,
+}
+
+impl Span {
+    pub fn context(&self) -> Option<&TraceContext> {
+        self.context.as_ref()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(context) = self.context.take() else {
+            return;
+        };
+        let duration_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        // Log while the span is still current so the line carries its ID.
+        crate::log_debug!(
+            "span finished",
+            span = self.name,
+            parent_id = context.parent_id.map(|id| format!("{:016x}", id)),
+            duration_ms = duration_ms,
+        );
+        CURRENT.with(|c| *c.borrow_mut() = self.previous.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parses_and_renders_traceparent() {
+        let ctx = TraceContext::parse(HEADER, Some(" congo=t61rcWkgMzE ")).unwrap();
+        assert_eq!(ctx.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.span_id, 0x00f067aa0ba902b7);
+        assert!(ctx.sampled());
+        assert_eq!(ctx.tracestate.as_deref(), Some("congo=t61rcWkgMzE"));
+        assert_eq!(ctx.traceparent(), HEADER);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        for header in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00_4bf92f3577b34da6a3ce929d0e0e4736_00f067aa0ba902b7_01",
+        ] {
+            assert_eq!(TraceContext::parse(header, None), None, "{}", header);
+        }
+    }
+
+    #[test]
+    fn later_versions_are_read_by_their_prefix() {
+        let header = format!("{}-future", HEADER.replacen("00", "cc", 1));
+        let ctx = TraceContext::parse(&header, None).unwrap();
+        assert_eq!(ctx.span_id_hex(), "00f067aa0ba902b7");
+    }
+
+    #[test]
+    fn spans_nest_under_the_call() {
+        let mut request = Request::default();
+        request
+            .headers
+            .insert("Traceparent".to_string(), vec![HEADER.to_string()]);
+        begin(request.trace_context());
+        let call = current().unwrap();
+        assert_eq!(call.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(call.parent_id, Some(0x00f067aa0ba902b7));
+        assert_ne!(call.span_id, 0x00f067aa0ba902b7);
+
+        {
+            let span = span("lookup");
+            let inner = span.context().unwrap();
+            assert_eq!(inner.parent_id, Some(call.span_id));
+            assert_eq!(current().as_ref(), Some(inner));
+        }
+        assert_eq!(current(), Some(call));
+
+        let envelope: serde_json::Value =
+            serde_json::from_str(&crate::PluginError::policy("denied").to_json()).unwrap();
+        assert_eq!(envelope["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+
+        begin(Request::default().trace_context());
+        assert_eq!(current(), None);
+        assert!(span("untraced").context().is_none());
+    }
+}
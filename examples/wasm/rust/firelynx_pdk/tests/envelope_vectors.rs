@@ -0,0 +1,128 @@
+//! Checks the SDK against the shared envelope vectors in
+//! `testdata/envelope-vectors/` at the repository root, the fixtures both
+//! sides of the plugin boundary verify against.
+//!
+//! Every `<name>.input.json` is an envelope as a host may send it. Its
+//! `<name>.output.json` is either the canonical version 2 envelope the SDK
+//! must parse it into, or `{"error": {...}}` with the error it must report.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use firelynx_pdk::codec::Codec;
+use firelynx_pdk::input::Input;
+use firelynx_pdk::PluginError;
+use serde_json::{json, Value};
+
+fn vectors_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../../testdata/envelope-vectors")
+}
+
+/// The vector names, sorted.
+fn vectors() -> Vec<String> {
+    let dir = vectors_dir();
+    let entries =
+        fs::read_dir(&dir).unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e));
+    let mut names: Vec<String> = entries
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter_map(|file| file.strip_suffix(".input.json").map(str::to_string))
+        .collect();
+    names.sort();
+    names
+}
+
+fn read(name: &str, kind: &str) -> String {
+    let path = vectors_dir().join(format!("{}.{}.json", name, kind));
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e))
+}
+
+/// The envelope as the SDK sees it after parsing, in the vectors' canonical
+/// shape: every request field present, `static_data` null when absent,
+/// `resume` only when present.
+fn canonical(input: &Input<Value>) -> Value {
+    let request = &input.request;
+    let url = &request.url;
+    let mut envelope = json!({
+        "schema_version": input.schema_version,
+        "request": {
+            "Body": request.body.text(),
+            "Headers": request.headers,
+            "QueryParams": request.query_params,
+            "Method": request.method,
+            "Proto": request.proto,
+            "Host": request.host,
+            "RemoteAddr": request.remote_addr,
+            "ContentLength": request.content_length,
+            "URL": {
+                "Scheme": url.scheme,
+                "Path": url.path,
+                "Host": url.host,
+                "RawQuery": url.raw_query,
+                "Fragment": url.fragment,
+            },
+        },
+        "static_data": input.static_data,
+    });
+    if let Some(state) = &input.resume {
+        envelope["resume"] = state.clone();
+    }
+    envelope
+}
+
+/// Checks one parse result against the vector's expected output.
+fn check(name: &str, parsed: Result<Input<Value>, PluginError>, expected: &Value) {
+    match (parsed, expected.get("error")) {
+        (Ok(input), None) => assert_eq!(&canonical(&input), expected, "{}", name),
+        (Err(err), Some(want)) => {
+            assert_eq!(err.code(), want["code"], "{}: {}", name, err);
+            if let Some(message) = want.get("message") {
+                assert_eq!(err.message(), *message, "{}", name);
+            }
+            if let Some(details) = want.get("details") {
+                assert_eq!(&json!(err.details()), details, "{}", name);
+            }
+        }
+        (Ok(input), Some(_)) => panic!("{}: expected an error, parsed {:?}", name, input),
+        (Err(err), None) => panic!("{}: expected an envelope, got {}", name, err),
+    }
+}
+
+#[test]
+fn vectors_parse_to_their_expected_output() {
+    let names = vectors();
+    assert!(
+        !names.is_empty(),
+        "no vectors in {}",
+        vectors_dir().display()
+    );
+    for name in &names {
+        let expected: Value = serde_json::from_str(&read(name, "output")).unwrap();
+        check(name, Input::from_json(&read(name, "input")), &expected);
+    }
+}
+
+/// The canonical envelope is a fixed point: parsing an expected output
+/// gives it back unchanged.
+#[test]
+fn canonical_outputs_round_trip() {
+    for name in vectors() {
+        let expected: Value = serde_json::from_str(&read(&name, "output")).unwrap();
+        if expected.get("error").is_some() {
+            continue;
+        }
+        check(&name, Input::from_json(&expected.to_string()), &expected);
+    }
+}
+
+/// Envelopes that are valid JSON mean the same thing in MessagePack.
+#[test]
+fn vectors_agree_across_codecs() {
+    for name in vectors() {
+        let Ok(envelope) = serde_json::from_str::<Value>(&read(&name, "input")) else {
+            continue;
+        };
+        let expected: Value = serde_json::from_str(&read(&name, "output")).unwrap();
+        let bytes = Codec::MessagePack.encode(&envelope).unwrap();
+        check(&name, Input::decode(Codec::MessagePack, bytes), &expected);
+    }
+}
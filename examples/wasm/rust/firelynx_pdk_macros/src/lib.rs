@@ -0,0 +1,315 @@
+//! Procedural macros for `firelynx-pdk`. Use them through the re-exports in
+//! `firelynx_pdk` rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Error, FnArg, Ident, ItemFn, LitStr, Token, Type};
+
+mod table;
+
+/// Turns a plain Rust function into an extism export.
+///
+/// ```ignore
+/// #[firelynx_plugin]
+/// fn count_characters(req: Request, cfg: StaticData) -> Result<CharacterReport> {
+///     // ...
+/// }
+/// ```
+///
+/// generates an `extern "C" fn CountCharacters() -> i32` that reads the input
+/// envelope, passes the request and its `static_data` (deserialized into the
+/// second parameter's type, or `Default` when absent) to the function, writes
+/// the `Ok` value as JSON output, and reports errors (and panics) through
+/// `error_set`. The configuration parameter may be omitted. The export name defaults to the
+/// function name in PascalCase and can be set with
+/// `#[firelynx_plugin(name = "...")]`.
+///
+/// A third parameter, `Option<State>`, makes the handler resumable (see
+/// `firelynx_pdk::resume`): the export passes `None`, and a second export
+/// named with a `Resume` suffix passes the state from the envelope.
+///
+/// Taking `RequestRef<'_>` instead of `Request` gives the handler a request
+/// borrowed from the input text; such exports read JSON envelopes only and
+/// cannot be resumable.
+#[proc_macro_attribute]
+pub fn firelynx_plugin(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as PluginArgs);
+    let func = parse_macro_input!(item as ItemFn);
+    expand(args, func)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Builds a `firelynx_pdk::table::CharClass` from a string literal at
+/// compile time.
+///
+/// ```ignore
+/// static VOWELS: CharClass = char_class!(ignore_case; "aeiou");
+/// ```
+///
+/// `ignore_case;` adds the single-character upper- and lowercase forms of
+/// each character.
+#[proc_macro]
+pub fn char_class(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as table::TableArgs);
+    table::expand_char_class(args)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Builds a `firelynx_pdk::table::Keywords` matcher from string literals at
+/// compile time, so no automaton is constructed at runtime.
+///
+/// ```ignore
+/// static MARKERS: Keywords = keywords!(ignore_case; "ignore previous instructions", "[inst]");
+/// ```
+///
+/// `ignore_case;` matches the way `firelynx_pdk::fold::find_fold` does.
+#[proc_macro]
+pub fn keywords(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as table::TableArgs);
+    table::expand_keywords(args)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct PluginArgs {
+    /// The export name, checked to be a plain identifier and spanned to the
+    /// string literal it came from.
+    name: Option<Ident>,
+}
+
+impl Parse for PluginArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = PluginArgs::default();
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "name" => args.name = Some(export_ident(input.parse()?)?),
+                other => {
+                    return Err(Error::new(
+                        key.span(),
+                        format!("unknown firelynx_plugin argument `{}`", other),
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Parses `name = "..."` as an identifier, so a name the export function
+/// could not be declared with is reported at the literal instead of making
+/// the macro panic.
+fn export_ident(lit: LitStr) -> syn::Result<Ident> {
+    let value = lit.value();
+    match syn::parse_str::<Ident>(&value) {
+        Ok(mut ident) if !value.starts_with("r#") => {
+            ident.set_span(lit.span());
+            Ok(ident)
+        }
+        _ => Err(Error::new(
+            lit.span(),
+            format!(
+                "firelynx_plugin name `{}` is not a valid Rust identifier",
+                value
+            ),
+        )),
+    }
+}
+
+fn expand(args: PluginArgs, func: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let sig = &func.sig;
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            sig,
+            "firelynx_plugin functions cannot be async or generic",
+        ));
+    }
+
+    let mut params = Vec::new();
+    for arg in &sig.inputs {
+        match arg {
+            FnArg::Typed(pat) => params.push(pat.ty.as_ref()),
+            FnArg::Receiver(r) => {
+                return Err(Error::new_spanned(
+                    r,
+                    "firelynx_plugin functions cannot take self",
+                ))
+            }
+        }
+    }
+
+    let handler = &sig.ident;
+    let export = match args.name {
+        Some(name) => name,
+        None => Ident::new(&pascal_case(&handler.to_string()), Span::call_site()),
+    };
+    let export_name = export.to_string();
+
+    let borrowed = params.first().is_some_and(|ty| is_request_ref(ty));
+    let run = if borrowed {
+        quote! { ::firelynx_pdk::export::run_ref }
+    } else {
+        quote! { ::firelynx_pdk::export::run }
+    };
+
+    let (call, resume) = match params.as_slice() {
+        [_request] => (
+            quote! { |request, _: ::firelynx_pdk::StaticData| #handler(request) },
+            None,
+        ),
+        [_request, config] => (
+            quote! { |request, config: #config| #handler(request, config) },
+            None,
+        ),
+        [request, _, _] if borrowed => {
+            return Err(Error::new_spanned(
+                request,
+                "resumable firelynx_plugin functions take `Request`, not `RequestRef`",
+            ))
+        }
+        [_request, config, _state] => {
+            let resume = Ident::new(&format!("{}Resume", export_name), Span::call_site());
+            (
+                quote! { |request, config: #config| #handler(request, config, None) },
+                Some(quote! {
+                    #[no_mangle]
+                    #[allow(non_snake_case)]
+                    pub extern "C" fn #resume() -> i32 {
+                        ::firelynx_pdk::resume::run(|request, config: #config, state| {
+                            #handler(request, config, Some(state))
+                        })
+                    }
+                }),
+            )
+        }
+        _ => {
+            return Err(Error::new_spanned(
+                &sig.inputs,
+                "expected `fn(Request)`, `fn(Request, Config)` or \
+                 `fn(Request, Config, Option<State>)`",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        #func
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn #export() -> i32 {
+            #run(#call)
+        }
+
+        #resume
+    })
+}
+
+/// Whether `ty` names `RequestRef`, with or without a path or lifetime.
+fn is_request_ref(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "RequestRef"),
+        _ => false,
+    }
+}
+
+fn pascal_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_names_are_pascal_case() {
+        assert_eq!(pascal_case("count_characters"), "CountCharacters");
+        assert_eq!(pascal_case("lint"), "Lint");
+        assert_eq!(pascal_case("_count__words_"), "CountWords");
+    }
+
+    #[test]
+    fn rejects_unsupported_signatures() {
+        let func: ItemFn =
+            syn::parse_quote! { fn f(a: A, b: B, c: C, d: D) -> Result<()> { todo!() } };
+        assert!(expand(PluginArgs::default(), func).is_err());
+
+        let func: ItemFn = syn::parse_quote! { async fn f(a: A) -> Result<()> { todo!() } };
+        assert!(expand(PluginArgs::default(), func).is_err());
+    }
+
+    #[test]
+    fn state_parameter_adds_a_resume_export() {
+        let func: ItemFn = syn::parse_quote! {
+            fn count_rows(req: Request, cfg: Config, state: Option<Scan>) -> Result<()> { todo!() }
+        };
+        let tokens = expand(PluginArgs::default(), func).unwrap().to_string();
+        assert!(tokens.contains("fn CountRows ()"));
+        assert!(tokens.contains("fn CountRowsResume ()"));
+        assert!(tokens.contains("count_rows (request , config , None)"));
+
+        let func: ItemFn = syn::parse_quote! { fn f(req: Request) -> Result<()> { todo!() } };
+        let tokens = expand(PluginArgs::default(), func).unwrap().to_string();
+        assert!(!tokens.contains("Resume"));
+    }
+
+    #[test]
+    fn request_ref_handlers_use_the_borrowing_runner() {
+        let func: ItemFn = syn::parse_quote! {
+            fn estimate(req: RequestRef<'_>, cfg: StaticData) -> Result<()> { todo!() }
+        };
+        let tokens = expand(PluginArgs::default(), func).unwrap().to_string();
+        assert!(tokens.contains("export :: run_ref ("));
+
+        let func: ItemFn = syn::parse_quote! {
+            fn f(req: firelynx_pdk::input::RequestRef<'_>, cfg: C, state: Option<S>) -> Result<()> { todo!() }
+        };
+        let err = expand(PluginArgs::default(), func).unwrap_err();
+        assert!(err.to_string().contains("not `RequestRef`"));
+    }
+
+    #[test]
+    fn honours_explicit_export_name() {
+        let func: ItemFn = syn::parse_quote! { fn f(req: Request) -> Result<()> { todo!() } };
+        let args: PluginArgs = syn::parse_quote! { name = "Custom" };
+        let tokens = expand(args, func).unwrap().to_string();
+        assert!(tokens.contains("fn Custom ()"));
+    }
+
+    #[test]
+    fn rejects_export_names_that_are_not_identifiers() {
+        for name in ["Count Rows", "", "9Lives", "fn", "r#type", "Count-Rows"] {
+            let result = syn::parse_str::<PluginArgs>(&format!("name = {:?}", name));
+            let err = result
+                .err()
+                .unwrap_or_else(|| panic!("{:?} was accepted", name));
+            assert!(
+                err.to_string().contains("is not a valid Rust identifier"),
+                "{}",
+                err
+            );
+        }
+    }
+}
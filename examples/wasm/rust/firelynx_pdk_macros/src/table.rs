@@ -0,0 +1,271 @@
+//! Expansion of `char_class!` and `keywords!`: the tables are computed here,
+//! at compile time, and emitted as constants for `firelynx_pdk::table`.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, Ident, LitStr, Token};
+
+/// More states than this do not fit the `u16` transition table.
+const MAX_STATES: usize = u16::MAX as usize;
+
+/// `[ignore_case;] "literal", ...`
+pub(crate) struct TableArgs {
+    ignore_case: bool,
+    literals: Vec<LitStr>,
+}
+
+impl Parse for TableArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut ignore_case = false;
+        if input.peek(Ident) {
+            let flag: Ident = input.parse()?;
+            if flag != "ignore_case" {
+                return Err(Error::new(
+                    flag.span(),
+                    format!("unknown flag `{}`, expected `ignore_case`", flag),
+                ));
+            }
+            input.parse::<Token![;]>()?;
+            ignore_case = true;
+        }
+        let literals = Punctuated::<LitStr, Token![,]>::parse_terminated(input)?;
+        Ok(TableArgs {
+            ignore_case,
+            literals: literals.into_iter().collect(),
+        })
+    }
+}
+
+pub(crate) fn expand_char_class(args: TableArgs) -> syn::Result<TokenStream> {
+    let [set] = args.literals.as_slice() else {
+        return Err(Error::new(
+            Span::call_site(),
+            "char_class! takes one string of characters",
+        ));
+    };
+
+    let mut chars = BTreeSet::new();
+    for c in set.value().chars() {
+        chars.insert(c);
+        if args.ignore_case {
+            for variant in [single(c.to_lowercase()), single(c.to_uppercase())] {
+                chars.extend(variant);
+            }
+        }
+    }
+
+    let mut ascii = [0u64; 2];
+    let mut other = Vec::new();
+    for c in chars {
+        if c.is_ascii() {
+            ascii[c as usize / 64] |= 1 << (c as u32 % 64);
+        } else {
+            other.push(c);
+        }
+    }
+    let [low, high] = ascii;
+    Ok(quote! {
+        ::firelynx_pdk::table::CharClass::from_raw([#low, #high], &[#(#other),*])
+    })
+}
+
+pub(crate) fn expand_keywords(args: TableArgs) -> syn::Result<TokenStream> {
+    if args.literals.is_empty() {
+        return Err(Error::new(
+            Span::call_site(),
+            "keywords! needs at least one keyword",
+        ));
+    }
+    for lit in &args.literals {
+        if lit.value().is_empty() {
+            return Err(Error::new(lit.span(), "keywords cannot be empty"));
+        }
+    }
+
+    let patterns: Vec<String> = args.literals.iter().map(LitStr::value).collect();
+    let automaton = Automaton::build(&patterns, args.ignore_case)
+        .map_err(|msg| Error::new(Span::call_site(), msg))?;
+
+    let ignore_case = args.ignore_case;
+    let Automaton {
+        classes,
+        stride,
+        transitions,
+        outputs,
+        lens,
+    } = automaton;
+    let outputs = outputs.iter().map(|out| quote! { &[#(#out),*] });
+    Ok(quote! {
+        ::firelynx_pdk::table::Keywords::from_raw(
+            &[#(#patterns),*],
+            #ignore_case,
+            &[#(#classes),*],
+            #stride,
+            &[#(#transitions),*],
+            &[#(#outputs),*],
+            &[#(#lens),*],
+        )
+    })
+}
+
+/// `Some(c)` when a case mapping yields exactly one character.
+fn single(mut mapped: impl Iterator<Item = char>) -> Option<char> {
+    let c = mapped.next()?;
+    mapped.next().is_none().then_some(c)
+}
+
+/// Same folding as `firelynx_pdk::fold`.
+fn fold(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| c.to_uppercase().flat_map(char::to_lowercase))
+        .collect()
+}
+
+/// An Aho-Corasick DFA over byte classes.
+struct Automaton {
+    /// Byte to class; class 0 is every byte no keyword uses.
+    classes: Vec<u8>,
+    stride: usize,
+    /// `state * stride + class` to the next state.
+    transitions: Vec<u16>,
+    /// The keywords that end at each state.
+    outputs: Vec<Vec<u16>>,
+    /// Byte length each keyword matches, 0 for keywords the DFA leaves out.
+    lens: Vec<usize>,
+}
+
+impl Automaton {
+    fn build(patterns: &[String], ignore_case: bool) -> Result<Automaton, String> {
+        // With `ignore_case` the DFA only runs on ASCII text, which folds to
+        // ASCII; keywords whose folded form is not ASCII cannot match there.
+        let keys: Vec<Option<Vec<u8>>> = patterns
+            .iter()
+            .map(|p| {
+                if ignore_case {
+                    let folded = fold(p);
+                    folded.is_ascii().then(|| folded.into_bytes())
+                } else {
+                    Some(p.as_bytes().to_vec())
+                }
+            })
+            .collect();
+
+        let mut classes = vec![0u8; 256];
+        let mut stride = 1;
+        for &b in keys.iter().flatten().flatten() {
+            if classes[b as usize] != 0 {
+                continue;
+            }
+            let class = u8::try_from(stride).map_err(|_| "too many distinct bytes in keywords")?;
+            classes[b as usize] = class;
+            if ignore_case && b.is_ascii_alphabetic() {
+                classes[b.to_ascii_uppercase() as usize] = class;
+            }
+            stride += 1;
+        }
+
+        // The trie; a 0 transition out of a non-root state is a gap until
+        // the failure links fill it.
+        let mut goto = vec![vec![0u16; stride]];
+        let mut outputs: Vec<Vec<u16>> = vec![Vec::new()];
+        for (i, key) in keys.iter().enumerate() {
+            let Some(key) = key else { continue };
+            let mut state = 0;
+            for &b in key {
+                let class = classes[b as usize] as usize;
+                if goto[state][class] == 0 {
+                    if goto.len() == MAX_STATES {
+                        return Err("keywords need too many automaton states".to_string());
+                    }
+                    goto[state][class] = goto.len() as u16;
+                    goto.push(vec![0; stride]);
+                    outputs.push(Vec::new());
+                }
+                state = goto[state][class] as usize;
+            }
+            outputs[state].push(i as u16);
+        }
+
+        let mut fail = vec![0usize; goto.len()];
+        let mut queue: VecDeque<usize> = goto[0]
+            .iter()
+            .filter(|&&s| s != 0)
+            .map(|&s| s as usize)
+            .collect();
+        while let Some(state) = queue.pop_front() {
+            // Shallower than `state`, so its row is already complete.
+            let fallbacks = goto[fail[state]].clone();
+            for (class, &fallback) in fallbacks.iter().enumerate() {
+                let next = goto[state][class] as usize;
+                if next == 0 {
+                    goto[state][class] = fallback;
+                } else {
+                    fail[next] = fallback as usize;
+                    let inherited = outputs[fallback as usize].clone();
+                    outputs[next].extend(inherited);
+                    queue.push_back(next);
+                }
+            }
+        }
+        for out in &mut outputs {
+            out.sort_unstable();
+        }
+
+        Ok(Automaton {
+            classes,
+            stride,
+            transitions: goto.concat(),
+            outputs,
+            lens: keys
+                .iter()
+                .map(|k| k.as_ref().map_or(0, Vec::len))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the DFA the way `Keywords::find` does, without the per-keyword
+    /// overlap filter.
+    fn ends(automaton: &Automaton, text: &str) -> Vec<(u16, usize)> {
+        let mut state = 0;
+        let mut found = Vec::new();
+        for (i, &b) in text.as_bytes().iter().enumerate() {
+            let class = automaton.classes[b as usize] as usize;
+            state = automaton.transitions[state * automaton.stride + class] as usize;
+            found.extend(automaton.outputs[state].iter().map(|&p| (p, i + 1)));
+        }
+        found
+    }
+
+    #[test]
+    fn reports_every_keyword_ending_at_each_byte() {
+        let patterns = ["he", "she", "his", "hers"].map(String::from);
+        let automaton = Automaton::build(&patterns, false).unwrap();
+        assert_eq!(ends(&automaton, "ushers"), vec![(0, 4), (1, 4), (3, 6)]);
+    }
+
+    #[test]
+    fn ignore_case_folds_keywords_and_text() {
+        let patterns = ["Key", "\u{212a}ey", "é"].map(String::from);
+        let automaton = Automaton::build(&patterns, true).unwrap();
+        assert_eq!(automaton.lens, vec![3, 3, 0]);
+        assert_eq!(ends(&automaton, "a KEY"), vec![(0, 5), (1, 5)]);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let args: TableArgs = syn::parse_quote! { "a", "" };
+        assert!(expand_keywords(args).is_err());
+        let args: TableArgs = syn::parse_quote! { "ab", "cd" };
+        assert!(expand_char_class(args).is_err());
+        assert!(syn::parse_str::<TableArgs>("ignorecase; \"a\"").is_err());
+    }
+}
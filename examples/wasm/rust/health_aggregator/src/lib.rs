@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use extism_pdk::{HttpRequest, HttpResponse};
+use firelynx_pdk::allowlist::AllowList;
+use firelynx_pdk::http::UpstreamErrorKind;
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// extism var holding the last report, so bursts of `/healthz` polling from
+/// several load balancers do not fan out to every upstream each time.
+const CACHE_VAR: &str = "health_aggregator.report";
+
+/// Aggregate health of the configured upstreams. Matches `HealthReport` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// `pass` when every check passed, `warn` when only non-critical checks
+    /// failed, `fail` when any critical check failed.
+    pub status: Status,
+    /// Unix seconds when the upstreams were probed.
+    pub checked_at: u64,
+    /// True when the report was served from the cache.
+    pub cached: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The outcome of probing one upstream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: Status,
+    pub critical: bool,
+    pub http_status: u16,
+    /// An `UpstreamErrorKind` label: `status_4xx` or `status_5xx`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+/// One upstream to probe, from `static_data.checks`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Check {
+    name: String,
+    url: String,
+    /// A failing non-critical check degrades the report to `warn` only.
+    #[serde(default = "default_critical")]
+    critical: bool,
+}
+
+fn default_critical() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    checks: Vec<Check>,
+    max_checks: u64,
+    cache_ttl_seconds: u64,
+    #[serde(default)]
+    outbound: Option<AllowList>,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::integer("max_checks").default(DefaultValue::Int(16)),
+        Field::integer("cache_ttl_seconds").default(DefaultValue::Int(5)),
+        Field::table("outbound"),
+    ];
+}
+
+/// A cached report together with the checks it was produced for, so a route
+/// with a different check list never sees another route's results.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    checks: Vec<Check>,
+    report: HealthReport,
+}
+
+#[firelynx_plugin]
+fn aggregate_health(_request: Request, static_data: StaticData) -> Result<HealthReport> {
+    let config = Config::from_static_data(&static_data)?;
+    if config.checks.is_empty() {
+        return Err(
+            PluginError::config("checks must list at least one upstream")
+                .with_detail("field", "checks"),
+        );
+    }
+    if config.checks.len() as u64 > config.max_checks {
+        return Err(PluginError::config("Too many checks configured")
+            .with_detail("field", "checks")
+            .with_detail("count", config.checks.len())
+            .with_detail("max_checks", config.max_checks));
+    }
+
+    // Refuse the whole request up front rather than probing some upstreams
+    // before discovering a disallowed one: that is a configuration mistake.
+    let allow = config.outbound.unwrap_or_default();
+    for check in &config.checks {
+        allow
+            .check(&check.url)
+            .map_err(|e| e.with_detail("check", check.name.as_str()))?;
+    }
+
+    let now = unix_now();
+    if let Some(entry) = cached(&config.checks) {
+        if now.saturating_sub(entry.report.checked_at) < config.cache_ttl_seconds {
+            return Ok(HealthReport {
+                cached: true,
+                ..entry.report
+            });
+        }
+    }
+
+    // extism HTTP calls are synchronous, so checks run one after another;
+    // `max_checks` bounds how long a single call can take.
+    let checks = config
+        .checks
+        .iter()
+        .map(probe)
+        .collect::<Result<Vec<_>>>()?;
+    let report = HealthReport {
+        status: overall(&checks),
+        checked_at: now,
+        cached: false,
+        checks,
+    };
+
+    if config.cache_ttl_seconds > 0 {
+        let entry = CacheEntry {
+            checks: config.checks,
+            report: report.clone(),
+        };
+        // A failed cache write only costs the next caller a fresh probe.
+        let _ = extism_pdk::var::set(CACHE_VAR, Json(entry));
+    }
+    Ok(report)
+}
+
+/// Probes one upstream. A refused connection or a host-side timeout traps
+/// in the HTTP host call and fails the whole export, so only a response
+/// gets here; the error case is the PDK failing to encode the request
+/// before the host is called, which says nothing about the upstream.
+fn probe(check: &Check) -> Result<CheckResult> {
+    let started = Instant::now();
+    let resp =
+        extism_pdk::http::request::<()>(&HttpRequest::new(&check.url), None).map_err(|e| {
+            PluginError::internal("Could not send the health check request")
+                .with_detail("check", check.name.as_str())
+                .with_detail("reason", e.to_string())
+        })?;
+    let latency_ms = millis(started.elapsed());
+    let http_status = resp.status_code();
+    free(resp);
+
+    let kind = UpstreamErrorKind::from_status(http_status);
+    if let Some(kind) = kind {
+        log_warn!(
+            "health check failed",
+            check = check.name,
+            error = kind.label(),
+            http_status = http_status,
+            latency_ms = latency_ms,
+        );
+    }
+    Ok(CheckResult {
+        name: check.name.clone(),
+        status: if kind.is_some() {
+            Status::Fail
+        } else {
+            Status::Pass
+        },
+        critical: check.critical,
+        http_status,
+        error: kind.map(|k| k.label().to_string()),
+        latency_ms,
+    })
+}
+
+fn overall(checks: &[CheckResult]) -> Status {
+    let failed = checks.iter().filter(|c| c.status == Status::Fail);
+    let mut status = Status::Pass;
+    for check in failed {
+        if check.critical {
+            return Status::Fail;
+        }
+        status = Status::Warn;
+    }
+    status
+}
+
+fn cached(checks: &[Check]) -> Option<CacheEntry> {
+    let Json(entry): Json<CacheEntry> = extism_pdk::var::get(CACHE_VAR).ok()??;
+    (entry.checks == checks).then_some(entry)
+}
+
+/// Health probes only need the status; release the body right away.
+fn free(resp: HttpResponse) {
+    resp.into_memory().free();
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn millis(d: Duration) -> u64 {
+    u64::try_from(d.as_millis()).unwrap_or(u64::MAX)
+}
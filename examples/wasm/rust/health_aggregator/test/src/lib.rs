@@ -0,0 +1,68 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn healthz(static_data: &[(&str, Value)]) -> String {
+    static_data
+        .iter()
+        .fold(
+            RequestFixture::new("").method("GET").path("/healthz"),
+            |f, (k, v)| f.static_data(*k, v.clone()),
+        )
+        .to_json()
+}
+
+fn fails(input: &str) -> bool {
+    xtp_test::call::<Json<Value>>("AggregateHealth", input).is_err()
+}
+
+// Only the paths that fail before any upstream is probed are covered here;
+// the test harness gives plugins no HTTP upstream to talk to.
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    let checks = json!([
+        {"name": "a", "url": "https://a.internal.example.com/healthz"},
+        {"name": "b", "url": "https://b.internal.example.com/healthz", "critical": false},
+    ]);
+    let outbound =
+        json!({"hosts": ["*.internal.example.com"], "schemes": ["https"], "ports": [443]});
+
+    xtp_test::group("configuration errors", || {
+        xtp_test::assert!("no checks is rejected", fails(&healthz(&[])));
+        xtp_test::assert!(
+            "more checks than max_checks is rejected",
+            fails(&healthz(&[
+                ("checks", checks.clone()),
+                ("outbound", outbound.clone()),
+                ("max_checks", json!(1)),
+            ]))
+        );
+        xtp_test::assert!(
+            "wrong-typed cache_ttl_seconds is rejected",
+            fails(&healthz(&[
+                ("checks", checks.clone()),
+                ("outbound", outbound.clone()),
+                ("cache_ttl_seconds", json!("soon")),
+            ]))
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("outbound allow-list", || {
+        xtp_test::assert!(
+            "no allow-list denies every upstream",
+            fails(&healthz(&[("checks", checks.clone())]))
+        );
+        let narrow = json!({"hosts": ["a.internal.example.com"]});
+        xtp_test::assert!(
+            "one disallowed upstream rejects the whole request",
+            fails(&healthz(&[
+                ("checks", checks.clone()),
+                ("outbound", narrow)
+            ]))
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::net::IpAddr;
+
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Whether the client may proceed. Matches `AccessDecision` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessDecision {
+    pub allowed: bool,
+    /// The status to answer with: 200 when allowed, 403 when blocked.
+    pub status: u16,
+    /// The address the lists were checked against, after trusted proxies.
+    /// IPv4-mapped IPv6 addresses are shown as IPv4.
+    pub client_ip: String,
+    /// Why the client is blocked. Present only when refused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<Reason>,
+    /// The `deny` entry that blocked the client, or the `allow` entry that
+    /// let it in. Absent when no entry matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The client is in a `deny` network.
+    Denied,
+    /// `allow` is set and the client is in none of its networks.
+    NotAllowed,
+}
+
+impl Reason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Reason::Denied => "denied",
+            Reason::NotAllowed => "not_allowed",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+    #[serde(default)]
+    trusted_proxies: Vec<String>,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string_list("allow"),
+        Field::string_list("deny"),
+        Field::string_list("trusted_proxies"),
+    ];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+}
+
+/// The parsed lists, each network with its text as configured.
+struct Lists<'c> {
+    allow: Vec<(Cidr, &'c str)>,
+    deny: Vec<(Cidr, &'c str)>,
+    trusted_proxies: TrustedProxies,
+}
+
+impl Config {
+    fn lists(&self) -> Result<Lists<'_>> {
+        if self.allow.is_empty() && self.deny.is_empty() {
+            return Err(
+                PluginError::config("allow or deny must list at least one network")
+                    .with_detail("field", "allow"),
+            );
+        }
+        Ok(Lists {
+            allow: parse_networks("allow", &self.allow)?,
+            deny: parse_networks("deny", &self.deny)?,
+            trusted_proxies: TrustedProxies(
+                parse_networks("trusted_proxies", &self.trusted_proxies)?
+                    .into_iter()
+                    .map(|(cidr, _)| cidr)
+                    .collect(),
+            ),
+        })
+    }
+}
+
+fn parse_networks<'c>(field: &str, entries: &'c [String]) -> Result<Vec<(Cidr, &'c str)>> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let cidr = entry.parse::<Cidr>().map_err(|_| {
+                PluginError::config(format!(
+                    "{} holds an entry that is not an IP address or CIDR",
+                    field
+                ))
+                .with_detail("field", field)
+                .with_detail("index", index)
+                .with_detail("value", entry.as_str())
+            })?;
+            Ok((cidr, entry.as_str()))
+        })
+        .collect()
+}
+
+#[firelynx_plugin]
+fn filter_ip(request: Request, static_data: StaticData) -> Result<AccessDecision> {
+    let config = Config::from_static_data(&static_data)?;
+    let lists = config.lists()?;
+    // An address that does not parse is an error, so the request is
+    // refused rather than let through unchecked.
+    let client_ip = request.client_ip(&lists.trusted_proxies)?.to_canonical();
+
+    // deny is checked first, so it can carve exceptions out of allow.
+    let (reason, network) = match matching(&lists.deny, client_ip) {
+        Some(network) => (Some(Reason::Denied), Some(network)),
+        None => match matching(&lists.allow, client_ip) {
+            Some(network) => (None, Some(network)),
+            None if lists.allow.is_empty() => (None, None),
+            None => (Some(Reason::NotAllowed), None),
+        },
+    };
+    if let Some(reason) = reason {
+        log_info!(
+            "client blocked",
+            client_ip = client_ip.to_string(),
+            reason = reason.as_str()
+        );
+    }
+
+    Ok(AccessDecision {
+        allowed: reason.is_none(),
+        status: if reason.is_none() { 200 } else { 403 },
+        client_ip: client_ip.to_string(),
+        reason,
+        network: network.map(str::to_string),
+    })
+}
+
+/// The first of `networks` holding `ip`, as configured.
+fn matching<'c>(networks: &[(Cidr, &'c str)], ip: IpAddr) -> Option<&'c str> {
+    networks
+        .iter()
+        .find(|(cidr, _)| cidr.contains(ip))
+        .map(|(_, text)| *text)
+}
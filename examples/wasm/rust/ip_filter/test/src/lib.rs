@@ -0,0 +1,87 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+/// An office network with one subnet carved out, behind a trusted proxy.
+fn office(remote_addr: &str) -> RequestFixture {
+    RequestFixture::new("")
+        .remote_addr(remote_addr)
+        .static_data("allow", json!(["10.0.0.0/8", "2001:db8::/32"]))
+        .static_data("deny", json!(["10.66.0.0/16"]))
+        .static_data("trusted_proxies", json!(["192.0.2.10"]))
+}
+
+fn filter(fixture: RequestFixture) -> Result<Value, Error> {
+    let Json(decision): Json<Value> = xtp_test::call("FilterIp", fixture.to_json())?;
+    Ok(decision)
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("allow and deny", || {
+        let decision = filter(office("10.1.2.3:40000"))?;
+        xtp_test::assert_eq!(
+            "an allowed network gets in",
+            &decision,
+            &json!({
+                "allowed": true,
+                "status": 200,
+                "client_ip": "10.1.2.3",
+                "network": "10.0.0.0/8"
+            })
+        );
+
+        let decision = filter(office("10.66.1.1:40000"))?;
+        xtp_test::assert_eq!("deny wins over allow", &decision["status"], &json!(403));
+        xtp_test::assert_eq!(
+            "the reason is structured",
+            &decision["reason"],
+            &json!("denied")
+        );
+
+        let decision = filter(office("203.0.113.5:40000"))?;
+        xtp_test::assert_eq!(
+            "addresses outside allow are blocked",
+            &decision["reason"],
+            &json!("not_allowed")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("IPv6", || {
+        let decision = filter(office("[2001:db8::7]:443"))?;
+        xtp_test::assert_eq!("IPv6 networks match", &decision["allowed"], &json!(true));
+        let decision = filter(office("[::ffff:10.66.0.1]:443"))?;
+        xtp_test::assert_eq!(
+            "IPv4-mapped addresses are checked as IPv4",
+            &decision["client_ip"],
+            &json!("10.66.0.1")
+        );
+        xtp_test::assert_eq!(
+            "and blocked like them",
+            &decision["reason"],
+            &json!("denied")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("trusted proxies", || {
+        let decision =
+            filter(office("192.0.2.10:5000").header("X-Forwarded-For", "10.1.2.3, 203.0.113.9"))?;
+        xtp_test::assert_eq!(
+            "the right-most untrusted hop is the client",
+            &decision["client_ip"],
+            &json!("203.0.113.9")
+        );
+
+        let decision = filter(office("203.0.113.9:5000").header("X-Forwarded-For", "10.1.2.3"))?;
+        xtp_test::assert_eq!(
+            "headers from untrusted peers are ignored",
+            &decision["allowed"],
+            &json!(false)
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+mod validate;
+
+use std::time::Duration;
+
+use firelynx_pdk::allowlist::AllowList;
+use firelynx_pdk::http::{self, ResponseLimits};
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use validate::{SchemaError, Validator, Violation};
+
+/// Prefix of the extism var caching each `schema_url`'s schema for the
+/// life of the plugin instance.
+const SCHEMA_VAR_PREFIX: &str = "json_validator.schema:";
+
+/// Media types a `schema_url` may answer with.
+const SCHEMA_TYPES: [&str; 2] = ["application/schema+json", "application/json"];
+
+/// Whether the body passed, and why not. Matches `ValidationReport` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    /// The first `max_violations` violations, in body order.
+    pub violations: Vec<Violation>,
+    /// True when there were more violations than `violations` lists.
+    pub violations_truncated: bool,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    schema: Option<Value>,
+    #[serde(default)]
+    schema_url: Option<String>,
+    max_violations: u64,
+    max_schema_bytes: u64,
+    schema_timeout_ms: u64,
+    #[serde(default)]
+    outbound: Option<AllowList>,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::table("schema"),
+        Field::string("schema_url").non_empty(),
+        Field::integer("max_violations").default(DefaultValue::Int(50)),
+        Field::integer("max_schema_bytes").default(DefaultValue::Int(256 * 1024)),
+        Field::integer("schema_timeout_ms").default(DefaultValue::Int(5000)),
+        Field::table("outbound"),
+    ];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+}
+
+#[firelynx_plugin]
+fn validate_body(request: Request, static_data: StaticData) -> Result<ValidationReport> {
+    let config = Config::from_static_data(&static_data)?;
+    let (schema, field) = match (&config.schema, &config.schema_url) {
+        (Some(schema), None) => (schema.clone(), "schema"),
+        (None, Some(url)) => (fetch_schema(url, &config)?, "schema_url"),
+        (None, None) => {
+            return Err(PluginError::config("schema or schema_url is required")
+                .with_detail("field", "schema"))
+        }
+        (Some(_), Some(_)) => {
+            return Err(
+                PluginError::config("schema and schema_url cannot both be set")
+                    .with_detail("field", "schema_url"),
+            )
+        }
+    };
+    let validator = Validator::compile(&schema).map_err(|e| schema_error(e, field))?;
+
+    // Whatever the Content-Type says, the body is checked as JSON.
+    let body: Value = request.decode_as("application/json")?;
+    let outcome = validator
+        .validate(
+            &body,
+            usize::try_from(config.max_violations).unwrap_or(usize::MAX),
+        )
+        .map_err(|e| schema_error(e, field))?;
+    if !outcome.violations.is_empty() {
+        log_debug!(
+            "body failed validation",
+            violations = outcome.violations.len(),
+            first = outcome.violations[0].pointer,
+        );
+    }
+    Ok(ValidationReport {
+        valid: outcome.violations.is_empty() && !outcome.truncated,
+        violations: outcome.violations,
+        violations_truncated: outcome.truncated,
+    })
+}
+
+/// The schema at `url`, fetched on the first call of the instance and
+/// kept in an extism var after that.
+fn fetch_schema(url: &str, config: &Config) -> Result<Value> {
+    let key = format!("{}{}", SCHEMA_VAR_PREFIX, url);
+    if let Ok(Some(Json(schema))) = extism_pdk::var::get::<Json<Value>>(&key) {
+        return Ok(schema);
+    }
+
+    let allow = config.outbound.clone().unwrap_or_default();
+    let limits = ResponseLimits {
+        max_body_bytes: usize::try_from(config.max_schema_bytes).unwrap_or(usize::MAX),
+        content_types: SCHEMA_TYPES.iter().map(|t| t.to_string()).collect(),
+        ..ResponseLimits::default()
+    };
+    let request = http::Request::get(url)
+        .header("accept", SCHEMA_TYPES.join(", "))
+        .timeout(Duration::from_millis(config.schema_timeout_ms))
+        .limits(limits);
+    let schema: Value = http::fetch(request, &allow)
+        .and_then(http::Response::error_for_status)
+        .and_then(|resp| resp.json())
+        .map_err(|e| e.with_detail("field", "schema_url"))?;
+
+    // A failed cache write only costs the next call another fetch.
+    let _ = extism_pdk::var::set(&key, Json(&schema));
+    Ok(schema)
+}
+
+fn schema_error(e: SchemaError, field: &str) -> PluginError {
+    PluginError::config("The schema is not a usable JSON Schema")
+        .with_detail("field", field)
+        .with_detail("schema_pointer", e.pointer)
+        .with_detail("reason", e.reason)
+}
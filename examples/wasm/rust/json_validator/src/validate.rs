@@ -0,0 +1,978 @@
+//! JSON Schema validation for the keywords a gateway checks bodies with.
+//!
+//! The schema is compiled once per call into a table of nodes, one per
+//! subschema, so a `$ref` is an index and a recursive schema is a cycle in
+//! the table rather than an endless expansion. Supported, with draft 2020-12
+//! semantics:
+//!
+//! - any value: `type`, `enum`, `const`, `allOf`, `anyOf`, `oneOf`, `not`,
+//!   and `$ref` to a `#` JSON Pointer fragment of the same schema (`$defs`)
+//! - numbers: `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`,
+//!   `multipleOf`
+//! - strings: `minLength`, `maxLength` (in characters), `pattern`
+//! - arrays: `prefixItems`, `items`, `contains`, `minContains`,
+//!   `maxContains`, `minItems`, `maxItems`, `uniqueItems`
+//! - objects: `properties`, `patternProperties`, `additionalProperties`,
+//!   `propertyNames`, `required`, `dependentRequired`, `minProperties`,
+//!   `maxProperties`
+//!
+//! Annotations (`title`, `description`, `format`, ...) and unknown keywords
+//! are ignored, as the specification asks. Keywords that assert something
+//! but are not implemented here (`UNSUPPORTED`) make the schema an error
+//! instead: a body must not pass because part of its schema was skipped.
+
+use std::collections::HashMap;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+/// Assertion keywords this validator does not implement.
+const UNSUPPORTED: [&str; 6] = [
+    "if",
+    "dependentSchemas",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+    "$dynamicRef",
+    "$recursiveRef",
+];
+
+/// Upper bound on one compiled `pattern`; a larger one (such as a big
+/// counted repetition) is a schema error rather than an allocation that
+/// exhausts the instance's memory.
+const MAX_PATTERN_BYTES: usize = 1 << 20;
+
+/// How many in-place applicators (`$ref`, `allOf`, ...) may apply to one
+/// value before the schema is taken to loop without descending into it.
+const MAX_IN_PLACE: u32 = 64;
+
+/// A schema that cannot be used, and where in it the problem is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// JSON Pointer into the schema.
+    pub pointer: String,
+    pub reason: String,
+}
+
+fn error(pointer: &str, reason: impl Into<String>) -> SchemaError {
+    SchemaError {
+        pointer: pointer.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// One way the body fails the schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    /// JSON Pointer to the offending value in the body; `""` is the body.
+    pub pointer: String,
+    /// The schema keyword that failed, or `false` for a `false` schema.
+    pub keyword: String,
+    /// JSON Pointer to the failing keyword in the schema.
+    pub schema_pointer: String,
+    pub message: String,
+}
+
+/// The violations found, up to the limit.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub violations: Vec<Violation>,
+    /// True when there were more violations than the limit.
+    pub truncated: bool,
+}
+
+impl Outcome {
+    fn push(
+        &mut self,
+        max: usize,
+        pointer: &str,
+        schema_pointer: String,
+        keyword: &str,
+        message: String,
+    ) {
+        if self.violations.len() >= max {
+            self.truncated = true;
+            return;
+        }
+        self.violations.push(Violation {
+            pointer: pointer.to_string(),
+            keyword: keyword.to_string(),
+            schema_pointer,
+            message,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    Integer,
+    String,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Type> {
+        match name {
+            "null" => Some(Type::Null),
+            "boolean" => Some(Type::Boolean),
+            "object" => Some(Type::Object),
+            "array" => Some(Type::Array),
+            "number" => Some(Type::Number),
+            "integer" => Some(Type::Integer),
+            "string" => Some(Type::String),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Boolean => "boolean",
+            Type::Object => "object",
+            Type::Array => "array",
+            Type::Number => "number",
+            Type::Integer => "integer",
+            Type::String => "string",
+        }
+    }
+
+    /// Integers are numbers, and so is `1.0` an integer.
+    fn matches(self, value: &Value) -> bool {
+        match (self, value) {
+            (Type::Null, Value::Null)
+            | (Type::Boolean, Value::Bool(_))
+            | (Type::Object, Value::Object(_))
+            | (Type::Array, Value::Array(_))
+            | (Type::Number, Value::Number(_))
+            | (Type::String, Value::String(_)) => true,
+            (Type::Integer, Value::Number(n)) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The name of `value`'s type, `integer` for whole numbers.
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        v if Type::Integer.matches(v) => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+    }
+}
+
+/// The keywords of one object schema. Subschemas are node indexes.
+#[derive(Debug, Default)]
+struct Keywords {
+    reference: Option<usize>,
+    types: Option<Vec<Type>>,
+    enumeration: Option<Vec<Value>>,
+    constant: Option<Value>,
+    all_of: Vec<usize>,
+    any_of: Vec<usize>,
+    one_of: Vec<usize>,
+    not: Option<usize>,
+
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+
+    min_length: Option<u64>,
+    max_length: Option<u64>,
+    pattern: Option<(String, Regex)>,
+
+    prefix_items: Vec<usize>,
+    items: Option<usize>,
+    contains: Option<usize>,
+    min_contains: Option<u64>,
+    max_contains: Option<u64>,
+    min_items: Option<u64>,
+    max_items: Option<u64>,
+    unique_items: bool,
+
+    properties: Vec<(String, usize)>,
+    pattern_properties: Vec<(Regex, usize)>,
+    additional_properties: Option<usize>,
+    property_names: Option<usize>,
+    required: Vec<String>,
+    dependent_required: Vec<(String, Vec<String>)>,
+    min_properties: Option<u64>,
+    max_properties: Option<u64>,
+}
+
+#[derive(Debug)]
+enum Kind {
+    /// `true`.
+    Any,
+    /// `false`.
+    Never,
+    Keywords(Box<Keywords>),
+}
+
+#[derive(Debug)]
+struct Node {
+    /// JSON Pointer to this subschema.
+    pointer: String,
+    kind: Kind,
+}
+
+/// A compiled schema; node 0 is the root.
+#[derive(Debug)]
+pub struct Validator {
+    nodes: Vec<Node>,
+}
+
+impl Validator {
+    pub fn compile(schema: &Value) -> Result<Validator, SchemaError> {
+        let mut compiler = Compiler {
+            root: schema,
+            nodes: Vec::new(),
+            compiled: HashMap::new(),
+        };
+        compiler.node(schema, String::new())?;
+        Ok(Validator {
+            nodes: compiler.nodes,
+        })
+    }
+
+    /// Checks `instance`, keeping the first `max_violations` violations.
+    /// Fails only for a schema that applies itself to a value without end.
+    pub fn validate(
+        &self,
+        instance: &Value,
+        max_violations: usize,
+    ) -> Result<Outcome, SchemaError> {
+        let mut outcome = Outcome::default();
+        Check {
+            nodes: &self.nodes,
+            max: max_violations,
+        }
+        .node(0, instance, "", 0, &mut outcome)?;
+        Ok(outcome)
+    }
+}
+
+struct Compiler<'s> {
+    root: &'s Value,
+    nodes: Vec<Node>,
+    /// Node index by schema pointer, so each subschema is compiled once
+    /// and `$ref` cycles close.
+    compiled: HashMap<String, usize>,
+}
+
+impl Compiler<'_> {
+    fn node(&mut self, schema: &Value, pointer: String) -> Result<usize, SchemaError> {
+        if let Some(&index) = self.compiled.get(&pointer) {
+            return Ok(index);
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            pointer: pointer.clone(),
+            kind: Kind::Any,
+        });
+        self.compiled.insert(pointer.clone(), index);
+        let kind = match schema {
+            Value::Bool(true) => Kind::Any,
+            Value::Bool(false) => Kind::Never,
+            Value::Object(map) => Kind::Keywords(Box::new(self.keywords(map, &pointer)?)),
+            _ => return Err(error(&pointer, "a schema must be an object or a boolean")),
+        };
+        self.nodes[index].kind = kind;
+        Ok(index)
+    }
+
+    fn keywords(&mut self, map: &Map<String, Value>, at: &str) -> Result<Keywords, SchemaError> {
+        if let Some(keyword) = UNSUPPORTED.iter().find(|k| map.contains_key(**k)) {
+            return Err(error(
+                &child(at, keyword),
+                format!("{} is not supported", keyword),
+            ));
+        }
+        let mut k = Keywords::default();
+        if let Some(value) = map.get("$ref") {
+            k.reference = Some(self.reference(value, &child(at, "$ref"))?);
+        }
+        if let Some(value) = map.get("type") {
+            k.types = Some(types(value, &child(at, "type"))?);
+        }
+        if let Some(value) = map.get("enum") {
+            let Some(values) = value.as_array() else {
+                return Err(error(&child(at, "enum"), "enum must be an array"));
+            };
+            k.enumeration = Some(values.clone());
+        }
+        k.constant = map.get("const").cloned();
+        k.all_of = self.list(map, at, "allOf")?;
+        k.any_of = self.list(map, at, "anyOf")?;
+        k.one_of = self.list(map, at, "oneOf")?;
+        k.not = self.optional(map, at, "not")?;
+
+        k.minimum = number(map, at, "minimum")?;
+        k.maximum = number(map, at, "maximum")?;
+        k.exclusive_minimum = number(map, at, "exclusiveMinimum")?;
+        k.exclusive_maximum = number(map, at, "exclusiveMaximum")?;
+        k.multiple_of = number(map, at, "multipleOf")?;
+        if k.multiple_of.is_some_and(|m| m <= 0.0) {
+            return Err(error(
+                &child(at, "multipleOf"),
+                "multipleOf must be greater than 0",
+            ));
+        }
+
+        k.min_length = count(map, at, "minLength")?;
+        k.max_length = count(map, at, "maxLength")?;
+        if let Some(value) = map.get("pattern") {
+            let pointer = child(at, "pattern");
+            let source = value
+                .as_str()
+                .ok_or_else(|| error(&pointer, "pattern must be a string"))?;
+            k.pattern = Some((source.to_string(), regex(source, &pointer)?));
+        }
+
+        if let Some(value) = map.get("prefixItems") {
+            k.prefix_items = self.array(value, &child(at, "prefixItems"))?;
+        }
+        if let Some(Value::Array(_)) = map.get("items") {
+            return Err(error(
+                &child(at, "items"),
+                "items as an array is the draft 2019-09 form; use prefixItems",
+            ));
+        }
+        k.items = self.optional(map, at, "items")?;
+        k.contains = self.optional(map, at, "contains")?;
+        k.min_contains = count(map, at, "minContains")?;
+        k.max_contains = count(map, at, "maxContains")?;
+        k.min_items = count(map, at, "minItems")?;
+        k.max_items = count(map, at, "maxItems")?;
+        if let Some(value) = map.get("uniqueItems") {
+            k.unique_items = value
+                .as_bool()
+                .ok_or_else(|| error(&child(at, "uniqueItems"), "uniqueItems must be a boolean"))?;
+        }
+
+        k.properties = self.members(map, at, "properties")?;
+        if let Some(value) = map.get("patternProperties") {
+            let pointer = child(at, "patternProperties");
+            let Some(patterns) = value.as_object() else {
+                return Err(error(&pointer, "patternProperties must be an object"));
+            };
+            for (source, schema) in patterns {
+                let pointer = child(&pointer, source);
+                let pattern = regex(source, &pointer)?;
+                k.pattern_properties
+                    .push((pattern, self.node(schema, pointer)?));
+            }
+        }
+        k.additional_properties = self.optional(map, at, "additionalProperties")?;
+        k.property_names = self.optional(map, at, "propertyNames")?;
+        if let Some(value) = map.get("required") {
+            k.required = strings(value, &child(at, "required"))?;
+        }
+        if let Some(value) = map.get("dependentRequired") {
+            let pointer = child(at, "dependentRequired");
+            let Some(dependencies) = value.as_object() else {
+                return Err(error(&pointer, "dependentRequired must be an object"));
+            };
+            for (name, required) in dependencies {
+                let required = strings(required, &child(&pointer, name))?;
+                k.dependent_required.push((name.clone(), required));
+            }
+        }
+        k.min_properties = count(map, at, "minProperties")?;
+        k.max_properties = count(map, at, "maxProperties")?;
+        Ok(k)
+    }
+
+    fn reference(&mut self, value: &Value, at: &str) -> Result<usize, SchemaError> {
+        let Some(reference) = value.as_str() else {
+            return Err(error(at, "$ref must be a string"));
+        };
+        let Some(target) = reference.strip_prefix('#') else {
+            return Err(error(
+                at,
+                format!(
+                    "{} is not a local reference; only #/... references are supported",
+                    reference
+                ),
+            ));
+        };
+        if !target.is_empty() && !target.starts_with('/') {
+            return Err(error(
+                at,
+                format!(
+                    "{} is an anchor; only JSON Pointer references are supported",
+                    reference
+                ),
+            ));
+        }
+        let root = self.root;
+        let Some(schema) = root.pointer(target) else {
+            return Err(error(at, format!("{} does not resolve", reference)));
+        };
+        self.node(schema, target.to_string())
+    }
+
+    fn optional(
+        &mut self,
+        map: &Map<String, Value>,
+        at: &str,
+        keyword: &str,
+    ) -> Result<Option<usize>, SchemaError> {
+        match map.get(keyword) {
+            Some(schema) => Ok(Some(self.node(schema, child(at, keyword))?)),
+            None => Ok(None),
+        }
+    }
+
+    fn list(
+        &mut self,
+        map: &Map<String, Value>,
+        at: &str,
+        keyword: &str,
+    ) -> Result<Vec<usize>, SchemaError> {
+        match map.get(keyword) {
+            Some(value) => {
+                let pointer = child(at, keyword);
+                let nodes = self.array(value, &pointer)?;
+                if nodes.is_empty() {
+                    return Err(error(&pointer, format!("{} must not be empty", keyword)));
+                }
+                Ok(nodes)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn array(&mut self, value: &Value, at: &str) -> Result<Vec<usize>, SchemaError> {
+        let Some(schemas) = value.as_array() else {
+            return Err(error(at, "must be an array of schemas"));
+        };
+        schemas
+            .iter()
+            .enumerate()
+            .map(|(i, schema)| self.node(schema, format!("{}/{}", at, i)))
+            .collect()
+    }
+
+    fn members(
+        &mut self,
+        map: &Map<String, Value>,
+        at: &str,
+        keyword: &str,
+    ) -> Result<Vec<(String, usize)>, SchemaError> {
+        let Some(value) = map.get(keyword) else {
+            return Ok(Vec::new());
+        };
+        let pointer = child(at, keyword);
+        let Some(members) = value.as_object() else {
+            return Err(error(&pointer, format!("{} must be an object", keyword)));
+        };
+        members
+            .iter()
+            .map(|(name, schema)| Ok((name.clone(), self.node(schema, child(&pointer, name))?)))
+            .collect()
+    }
+}
+
+fn types(value: &Value, at: &str) -> Result<Vec<Type>, SchemaError> {
+    let names: Vec<&Value> = match value {
+        Value::Array(names) => names.iter().collect(),
+        name => vec![name],
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            name.as_str()
+                .and_then(Type::parse)
+                .ok_or_else(|| error(at, format!("{} is not a JSON Schema type", name)))
+        })
+        .collect()
+}
+
+fn number(map: &Map<String, Value>, at: &str, keyword: &str) -> Result<Option<f64>, SchemaError> {
+    match map.get(keyword) {
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| error(&child(at, keyword), format!("{} must be a number", keyword))),
+        None => Ok(None),
+    }
+}
+
+fn count(map: &Map<String, Value>, at: &str, keyword: &str) -> Result<Option<u64>, SchemaError> {
+    match map.get(keyword) {
+        Some(value) if Type::Integer.matches(value) && value.as_f64().is_some_and(|f| f >= 0.0) => {
+            Ok(Some(value.as_u64().unwrap_or(u64::MAX)))
+        }
+        Some(_) => Err(error(
+            &child(at, keyword),
+            format!("{} must be a non-negative integer", keyword),
+        )),
+        None => Ok(None),
+    }
+}
+
+fn strings(value: &Value, at: &str) -> Result<Vec<String>, SchemaError> {
+    value
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| error(at, "must be an array of strings"))
+}
+
+fn regex(source: &str, at: &str) -> Result<Regex, SchemaError> {
+    RegexBuilder::new(source)
+        .size_limit(MAX_PATTERN_BYTES)
+        .build()
+        .map_err(|e| error(at, format!("{} is not a valid pattern: {}", source, e)))
+}
+
+/// `pointer` extended by one reference token.
+fn child(pointer: &str, token: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        token.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+/// JSON equality, under which `1` and `1.0` are the same number.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => same_number(x, y),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, a)| y.get(k).is_some_and(|b| equal(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+fn same_number(x: &Number, y: &Number) -> bool {
+    if let (Some(x), Some(y)) = (x.as_i64(), y.as_i64()) {
+        return x == y;
+    }
+    if let (Some(x), Some(y)) = (x.as_u64(), y.as_u64()) {
+        return x == y;
+    }
+    x.as_f64() == y.as_f64()
+}
+
+fn is_multiple(x: &Number, of: f64) -> bool {
+    if let Some(x) = x.as_i64() {
+        if of.fract() == 0.0 && of < i64::MAX as f64 {
+            return x % (of as i64) == 0;
+        }
+    }
+    let quotient = x.as_f64().unwrap_or(f64::NAN) / of;
+    // Decimal steps such as 0.1 are not exact in binary.
+    (quotient - quotient.round()).abs() <= 1e-9 * quotient.abs().max(1.0)
+}
+
+/// `n` with the noun for one or for several.
+fn plural(n: usize, one: &str, several: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { several })
+}
+
+struct Check<'v> {
+    nodes: &'v [Node],
+    max: usize,
+}
+
+impl Check<'_> {
+    /// Whether `instance` passes `node`, without recording why not.
+    fn passes(
+        &self,
+        node: usize,
+        instance: &Value,
+        pointer: &str,
+        in_place: u32,
+    ) -> Result<bool, SchemaError> {
+        let mut outcome = Outcome::default();
+        Check {
+            nodes: self.nodes,
+            max: 0,
+        }
+        .node(node, instance, pointer, in_place, &mut outcome)?;
+        Ok(!outcome.truncated)
+    }
+
+    fn node(
+        &self,
+        index: usize,
+        instance: &Value,
+        pointer: &str,
+        in_place: u32,
+        out: &mut Outcome,
+    ) -> Result<(), SchemaError> {
+        if out.truncated {
+            return Ok(());
+        }
+        let node = &self.nodes[index];
+        if in_place > MAX_IN_PLACE {
+            return Err(error(
+                &node.pointer,
+                "the schema applies itself to the same value without end",
+            ));
+        }
+        let k = match &node.kind {
+            Kind::Any => return Ok(()),
+            Kind::Never => {
+                out.push(
+                    self.max,
+                    pointer,
+                    node.pointer.clone(),
+                    "false",
+                    "no value is allowed here".to_string(),
+                );
+                return Ok(());
+            }
+            Kind::Keywords(k) => k,
+        };
+        let mut fail = |out: &mut Outcome, keyword: &str, message: String| {
+            out.push(
+                self.max,
+                pointer,
+                child(&node.pointer, keyword),
+                keyword,
+                message,
+            )
+        };
+
+        if let Some(reference) = k.reference {
+            self.node(reference, instance, pointer, in_place + 1, out)?;
+        }
+        if let Some(types) = &k.types {
+            if !types.iter().any(|t| t.matches(instance)) {
+                let expected: Vec<&str> = types.iter().map(|t| t.name()).collect();
+                fail(
+                    out,
+                    "type",
+                    format!(
+                        "expected {}, got {}",
+                        expected.join(" or "),
+                        type_of(instance)
+                    ),
+                );
+            }
+        }
+        if let Some(values) = &k.enumeration {
+            if !values.iter().any(|v| equal(v, instance)) {
+                fail(
+                    out,
+                    "enum",
+                    "value is not one of the enum values".to_string(),
+                );
+            }
+        }
+        if let Some(value) = &k.constant {
+            if !equal(value, instance) {
+                fail(out, "const", format!("value is not {}", value));
+            }
+        }
+        for &schema in &k.all_of {
+            self.node(schema, instance, pointer, in_place + 1, out)?;
+        }
+        if !k.any_of.is_empty() {
+            let mut any = false;
+            for &schema in &k.any_of {
+                if self.passes(schema, instance, pointer, in_place + 1)? {
+                    any = true;
+                    break;
+                }
+            }
+            if !any {
+                fail(
+                    out,
+                    "anyOf",
+                    "value matches none of the anyOf schemas".to_string(),
+                );
+            }
+        }
+        if !k.one_of.is_empty() {
+            let mut matched = 0;
+            for &schema in &k.one_of {
+                if self.passes(schema, instance, pointer, in_place + 1)? {
+                    matched += 1;
+                }
+            }
+            if matched != 1 {
+                fail(
+                    out,
+                    "oneOf",
+                    format!(
+                        "value matches {} of the oneOf schemas, not exactly one",
+                        matched
+                    ),
+                );
+            }
+        }
+        if let Some(schema) = k.not {
+            if self.passes(schema, instance, pointer, in_place + 1)? {
+                fail(out, "not", "value matches the not schema".to_string());
+            }
+        }
+
+        match instance {
+            Value::Number(n) => {
+                let x = n.as_f64().unwrap_or(f64::NAN);
+                if let Some(min) = k.minimum.filter(|&min| x < min) {
+                    fail(out, "minimum", format!("{} is less than {}", n, min));
+                }
+                if let Some(max) = k.maximum.filter(|&max| x > max) {
+                    fail(out, "maximum", format!("{} is greater than {}", n, max));
+                }
+                if let Some(min) = k.exclusive_minimum.filter(|&min| x <= min) {
+                    fail(
+                        out,
+                        "exclusiveMinimum",
+                        format!("{} is not greater than {}", n, min),
+                    );
+                }
+                if let Some(max) = k.exclusive_maximum.filter(|&max| x >= max) {
+                    fail(
+                        out,
+                        "exclusiveMaximum",
+                        format!("{} is not less than {}", n, max),
+                    );
+                }
+                if let Some(of) = k.multiple_of.filter(|&of| !is_multiple(n, of)) {
+                    fail(
+                        out,
+                        "multipleOf",
+                        format!("{} is not a multiple of {}", n, of),
+                    );
+                }
+            }
+            Value::String(s) => {
+                let length = s.chars().count() as u64;
+                if let Some(min) = k.min_length.filter(|&min| length < min) {
+                    fail(
+                        out,
+                        "minLength",
+                        format!("string is {} characters long, fewer than {}", length, min),
+                    );
+                }
+                if let Some(max) = k.max_length.filter(|&max| length > max) {
+                    fail(
+                        out,
+                        "maxLength",
+                        format!("string is {} characters long, more than {}", length, max),
+                    );
+                }
+                if let Some((source, pattern)) = &k.pattern {
+                    if !pattern.is_match(s) {
+                        fail(out, "pattern", format!("string does not match {}", source));
+                    }
+                }
+            }
+            Value::Array(items) => self.array(k, items, pointer, out, &mut fail)?,
+            Value::Object(members) => self.object(k, members, pointer, out, &mut fail)?,
+            Value::Null | Value::Bool(_) => {}
+        }
+        Ok(())
+    }
+
+    fn array(
+        &self,
+        k: &Keywords,
+        items: &[Value],
+        pointer: &str,
+        out: &mut Outcome,
+        fail: &mut impl FnMut(&mut Outcome, &str, String),
+    ) -> Result<(), SchemaError> {
+        let len = items.len() as u64;
+        if let Some(min) = k.min_items.filter(|&min| len < min) {
+            fail(
+                out,
+                "minItems",
+                format!(
+                    "array has {}, fewer than {}",
+                    plural(items.len(), "item", "items"),
+                    min
+                ),
+            );
+        }
+        if let Some(max) = k.max_items.filter(|&max| len > max) {
+            fail(
+                out,
+                "maxItems",
+                format!(
+                    "array has {}, more than {}",
+                    plural(items.len(), "item", "items"),
+                    max
+                ),
+            );
+        }
+        if k.unique_items {
+            let duplicate = (0..items.len()).find_map(|j| {
+                (0..j)
+                    .find(|&i| equal(&items[i], &items[j]))
+                    .map(|i| (i, j))
+            });
+            if let Some((i, j)) = duplicate {
+                fail(
+                    out,
+                    "uniqueItems",
+                    format!("items {} and {} are equal", i, j),
+                );
+            }
+        }
+        for (i, item) in items.iter().enumerate() {
+            if out.truncated {
+                return Ok(());
+            }
+            let schema = match k.prefix_items.get(i) {
+                Some(&schema) => Some(schema),
+                None => k.items,
+            };
+            if let Some(schema) = schema {
+                self.node(schema, item, &format!("{}/{}", pointer, i), 0, out)?;
+            }
+        }
+        if let Some(contains) = k.contains {
+            let mut matched = 0;
+            for (i, item) in items.iter().enumerate() {
+                if self.passes(contains, item, &format!("{}/{}", pointer, i), 0)? {
+                    matched += 1;
+                }
+            }
+            let min = k.min_contains.unwrap_or(1);
+            if (matched as u64) < min {
+                fail(
+                    out,
+                    "contains",
+                    format!(
+                        "array has {} matching contains, fewer than {}",
+                        plural(matched, "item", "items"),
+                        min
+                    ),
+                );
+            }
+            if let Some(max) = k.max_contains.filter(|&max| matched as u64 > max) {
+                fail(
+                    out,
+                    "maxContains",
+                    format!(
+                        "array has {} matching contains, more than {}",
+                        plural(matched, "item", "items"),
+                        max
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn object(
+        &self,
+        k: &Keywords,
+        members: &Map<String, Value>,
+        pointer: &str,
+        out: &mut Outcome,
+        fail: &mut impl FnMut(&mut Outcome, &str, String),
+    ) -> Result<(), SchemaError> {
+        let len = members.len() as u64;
+        if let Some(min) = k.min_properties.filter(|&min| len < min) {
+            fail(
+                out,
+                "minProperties",
+                format!(
+                    "object has {}, fewer than {}",
+                    plural(members.len(), "property", "properties"),
+                    min
+                ),
+            );
+        }
+        if let Some(max) = k.max_properties.filter(|&max| len > max) {
+            fail(
+                out,
+                "maxProperties",
+                format!(
+                    "object has {}, more than {}",
+                    plural(members.len(), "property", "properties"),
+                    max
+                ),
+            );
+        }
+        for name in &k.required {
+            if !members.contains_key(name) {
+                fail(
+                    out,
+                    "required",
+                    format!("missing required property {:?}", name),
+                );
+            }
+        }
+        for (name, required) in &k.dependent_required {
+            if !members.contains_key(name) {
+                continue;
+            }
+            for other in required.iter().filter(|r| !members.contains_key(*r)) {
+                fail(
+                    out,
+                    "dependentRequired",
+                    format!(
+                        "property {:?} is required when {:?} is present",
+                        other, name
+                    ),
+                );
+            }
+        }
+        for (name, value) in members {
+            if out.truncated {
+                return Ok(());
+            }
+            let at = child(pointer, name);
+            if let Some(names) = k.property_names {
+                self.node(names, &Value::String(name.clone()), &at, 0, out)?;
+            }
+            let mut evaluated = false;
+            if let Some(&(_, schema)) = k.properties.iter().find(|(p, _)| p == name) {
+                evaluated = true;
+                self.node(schema, value, &at, 0, out)?;
+            }
+            for (pattern, schema) in &k.pattern_properties {
+                if pattern.is_match(name) {
+                    evaluated = true;
+                    self.node(*schema, value, &at, 0, out)?;
+                }
+            }
+            match k.additional_properties {
+                Some(schema) if !evaluated => {
+                    if matches!(self.nodes[schema].kind, Kind::Never) {
+                        // Name the property rather than "no value is allowed".
+                        out.push(
+                            self.max,
+                            &at,
+                            self.nodes[schema].pointer.clone(),
+                            "additionalProperties",
+                            format!("property {:?} is not allowed", name),
+                        );
+                    } else {
+                        self.node(schema, value, &at, 0, out)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn request(body: &str, schema: Value) -> String {
+    RequestFixture::new(body)
+        .method("POST")
+        .static_data("schema", schema)
+        .to_json()
+}
+
+fn validate(input: &str) -> Result<Value, Error> {
+    let Json(report): Json<Value> = xtp_test::call("ValidateBody", input)?;
+    Ok(report)
+}
+
+fn fails(input: &str) -> bool {
+    xtp_test::call::<Json<Value>>("ValidateBody", input).is_err()
+}
+
+fn pointers(report: &Value) -> Vec<Value> {
+    report["violations"]
+        .as_array()
+        .map(|violations| violations.iter().map(|v| v["pointer"].clone()).collect())
+        .unwrap_or_default()
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    let order = json!({
+        "type": "object",
+        "required": ["id", "items"],
+        "properties": {
+            "id": {"type": "integer"},
+            "items": {
+                "type": "array",
+                "minItems": 1,
+                "items": {"$ref": "#/$defs/item"}
+            }
+        },
+        "$defs": {
+            "item": {
+                "type": "object",
+                "required": ["sku"],
+                "properties": {
+                    "sku": {"type": "string", "pattern": "^[A-Z]{3}-[0-9]+$"},
+                    "quantity": {"type": "integer", "minimum": 1}
+                },
+                "additionalProperties": false
+            }
+        }
+    });
+
+    xtp_test::group("validation", || {
+        let report = validate(&request(
+            r#"{"id": 7, "items": [{"sku": "ABC-1", "quantity": 2}]}"#,
+            order.clone(),
+        ))?;
+        xtp_test::assert_eq!("valid body passes", &report["valid"], &json!(true));
+        xtp_test::assert_eq!("no violations", &report["violations"], &json!([]));
+
+        let report = validate(&request(
+            r#"{"id": "7", "items": [{"sku": "abc", "quantity": 0, "note": "x"}]}"#,
+            order.clone(),
+        ))?;
+        xtp_test::assert_eq!("invalid body fails", &report["valid"], &json!(false));
+        xtp_test::assert_eq!(
+            "each violation points into the body",
+            &pointers(&report),
+            &vec![
+                json!("/id"),
+                json!("/items/0/note"),
+                json!("/items/0/quantity"),
+                json!("/items/0/sku"),
+            ]
+        );
+        xtp_test::assert_eq!(
+            "the keyword and its place in the schema are named",
+            &report["violations"][3]["schema_pointer"],
+            &json!("/$defs/item/properties/sku/pattern")
+        );
+
+        let report = validate(&request(r#"{"items": []}"#, order.clone()))?;
+        xtp_test::assert_eq!(
+            "a missing property is reported at its object",
+            &report["violations"][0],
+            &json!({
+                "pointer": "",
+                "keyword": "required",
+                "schema_pointer": "/required",
+                "message": "missing required property \"id\"",
+            })
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("violation limit", || {
+        let strict = json!({"type": "array", "items": {"type": "string"}});
+        let input = RequestFixture::new("[1, 2, 3]")
+            .static_data("schema", strict.clone())
+            .static_data("max_violations", 2)
+            .to_json();
+        let report = validate(&input)?;
+        xtp_test::assert_eq!(
+            "violations stop at max_violations",
+            &pointers(&report),
+            &vec![json!("/0"), json!("/1")]
+        );
+        xtp_test::assert_eq!(
+            "truncation is reported",
+            &report["violations_truncated"],
+            &json!(true)
+        );
+
+        let input = RequestFixture::new("[1]")
+            .static_data("schema", strict)
+            .static_data("max_violations", 0)
+            .to_json();
+        let report = validate(&input)?;
+        xtp_test::assert_eq!(
+            "max_violations 0 still fails the body",
+            &report["valid"],
+            &json!(false)
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("configuration errors", || {
+        xtp_test::assert!(
+            "no schema is rejected",
+            fails(&RequestFixture::new("{}").empty_static_data().to_json())
+        );
+        xtp_test::assert!(
+            "unsupported keywords are rejected",
+            fails(&request("{}", json!({"if": {"required": ["a"]}})))
+        );
+        xtp_test::assert!(
+            "remote references are rejected",
+            fails(&request(
+                "{}",
+                json!({"$ref": "https://example.com/order.json"})
+            ))
+        );
+        xtp_test::assert!(
+            "a schema_url outside the allow-list is rejected",
+            fails(
+                &RequestFixture::new("{}")
+                    .static_data("schema_url", "https://schemas.example.com/order.json")
+                    .to_json()
+            )
+        );
+        xtp_test::assert!(
+            "a body that is not JSON is rejected",
+            fails(&request("id=7", order.clone()))
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,249 @@
+mod token;
+
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use token::{Key, KeyConfig, Token};
+
+/// Whether the request carried an acceptable bearer token. Matches
+/// `AuthDecision` in `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthDecision {
+    pub authenticated: bool,
+    /// The status to answer with: 200 when the token was accepted, 401 when
+    /// it was missing or invalid, 400 for a malformed `Authorization`
+    /// header.
+    pub status: u16,
+    /// The verified claims, for downstream routes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims: Option<Map<String, Value>>,
+    /// The RFC 6750 error code: `invalid_request` or `invalid_token`.
+    /// Absent when the request simply had no token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+    /// The `WWW-Authenticate` header value to send with a refusal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub www_authenticate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    keys: Vec<KeyConfig>,
+    #[serde(default)]
+    issuers: Vec<String>,
+    #[serde(default)]
+    audiences: Vec<String>,
+    leeway_seconds: u64,
+    require_exp: bool,
+    realm: String,
+    max_token_bytes: u64,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string_list("issuers"),
+        Field::string_list("audiences"),
+        Field::integer("leeway_seconds").default(DefaultValue::Int(60)),
+        Field::bool("require_exp").default(DefaultValue::Bool(true)),
+        Field::string("realm").default(DefaultValue::Str("firelynx")),
+        Field::integer("max_token_bytes").default(DefaultValue::Int(8192)),
+    ];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+}
+
+impl Config {
+    fn keys(&self) -> Result<Vec<Key>> {
+        if self.keys.is_empty() {
+            return Err(
+                PluginError::config("keys must list at least one verification key")
+                    .with_detail("field", "keys"),
+            );
+        }
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| Key::new(key, i))
+            .collect()
+    }
+
+    /// Checks the registered claims of a token whose signature verified.
+    fn check_claims(&self, claims: &Map<String, Value>, now: f64) -> Result<(), String> {
+        let leeway = self.leeway_seconds as f64;
+        match claims.get("exp") {
+            Some(exp) if now >= numeric_date(exp, "exp")? + leeway => {
+                return Err("token has expired".to_string())
+            }
+            None if self.require_exp => return Err("token has no exp claim".to_string()),
+            _ => {}
+        }
+        if let Some(nbf) = claims.get("nbf") {
+            if now + leeway < numeric_date(nbf, "nbf")? {
+                return Err("token is not valid yet".to_string());
+            }
+        }
+        if !self.issuers.is_empty() {
+            let issuer = claims.get("iss").and_then(Value::as_str);
+            if !issuer.is_some_and(|iss| self.issuers.iter().any(|i| i == iss)) {
+                return Err("token issuer is not accepted".to_string());
+            }
+        }
+        if !self.audiences.is_empty() {
+            let audiences: Vec<&str> = match claims.get("aud") {
+                Some(Value::String(aud)) => vec![aud],
+                Some(Value::Array(auds)) => auds.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !audiences
+                .iter()
+                .any(|aud| self.audiences.iter().any(|a| a == aud))
+            {
+                return Err("token audience is not accepted".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn refuse(&self, refusal: Refusal) -> AuthDecision {
+        let mut challenge = format!("Bearer realm=\"{}\"", quoted(&self.realm));
+        if let Some(error) = refusal.error {
+            challenge.push_str(&format!(", error=\"{}\"", error));
+        }
+        if let Some(description) = &refusal.description {
+            challenge.push_str(&format!(", error_description=\"{}\"", quoted(description)));
+        }
+        AuthDecision {
+            authenticated: false,
+            status: refusal.status,
+            claims: None,
+            error: refusal.error.map(str::to_string),
+            error_description: refusal.description,
+            www_authenticate: Some(challenge),
+        }
+    }
+}
+
+/// Why a request is turned away.
+struct Refusal {
+    status: u16,
+    error: Option<&'static str>,
+    description: Option<String>,
+}
+
+impl Refusal {
+    fn invalid_request(description: &str) -> Refusal {
+        Refusal {
+            status: 400,
+            error: Some("invalid_request"),
+            description: Some(description.to_string()),
+        }
+    }
+
+    fn invalid_token(description: String) -> Refusal {
+        Refusal {
+            status: 401,
+            error: Some("invalid_token"),
+            description: Some(description),
+        }
+    }
+}
+
+#[firelynx_plugin]
+fn verify_jwt(request: Request, static_data: StaticData) -> Result<AuthDecision> {
+    let config = Config::from_static_data(&static_data)?;
+    let keys = config.keys()?;
+    match authenticate(&request, &config, &keys) {
+        Ok(claims) => Ok(AuthDecision {
+            authenticated: true,
+            status: 200,
+            claims: Some(claims),
+            error: None,
+            error_description: None,
+            www_authenticate: None,
+        }),
+        Err(refusal) => {
+            log_info!(
+                "bearer token refused",
+                status = refusal.status,
+                reason = refusal.description,
+            );
+            Ok(config.refuse(refusal))
+        }
+    }
+}
+
+fn authenticate(
+    request: &Request,
+    config: &Config,
+    keys: &[Key],
+) -> Result<Map<String, Value>, Refusal> {
+    let token = match request.header_values("authorization") {
+        None => {
+            // RFC 6750: a request without credentials gets no error code.
+            return Err(Refusal {
+                status: 401,
+                error: None,
+                description: None,
+            });
+        }
+        Some([value]) => bearer(value)?,
+        Some(_) => {
+            return Err(Refusal::invalid_request(
+                "more than one Authorization header",
+            ))
+        }
+    };
+    if token.len() as u64 > config.max_token_bytes {
+        return Err(Refusal::invalid_token(format!(
+            "token is longer than {} bytes",
+            config.max_token_bytes
+        )));
+    }
+    let token = Token::decode(token).map_err(Refusal::invalid_token)?;
+    token.verify(keys).map_err(Refusal::invalid_token)?;
+    let now = clock::unix_millis() as f64 / 1000.0;
+    config
+        .check_claims(&token.claims, now)
+        .map_err(Refusal::invalid_token)?;
+    Ok(token.claims)
+}
+
+/// The token of a `Bearer` credential; the scheme is case-insensitive.
+fn bearer(value: &str) -> Result<&str, Refusal> {
+    let value = value.trim();
+    let (scheme, token) = value.split_once(' ').unwrap_or((value, ""));
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return Err(Refusal::invalid_request(
+            "Authorization is not a Bearer credential",
+        ));
+    }
+    let token = token.trim_start();
+    if token.is_empty() || token.contains(char::is_whitespace) {
+        return Err(Refusal::invalid_request("the Bearer token is malformed"));
+    }
+    Ok(token)
+}
+
+/// A NumericDate claim: seconds since the epoch, fractions allowed.
+fn numeric_date(value: &Value, claim: &str) -> Result<f64, String> {
+    value
+        .as_f64()
+        .filter(|seconds| seconds.is_finite())
+        .ok_or_else(|| format!("token {} claim is not a number", claim))
+}
+
+/// `text` made safe inside a quoted header parameter: RFC 6750 allows
+/// neither `"` nor `\` there, nor anything outside printable ASCII.
+fn quoted(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '"' | '\\' => '\'',
+            ' '..='~' => c,
+            _ => '?',
+        })
+        .collect()
+}
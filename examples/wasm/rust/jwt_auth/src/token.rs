@@ -0,0 +1,243 @@
+//! Compact JWS tokens (RFC 7515) and the keys that verify them.
+//!
+//! A token is `header.payload.signature`, each part base64url without
+//! padding. The configured keys decide which algorithms are accepted: a
+//! token is only checked against keys declared for the `alg` its header
+//! names, so it cannot select `none`, or have an RSA public key used as an
+//! HMAC secret.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use firelynx_pdk::limits::ParseLimits;
+use firelynx_pdk::PluginError;
+use hmac::{Hmac, Mac};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier as _;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+/// HMAC secrets shorter than this are rejected, as `firelynx_pdk::session`
+/// does: HS256 is only as strong as its secret.
+pub const MIN_SECRET_LEN: usize = 32;
+
+/// RSA moduli smaller than this are rejected.
+pub const MIN_RSA_BITS: usize = 2048;
+
+/// The signature algorithms a key can be declared for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    HS256,
+    RS256,
+    EdDSA,
+}
+
+impl Algorithm {
+    pub const NAMES: [&'static str; 3] = ["HS256", "RS256", "EdDSA"];
+
+    fn parse(name: &str) -> Option<Algorithm> {
+        match name {
+            "HS256" => Some(Algorithm::HS256),
+            "RS256" => Some(Algorithm::RS256),
+            "EdDSA" => Some(Algorithm::EdDSA),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of `static_data.keys`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyConfig {
+    /// Matched against the token's `kid` header when both have one.
+    #[serde(default)]
+    pub kid: Option<String>,
+    /// One of `Algorithm::NAMES`.
+    pub alg: String,
+    /// The shared secret, for `HS256`.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// A PEM public key, for `RS256` (SPKI or PKCS#1) and `EdDSA` (SPKI).
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+enum Verifier {
+    Hmac(Vec<u8>),
+    Rsa(Box<rsa::pkcs1v15::VerifyingKey<Sha256>>),
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+/// A configured key, ready to verify signatures.
+pub struct Key {
+    kid: Option<String>,
+    alg: Algorithm,
+    verifier: Verifier,
+}
+
+impl Key {
+    /// Builds key number `index` of `static_data.keys`; a key that cannot
+    /// be used is a `CONFIG_ERROR` naming it.
+    pub fn new(config: &KeyConfig, index: usize) -> Result<Key, PluginError> {
+        let invalid = |message: &str| {
+            let err = PluginError::config(message.to_string())
+                .with_detail("field", "keys")
+                .with_detail("index", index);
+            match &config.kid {
+                Some(kid) => err.with_detail("kid", kid.as_str()),
+                None => err,
+            }
+        };
+        let Some(alg) = Algorithm::parse(&config.alg) else {
+            return Err(invalid("alg must be HS256, RS256 or EdDSA")
+                .with_detail("value", config.alg.as_str())
+                .with_detail("supported", Algorithm::NAMES.to_vec()));
+        };
+        let verifier = match alg {
+            Algorithm::HS256 => {
+                let Some(secret) = &config.secret else {
+                    return Err(invalid("An HS256 key needs a secret"));
+                };
+                if secret.len() < MIN_SECRET_LEN {
+                    return Err(invalid(&format!(
+                        "HS256 secrets must be at least {} bytes",
+                        MIN_SECRET_LEN
+                    )));
+                }
+                Verifier::Hmac(secret.as_bytes().to_vec())
+            }
+            Algorithm::RS256 => {
+                let Some(pem) = &config.public_key else {
+                    return Err(invalid("An RS256 key needs a public_key"));
+                };
+                let key = RsaPublicKey::from_public_key_pem(pem)
+                    .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+                    .map_err(|e| {
+                        invalid("public_key is not a PEM RSA public key")
+                            .with_detail("reason", e.to_string())
+                    })?;
+                if key.size() * 8 < MIN_RSA_BITS {
+                    return Err(invalid(&format!(
+                        "RS256 keys must be at least {} bits",
+                        MIN_RSA_BITS
+                    )));
+                }
+                Verifier::Rsa(Box::new(rsa::pkcs1v15::VerifyingKey::new(key)))
+            }
+            Algorithm::EdDSA => {
+                let Some(pem) = &config.public_key else {
+                    return Err(invalid("An EdDSA key needs a public_key"));
+                };
+                let key = ed25519_dalek::VerifyingKey::from_public_key_pem(pem).map_err(|e| {
+                    invalid("public_key is not a PEM Ed25519 public key")
+                        .with_detail("reason", e.to_string())
+                })?;
+                Verifier::Ed25519(key)
+            }
+        };
+        Ok(Key {
+            kid: config.kid.clone(),
+            alg,
+            verifier,
+        })
+    }
+
+    fn verifies(&self, message: &[u8], signature: &[u8]) -> bool {
+        match &self.verifier {
+            Verifier::Hmac(secret) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+                    .expect("HMAC accepts keys of any length");
+                mac.update(message);
+                mac.verify_slice(signature).is_ok()
+            }
+            Verifier::Rsa(key) => rsa::pkcs1v15::Signature::try_from(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Verifier::Ed25519(key) => ed25519_dalek::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify_strict(message, &signature).is_ok()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+    /// Extensions the token says must be understood; none are.
+    #[serde(default)]
+    crit: Option<Value>,
+}
+
+/// A token that decoded, not yet verified.
+pub struct Token<'t> {
+    header: Header,
+    /// `header.payload`, the signed bytes.
+    signed: &'t str,
+    signature: Vec<u8>,
+    pub claims: Map<String, Value>,
+}
+
+impl<'t> Token<'t> {
+    /// Splits and decodes `token`. The error says what is malformed.
+    pub fn decode(token: &'t str) -> Result<Token<'t>, String> {
+        let (Some((signed, signature)), 2) = (token.rsplit_once('.'), token.matches('.').count())
+        else {
+            return Err("token is not three dot-separated parts".to_string());
+        };
+        let (header, payload) = signed.split_once('.').expect("two dots were counted");
+        let header: Header = decode_json(header, "header")?;
+        if header.crit.is_some() {
+            return Err("token has critical header extensions".to_string());
+        }
+        let claims: Map<String, Value> = decode_json(payload, "payload")?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| "token signature is not base64url".to_string())?;
+        Ok(Token {
+            header,
+            signed,
+            signature,
+            claims,
+        })
+    }
+
+    /// Checks the signature with the keys for the token's `alg`. When both
+    /// the token and a key name a `kid` they must match; a key without one
+    /// is tried for any token.
+    pub fn verify(&self, keys: &[Key]) -> Result<(), String> {
+        let Some(alg) = Algorithm::parse(&self.header.alg) else {
+            return Err(format!("alg {} is not accepted", self.header.alg));
+        };
+        let mut candidates = keys
+            .iter()
+            .filter(|key| key.alg == alg)
+            .filter(|key| match (&key.kid, &self.header.kid) {
+                (Some(key_kid), Some(kid)) => key_kid == kid,
+                _ => true,
+            })
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(match &self.header.kid {
+                Some(kid) => format!("no {} key has kid {}", self.header.alg, kid),
+                None => format!("alg {} is not accepted", self.header.alg),
+            });
+        }
+        if candidates.any(|key| key.verifies(self.signed.as_bytes(), &self.signature)) {
+            Ok(())
+        } else {
+            Err("token signature does not verify".to_string())
+        }
+    }
+}
+
+fn decode_json<T: serde::de::DeserializeOwned>(part: &str, name: &str) -> Result<T, String> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|_| format!("token {} is not base64url", name))?;
+    ParseLimits::active()
+        .check_json(&bytes)
+        .map_err(|_| format!("token {} exceeds the parse limits", name))?;
+    serde_json::from_slice(&bytes).map_err(|_| format!("token {} is not a JSON object", name))
+}
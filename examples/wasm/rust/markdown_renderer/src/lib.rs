@@ -0,0 +1,73 @@
+mod sanitize;
+
+use firelynx_pdk::prelude::*;
+use pulldown_cmark::{html, Options, Parser};
+use serde::{Deserialize, Serialize};
+
+/// The page to send. Matches `RenderedResponse` in `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedResponse {
+    pub status: u16,
+    /// The `Content-Type` header for `body`.
+    pub content_type: String,
+    pub body: String,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    tables: bool,
+    footnotes: bool,
+    code_classes: bool,
+    max_body_bytes: u64,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::bool("tables").default(DefaultValue::Bool(true)),
+        Field::bool("footnotes").default(DefaultValue::Bool(false)),
+        Field::bool("code_classes").default(DefaultValue::Bool(true)),
+        Field::integer("max_body_bytes").default(DefaultValue::Int(1024 * 1024)),
+    ];
+}
+
+impl Config {
+    fn options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options
+    }
+}
+
+#[firelynx_plugin]
+fn render_markdown(request: Request, static_data: StaticData) -> Result<RenderedResponse> {
+    let config = Config::from_static_data(&static_data)?;
+    let size = request.body.encoded_len() as u64;
+    if size > config.max_body_bytes {
+        return Err(PluginError::too_large(format!(
+            "The body is larger than max_body_bytes ({} bytes)",
+            config.max_body_bytes
+        ))
+        .with_detail("max_body_bytes", config.max_body_bytes)
+        .with_detail("body_bytes", size));
+    }
+
+    let markdown = request.body.text();
+    let events = sanitize::events(
+        Parser::new_ext(&markdown, config.options()),
+        config.code_classes,
+    );
+    let mut body = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut body, events);
+
+    log_debug!(
+        "rendered markdown",
+        markdown_bytes = markdown.len(),
+        html_bytes = body.len(),
+    );
+    Ok(RenderedResponse {
+        status: 200,
+        content_type: "text/html; charset=utf-8".to_string(),
+        body,
+    })
+}
@@ -0,0 +1,76 @@
+//! Rewrites parser events so a Markdown body from an untrusted client
+//! renders to HTML that carries no markup or URLs of the client's choosing.
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+
+/// The schemes a link or image may point to. URLs without a scheme are
+/// relative and always allowed.
+const SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// `events` with:
+/// - raw HTML, block or inline, turned into text, which the renderer escapes
+/// - links and images to any other scheme (`javascript:`, `data:`) reduced
+///   to their text
+/// - the info string of fenced code blocks cut to a language name, or
+///   dropped when `code_classes` is off, so it cannot add classes
+pub fn events<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    code_classes: bool,
+) -> impl Iterator<Item = Event<'a>> {
+    // Whether each open link or image kept its markup, for its end tag.
+    let mut open = Vec::new();
+    events.filter_map(move |event| {
+        let event = match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let language = if code_classes {
+                    language(&info)
+                } else {
+                    CowStr::Borrowed("")
+                };
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language)))
+            }
+            event => event,
+        };
+        match &event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                let kept = safe_url(dest_url);
+                open.push(kept);
+                kept.then_some(event)
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => open.pop().unwrap_or(true).then_some(event),
+            _ => Some(event),
+        }
+    })
+}
+
+/// The first word of a fence's info string if it reads as a language name
+/// (`rust`, `c++`, `objective-c`), else nothing. The renderer emits it as
+/// `class="language-<name>"`.
+fn language<'a>(info: &str) -> CowStr<'a> {
+    let word = info.split_whitespace().next().unwrap_or_default();
+    let valid = word
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '#' | '.'));
+    if valid {
+        CowStr::from(word.to_string())
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+/// Whether `url` is relative or has one of `SCHEMES`. Browsers ignore
+/// whitespace and control characters in a scheme (`java\tscript:`), so
+/// they are dropped before it is read.
+fn safe_url(url: &str) -> bool {
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => SCHEMES
+            .iter()
+            .any(|scheme| url[..end].eq_ignore_ascii_case(scheme)),
+        _ => true,
+    }
+}
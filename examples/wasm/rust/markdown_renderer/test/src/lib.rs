@@ -0,0 +1,109 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn render(input: &str) -> Result<String, Error> {
+    let Json(response): Json<Value> = xtp_test::call("RenderMarkdown", input)?;
+    Ok(response["body"].as_str().unwrap_or_default().to_string())
+}
+
+fn markdown(body: &str) -> RequestFixture {
+    RequestFixture::new(body)
+        .without_header("Content-Type")
+        .header("Content-Type", "text/markdown")
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("rendering", || {
+        let Json(response): Json<Value> =
+            xtp_test::call("RenderMarkdown", markdown("Hello *world*").to_json())?;
+        xtp_test::assert_eq!(
+            "inline markup is rendered",
+            &response["body"],
+            &json!("<p>Hello <em>world</em></p>\n")
+        );
+        xtp_test::assert_eq!(
+            "the page is HTML",
+            &response["content_type"],
+            &json!("text/html; charset=utf-8")
+        );
+
+        let table = "| a | b |\n|---|---|\n| 1 | 2 |\n";
+        xtp_test::assert!(
+            "tables are on by default",
+            render(&markdown(table).to_json())?.contains("<table>")
+        );
+        xtp_test::assert!(
+            "tables can be turned off",
+            !render(&markdown(table).static_data("tables", false).to_json())?.contains("<table>")
+        );
+
+        let note = "Text[^1].\n\n[^1]: The note.\n";
+        xtp_test::assert!(
+            "footnotes are off by default",
+            !render(&markdown(note).to_json())?.contains("footnote-definition")
+        );
+        xtp_test::assert!(
+            "footnotes can be turned on",
+            render(&markdown(note).static_data("footnotes", true).to_json())?
+                .contains("footnote-definition")
+        );
+
+        let code = "```rust\nfn main() {}\n```\n";
+        xtp_test::assert!(
+            "fenced code is classed by language",
+            render(&markdown(code).to_json())?.contains(r#"<code class="language-rust">"#)
+        );
+        xtp_test::assert!(
+            "code classes can be turned off",
+            render(&markdown(code).static_data("code_classes", false).to_json())?
+                .contains("<pre><code>")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("sanitizing", || {
+        let page =
+            render(&markdown("<script>alert(1)</script>\n\nHi <b onclick=x>there</b>").to_json())?;
+        xtp_test::assert!(
+            "raw HTML is escaped",
+            page.contains("&lt;script&gt;") && !page.contains("<script") && !page.contains("<b ")
+        );
+
+        let page = render(
+            &markdown("[click](javascript:alert(1)) and ![x](data:image/png;base64,AA)").to_json(),
+        )?;
+        xtp_test::assert!(
+            "unsafe links and images keep only their text",
+            page.contains("click") && !page.contains("<a") && !page.contains("<img")
+        );
+
+        let page = render(&markdown("[docs](https://example.com/docs) and [home](/)").to_json())?;
+        xtp_test::assert!(
+            "http and relative links are kept",
+            page.contains(r#"<a href="https://example.com/docs">"#)
+                && page.contains(r#"<a href="/">"#)
+        );
+
+        let page = render(&markdown("```rust\" onclick=\"x\nfn main() {}\n```\n").to_json())?;
+        xtp_test::assert!(
+            "a fence cannot add attributes",
+            page.contains("<pre><code>")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("limits", || {
+        let input = markdown("# Too long")
+            .static_data("max_body_bytes", 4)
+            .to_json();
+        xtp_test::assert!(
+            "a body over max_body_bytes is rejected",
+            xtp_test::call::<Json<Value>>("RenderMarkdown", input).is_err()
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
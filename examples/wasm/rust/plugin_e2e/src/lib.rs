@@ -0,0 +1,182 @@
+//! Drives the example plugins through the extism CLI, the way a host loads
+//! them: a release `wasm32-wasip1` module, a fresh instance per call, the
+//! envelope on stdin and extism config vars on the command line. The xtp
+//! tests run inside the runtime and only see handler results; these see the
+//! exit status, the raw output bytes and the error the plugin set.
+//!
+//! ```ignore
+//! let plugin = Plugin::build("char_counter");
+//! let report = plugin
+//!     .call("CountCharacters", RequestFixture::new("Hello").to_json())
+//!     .run()
+//!     .json();
+//! ```
+//!
+//! The CLI is found through the `EXTISM` environment variable, else `extism`
+//! on `PATH`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+/// Plugins built by this test process, by crate directory.
+static BUILT: Mutex<Option<HashMap<String, PathBuf>>> = Mutex::new(None);
+
+/// A built example plugin.
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    wasm: PathBuf,
+}
+
+impl Plugin {
+    /// Builds the example in `../<dir>` once per test process. Builds are
+    /// serialized so parallel tests do not wait on each other's cargo locks.
+    pub fn build(dir: &str) -> Plugin {
+        let mut built = BUILT.lock().unwrap_or_else(|e| e.into_inner());
+        let built = built.get_or_insert_with(HashMap::new);
+        if let Some(wasm) = built.get(dir) {
+            return Plugin { wasm: wasm.clone() };
+        }
+
+        let crate_dir = examples_dir().join(dir);
+        let status = Command::new("cargo")
+            .current_dir(&crate_dir)
+            .args(["build", "--quiet", "--release", "--target", "wasm32-wasip1"])
+            .status()
+            .unwrap_or_else(|e| panic!("failed to run cargo for {}: {}", dir, e));
+        assert!(status.success(), "building {} failed: {}", dir, status);
+
+        let wasm = crate_dir.join("target/wasm32-wasip1/release/plugin.wasm");
+        built.insert(dir.to_string(), wasm.clone());
+        Plugin { wasm }
+    }
+
+    /// Starts a call of the export `function` with `input` as the envelope.
+    pub fn call(&self, function: &str, input: impl Into<Vec<u8>>) -> Call<'_> {
+        Call {
+            plugin: self,
+            function: function.to_string(),
+            input: input.into(),
+            config: Vec::new(),
+        }
+    }
+}
+
+/// One CLI invocation under construction.
+pub struct Call<'p> {
+    plugin: &'p Plugin,
+    function: String,
+    input: Vec<u8>,
+    config: Vec<(String, String)>,
+}
+
+impl Call<'_> {
+    /// Sets an extism config var for the call.
+    pub fn config(mut self, key: &str, value: &str) -> Self {
+        self.config.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn run(self) -> Outcome {
+        let extism = std::env::var("EXTISM").unwrap_or_else(|_| "extism".to_string());
+        let mut command = Command::new(&extism);
+        command
+            .arg("call")
+            .arg(&self.plugin.wasm)
+            .arg(&self.function)
+            .args(["--wasi", "--stdin"]);
+        for (key, value) in &self.config {
+            command.arg("--config").arg(format!("{}={}", key, value));
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| panic!("failed to run {} (set EXTISM to the CLI): {}", extism, e));
+        // Write from another thread so a large input cannot deadlock against
+        // a full stdout pipe.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = self.input;
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output().expect("extism CLI did not exit");
+        // The CLI may stop reading once the plugin fails; a broken pipe is
+        // part of the outcome, not a harness error.
+        let _ = writer.join().expect("stdin writer panicked");
+        Outcome {
+            success: output.status.success(),
+            stdout: output.stdout,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
+
+/// What the CLI reported for one call.
+#[derive(Debug)]
+pub struct Outcome {
+    pub success: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+impl Outcome {
+    /// The output as JSON. Panics unless the call succeeded.
+    pub fn json(&self) -> Value {
+        self.assert_success();
+        serde_json::from_slice(self.stdout.trim_ascii_end()).unwrap_or_else(|e| {
+            panic!(
+                "output is not JSON ({}): {}",
+                e,
+                String::from_utf8_lossy(&self.stdout)
+            )
+        })
+    }
+
+    /// The output as UTF-8 text, for plugins that write something other
+    /// than JSON. Panics unless the call succeeded.
+    pub fn text(&self) -> String {
+        self.assert_success();
+        String::from_utf8(self.stdout.clone()).expect("output is not UTF-8")
+    }
+
+    /// The output decoded from MessagePack. Panics unless the call
+    /// succeeded.
+    pub fn msgpack(&self) -> Value {
+        self.assert_success();
+        rmp_serde::from_read(self.stdout.as_slice())
+            .unwrap_or_else(|e| panic!("output is not MessagePack: {}", e))
+    }
+
+    /// The error envelope the plugin set. Panics unless the call failed.
+    pub fn error(&self) -> Value {
+        assert!(
+            !self.success,
+            "expected the call to fail, got {}",
+            String::from_utf8_lossy(&self.stdout)
+        );
+        // The CLI wraps the message in its own text; the envelope is the
+        // JSON object inside it.
+        let start = self
+            .stderr
+            .find('{')
+            .unwrap_or_else(|| panic!("no error envelope in {:?}", self.stderr));
+        let end = self.stderr.rfind('}').expect("unterminated error envelope");
+        serde_json::from_str(&self.stderr[start..=end])
+            .unwrap_or_else(|e| panic!("bad error envelope ({}): {}", e, self.stderr))
+    }
+
+    fn assert_success(&self) {
+        assert!(self.success, "call failed: {}", self.stderr);
+    }
+}
+
+/// The directory holding the example crates, this crate's parent.
+fn examples_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("plugin_e2e lives inside examples/wasm/rust")
+}
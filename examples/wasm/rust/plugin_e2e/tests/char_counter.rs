@@ -0,0 +1,665 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+
+const FUNCTION: &str = "CountCharacters";
+
+fn plugin() -> Plugin {
+    Plugin::build("char_counter")
+}
+
+#[test]
+fn counts_with_default_and_configured_sets() {
+    let report = plugin()
+        .call(
+            FUNCTION,
+            RequestFixture::new("Hello World")
+                .empty_static_data()
+                .to_json(),
+        )
+        .run()
+        .json();
+    assert_eq!(report["count"], 3);
+    assert_eq!(report["characters"], "aeiouAEIOU");
+
+    let input = RequestFixture::new("AaBb")
+        .static_data("search_characters", "a")
+        .static_data("case_sensitive", true)
+        .to_json();
+    assert_eq!(plugin().call(FUNCTION, input).run().json()["count"], 1);
+}
+
+#[test]
+fn binary_safe_bodies_survive_both_codecs() {
+    // Control characters, NUL and the replacement character the Go host
+    // substitutes for invalid UTF-8.
+    let fixture = RequestFixture::new("\u{0}\u{1}\u{1f}é\u{fffd}a\r\n").empty_static_data();
+
+    let report = plugin().call(FUNCTION, fixture.to_json()).run().json();
+    assert_eq!(report["count"], 1);
+
+    let input = rmp_serde::to_vec_named(&fixture.to_value()).unwrap();
+    let report = plugin()
+        .call(FUNCTION, input)
+        .config("envelope_codec", "msgpack")
+        .run()
+        .msgpack();
+    assert_eq!(report["count"], 1);
+}
+
+#[test]
+fn handles_large_bodies() {
+    let body = "abcde".repeat(2 * 1024 * 1024);
+    let report = plugin()
+        .call(
+            FUNCTION,
+            RequestFixture::new(body).empty_static_data().to_json(),
+        )
+        .run()
+        .json();
+    assert_eq!(report["count"], 4 * 1024 * 1024);
+}
+
+#[test]
+fn parse_limits_come_from_config_vars() {
+    let input = RequestFixture::new("a".repeat(4096)).to_json();
+    let err = plugin()
+        .call(FUNCTION, input)
+        .config("parse_max_string_bytes", "1024")
+        .run()
+        .error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    assert_eq!(err["details"]["limit"], "max_string_bytes");
+}
+
+#[test]
+fn reports_errors_through_error_set() {
+    let err = plugin().call(FUNCTION, "not json").run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+
+    let input = RequestFixture::new("x")
+        .static_data("search_characters", "")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "search_characters");
+
+    let err = plugin()
+        .call(FUNCTION, RequestFixture::new("x").to_json())
+        .run()
+        .error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "static_data");
+
+    let err = plugin()
+        .call(FUNCTION, RequestFixture::new("x").to_json())
+        .config("envelope_codec", "xml")
+        .run()
+        .error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "envelope_codec");
+}
+
+#[test]
+fn result_envelope_reports_both_outcomes_as_output() {
+    let input = RequestFixture::new("Hello")
+        .static_data("search_characters", "l")
+        .to_json();
+    let out = plugin()
+        .call(FUNCTION, input)
+        .config("result_envelope", "true")
+        .run()
+        .json();
+    assert_eq!(out["ok"]["count"], 2);
+    assert!(out.get("error").is_none());
+
+    let input = RequestFixture::new("x")
+        .static_data("search_characters", "")
+        .to_json();
+    let out = plugin()
+        .call(FUNCTION, input)
+        .config("result_envelope", "true")
+        .run()
+        .json();
+    assert_eq!(out["error"]["code"], "CONFIG_ERROR");
+    assert_eq!(out["error"]["details"]["field"], "search_characters");
+
+    let err = plugin()
+        .call(FUNCTION, RequestFixture::new("x").to_json())
+        .config("result_envelope", "yes")
+        .run()
+        .error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "result_envelope");
+}
+
+#[test]
+fn invalid_search_pattern_is_a_config_error() {
+    let input = RequestFixture::new("x")
+        .static_data("search_pattern", "(unclosed")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "search_pattern");
+    assert!(err["details"]["reason"].is_string());
+}
+
+#[test]
+fn unknown_character_class_is_a_config_error() {
+    let input = RequestFixture::new("x")
+        .static_data("search_characters", "ab\\p{Vowels}")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "search_characters");
+    assert_eq!(err["details"]["value"], "\\p{Vowels}");
+    assert_eq!(err["details"]["reason"], "Unicode property not found");
+}
+
+#[test]
+fn range_syntax_is_opt_in() {
+    let input = RequestFixture::new("a-c, x")
+        .static_data("search_characters", "a-c")
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 3);
+
+    let input = RequestFixture::new("a-c, b")
+        .static_data("search_characters", "a-c")
+        .static_data("search_syntax", "ranges")
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 3);
+
+    let input = RequestFixture::new("x")
+        .static_data("search_characters", "a-c,z-x")
+        .static_data("search_syntax", "ranges")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "search_characters");
+    assert_eq!(err["details"]["value"], "z-x");
+    assert_eq!(err["details"]["reason"], "the range is reversed");
+}
+
+#[test]
+fn search_sets_count_each_set_in_one_call() {
+    let input = RequestFixture::new("Order 66, aisle 3")
+        .static_data(
+            "search_sets",
+            serde_json::json!({"vowels": "aeiou", "digits": "0-9"}),
+        )
+        .static_data("search_syntax", "ranges")
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(
+        report["sets"],
+        serde_json::json!({"digits": 3, "vowels": 5})
+    );
+    assert_eq!(report["count"], 8);
+
+    let input = RequestFixture::new("x")
+        .static_data("search_sets", serde_json::json!({"empty": ""}))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "search_sets");
+    assert_eq!(err["details"]["set"], "empty");
+}
+
+#[test]
+fn normalize_matches_both_spellings_of_an_accent() {
+    let input = RequestFixture::new("cafe\u{301} caf\u{e9}")
+        .static_data("search_characters", "\u{e9}")
+        .static_data("normalize", "nfc")
+        .static_data("report_version", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 2);
+    // Offsets are in the normalized body, where both spellings are "café".
+    assert_eq!(
+        report["positions"],
+        serde_json::json!([{"start": 3, "end": 5}, {"start": 9, "end": 11}])
+    );
+
+    let input = RequestFixture::new("")
+        .static_data("search_characters", "\u{e9}")
+        .static_data("normalize", "nfd")
+        .to_json();
+    let lint = plugin().call("LintConfig", input).run().json();
+    assert_eq!(lint["warnings"][0]["field"], "normalize");
+}
+
+#[test]
+fn version_1_output_is_unchanged_by_default() {
+    let outcome = plugin()
+        .call(
+            FUNCTION,
+            RequestFixture::new("Hello World")
+                .empty_static_data()
+                .to_json(),
+        )
+        .run();
+    assert!(outcome.success, "{}", outcome.stderr);
+    assert_eq!(
+        outcome.stdout.trim_ascii_end(),
+        br#"{"count":3,"characters":"aeiouAEIOU"}"#
+    );
+
+    let input = RequestFixture::new("Hello World")
+        .static_data("report_version", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["report_version"], 2);
+    assert_eq!(report["count"], 3);
+    assert_eq!(report["positions"].as_array().unwrap().len(), 3);
+    assert_eq!(report["positions_truncated"], false);
+}
+
+#[test]
+fn counts_words_and_lines() {
+    let input = RequestFixture::new("one two\nthree, four!\n\n").to_json();
+    let words = plugin().call("CountWords", input.clone()).run().json();
+    assert_eq!(words["count"], 4);
+    assert_eq!(words["segmentation"], "unicode");
+
+    let lines = plugin().call("CountLines", input).run().json();
+    assert_eq!(lines["count"], 3);
+    assert_eq!(lines["blank"], 1);
+}
+
+#[test]
+fn lint_config_reports_problems_without_failing() {
+    let input = RequestFixture::new("")
+        .static_data("search_characters", "aa")
+        .static_data("report_version", 9)
+        .static_data("colour", "blue")
+        .to_json();
+    let report = plugin().call("LintConfig", input).run().json();
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["errors"][0]["field"], "report_version");
+    assert_eq!(report["errors"][0]["kind"], "invalid");
+    let warnings: Vec<&str> = report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| w["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(warnings, ["colour", "search_characters"]);
+}
+
+#[test]
+fn chunked_count_starts_with_begin() {
+    // Every CLI call is a fresh instance, so each one sees no open count.
+    let input = RequestFixture::new("Hello e\u{301}")
+        .static_data("search_characters", "e\u{301}")
+        .static_data("count_mode", "graphemes")
+        .to_json();
+    let progress = plugin().call("CountCharactersBegin", input).run().json();
+    // The last cluster waits for the next piece.
+    assert_eq!(progress, serde_json::json!({"count": 0, "bytes": 6}));
+
+    let input = RequestFixture::new("abc").empty_static_data().to_json();
+    let err = plugin()
+        .call("CountCharactersChunk", input.clone())
+        .run()
+        .error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    let err = plugin().call("CountCharactersEnd", input).run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+
+    let input = RequestFixture::new("abc")
+        .static_data("search_pattern", "a")
+        .to_json();
+    let err = plugin().call("CountCharactersBegin", input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "search_pattern");
+}
+
+#[test]
+fn source_selects_the_part_of_the_request() {
+    let request = |source: &str| {
+        RequestFixture::new("aaaa")
+            .without_header("User-Agent")
+            .header("User-Agent", "Mozilla")
+            .header("User-Agent", "Opera")
+            .path("/api/items")
+            .static_data("source", source)
+            .to_json()
+    };
+    let report = plugin()
+        .call(FUNCTION, request("header:user-agent"))
+        .run()
+        .json();
+    // "Mozilla, Opera"
+    assert_eq!(report["count"], 6);
+    let report = plugin().call(FUNCTION, request("path")).run().json();
+    assert_eq!(report["count"], 3);
+
+    let err = plugin().call(FUNCTION, request("header:")).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "source");
+    assert_eq!(err["details"]["value"], "header:");
+}
+
+#[test]
+fn case_folding_follows_the_locale() {
+    let request = |locale: Option<&str>| {
+        let fixture = RequestFixture::new("IİıiK")
+            .static_data("search_characters", "i")
+            .static_data("include_histogram", true);
+        match locale {
+            Some(locale) => fixture.static_data("locale", locale),
+            None => fixture,
+        }
+        .to_json()
+    };
+    // "I" and "i"; "İ" is one character and counts only under tr.
+    let report = plugin().call(FUNCTION, request(None)).run().json();
+    assert_eq!(report["count"], 2);
+    let report = plugin().call(FUNCTION, request(Some("tr"))).run().json();
+    assert_eq!(report["count"], 2);
+    assert_eq!(report["histogram"]["i"], 2);
+
+    let err = plugin().call(FUNCTION, request(Some("tr_"))).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "locale");
+}
+
+#[test]
+fn top_n_lists_the_most_frequent_characters() {
+    let input = RequestFixture::new("banana bread")
+        .static_data("search_characters", "abnr")
+        .static_data("top_n", 3)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    // a: 4, n: 2, b: 2, r: 1; the tie between b and n goes to b.
+    assert_eq!(
+        report["top"].to_string(),
+        r#"[{"character":"a","count":4},{"character":"b","count":2},{"character":"n","count":2}]"#
+    );
+    assert!(report.get("histogram").is_none());
+}
+
+#[test]
+fn include_stats_reports_length_and_ratios() {
+    let input = RequestFixture::new("aabb")
+        .static_data("include_stats", true)
+        .static_data("search_characters", "a")
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 2);
+    assert_eq!(report["total_chars"], 4);
+    assert_eq!(report["match_ratio"], 0.5);
+    assert!(report.get("set_percentages").is_none());
+}
+
+#[test]
+fn invert_counts_characters_outside_the_set() {
+    let input = RequestFixture::new("naïve café")
+        .static_data("search_characters", "\\p{ASCII}")
+        .static_data("invert", true)
+        .static_data("include_histogram", true)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 2);
+    assert_eq!(report["histogram"]["ï"], 1);
+    assert_eq!(report["histogram"]["é"], 1);
+}
+
+#[test]
+fn bytes_mode_counts_decoded_binary() {
+    let input = RequestFixture::new("AAEC/w==")
+        .static_data("count_mode", "bytes")
+        .static_data("body_encoding", "base64")
+        .static_data("search_characters", "\\P{ASCII}")
+        .static_data("include_histogram", true)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    // 00 01 02 ff
+    assert_eq!(report["count"], 1);
+    assert_eq!(report["histogram"]["0xff"], 1);
+
+    let input = RequestFixture::new("not base64!")
+        .static_data("count_mode", "bytes")
+        .static_data("body_encoding", "base64")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+}
+
+#[test]
+fn accept_header_picks_the_report_format() {
+    let request = |accept: &str| {
+        RequestFixture::new("a,b \"a\"")
+            .header("Accept", accept)
+            .static_data("search_characters", "a,\"")
+            .static_data("include_histogram", true)
+            .to_json()
+    };
+    let report = plugin()
+        .call(FUNCTION, request("application/json"))
+        .run()
+        .json();
+    assert_eq!(report["count"], 5);
+
+    let text = plugin().call(FUNCTION, request("text/plain")).run().text();
+    assert_eq!(
+        text,
+        "count: 5\ncharacters: a,\\\"\ncharacter \\\": 2\ncharacter ,: 1\ncharacter a: 2\n"
+    );
+    // Types the plugin does not offer are passed over.
+    let csv = plugin()
+        .call(FUNCTION, request("text/html, text/csv;q=0.9, */*;q=0.1"))
+        .run()
+        .text();
+    assert_eq!(
+        csv,
+        "kind,key,count\r\ntotal,\"a,\"\"\",5\r\ncharacter,\"\"\"\",2\r\ncharacter,\",\",1\r\ncharacter,a,2\r\n"
+    );
+
+    let err = plugin().call(FUNCTION, request("image/png")).run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    assert_eq!(err["details"]["accept"], "image/png");
+    assert_eq!(err["details"]["supported"][2], "text/csv");
+}
+
+#[test]
+fn max_body_bytes_rejects_larger_bodies() {
+    let input = |body: &str| {
+        RequestFixture::new(body)
+            .static_data("max_body_bytes", 5)
+            .to_json()
+    };
+    let report = plugin().call(FUNCTION, input("Hello")).run().json();
+    assert_eq!(report["count"], 2);
+
+    let err = plugin().call(FUNCTION, input("Hello!")).run().error();
+    assert_eq!(err["code"], "PAYLOAD_TOO_LARGE");
+    assert_eq!(err["details"]["max_body_bytes"], 5);
+    assert_eq!(err["details"]["body_bytes"], 6);
+    let err = plugin()
+        .call("CountCharactersBegin", input("Hello!"))
+        .run()
+        .error();
+    assert_eq!(err["code"], "PAYLOAD_TOO_LARGE");
+
+    let input = RequestFixture::new("Hello")
+        .static_data("max_body_bytes", 0)
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "max_body_bytes");
+}
+
+#[test]
+fn include_positions_locates_matches_in_version_1() {
+    let input = RequestFixture::new("Hello World")
+        .static_data("include_positions", true)
+        .static_data("max_positions", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 3);
+    assert_eq!(
+        report["positions"],
+        serde_json::json!([{"start": 1, "end": 2}, {"start": 4, "end": 5}])
+    );
+    assert_eq!(report["positions_truncated"], true);
+    assert!(report.get("report_version").is_none());
+
+    let input = RequestFixture::new("Hello World").to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert!(report.get("positions").is_none());
+}
+
+#[test]
+fn match_description_is_filled_in() {
+    let input = RequestFixture::new("Hello World")
+        .static_data("search_pattern", "l+")
+        .static_data("match_description", "{count} runs of {characters}")
+        .static_data("report_version", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["description"], "2 runs of l+");
+
+    let input = RequestFixture::new("Hello World")
+        .header("Accept", "text/plain")
+        .static_data("match_description", "{count} vowels")
+        .to_json();
+    let text = plugin().call(FUNCTION, input).run().text();
+    assert_eq!(
+        text,
+        "count: 3\ncharacters: aeiouAEIOU\ndescription: 3 vowels\n"
+    );
+
+    let input = RequestFixture::new("Hello World")
+        .static_data("match_description", "{count vowels")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "match_description");
+    assert_eq!(
+        err["details"]["placeholders"],
+        serde_json::json!(["count", "characters"])
+    );
+}
+
+#[test]
+fn graphemes_count_emoji_sequences_once() {
+    let family = "\u{1f469}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+    let body = format!("{family} \u{1f1ef}\u{1f1f5} 1\u{fe0f}\u{20e3} \u{1f44d}\u{1f3fd}");
+    let input = RequestFixture::new(&body)
+        .static_data("count_mode", "graphemes")
+        .static_data("search_characters", "\\p{emoji}")
+        .static_data("include_histogram", true)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    // The keycap starts with a digit, which the alias leaves out.
+    assert_eq!(report["count"], 3);
+    assert_eq!(report["histogram"][family], 1);
+    assert_eq!(report["histogram"]["\u{1f1ef}\u{1f1f5}"], 1);
+
+    let input = RequestFixture::new("")
+        .static_data("search_characters", family)
+        .to_json();
+    let report = plugin().call("LintConfig", input).run().json();
+    let warnings: Vec<_> = report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| w["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(warnings, ["search_characters", "count_mode"]);
+}
+
+#[test]
+fn strip_diacritics_counts_accented_vowels() {
+    let input = RequestFixture::new("Où est le café? À côté.")
+        .static_data("strip_diacritics", true)
+        .static_data("include_histogram", true)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 9);
+    assert_eq!(
+        report["histogram"],
+        serde_json::json!({"a": 2, "e": 4, "o": 2, "u": 1})
+    );
+
+    let input = RequestFixture::new("Où est le café? À côté.")
+        .static_data("strip_diacritics", true)
+        .static_data("report_version", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    // Positions are in the stripped text, where "ù" is one byte.
+    assert_eq!(report["positions"][2]["start"], 3);
+    assert_eq!(report["stats"]["bytes"], 23);
+}
+
+#[test]
+fn strip_html_counts_only_the_text() {
+    let page = "<html><head><style>a { color: red }</style></head>\
+                <body><p title=\"a > b\">Fa&ccedil;ade &amp; caf&eacute;</p><br/></body></html>";
+    let input = RequestFixture::new(page)
+        .static_data("strip_html", true)
+        .static_data("report_version", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    // "Façade & café": the vowels of the text only, "é" not among them.
+    assert_eq!(report["count"], 4);
+    assert_eq!(report["counts"], serde_json::json!({"a": 3, "e": 1}));
+    assert_eq!(report["stats"]["units"], 13);
+
+    let input = RequestFixture::new(page)
+        .static_data("strip_html", true)
+        .static_data("strip_diacritics", true)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["count"], 5);
+}
+
+#[test]
+fn preprocess_trims_and_collapses_whitespace() {
+    let input = RequestFixture::new("\t Hello,   world \r\n")
+        .static_data("search_characters", "\\p{whitespace}")
+        .static_data(
+            "preprocess",
+            serde_json::json!(["trim", "collapse_whitespace"]),
+        )
+        .static_data("report_version", 2)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    // "Hello, world"
+    assert_eq!(report["count"], 1);
+    assert_eq!(report["stats"]["bytes"], 12);
+    assert_eq!(report["positions"][0]["start"], 6);
+
+    let input = RequestFixture::new("x")
+        .static_data("preprocess", serde_json::json!(["trim", "squash"]))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["value"], "squash");
+}
+
+#[test]
+fn deployment_default_search_characters() {
+    let run = |fixture: RequestFixture, default: &str| {
+        plugin()
+            .call(FUNCTION, fixture.to_json())
+            .config("default_search_characters", default)
+            .run()
+    };
+    let report = run(
+        RequestFixture::new("Hello 2024").empty_static_data(),
+        "\\p{digits}",
+    )
+    .json();
+    assert_eq!(report["count"], 4);
+    assert_eq!(report["characters"], "\\p{digits}");
+    // The route's own set wins.
+    let own = RequestFixture::new("Hello 2024").static_data("search_characters", "l");
+    assert_eq!(run(own, "\\p{digits}").json()["count"], 2);
+
+    let err = run(RequestFixture::new("x").empty_static_data(), "").error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "default_search_characters");
+}
@@ -0,0 +1,49 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::{json, Value};
+
+fn plugin() -> Plugin {
+    Plugin::build("csv_converter")
+}
+
+#[test]
+fn round_trips_a_large_file() {
+    let mut csv = String::from("id,name,note\r\n");
+    for i in 0..20_000 {
+        csv.push_str(&format!(
+            "{},\"Name, {}\",\"line one\r\nline \"\"two\"\"\"\r\n",
+            i, i
+        ));
+    }
+    let plugin = plugin();
+
+    let input = RequestFixture::new(csv.as_str()).to_json();
+    let json = plugin.call("CsvToJson", input).run().text();
+    let rows: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(rows.as_array().unwrap().len(), 20_000);
+    assert_eq!(
+        rows[19_999],
+        json!({"id": "19999", "name": "Name, 19999", "note": "line one\r\nline \"two\""})
+    );
+
+    let input = RequestFixture::new(json).to_json();
+    assert_eq!(plugin.call("JsonToCsv", input).run().text(), csv);
+}
+
+#[test]
+fn reports_the_line_of_a_bad_record() {
+    let input = RequestFixture::new("a,b\n1,2\n\"3\"x,4\n").to_json();
+    let err = plugin().call("CsvToJson", input).run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    assert_eq!(err["details"]["line"], 3);
+}
+
+#[test]
+fn bounds_records_with_max_record_bytes() {
+    let input = RequestFixture::new(format!("a\n\"{}", "x".repeat(1000)))
+        .static_data("max_record_bytes", 100)
+        .to_json();
+    let err = plugin().call("CsvToJson", input).run().error();
+    assert_eq!(err["code"], "PAYLOAD_TOO_LARGE");
+    assert_eq!(err["details"]["line"], 2);
+}
@@ -0,0 +1,35 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "AggregateHealth";
+
+// Probing needs a reachable upstream, so only the checks made before any
+// outbound call are exercised here.
+
+#[test]
+fn requires_checks() {
+    let plugin = Plugin::build("health_aggregator");
+    let err = plugin
+        .call(FUNCTION, RequestFixture::new("").to_json())
+        .run()
+        .error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "checks");
+}
+
+#[test]
+fn refuses_destinations_outside_the_allow_list() {
+    let plugin = Plugin::build("health_aggregator");
+    let input = RequestFixture::new("")
+        .static_data(
+            "checks",
+            json!([{"name": "db", "url": "https://db.internal.test/health"}]),
+        )
+        .static_data("outbound", json!({"hosts": ["api.example.com"]}))
+        .to_json();
+    let err = plugin.call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "POLICY_VIOLATION");
+    assert_eq!(err["details"]["check"], "db");
+    assert_eq!(err["details"]["reason"], "host");
+}
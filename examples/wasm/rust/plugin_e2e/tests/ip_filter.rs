@@ -0,0 +1,74 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "FilterIp";
+
+fn plugin() -> Plugin {
+    Plugin::build("ip_filter")
+}
+
+#[test]
+fn blocks_a_denied_network_with_its_reason() {
+    let input = RequestFixture::new("")
+        .remote_addr("198.51.100.7:51000")
+        .static_data("deny", json!(["198.51.100.0/24"]))
+        .to_json();
+    let decision = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(
+        decision,
+        json!({
+            "allowed": false,
+            "status": 403,
+            "client_ip": "198.51.100.7",
+            "reason": "denied",
+            "network": "198.51.100.0/24"
+        })
+    );
+}
+
+#[test]
+fn allows_clients_only_a_deny_list_does_not_name() {
+    let input = RequestFixture::new("")
+        .remote_addr("[2001:db8::1]:443")
+        .static_data("deny", json!(["198.51.100.0/24"]))
+        .to_json();
+    let decision = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(
+        decision,
+        json!({"allowed": true, "status": 200, "client_ip": "2001:db8::1"})
+    );
+}
+
+#[test]
+fn reads_the_client_through_a_trusted_proxy() {
+    let input = RequestFixture::new("")
+        .remote_addr("[::1]:12345")
+        .header("X-Forwarded-For", "2001:db8:bad::1")
+        .static_data("allow", json!(["2001:db8:1::/48"]))
+        .static_data("trusted_proxies", json!(["::1"]))
+        .to_json();
+    let decision = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(decision["client_ip"], "2001:db8:bad::1");
+    assert_eq!(decision["reason"], "not_allowed");
+}
+
+#[test]
+fn reports_the_entry_that_does_not_parse() {
+    let input = RequestFixture::new("")
+        .static_data("allow", json!(["10.0.0.0/8", "10.0.0.0/40"]))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "allow");
+    assert_eq!(err["details"]["index"], 1);
+}
+
+#[test]
+fn requires_a_list() {
+    let input = RequestFixture::new("")
+        .static_data("trusted_proxies", json!(["10.0.0.1"]))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+}
@@ -0,0 +1,71 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "ValidateBody";
+
+fn plugin() -> Plugin {
+    Plugin::build("json_validator")
+}
+
+#[test]
+fn reports_violations_by_pointer() {
+    let schema = json!({
+        "type": "object",
+        "required": ["id"],
+        "properties": {
+            "id": {"type": "integer"},
+            "tags": {"type": "array", "items": {"type": "string", "maxLength": 3}}
+        }
+    });
+    let input = RequestFixture::new(r#"{"id": 1, "tags": ["a"]}"#)
+        .static_data("schema", schema.clone())
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["valid"], true);
+
+    let input = RequestFixture::new(r#"{"tags": ["abcd", 5]}"#)
+        .static_data("schema", schema)
+        .to_json();
+    let report = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["violations_truncated"], false);
+    let found: Vec<_> = report["violations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| (v["pointer"].clone(), v["keyword"].clone()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (json!(""), json!("required")),
+            (json!("/tags/0"), json!("maxLength")),
+            (json!("/tags/1"), json!("type")),
+        ]
+    );
+}
+
+#[test]
+fn rejects_schemas_it_cannot_apply() {
+    let input = RequestFixture::new("{}")
+        .static_data("schema", json!({"properties": {"a": {"if": {}}}}))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "schema");
+    assert_eq!(err["details"]["schema_pointer"], "/properties/a/if");
+}
+
+// Fetching needs a reachable schema host, so only the allow-list check made
+// before any outbound call is exercised here.
+#[test]
+fn refuses_schema_urls_outside_the_allow_list() {
+    let input = RequestFixture::new("{}")
+        .static_data("schema_url", "https://schemas.internal.test/order.json")
+        .static_data("outbound", json!({"hosts": ["schemas.example.com"]}))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "POLICY_VIOLATION");
+    assert_eq!(err["details"]["field"], "schema_url");
+}
@@ -0,0 +1,55 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+
+const FUNCTION: &str = "RenderMarkdown";
+
+fn plugin() -> Plugin {
+    Plugin::build("markdown_renderer")
+}
+
+fn markdown(body: &str) -> RequestFixture {
+    RequestFixture::new(body)
+        .without_header("Content-Type")
+        .header("Content-Type", "text/markdown")
+}
+
+#[test]
+fn renders_a_document() {
+    let input =
+        markdown("# Notes\n\n| key | value |\n|-----|-------|\n| a | 1 |\n\n```toml\nx = 1\n```\n")
+            .to_json();
+    let response = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(response["status"], 200);
+    assert_eq!(response["content_type"], "text/html; charset=utf-8");
+    let page = response["body"].as_str().unwrap();
+    assert!(page.starts_with("<h1>Notes</h1>"), "{}", page);
+    assert!(page.contains("<td>a</td>"), "{}", page);
+    assert!(page.contains(r#"<code class="language-toml">"#), "{}", page);
+}
+
+#[test]
+fn keeps_client_markup_out_of_the_page() {
+    let input = markdown(
+        "<img src=x onerror=alert(1)>\n\n[a](JaVaScRiPt:alert(1)) <span>b</span> <javascript:alert(2)>",
+    )
+    .to_json();
+    let response = plugin().call(FUNCTION, input).run().json();
+    let page = response["body"].as_str().unwrap();
+    assert!(
+        page.contains("&lt;img src=x onerror=alert(1)&gt;"),
+        "{}",
+        page
+    );
+    assert!(page.contains("&lt;span&gt;b&lt;/span&gt;"), "{}", page);
+    assert!(!page.contains("<a "), "{}", page);
+}
+
+#[test]
+fn refuses_bodies_over_max_body_bytes() {
+    let input = markdown(&"word ".repeat(100))
+        .static_data("max_body_bytes", 64)
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "PAYLOAD_TOO_LARGE");
+    assert_eq!(err["details"]["max_body_bytes"], 64);
+}
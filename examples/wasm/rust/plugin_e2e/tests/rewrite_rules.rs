@@ -0,0 +1,79 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "ApplyRules";
+
+fn plugin() -> Plugin {
+    Plugin::build("rewrite_rules")
+}
+
+#[test]
+fn the_first_matching_rule_wins() {
+    let input = RequestFixture::new("")
+        .path("/docs/intro")
+        .query("q", "1")
+        .static_data(
+            "rules",
+            json!([
+                {"path_prefix": "/docs", "redirect": "https://docs.example.com/", "status": 307},
+                {"path_prefix": "/", "rewrite": "/index"}
+            ]),
+        )
+        .to_json();
+    let decision = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(
+        decision,
+        json!({
+            "action": "redirect",
+            "rule": 0,
+            "status": 307,
+            "location": "https://docs.example.com/?q=1"
+        })
+    );
+}
+
+#[test]
+fn matches_subdomains_of_a_wildcard_host() {
+    let rules = json!([{"host": "*.example.net", "rewrite": "/tenant?from=subdomain"}]);
+    let decision = |host: &str| {
+        let input = RequestFixture::new("")
+            .host(host)
+            .static_data("rules", rules.clone())
+            .to_json();
+        plugin().call(FUNCTION, input).run().json()
+    };
+    assert_eq!(
+        decision("shop.example.net"),
+        json!({"action": "rewrite", "rule": 0, "path": "/tenant", "query": "from=subdomain"})
+    );
+    assert_eq!(decision("example.net"), json!({"action": "pass"}));
+}
+
+#[test]
+fn reports_a_reference_to_a_missing_group() {
+    let input = RequestFixture::new("")
+        .static_data(
+            "rules",
+            json!([
+                {"path_prefix": "/a", "redirect": "/b"},
+                {"path_regex": "^/old/(.*)$", "redirect": "/new/$2"}
+            ]),
+        )
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "rules");
+    assert_eq!(err["details"]["index"], 1);
+    assert_eq!(
+        err["details"]["reason"],
+        "redirect: path_regex has no group 2"
+    );
+}
+
+#[test]
+fn requires_rules() {
+    let input = RequestFixture::new("").empty_static_data().to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+}
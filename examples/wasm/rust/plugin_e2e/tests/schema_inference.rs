@@ -0,0 +1,29 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+
+const FUNCTION: &str = "InferSchema";
+
+#[test]
+fn infers_a_schema_per_instance() {
+    let plugin = Plugin::build("schema_inference");
+    let input = RequestFixture::new(r#"{"id": 1, "tags": ["a"]}"#)
+        .path("/orders")
+        .to_json();
+    let report = plugin.call(FUNCTION, input).run().json();
+    // Every CLI call is a fresh instance, so state starts over.
+    assert_eq!(report["path"], "/orders");
+    assert_eq!(report["samples"], 1);
+    assert_eq!(report["skipped"], 0);
+    assert_eq!(
+        report["schema"]["$schema"],
+        "https://json-schema.org/draft/2020-12/schema"
+    );
+    assert_eq!(report["schema"]["type"], "object");
+
+    let report = plugin
+        .call(FUNCTION, RequestFixture::new("not json").to_json())
+        .run()
+        .json();
+    assert_eq!(report["samples"], 0);
+    assert_eq!(report["skipped"], 1);
+}
@@ -0,0 +1,49 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "RenderTemplate";
+
+fn plugin() -> Plugin {
+    Plugin::build("template_renderer")
+}
+
+#[test]
+fn renders_the_embedded_page() {
+    let input = RequestFixture::new(
+        r#"{"title": "Status", "message": "All good", "items": ["api", "db"]}"#,
+    )
+    .static_data("asset", "page.html")
+    .to_json();
+    let response = plugin().call(FUNCTION, input).run().json();
+    assert_eq!(response["status"], 200);
+    assert_eq!(response["content_type"], "text/html; charset=utf-8");
+    let page = response["body"].as_str().unwrap();
+    assert!(page.contains("<title>Status</title>"), "{}", page);
+    assert!(page.contains("<li>db</li>"), "{}", page);
+}
+
+#[test]
+fn stops_at_max_output_bytes() {
+    let input = RequestFixture::new(r#"{"rows": [1, 2, 3, 4, 5, 6, 7, 8]}"#)
+        .static_data(
+            "template",
+            "{{#each rows}}{{#each ../rows}}<td>{{this}}</td>{{/each}}{{/each}}",
+        )
+        .static_data("max_output_bytes", 100)
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    assert_eq!(err["details"]["field"], "max_output_bytes");
+}
+
+#[test]
+fn locates_template_syntax_errors() {
+    let input = RequestFixture::new("{}")
+        .static_data("template", "<p>\n{{#each items}}\n</p>")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "template");
+    assert_eq!(err["details"]["line"], json!(3));
+}
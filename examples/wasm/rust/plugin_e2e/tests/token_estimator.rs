@@ -0,0 +1,38 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+
+const FUNCTION: &str = "EstimateTokens";
+
+fn plugin() -> Plugin {
+    Plugin::build("token_estimator")
+}
+
+#[test]
+fn estimates_and_enforces_budgets() {
+    let body = "naïve café ".repeat(1000);
+    let estimate = plugin()
+        .call(FUNCTION, RequestFixture::new(body.as_str()).to_json())
+        .run()
+        .json();
+    assert_eq!(estimate["profile"], "cl100k");
+    assert_eq!(estimate["bytes"], body.len());
+    assert_eq!(estimate["characters"], body.chars().count());
+    assert!(estimate["tokens"].as_u64().unwrap() > 0);
+
+    let input = RequestFixture::new(body.as_str())
+        .static_data("max_tokens", 10)
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "POLICY_VIOLATION");
+    assert_eq!(err["details"]["max_tokens"], 10);
+}
+
+#[test]
+fn rejects_unknown_profiles() {
+    let input = RequestFixture::new("x")
+        .static_data("profile", "nope")
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["value"], "nope");
+}
@@ -0,0 +1,74 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "VerifyWebhook";
+
+fn plugin() -> Plugin {
+    Plugin::build("webhook_verifier")
+}
+
+/// A Stripe delivery of `{"id":"evt_1"}` signed with whsec_example at
+/// 1700000000.
+fn stripe_delivery() -> String {
+    RequestFixture::new(r#"{"id":"evt_1"}"#)
+        .method("POST")
+        .header(
+            "Stripe-Signature",
+            "t=1700000000,v0=00,v1=2f6f24854ba5c8d505c37e6fc0a06fc74456f1a4042208e7acdd4bd0bdbd599e",
+        )
+        .static_data("provider", "stripe")
+        .static_data("secrets", json!(["whsec_example"]))
+        .static_data("tolerance_seconds", 60)
+        .to_json()
+}
+
+#[test]
+fn accepts_stripe_deliveries_within_the_tolerance() {
+    for (now_ms, verified) in [
+        ("1699999939000", false),
+        ("1699999940000", true),
+        ("1700000060000", true),
+        ("1700000061000", false),
+    ] {
+        let verdict = plugin()
+            .call(FUNCTION, stripe_delivery())
+            .config("clock_fixed_unix_ms", now_ms)
+            .run()
+            .json();
+        assert_eq!(verdict["verified"], verified, "at {}", now_ms);
+        assert_eq!(verdict["timestamp"], 1700000000);
+    }
+}
+
+#[test]
+fn verifies_slack_signatures_over_the_form_body() {
+    let input = RequestFixture::new("command=%2Fweather")
+        .method("POST")
+        .header(
+            "X-Slack-Signature",
+            "v0=6a02690db4ab1a17856e50aa87e8bcc136108c34c34f601833116801f703b1f2",
+        )
+        .header("X-Slack-Request-Timestamp", "1700000000")
+        .static_data("provider", "slack")
+        .static_data("secrets", json!(["slack-signing-secret"]))
+        .to_json();
+    let verdict = plugin()
+        .call(FUNCTION, input)
+        .config("clock_fixed_unix_ms", "1700000100000")
+        .run()
+        .json();
+    assert_eq!(verdict["status"], 200);
+}
+
+#[test]
+fn names_an_empty_secret() {
+    let input = RequestFixture::new("{}")
+        .static_data("provider", "github")
+        .static_data("secrets", json!(["current-secret", ""]))
+        .to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "CONFIG_ERROR");
+    assert_eq!(err["details"]["field"], "secrets");
+    assert_eq!(err["details"]["index"], 1);
+}
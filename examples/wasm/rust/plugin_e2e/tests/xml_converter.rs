@@ -0,0 +1,61 @@
+use firelynx_fixture::RequestFixture;
+use plugin_e2e::Plugin;
+use serde_json::json;
+
+const FUNCTION: &str = "XmlToJson";
+
+fn plugin() -> Plugin {
+    Plugin::build("xml_converter")
+}
+
+fn xml(body: &str) -> RequestFixture {
+    RequestFixture::new(body)
+        .without_header("Content-Type")
+        .header("Content-Type", "application/soap+xml")
+}
+
+#[test]
+fn maps_a_soap_request_the_same_for_any_prefix() {
+    for prefix in ["soap", "s", "env"] {
+        let body = format!(
+            r#"<{p}:Envelope xmlns:{p}="http://www.w3.org/2003/05/soap-envelope"><{p}:Body><Ping xml:lang="en">hi</Ping></{p}:Body></{p}:Envelope>"#,
+            p = prefix
+        );
+        let input = xml(&body)
+            .static_data(
+                "namespaces",
+                json!({"soap": "http://www.w3.org/2003/05/soap-envelope"}),
+            )
+            .to_json();
+        let document = plugin().call(FUNCTION, input).run().json();
+        assert_eq!(
+            document,
+            json!({"soap:Envelope": {"soap:Body": {"Ping": {"@xml:lang": "en", "#text": "hi"}}}}),
+            "prefix {}",
+            prefix
+        );
+    }
+}
+
+#[test]
+fn refuses_deep_documents() {
+    let body = format!("{}{}", "<a>".repeat(100), "</a>".repeat(100));
+    let input = xml(&body).to_json();
+    let err = plugin()
+        .call(FUNCTION, input)
+        .config("parse_max_depth", "32")
+        .run()
+        .error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    assert_eq!(err["details"]["limit"], "max_depth");
+}
+
+#[test]
+fn reports_where_the_body_is_malformed() {
+    let input = xml("<a>\n  <b>x</c>\n</a>").to_json();
+    let err = plugin().call(FUNCTION, input).run().error();
+    assert_eq!(err["code"], "INVALID_INPUT");
+    assert!(err["details"]["reason"]
+        .as_str()
+        .is_some_and(|reason| reason.contains("</b>")));
+}
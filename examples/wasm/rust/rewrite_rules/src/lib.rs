@@ -0,0 +1,137 @@
+mod rule;
+mod template;
+
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use rule::{Applied, Rule, RuleConfig};
+
+/// What the host should do with the request. Matches `RuleDecision` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleDecision {
+    pub action: Action,
+    /// The index in `rules` of the rule that matched. Absent with `pass`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<usize>,
+    /// The redirect status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// The `Location` header of a redirect.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// The path to route a rewritten request by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The query string of a rewritten request, without `?`; empty for
+    /// none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Answer with `status` and `location`.
+    Redirect,
+    /// Route the request again by `path` and `query`.
+    Rewrite,
+    /// No rule matched: route the request as it is.
+    Pass,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    rules: Vec<RuleConfig>,
+    max_rules: u64,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[Field::integer("max_rules").default(DefaultValue::Int(256))];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+}
+
+impl Config {
+    fn rules(self) -> Result<Vec<Rule>> {
+        if self.rules.is_empty() {
+            return Err(PluginError::config("rules must list at least one rule")
+                .with_detail("field", "rules"));
+        }
+        if self.rules.len() as u64 > self.max_rules {
+            return Err(PluginError::config("Too many rules configured")
+                .with_detail("field", "rules")
+                .with_detail("count", self.rules.len())
+                .with_detail("max_rules", self.max_rules));
+        }
+        self.rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                Rule::compile(rule).map_err(|reason| {
+                    PluginError::config("rules holds a rule that is not valid")
+                        .with_detail("field", "rules")
+                        .with_detail("index", index)
+                        .with_detail("reason", reason)
+                })
+            })
+            .collect()
+    }
+}
+
+#[firelynx_plugin]
+fn apply_rules(request: Request, static_data: StaticData) -> Result<RuleDecision> {
+    let rules = Config::from_static_data(&static_data)?.rules()?;
+    let host = hostname(&request.host);
+    let path = match request.url.path.as_str() {
+        "" => "/",
+        path => path,
+    };
+
+    for (index, rule) in rules.iter().enumerate() {
+        let applied = rule
+            .apply(&host, path, &request.url.raw_query)
+            .map_err(|e| e.with_detail("rule", index))?;
+        let Some(applied) = applied else {
+            continue;
+        };
+        log_debug!("rule matched", rule = index, path = path);
+        return Ok(match applied {
+            Applied::Redirect { status, location } => RuleDecision {
+                action: Action::Redirect,
+                rule: Some(index),
+                status: Some(status),
+                location: Some(location),
+                path: None,
+                query: None,
+            },
+            Applied::Rewrite { path, query } => RuleDecision {
+                action: Action::Rewrite,
+                rule: Some(index),
+                status: None,
+                location: None,
+                path: Some(path),
+                query: Some(query),
+            },
+        });
+    }
+
+    Ok(RuleDecision {
+        action: Action::Pass,
+        rule: None,
+        status: None,
+        location: None,
+        path: None,
+        query: None,
+    })
+}
+
+/// The request's host name, lowercase and without the port.
+fn hostname(host: &str) -> String {
+    let name = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
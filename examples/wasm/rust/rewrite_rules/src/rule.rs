@@ -0,0 +1,258 @@
+//! One entry of `rules`: what it matches and what it does.
+
+use firelynx_pdk::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::template::Template;
+
+/// Upper bound on a compiled `path_regex`, as in json_validator.
+const MAX_PATTERN_BYTES: usize = 1 << 20;
+
+/// The statuses a redirect may have.
+pub const REDIRECT_STATUSES: [u16; 4] = [301, 302, 307, 308];
+
+/// A rule as written in `static_data`. Unknown keys are errors, so a
+/// misspelt condition cannot turn a rule into a catch-all.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    #[serde(default)]
+    host: Option<String>,
+    #[serde(default)]
+    path_prefix: Option<String>,
+    #[serde(default)]
+    path_regex: Option<String>,
+    #[serde(default)]
+    redirect: Option<String>,
+    #[serde(default)]
+    rewrite: Option<String>,
+    #[serde(default)]
+    status: Option<i64>,
+    #[serde(default = "default_keep_query")]
+    keep_query: bool,
+}
+
+fn default_keep_query() -> bool {
+    true
+}
+
+enum Action {
+    Redirect {
+        target: Template,
+        status: u16,
+    },
+    /// The query part is separate so captured text is escaped there but
+    /// not in the path, which the host keeps decoded.
+    Rewrite {
+        path: Template,
+        query: Option<Template>,
+    },
+}
+
+/// What a matching rule tells the host to do.
+pub enum Applied {
+    Redirect { status: u16, location: String },
+    Rewrite { path: String, query: String },
+}
+
+pub struct Rule {
+    /// A lowercase host name, or `*.suffix` for its subdomains.
+    host: Option<String>,
+    path_prefix: Option<String>,
+    path_regex: Option<Regex>,
+    action: Action,
+    keep_query: bool,
+}
+
+impl Rule {
+    /// Checks and compiles a rule. The error says what is wrong; the
+    /// caller adds which rule.
+    pub fn compile(config: RuleConfig) -> Result<Rule, String> {
+        let host = match config.host {
+            Some(host) => {
+                let host = host.trim_end_matches('.').to_ascii_lowercase();
+                if host.is_empty() || host == "*." || host.contains(['/', ':']) {
+                    return Err("host must be a host name or *.suffix".to_string());
+                }
+                Some(host)
+            }
+            None => None,
+        };
+        if let Some(prefix) = &config.path_prefix {
+            if !prefix.starts_with('/') {
+                return Err("path_prefix must start with /".to_string());
+            }
+        }
+        let path_regex = match &config.path_regex {
+            Some(source) => Some(
+                RegexBuilder::new(source)
+                    .size_limit(MAX_PATTERN_BYTES)
+                    .build()
+                    .map_err(|e| format!("path_regex is not a valid pattern: {}", e))?,
+            ),
+            None => None,
+        };
+
+        let action = match (&config.redirect, &config.rewrite) {
+            (Some(target), None) => {
+                if !is_redirect_target(target) {
+                    return Err(
+                        "redirect must be a path or an http or https URL, in printable ASCII"
+                            .to_string(),
+                    );
+                }
+                let status = match config.status {
+                    None => 301,
+                    Some(status) => REDIRECT_STATUSES
+                        .into_iter()
+                        .find(|&s| i64::from(s) == status)
+                        .ok_or_else(|| "status must be 301, 302, 307 or 308".to_string())?,
+                };
+                Action::Redirect {
+                    target: Template::parse(target, path_regex.as_ref())
+                        .map_err(|e| format!("redirect: {}", e))?,
+                    status,
+                }
+            }
+            (None, Some(target)) => {
+                if config.status.is_some() {
+                    return Err("status only applies to redirect rules".to_string());
+                }
+                if !target.starts_with('/') {
+                    return Err("rewrite must start with /".to_string());
+                }
+                let (path, query) = match target.split_once('?') {
+                    Some((path, query)) => (path, Some(query)),
+                    None => (target.as_str(), None),
+                };
+                let parse = |source| {
+                    Template::parse(source, path_regex.as_ref())
+                        .map_err(|e| format!("rewrite: {}", e))
+                };
+                Action::Rewrite {
+                    path: parse(path)?,
+                    query: query.map(parse).transpose()?,
+                }
+            }
+            _ => return Err("a rule must have exactly one of redirect and rewrite".to_string()),
+        };
+
+        Ok(Rule {
+            host,
+            path_prefix: config.path_prefix,
+            path_regex,
+            action,
+            keep_query: config.keep_query,
+        })
+    }
+
+    /// What the rule does with a request for `host` (lowercase, no port),
+    /// `path` and `query`, or `None` when a condition does not hold.
+    pub fn apply(&self, host: &str, path: &str, query: &str) -> Result<Option<Applied>> {
+        if let Some(pattern) = &self.host {
+            if !host_matches(pattern, host) {
+                return Ok(None);
+            }
+        }
+        if let Some(prefix) = &self.path_prefix {
+            if !has_prefix(path, prefix) {
+                return Ok(None);
+            }
+        }
+        let captures = match &self.path_regex {
+            Some(regex) => match regex.captures(path) {
+                Some(captures) => Some(captures),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        let query = if self.keep_query { query } else { "" };
+
+        Ok(Some(match &self.action {
+            Action::Redirect { target, status } => {
+                let location = target.render(captures.as_ref(), true);
+                // "/$1" with $1 = "/evil.example" would leave the site.
+                if location.starts_with("//") {
+                    return Err(PluginError::invalid_input(
+                        "The redirect for this path would go to another host",
+                    )
+                    .with_detail("path", path));
+                }
+                Applied::Redirect {
+                    status: *status,
+                    location: with_query(location, query),
+                }
+            }
+            Action::Rewrite {
+                path: path_template,
+                query: query_template,
+            } => {
+                let target_query = query_template
+                    .as_ref()
+                    .map(|t| t.render(captures.as_ref(), true))
+                    .unwrap_or_default();
+                Applied::Rewrite {
+                    path: path_template.render(captures.as_ref(), false),
+                    query: join_queries(&target_query, query),
+                }
+            }
+        }))
+    }
+}
+
+fn is_redirect_target(target: &str) -> bool {
+    let lower = target.to_ascii_lowercase();
+    (lower.starts_with("https://")
+        || lower.starts_with("http://")
+        || (target.starts_with('/') && !target.starts_with("//") && !target.starts_with("/\\")))
+        && target.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Whether `path` is `prefix` or below it: `/docs` matches `/docs` and
+/// `/docs/intro` but not `/docsify`.
+fn has_prefix(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'))
+}
+
+/// As in `firelynx_pdk::allowlist`: `*.example.com` matches subdomains of
+/// `example.com` but not `example.com` itself.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+/// `location` with `query` added before any fragment.
+fn with_query(location: String, query: &str) -> String {
+    if query.is_empty() {
+        return location;
+    }
+    let (base, fragment) = match location.split_once('#') {
+        Some((base, fragment)) => (base, Some(fragment)),
+        None => (location.as_str(), None),
+    };
+    let (base, target_query) = match base.split_once('?') {
+        Some((base, target_query)) => (base, target_query),
+        None => (base, ""),
+    };
+    let mut out = format!("{}?{}", base, join_queries(target_query, query));
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(fragment);
+    }
+    out
+}
+
+/// Two query strings as one, the target's parameters first.
+fn join_queries(first: &str, second: &str) -> String {
+    match (first.is_empty(), second.is_empty()) {
+        (_, true) => first.to_string(),
+        (true, false) => second.to_string(),
+        (false, false) => format!("{}&{}", first, second),
+    }
+}
@@ -0,0 +1,114 @@
+//! Redirect and rewrite targets, with `$1` and `${name}` references to the
+//! groups of a rule's `path_regex`.
+//!
+//! The syntax is the regex crate's `Captures::expand`: `$$` is a dollar
+//! sign, a `$` not followed by a name is itself, and `$name` takes the
+//! longest run of letters, digits and `_`, so `$1st` names a group `1st`
+//! and `${1}st` is group 1 followed by `st`. Unlike `expand`, a reference
+//! to a group the regex does not have is an error rather than empty text.
+
+use std::fmt::Write;
+use std::mem;
+
+use regex::{Captures, Regex};
+
+enum Part {
+    Literal(String),
+    Group(usize),
+}
+
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses `source`, resolving its references against `regex`. The
+    /// error says what is wrong.
+    pub fn parse(source: &str, regex: Option<&Regex>) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = source;
+        while let Some(at) = rest.find('$') {
+            literal.push_str(&rest[..at]);
+            let after = &rest[at + 1..];
+            let (name, next) = if let Some(after) = after.strip_prefix('$') {
+                literal.push('$');
+                rest = after;
+                continue;
+            } else if let Some(braced) = after.strip_prefix('{') {
+                let end = braced
+                    .find('}')
+                    .ok_or_else(|| "a ${ is not closed".to_string())?;
+                (&braced[..end], &braced[end + 1..])
+            } else {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                if end == 0 {
+                    literal.push('$');
+                    rest = after;
+                    continue;
+                }
+                after.split_at(end)
+            };
+            let index = resolve(name, regex)?;
+            if !literal.is_empty() {
+                parts.push(Part::Literal(mem::take(&mut literal)));
+            }
+            parts.push(Part::Group(index));
+            rest = next;
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// The target for a match. A group that did not take part in the match
+    /// is empty. With `escape`, captured text is percent-encoded for a URL,
+    /// so a path the host has already decoded cannot add a query, a
+    /// fragment or a line break.
+    pub fn render(&self, captures: Option<&Captures>, escape: bool) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Group(index) => {
+                    let text = captures
+                        .and_then(|c| c.get(*index))
+                        .map_or("", |m| m.as_str());
+                    if escape {
+                        percent_encode(text, &mut out);
+                    } else {
+                        out.push_str(text);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn resolve(name: &str, regex: Option<&Regex>) -> Result<usize, String> {
+    let Some(regex) = regex else {
+        return Err(format!("${{{}}} needs a path_regex to refer to", name));
+    };
+    let index = match name.parse::<usize>() {
+        Ok(index) => Some(index).filter(|&index| index < regex.captures_len()),
+        Err(_) => regex.capture_names().position(|n| n == Some(name)),
+    };
+    index.ok_or_else(|| format!("path_regex has no group {}", name))
+}
+
+/// Appends `text` with every byte that may not appear as itself in a URL
+/// path percent-encoded; `/` stays, so a group can hold several segments.
+fn percent_encode(text: &str, out: &mut String) {
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&b) {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn site_rules() -> Value {
+    json!([
+        {"host": "old.example.com", "redirect": "https://www.example.com/", "status": 308},
+        {"path_regex": "^/blog/(\\d{4})/(?P<slug>[^/]+)/?$", "redirect": "/posts/${slug}?year=$1"},
+        {"path_regex": "^/api/v1(/.*)?$", "rewrite": "/api/v2$1"},
+        {"path_prefix": "/beta", "redirect": "/", "status": 302, "keep_query": false},
+        {"path_regex": "^/go/(.*)$", "redirect": "/$1"}
+    ])
+}
+
+fn apply(fixture: RequestFixture) -> Result<Value, Error> {
+    let input = fixture.static_data("rules", site_rules()).to_json();
+    let Json(decision): Json<Value> = xtp_test::call("ApplyRules", input)?;
+    Ok(decision)
+}
+
+fn get(path: &str) -> RequestFixture {
+    RequestFixture::new("").method("GET").path(path)
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("redirects", || {
+        let decision = apply(get("/anything").host("Old.Example.com:8080"))?;
+        xtp_test::assert_eq!(
+            "the host rule matches in any case and on any port",
+            &decision,
+            &json!({
+                "action": "redirect",
+                "rule": 0,
+                "status": 308,
+                "location": "https://www.example.com/"
+            })
+        );
+
+        let decision = apply(get("/blog/2024/hello-world").query("utm_source", "feed"))?;
+        xtp_test::assert_eq!(
+            "regex groups fill the target and the query is kept",
+            &decision["location"],
+            &json!("/posts/hello-world?year=2024&utm_source=feed")
+        );
+        xtp_test::assert_eq!(
+            "redirects are 301 by default",
+            &decision["status"],
+            &json!(301)
+        );
+
+        let decision = apply(get("/beta/signup").query("ref", "x"))?;
+        xtp_test::assert_eq!(
+            "keep_query = false drops the query",
+            &decision["location"],
+            &json!("/")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("rewrites", || {
+        let decision = apply(get("/api/v1/users/7").query("page", "2"))?;
+        xtp_test::assert_eq!(
+            "the path is rewritten and the query kept",
+            &decision,
+            &json!({"action": "rewrite", "rule": 2, "path": "/api/v2/users/7", "query": "page=2"})
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("no match", || {
+        xtp_test::assert_eq!(
+            "a path prefix matches whole segments only",
+            &apply(get("/betamax"))?,
+            &json!({"action": "pass"})
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("unsafe redirects", || {
+        let decision = apply(get("/go/a b?c"))?;
+        xtp_test::assert_eq!(
+            "captured text is percent-encoded",
+            &decision["location"],
+            &json!("/a%20b%3Fc")
+        );
+        xtp_test::assert!(
+            "a redirect to another host is refused",
+            apply(get("/go//evil.example")).is_err()
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+mod shape;
+
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use shape::{Limits, Shape};
+
+/// Prefix of the extism var holding each route's inference state.
+const STATE_VAR_PREFIX: &str = "schema_inference:";
+
+/// The schema inferred so far for one route. Matches `InferenceReport` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InferenceReport {
+    /// The request path the samples were collected for.
+    pub path: String,
+    /// Bodies merged into the schema.
+    pub samples: u64,
+    /// Bodies left out: not JSON, larger than `max_body_bytes`, or received
+    /// after `max_samples` was reached.
+    pub skipped: u64,
+    /// The inferred JSON Schema (draft 2020-12).
+    pub schema: Value,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    samples: u64,
+    skipped: u64,
+    shape: Shape,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    max_body_bytes: u64,
+    max_samples: u64,
+    max_depth: u64,
+    max_properties: u64,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::integer("max_body_bytes").default(DefaultValue::Int(64 * 1024)),
+        Field::integer("max_samples").default(DefaultValue::Int(1000)),
+        Field::integer("max_depth").default(DefaultValue::Int(16)),
+        Field::integer("max_properties").default(DefaultValue::Int(256)),
+    ];
+}
+
+#[firelynx_plugin]
+fn infer_schema(request: Request, static_data: StaticData) -> Result<InferenceReport> {
+    let config = Config::from_static_data(&static_data)?;
+    let limits = Limits {
+        max_depth: config.max_depth as usize,
+        max_properties: config.max_properties as usize,
+    };
+
+    let path = request.url.path.clone();
+    let key = format!("{}{}", STATE_VAR_PREFIX, path);
+    let mut state = load(&key)?;
+
+    let sample = if state.samples >= config.max_samples
+        || request.body.encoded_len() as u64 > config.max_body_bytes
+    {
+        None
+    } else {
+        serde_json::from_str::<Value>(&request.body.text()).ok()
+    };
+    match sample {
+        Some(body) => {
+            state.shape.merge(&body, limits);
+            state.samples += 1;
+        }
+        None => state.skipped += 1,
+    }
+
+    extism_pdk::var::set(&key, Json(&state))
+        .map_err(|e| PluginError::internal(format!("Failed to save inference state: {}", e)))?;
+
+    let mut schema = state.shape.to_schema();
+    if let Value::Object(map) = &mut schema {
+        map.insert(
+            "$schema".to_string(),
+            "https://json-schema.org/draft/2020-12/schema".into(),
+        );
+    }
+    Ok(InferenceReport {
+        path,
+        samples: state.samples,
+        skipped: state.skipped,
+        schema,
+    })
+}
+
+fn load(key: &str) -> Result<State> {
+    match extism_pdk::var::get::<Json<State>>(key) {
+        Ok(Some(Json(state))) => Ok(state),
+        Ok(None) => Ok(State::default()),
+        Err(e) => Err(PluginError::internal(format!(
+            "Failed to load inference state: {}",
+            e
+        ))),
+    }
+}
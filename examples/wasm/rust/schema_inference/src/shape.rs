@@ -0,0 +1,161 @@
+//! Incremental JSON shape inference.
+//!
+//! A `Shape` summarizes every value merged into it: which JSON types were
+//! seen, and for objects and arrays the shapes of their members. It only
+//! grows, so merging samples one at a time across calls gives the same
+//! result as merging them all at once.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Bounds that keep the inferred state small no matter what traffic looks
+/// like.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Nesting below this depth is recorded as "any value".
+    pub max_depth: usize,
+    /// New property names beyond this many per object are ignored.
+    pub max_properties: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonType {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Shape {
+    types: BTreeSet<JsonType>,
+    /// Objects merged into this shape; a property is required when it was
+    /// present in all of them.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    objects: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, Property>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    items: Option<Box<Shape>>,
+    /// Set when values were cut off by `max_depth` or `max_properties`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    truncated: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Property {
+    seen: u64,
+    shape: Shape,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl Shape {
+    pub fn merge(&mut self, value: &Value, limits: Limits) {
+        self.merge_at(value, limits, 0);
+    }
+
+    fn merge_at(&mut self, value: &Value, limits: Limits, depth: usize) {
+        if depth >= limits.max_depth {
+            self.truncated = true;
+            return;
+        }
+        match value {
+            Value::Null => {
+                self.types.insert(JsonType::Null);
+            }
+            Value::Bool(_) => {
+                self.types.insert(JsonType::Boolean);
+            }
+            Value::Number(n) if n.is_f64() => {
+                self.types.insert(JsonType::Number);
+            }
+            Value::Number(_) => {
+                self.types.insert(JsonType::Integer);
+            }
+            Value::String(_) => {
+                self.types.insert(JsonType::String);
+            }
+            Value::Array(items) => {
+                self.types.insert(JsonType::Array);
+                let shape = self.items.get_or_insert_with(Box::default);
+                for item in items {
+                    shape.merge_at(item, limits, depth + 1);
+                }
+            }
+            Value::Object(map) => {
+                self.types.insert(JsonType::Object);
+                self.objects += 1;
+                for (key, value) in map {
+                    let known = self.properties.contains_key(key);
+                    if !known && self.properties.len() >= limits.max_properties {
+                        self.truncated = true;
+                        continue;
+                    }
+                    let property = self.properties.entry(key.clone()).or_default();
+                    property.seen += 1;
+                    property.shape.merge_at(value, limits, depth + 1);
+                }
+            }
+        }
+    }
+
+    /// Renders the shape as a JSON Schema (draft 2020-12) fragment.
+    pub fn to_schema(&self) -> Value {
+        let mut schema = Map::new();
+        let mut types: Vec<JsonType> = self.types.iter().copied().collect();
+        // An integer seen next to a float is just a number.
+        if types.contains(&JsonType::Number) {
+            types.retain(|t| *t != JsonType::Integer);
+        }
+        match types.as_slice() {
+            [] => {}
+            [single] => {
+                schema.insert("type".into(), json!(single));
+            }
+            many => {
+                schema.insert("type".into(), json!(many));
+            }
+        }
+
+        if !self.properties.is_empty() {
+            let properties: Map<String, Value> = self
+                .properties
+                .iter()
+                .map(|(k, p)| (k.clone(), p.shape.to_schema()))
+                .collect();
+            let required: Vec<&str> = self
+                .properties
+                .iter()
+                .filter(|(_, p)| p.seen == self.objects)
+                .map(|(k, _)| k.as_str())
+                .collect();
+            schema.insert("properties".into(), Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".into(), json!(required));
+            }
+        }
+        if let Some(items) = &self.items {
+            schema.insert("items".into(), items.to_schema());
+        }
+        if self.truncated {
+            schema.insert(
+                "$comment".into(),
+                json!("truncated by max_depth or max_properties"),
+            );
+        }
+        Value::Object(schema)
+    }
+}
@@ -0,0 +1,77 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn sample(path: &str, body: &str) -> String {
+    RequestFixture::new(body).path(path).to_json()
+}
+
+fn infer(input: &str) -> Result<Value, Error> {
+    let Json(report): Json<Value> = xtp_test::call("InferSchema", input)?;
+    Ok(report)
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("inference", || {
+        let report = infer(&sample(
+            "/orders",
+            r#"{"id": 1, "name": "a", "tags": ["x"]}"#,
+        ))?;
+        xtp_test::assert_eq!("first sample counted", &report["samples"], &json!(1));
+        xtp_test::assert_eq!("object root", &report["schema"]["type"], &json!("object"));
+        xtp_test::assert_eq!(
+            "integer field",
+            &report["schema"]["properties"]["id"]["type"],
+            &json!("integer")
+        );
+        xtp_test::assert_eq!(
+            "array items",
+            &report["schema"]["properties"]["tags"]["items"]["type"],
+            &json!("string")
+        );
+
+        let report = infer(&sample("/orders", r#"{"id": 2.5, "name": null}"#))?;
+        xtp_test::assert_eq!("second sample counted", &report["samples"], &json!(2));
+        xtp_test::assert_eq!(
+            "integer and float widen to number",
+            &report["schema"]["properties"]["id"]["type"],
+            &json!("number")
+        );
+        xtp_test::assert_eq!(
+            "nullable field",
+            &report["schema"]["properties"]["name"]["type"],
+            &json!(["null", "string"])
+        );
+        xtp_test::assert_eq!(
+            "only always-present fields are required",
+            &report["schema"]["required"],
+            &json!(["id", "name"])
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("bounds", || {
+        let report = infer(&sample("/notes", "plain text"))?;
+        xtp_test::assert_eq!("non-JSON body skipped", &report["skipped"], &json!(1));
+        xtp_test::assert_eq!("nothing sampled", &report["samples"], &json!(0));
+
+        let big = RequestFixture::new(format!(r#"{{"blob": "{}"}}"#, "x".repeat(100)))
+            .path("/big")
+            .static_data("max_body_bytes", 50)
+            .to_json();
+        let report = infer(&big)?;
+        xtp_test::assert_eq!("oversized body skipped", &report["skipped"], &json!(1));
+
+        let report = infer(&sample("/orders", r#"{"other": true}"#))?;
+        xtp_test::assert_eq!(
+            "paths are tracked separately",
+            &report["path"],
+            &json!("/orders")
+        );
+        xtp_test::assert_eq!("orders keeps its samples", &report["samples"], &json!(3));
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! Templates compiled into the plugin, chosen with `static_data.asset`.
+//!
+//! Each is a file under `templates/` named `<asset>.hbs`; the extension
+//! before `.hbs` sets the default content type. Add a template by adding
+//! the file and a line to `ASSETS`.
+
+/// `(name, source)` of every embedded template.
+pub const ASSETS: [(&str, &str); 2] = [
+    ("page.html", include_str!("../templates/page.html.hbs")),
+    ("message.txt", include_str!("../templates/message.txt.hbs")),
+];
+
+/// The source of the embedded template `name`.
+pub fn source(name: &str) -> Option<&'static str> {
+    ASSETS
+        .iter()
+        .find(|(asset, _)| *asset == name)
+        .map(|(_, source)| *source)
+}
+
+/// The names `asset` can take.
+pub fn names() -> Vec<&'static str> {
+    ASSETS.iter().map(|(name, _)| *name).collect()
+}
+
+/// The content type a template named `name` renders, by its extension.
+pub fn content_type(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("css") => "text/css; charset=utf-8",
+        Some("md") => "text/markdown; charset=utf-8",
+        _ => "text/html; charset=utf-8",
+    }
+}
@@ -0,0 +1,188 @@
+mod assets;
+
+use std::io;
+
+use firelynx_pdk::prelude::*;
+use handlebars::{no_escape, Handlebars, RenderErrorReason, TemplateError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The registry name of the template being rendered.
+const MAIN: &str = "main";
+
+/// The page to send. Matches `RenderedResponse` in `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedResponse {
+    pub status: u16,
+    /// The `Content-Type` header for `body`.
+    pub content_type: String,
+    pub body: String,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    template: Option<String>,
+    #[serde(default)]
+    asset: Option<String>,
+    #[serde(default)]
+    content_type: Option<String>,
+    #[serde(default)]
+    partials: Map<String, Value>,
+    strict: bool,
+    max_output_bytes: u64,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string("template").non_empty(),
+        Field::string("asset").non_empty(),
+        Field::string("content_type").non_empty(),
+        Field::table("partials"),
+        Field::bool("strict").default(DefaultValue::Bool(false)),
+        Field::integer("max_output_bytes").default(DefaultValue::Int(1024 * 1024)),
+    ];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+}
+
+impl Config {
+    /// The template source, the field it came from, and the content type
+    /// it renders unless `content_type` says otherwise.
+    fn template(&self) -> Result<(&str, &'static str, &'static str)> {
+        match (&self.template, &self.asset) {
+            (Some(template), None) => Ok((template, "template", "text/html; charset=utf-8")),
+            (None, Some(name)) => match assets::source(name) {
+                Some(source) => Ok((source, "asset", assets::content_type(name))),
+                None => Err(PluginError::config("asset names no embedded template")
+                    .with_detail("field", "asset")
+                    .with_detail("value", name.as_str())
+                    .with_detail("supported", assets::names())),
+            },
+            (None, None) => Err(PluginError::config("template or asset is required")
+                .with_detail("field", "template")),
+            (Some(_), Some(_)) => Err(PluginError::config("template and asset cannot both be set")
+                .with_detail("field", "asset")),
+        }
+    }
+}
+
+#[firelynx_plugin]
+fn render_template(request: Request, static_data: StaticData) -> Result<RenderedResponse> {
+    let config = Config::from_static_data(&static_data)?;
+    let (source, field, default_type) = config.template()?;
+    let content_type = config
+        .content_type
+        .clone()
+        .unwrap_or_else(|| default_type.to_string());
+
+    let mut registry = Handlebars::new();
+    registry.set_strict_mode(config.strict);
+    // Only markup needs its values escaped; escaping a text or JSON
+    // response would put entities in it.
+    if !is_markup(&content_type) {
+        registry.register_escape_fn(no_escape);
+    }
+    registry
+        .register_template_string(MAIN, source)
+        .map_err(|e| template_error(e, field))?;
+    for (name, partial) in &config.partials {
+        let Some(partial) = partial.as_str() else {
+            return Err(PluginError::config("partials values must be strings")
+                .with_detail("field", "partials")
+                .with_detail("partial", name.as_str()));
+        };
+        registry
+            .register_partial(name, partial)
+            .map_err(|e| template_error(e, "partials").with_detail("partial", name.as_str()))?;
+    }
+
+    // An empty body renders with an empty context.
+    let context: Value = if request.body.is_empty() {
+        Value::Object(Map::new())
+    } else {
+        request.decode_as("application/json")?
+    };
+
+    let limit = usize::try_from(config.max_output_bytes).unwrap_or(usize::MAX);
+    let mut body = LimitedWriter {
+        buf: Vec::new(),
+        limit,
+    };
+    if let Err(e) = registry.render_to_write(MAIN, &context, &mut body) {
+        if body.buf.len() > limit {
+            return Err(PluginError::invalid_input(format!(
+                "The rendered page is larger than {} bytes",
+                limit
+            ))
+            .with_detail("field", "max_output_bytes"));
+        }
+        let reason = match e.reason() {
+            RenderErrorReason::MissingVariable(Some(path)) => {
+                format!("{} is not in the request body", path)
+            }
+            _ => e.to_string(),
+        };
+        return Err(
+            PluginError::invalid_input("The request body does not fit the template")
+                .with_detail("reason", reason),
+        );
+    }
+
+    log_debug!(
+        "rendered template",
+        bytes = body.buf.len(),
+        content_type = content_type,
+    );
+    Ok(RenderedResponse {
+        status: 200,
+        content_type,
+        // Templates and the JSON context are UTF-8, so the output is too.
+        body: String::from_utf8(body.buf).unwrap_or_default(),
+    })
+}
+
+/// Whether `content_type` is HTML or XML, whose values need escaping.
+fn is_markup(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "text/html"
+        || essence == "application/xhtml+xml"
+        || essence.ends_with("/xml")
+        || essence.ends_with("+xml")
+}
+
+fn template_error(e: TemplateError, field: &str) -> PluginError {
+    let err = PluginError::config("The template does not parse")
+        .with_detail("field", field)
+        .with_detail("reason", e.reason().to_string());
+    match e.pos() {
+        Some((line, column)) => err.with_detail("line", line).with_detail("column", column),
+        None => err,
+    }
+}
+
+/// Collects the rendered page, failing the render once it passes `limit`
+/// bytes so a template that multiplies a large body stops early.
+struct LimitedWriter {
+    buf: Vec<u8>,
+    limit: usize,
+}
+
+impl io::Write for LimitedWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(bytes);
+        if self.buf.len() > self.limit {
+            return Err(io::Error::other("output limit reached"));
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
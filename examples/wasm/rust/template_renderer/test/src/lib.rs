@@ -0,0 +1,111 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn render(input: &str) -> Result<Value, Error> {
+    let Json(response): Json<Value> = xtp_test::call("RenderTemplate", input)?;
+    Ok(response)
+}
+
+fn fails(input: &str) -> bool {
+    xtp_test::call::<Json<Value>>("RenderTemplate", input).is_err()
+}
+
+fn inline(body: &str, template: &str) -> RequestFixture {
+    RequestFixture::new(body).static_data("template", template)
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("inline templates", || {
+        let response = render(&inline(r#"{"name": "Ada"}"#, "<p>Hello, {{name}}!</p>").to_json())?;
+        xtp_test::assert_eq!(
+            "the body fills the template",
+            &response["body"],
+            &json!("<p>Hello, Ada!</p>")
+        );
+        xtp_test::assert_eq!(
+            "inline templates are HTML",
+            &response["content_type"],
+            &json!("text/html; charset=utf-8")
+        );
+
+        let response = render(&inline(r#"{"name": "<script>"}"#, "<p>{{name}}</p>").to_json())?;
+        xtp_test::assert_eq!(
+            "HTML values are escaped",
+            &response["body"],
+            &json!("<p>&lt;script&gt;</p>")
+        );
+
+        let input = inline(r#"{"name": "<script>"}"#, "{{name}}")
+            .static_data("content_type", "text/plain; charset=utf-8")
+            .to_json();
+        xtp_test::assert_eq!(
+            "text is not escaped",
+            &render(&input)?["body"],
+            &json!("<script>")
+        );
+
+        let input = inline(
+            r#"{"items": ["a", "b"]}"#,
+            "{{#each items}}{{> item}}{{/each}}",
+        )
+        .static_data("partials", json!({"item": "[{{this}}]"}))
+        .to_json();
+        xtp_test::assert_eq!(
+            "partials are available",
+            &render(&input)?["body"],
+            &json!("[a][b]")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("embedded templates", || {
+        let input = RequestFixture::new(r#"{"title": "Orders", "items": ["first"]}"#)
+            .static_data("asset", "page.html")
+            .to_json();
+        let response = render(&input)?;
+        xtp_test::assert!(
+            "the page has the title",
+            response["body"]
+                .as_str()
+                .is_some_and(|page| page.contains("<h1>Orders</h1>"))
+        );
+
+        let input = RequestFixture::new(r#"{"message": "Shipped"}"#)
+            .static_data("asset", "message.txt")
+            .to_json();
+        xtp_test::assert_eq!(
+            "a .txt asset is plain text",
+            &render(&input)?["content_type"],
+            &json!("text/plain; charset=utf-8")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("errors", || {
+        xtp_test::assert!(
+            "no template is rejected",
+            fails(&RequestFixture::new("{}").empty_static_data().to_json())
+        );
+        xtp_test::assert!(
+            "a template that does not parse is rejected",
+            fails(&inline("{}", "{{#if open}}").to_json())
+        );
+        xtp_test::assert!(
+            "strict mode rejects missing values",
+            fails(
+                &inline("{}", "{{name}}")
+                    .static_data("strict", true)
+                    .to_json()
+            )
+        );
+        xtp_test::assert!(
+            "a body that is not JSON is rejected",
+            fails(&inline("name=Ada", "{{name}}").to_json())
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
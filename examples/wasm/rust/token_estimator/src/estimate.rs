@@ -0,0 +1,141 @@
+//! Approximate BPE token counting.
+//!
+//! Real tokenizers need their vocabulary tables, which are megabytes each.
+//! This splits text the way GPT-style pre-tokenizers do (letter runs, digit
+//! runs, punctuation runs, whitespace) and charges each piece by length using
+//! per-model ratios. Every piece is rounded up, so estimates err on the high
+//! side, which is the safe direction for enforcing a budget.
+
+use serde::Deserialize;
+
+/// Per-model ratios used to price each piece of text.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// ASCII letters per token in a word.
+    pub chars_per_token: f64,
+    /// Digits per token; GPT-4 era tokenizers split numbers into groups of
+    /// three, SentencePiece models into single digits.
+    pub digits_per_token: f64,
+    /// ASCII punctuation characters per token.
+    pub punctuation_per_token: f64,
+    /// UTF-8 bytes per token for non-ASCII text.
+    pub non_ascii_bytes_per_token: f64,
+}
+
+/// Profiles available without configuration.
+pub const BUILTIN: &[(&str, Profile)] = &[
+    (
+        "cl100k",
+        Profile {
+            chars_per_token: 4.0,
+            digits_per_token: 3.0,
+            punctuation_per_token: 2.0,
+            non_ascii_bytes_per_token: 2.5,
+        },
+    ),
+    (
+        "o200k",
+        Profile {
+            chars_per_token: 4.4,
+            digits_per_token: 3.0,
+            punctuation_per_token: 2.0,
+            non_ascii_bytes_per_token: 3.5,
+        },
+    ),
+    (
+        "p50k",
+        Profile {
+            chars_per_token: 4.0,
+            digits_per_token: 2.0,
+            punctuation_per_token: 1.5,
+            non_ascii_bytes_per_token: 1.5,
+        },
+    ),
+    (
+        "llama",
+        Profile {
+            chars_per_token: 3.6,
+            digits_per_token: 1.0,
+            punctuation_per_token: 1.0,
+            non_ascii_bytes_per_token: 1.5,
+        },
+    ),
+];
+
+impl Profile {
+    pub fn builtin(name: &str) -> Option<Profile> {
+        BUILTIN.iter().find(|(n, _)| *n == name).map(|(_, p)| *p)
+    }
+
+    /// Returns the name of the first ratio that is not a positive number.
+    pub fn invalid_ratio(&self) -> Option<&'static str> {
+        [
+            ("chars_per_token", self.chars_per_token),
+            ("digits_per_token", self.digits_per_token),
+            ("punctuation_per_token", self.punctuation_per_token),
+            ("non_ascii_bytes_per_token", self.non_ascii_bytes_per_token),
+        ]
+        .into_iter()
+        .find(|(_, ratio)| !(ratio.is_finite() && *ratio > 0.0))
+        .map(|(name, _)| name)
+    }
+
+    /// Estimates the number of tokens in `text`.
+    pub fn estimate(&self, text: &str) -> u64 {
+        let mut tokens = 0;
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let (class, len) = piece(rest, c);
+            let (piece, tail) = rest.split_at(len);
+            tokens += match class {
+                Class::Letters => cost(len, self.chars_per_token),
+                Class::Digits => cost(len, self.digits_per_token),
+                Class::Punctuation => cost(len, self.punctuation_per_token),
+                Class::NonAscii => cost(len, self.non_ascii_bytes_per_token),
+                // A single space is merged into the word that follows it.
+                Class::Whitespace if piece == " " && !tail.is_empty() => 0,
+                Class::Whitespace => 1,
+            };
+            rest = tail;
+        }
+        tokens
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Letters,
+    Digits,
+    Punctuation,
+    Whitespace,
+    NonAscii,
+}
+
+fn class(c: char) -> Class {
+    if c.is_ascii_alphabetic() {
+        Class::Letters
+    } else if c.is_ascii_digit() {
+        Class::Digits
+    } else if c.is_whitespace() {
+        Class::Whitespace
+    } else if c.is_ascii() {
+        Class::Punctuation
+    } else {
+        Class::NonAscii
+    }
+}
+
+/// The class of the run starting with `first` and its length in bytes.
+fn piece(text: &str, first: char) -> (Class, usize) {
+    let class = class(first);
+    let len = text
+        .char_indices()
+        .find(|(_, c)| self::class(*c) != class)
+        .map_or(text.len(), |(i, _)| i);
+    (class, len)
+}
+
+fn cost(len: usize, per_token: f64) -> u64 {
+    (len as f64 / per_token).ceil() as u64
+}
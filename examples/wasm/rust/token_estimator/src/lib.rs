@@ -0,0 +1,96 @@
+mod estimate;
+
+use std::collections::HashMap;
+
+use firelynx_pdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use estimate::Profile;
+
+/// The estimated size of a request body. Matches `TokenEstimate` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenEstimate {
+    /// The profile the estimate was made with.
+    pub profile: String,
+    pub tokens: u64,
+    pub characters: u64,
+    pub bytes: u64,
+    /// The configured budget, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Tokens left in the budget after this body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    profile: String,
+    max_tokens: Option<u64>,
+    /// Custom profiles by name; these take precedence over built-ins.
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string("profile")
+            .non_empty()
+            .default(DefaultValue::Str("cl100k")),
+        Field::integer("max_tokens"),
+        Field::table("profiles"),
+    ];
+}
+
+impl Config {
+    fn profile(&self) -> Result<Profile> {
+        let profile = self
+            .profiles
+            .get(&self.profile)
+            .copied()
+            .or_else(|| Profile::builtin(&self.profile));
+        let Some(profile) = profile else {
+            let mut known: Vec<&str> = estimate::BUILTIN.iter().map(|(n, _)| *n).collect();
+            known.extend(self.profiles.keys().map(String::as_str));
+            known.sort_unstable();
+            known.dedup();
+            return Err(PluginError::config("profile is not a known token profile")
+                .with_detail("field", "profile")
+                .with_detail("value", self.profile.as_str())
+                .with_detail("known", known));
+        };
+        if let Some(ratio) = profile.invalid_ratio() {
+            return Err(
+                PluginError::config(format!("{} must be a positive number", ratio))
+                    .with_detail("field", "profiles")
+                    .with_detail("profile", self.profile.as_str()),
+            );
+        }
+        Ok(profile)
+    }
+}
+
+#[firelynx_plugin]
+fn estimate_tokens(request: RequestRef<'_>, static_data: StaticData) -> Result<TokenEstimate> {
+    let config = Config::from_static_data(&static_data)?;
+    let profile = config.profile()?;
+
+    let body = request.body;
+    let tokens = profile.estimate(&body);
+    if let Some(max) = config.max_tokens.filter(|max| tokens > *max) {
+        return Err(PluginError::policy("Token budget exceeded")
+            .with_detail("tokens", tokens)
+            .with_detail("max_tokens", max)
+            .with_detail("profile", config.profile));
+    }
+
+    Ok(TokenEstimate {
+        tokens,
+        characters: body.chars().count() as u64,
+        bytes: body.len() as u64,
+        max_tokens: config.max_tokens,
+        remaining: config.max_tokens.map(|max| max - tokens),
+        profile: config.profile,
+    })
+}
@@ -0,0 +1,104 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+fn request(body: &str, static_data: &[(&str, Value)]) -> String {
+    static_data
+        .iter()
+        .fold(RequestFixture::new(body).path("/v1/chat"), |f, (k, v)| {
+            f.static_data(*k, v.clone())
+        })
+        .to_json()
+}
+
+fn estimate(input: &str) -> Result<Value, Error> {
+    let Json(estimate): Json<Value> = xtp_test::call("EstimateTokens", input)?;
+    Ok(estimate)
+}
+
+fn tokens(body: &str, profile: &str) -> Result<u64, Error> {
+    let estimate = estimate(&request(body, &[("profile", json!(profile))]))?;
+    Ok(estimate["tokens"].as_u64().unwrap_or_default())
+}
+
+fn fails(input: &str) -> bool {
+    xtp_test::call::<Json<Value>>("EstimateTokens", input).is_err()
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("estimation", || {
+        let report = estimate(&request("Hello world", &[]))?;
+        xtp_test::assert_eq!("default profile", &report["profile"], &json!("cl100k"));
+        xtp_test::assert_eq!("two words", &report["tokens"], &json!(4));
+        xtp_test::assert_eq!("byte count", &report["bytes"], &json!(11));
+        xtp_test::assert!("no budget reported", report.get("max_tokens").is_none());
+
+        let empty = estimate(&request("", &[]))?;
+        xtp_test::assert_eq!("empty body is free", &empty["tokens"], &json!(0));
+
+        xtp_test::assert!(
+            "llama prices digits individually",
+            tokens("123456", "llama")? > tokens("123456", "cl100k")?
+        );
+        let text = "The quick brown fox jumps over the lazy dog";
+        xtp_test::assert!(
+            "o200k needs no more tokens than cl100k",
+            tokens(text, "o200k")? <= tokens(text, "cl100k")?
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("budget", || {
+        let report = estimate(&request("Hello world", &[("max_tokens", json!(10))]))?;
+        xtp_test::assert_eq!("remaining budget", &report["remaining"], &json!(6));
+        xtp_test::assert!(
+            "over budget is rejected",
+            fails(&request(&"word ".repeat(100), &[("max_tokens", json!(10))]))
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("profiles", || {
+        let unit = json!({
+            "chars_per_token": 1,
+            "digits_per_token": 1,
+            "punctuation_per_token": 1,
+            "non_ascii_bytes_per_token": 1
+        });
+        let report = estimate(&request(
+            "abc",
+            &[
+                ("profile", json!("unit")),
+                ("profiles", json!({"unit": unit})),
+            ],
+        ))?;
+        xtp_test::assert_eq!("custom profile used", &report["tokens"], &json!(3));
+
+        xtp_test::assert!(
+            "unknown profile is rejected",
+            fails(&request("abc", &[("profile", json!("nope"))]))
+        );
+        xtp_test::assert!(
+            "zero ratio is rejected",
+            fails(&request(
+                "abc",
+                &[
+                    ("profile", json!("bad")),
+                    (
+                        "profiles",
+                        json!({"bad": {
+                            "chars_per_token": 0,
+                            "digits_per_token": 1,
+                            "punctuation_per_token": 1,
+                            "non_ascii_bytes_per_token": 1
+                        }})
+                    ),
+                ],
+            ))
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+mod signature;
+
+use firelynx_pdk::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use signature::Provider;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Whether the delivery is genuine. Matches `WebhookVerdict` in
+/// `schema.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookVerdict {
+    pub verified: bool,
+    /// The status to answer with: 200 for a verified delivery, 401 for any
+    /// other.
+    pub status: u16,
+    pub provider: String,
+    /// The signed timestamp, for providers whose scheme has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// Why the delivery was refused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    provider: String,
+    secrets: Vec<String>,
+    tolerance_seconds: u64,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::string("provider").required(),
+        Field::string_list("secrets").required().non_empty(),
+        Field::integer("tolerance_seconds").default(DefaultValue::Int(300)),
+    ];
+
+    const WHEN_ABSENT: WhenAbsent = WhenAbsent::Error;
+}
+
+impl Config {
+    fn provider(&self) -> Result<Provider> {
+        Provider::parse(&self.provider).ok_or_else(|| {
+            PluginError::config("provider must be github, stripe or slack")
+                .with_detail("field", "provider")
+                .with_detail("value", self.provider.as_str())
+                .with_detail("supported", Provider::NAMES.to_vec())
+        })
+    }
+
+    fn macs(&self) -> Result<Vec<HmacSha256>> {
+        self.secrets
+            .iter()
+            .enumerate()
+            .map(|(index, secret)| {
+                if secret.is_empty() {
+                    return Err(PluginError::config("secrets must not be empty strings")
+                        .with_detail("field", "secrets")
+                        .with_detail("index", index));
+                }
+                Ok(HmacSha256::new_from_slice(secret.as_bytes())
+                    .expect("HMAC accepts keys of any length"))
+            })
+            .collect()
+    }
+}
+
+#[firelynx_plugin]
+fn verify_webhook(request: Request, static_data: StaticData) -> Result<WebhookVerdict> {
+    let config = Config::from_static_data(&static_data)?;
+    let provider = config.provider()?;
+    let macs = config.macs()?;
+
+    let mut verdict = WebhookVerdict {
+        verified: false,
+        status: 401,
+        provider: provider.name().to_string(),
+        timestamp: None,
+        reason: None,
+    };
+    match verify(&request, provider, &macs, &config) {
+        Ok(timestamp) => {
+            verdict.verified = true;
+            verdict.status = 200;
+            verdict.timestamp = timestamp;
+        }
+        Err((timestamp, reason)) => {
+            log_info!(
+                "webhook delivery refused",
+                provider = verdict.provider,
+                reason = reason,
+            );
+            verdict.timestamp = timestamp;
+            verdict.reason = Some(reason);
+        }
+    }
+    Ok(verdict)
+}
+
+/// Checks the delivery's signature, then its age. Returns the signed
+/// timestamp, with the refusal reason on failure.
+fn verify(
+    request: &Request,
+    provider: Provider,
+    macs: &[HmacSha256],
+    config: &Config,
+) -> Result<Option<i64>, (Option<i64>, String)> {
+    let claim = provider.claim(request).map_err(|reason| (None, reason))?;
+    let refuse = |reason: &str| (claim.timestamp, reason.to_string());
+
+    let payload = provider.signed_payload(claim.timestamp, &request.body.text());
+    let genuine = macs.iter().any(|mac| {
+        let mut mac = mac.clone();
+        mac.update(&payload);
+        // verify_slice compares in constant time; each signature needs its
+        // own copy of the finished MAC.
+        claim
+            .signatures
+            .iter()
+            .any(|signature| mac.clone().verify_slice(signature).is_ok())
+    });
+    if !genuine {
+        return Err(refuse("signature does not match"));
+    }
+
+    if let Some(timestamp) = claim.timestamp {
+        let now = (clock::unix_millis() / 1000) as i64;
+        if now.abs_diff(timestamp) > config.tolerance_seconds {
+            return Err(refuse("timestamp is outside the tolerance"));
+        }
+    }
+    Ok(claim.timestamp)
+}
@@ -0,0 +1,146 @@
+//! How each provider signs a delivery.
+//!
+//! All three sign with HMAC-SHA256 and hex-encode the result; they differ in
+//! which headers carry the signature and timestamp, and in what exactly is
+//! signed:
+//!
+//! - GitHub: `X-Hub-Signature-256: sha256=<hex>` over the body. There is no
+//!   timestamp, so a captured delivery can be replayed.
+//! - Stripe: `Stripe-Signature: t=<unix>,v1=<hex>[,v1=<hex>...]` over
+//!   `<t>.<body>`. Stripe sends one `v1` per active secret while a secret
+//!   is being rolled.
+//! - Slack: `X-Slack-Signature: v0=<hex>` over `v0:<timestamp>:<body>`, the
+//!   timestamp in `X-Slack-Request-Timestamp`.
+
+use firelynx_pdk::prelude::Request;
+
+/// The signing schemes `provider` can name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    GitHub,
+    Stripe,
+    Slack,
+}
+
+/// What a delivery says about itself: the signatures to check and, when the
+/// scheme has one, the signed timestamp in Unix seconds.
+pub struct Claim {
+    pub timestamp: Option<i64>,
+    pub signatures: Vec<Vec<u8>>,
+}
+
+impl Provider {
+    pub const NAMES: [&'static str; 3] = ["github", "stripe", "slack"];
+
+    pub fn parse(name: &str) -> Option<Provider> {
+        match name {
+            "github" => Some(Provider::GitHub),
+            "stripe" => Some(Provider::Stripe),
+            "slack" => Some(Provider::Slack),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::GitHub => "github",
+            Provider::Stripe => "stripe",
+            Provider::Slack => "slack",
+        }
+    }
+
+    /// Reads the signature headers. The error says what is missing or
+    /// malformed.
+    pub fn claim(self, request: &Request) -> Result<Claim, String> {
+        match self {
+            Provider::GitHub => {
+                let value = single_header(request, "X-Hub-Signature-256")?;
+                let signature = value
+                    .strip_prefix("sha256=")
+                    .and_then(hex)
+                    .ok_or("X-Hub-Signature-256 is not sha256=<hex>")?;
+                Ok(Claim {
+                    timestamp: None,
+                    signatures: vec![signature],
+                })
+            }
+            Provider::Stripe => {
+                let value = single_header(request, "Stripe-Signature")?;
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for (key, value) in value.split(',').filter_map(|part| part.split_once('=')) {
+                    match key.trim() {
+                        "t" => timestamp = Some(unix_seconds(value, "Stripe-Signature t")?),
+                        "v1" => signatures
+                            .push(hex(value.trim()).ok_or("Stripe-Signature v1 is not hex")?),
+                        // v0 is Stripe's test-mode scheme; other keys are
+                        // future schemes.
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.ok_or("Stripe-Signature has no t")?;
+                if signatures.is_empty() {
+                    return Err("Stripe-Signature has no v1 signature".to_string());
+                }
+                Ok(Claim {
+                    timestamp: Some(timestamp),
+                    signatures,
+                })
+            }
+            Provider::Slack => {
+                let value = single_header(request, "X-Slack-Signature")?;
+                let signature = value
+                    .strip_prefix("v0=")
+                    .and_then(hex)
+                    .ok_or("X-Slack-Signature is not v0=<hex>")?;
+                let timestamp = single_header(request, "X-Slack-Request-Timestamp")?;
+                Ok(Claim {
+                    timestamp: Some(unix_seconds(timestamp, "X-Slack-Request-Timestamp")?),
+                    signatures: vec![signature],
+                })
+            }
+        }
+    }
+
+    /// The bytes the provider's HMAC covers.
+    pub fn signed_payload(self, timestamp: Option<i64>, body: &str) -> Vec<u8> {
+        let prefix = match (self, timestamp) {
+            (Provider::Stripe, Some(t)) => format!("{}.", t),
+            (Provider::Slack, Some(t)) => format!("v0:{}:", t),
+            _ => String::new(),
+        };
+        let mut payload = prefix.into_bytes();
+        payload.extend_from_slice(body.as_bytes());
+        payload
+    }
+}
+
+/// The value of header `name`, which must be sent exactly once. Lookup is
+/// case-insensitive; `name` is spelled the way the provider documents it
+/// because it appears in refusal reasons.
+fn single_header<'r>(request: &'r Request, name: &str) -> Result<&'r str, String> {
+    match request.header_values(name) {
+        Some([value]) => Ok(value.trim()),
+        Some(_) => Err(format!("{} is repeated", name)),
+        None => Err(format!("{} is missing", name)),
+    }
+}
+
+fn unix_seconds(value: &str, what: &str) -> Result<i64, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} is not a Unix timestamp", what))
+}
+
+/// Decodes lowercase or uppercase hex.
+fn hex(text: &str) -> Option<Vec<u8>> {
+    let pairs = text.as_bytes().chunks_exact(2);
+    if text.is_empty() || !pairs.remainder().is_empty() {
+        return None;
+    }
+    let digit = |b: u8| (b as char).to_digit(16);
+    pairs
+        .map(|pair| Some((digit(pair[0])? * 16 + digit(pair[1])?) as u8))
+        .collect()
+}
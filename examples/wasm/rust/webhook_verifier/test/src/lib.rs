@@ -0,0 +1,146 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+// The example from GitHub's webhook documentation.
+const GITHUB_SECRET: &str = "It's a Secret to Everybody";
+const GITHUB_BODY: &str = "Hello, World!";
+const GITHUB_SIGNATURE: &str =
+    "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+fn github(body: &str, signature: Option<&str>) -> String {
+    let fixture = RequestFixture::new(body)
+        .method("POST")
+        .static_data("provider", "github")
+        .static_data("secrets", json!([GITHUB_SECRET]));
+    match signature {
+        Some(signature) => fixture.header("X-Hub-Signature-256", signature),
+        None => fixture,
+    }
+    .to_json()
+}
+
+fn verify(input: &str) -> Result<Value, Error> {
+    let Json(verdict): Json<Value> = xtp_test::call("VerifyWebhook", input)?;
+    Ok(verdict)
+}
+
+fn fails(input: &str) -> bool {
+    xtp_test::call::<Json<Value>>("VerifyWebhook", input).is_err()
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("github signatures", || {
+        let verdict = verify(&github(GITHUB_BODY, Some(GITHUB_SIGNATURE)))?;
+        xtp_test::assert_eq!(
+            "a genuine delivery verifies",
+            &verdict["verified"],
+            &json!(true)
+        );
+        xtp_test::assert_eq!("with status 200", &verdict["status"], &json!(200));
+
+        let verdict = verify(&github("Hello, World?", Some(GITHUB_SIGNATURE)))?;
+        xtp_test::assert_eq!("a changed body is refused", &verdict["status"], &json!(401));
+        xtp_test::assert_eq!(
+            "because the signature no longer matches",
+            &verdict["reason"],
+            &json!("signature does not match")
+        );
+
+        let verdict = verify(&github(GITHUB_BODY, None))?;
+        xtp_test::assert_eq!(
+            "an unsigned delivery is refused",
+            &verdict["reason"],
+            &json!("X-Hub-Signature-256 is missing")
+        );
+
+        let verdict = verify(&github(GITHUB_BODY, Some("sha1=757107ea")))?;
+        xtp_test::assert_eq!(
+            "another digest is refused",
+            &verdict["verified"],
+            &json!(false)
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("secret rotation", || {
+        let input = RequestFixture::new(GITHUB_BODY)
+            .header("X-Hub-Signature-256", GITHUB_SIGNATURE)
+            .static_data("provider", "github")
+            .static_data("secrets", json!(["the-new-secret", GITHUB_SECRET]))
+            .to_json();
+        let verdict = verify(&input)?;
+        xtp_test::assert_eq!(
+            "any listed secret verifies",
+            &verdict["verified"],
+            &json!(true)
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("timestamped schemes", || {
+        // Genuinely signed with whsec_example, but in 2001: far outside any
+        // tolerance.
+        let input = RequestFixture::new("{}")
+            .header(
+                "Stripe-Signature",
+                "t=1000000000,v1=be6585584f1a6e288c0a199f56e8d5c165528b529b62734a2e7e7daeea02662b",
+            )
+            .static_data("provider", "stripe")
+            .static_data("secrets", json!(["whsec_example"]))
+            .to_json();
+        let verdict = verify(&input)?;
+        xtp_test::assert_eq!(
+            "a replayed delivery is refused",
+            &verdict["reason"],
+            &json!("timestamp is outside the tolerance")
+        );
+        xtp_test::assert_eq!(
+            "the signed timestamp is reported",
+            &verdict["timestamp"],
+            &json!(1000000000)
+        );
+
+        let input = RequestFixture::new("command=%2Fweather")
+            .header("X-Slack-Signature", "v0=abcd")
+            .static_data("provider", "slack")
+            .static_data("secrets", json!(["slack-signing-secret"]))
+            .to_json();
+        let verdict = verify(&input)?;
+        xtp_test::assert_eq!(
+            "a slack delivery needs its timestamp header",
+            &verdict["reason"],
+            &json!("X-Slack-Request-Timestamp is missing")
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("configuration errors", || {
+        xtp_test::assert!(
+            "empty static_data is rejected",
+            fails(&RequestFixture::new("").empty_static_data().to_json())
+        );
+        xtp_test::assert!(
+            "an unknown provider is rejected",
+            fails(
+                &RequestFixture::new("")
+                    .static_data("provider", "gitlab")
+                    .static_data("secrets", json!(["s"]))
+                    .to_json()
+            )
+        );
+        xtp_test::assert!(
+            "an empty secret list is rejected",
+            fails(
+                &RequestFixture::new("")
+                    .static_data("provider", "github")
+                    .static_data("secrets", json!([]))
+                    .to_json()
+            )
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+mod mapping;
+
+use std::collections::HashMap;
+
+use firelynx_pdk::limits::ParseLimits;
+use firelynx_pdk::prelude::*;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use mapping::{Options, XML_NAMESPACE};
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    namespaces: Map<String, Value>,
+    strip_namespaces: bool,
+    #[serde(default)]
+    force_array: Vec<String>,
+    trim_text: bool,
+}
+
+impl ConfigSchema for Config {
+    const FIELDS: &'static [Field] = &[
+        Field::table("namespaces"),
+        Field::bool("strip_namespaces").default(DefaultValue::Bool(false)),
+        Field::string_list("force_array"),
+        Field::bool("trim_text").default(DefaultValue::Bool(true)),
+    ];
+}
+
+impl Config {
+    fn options(&self) -> Result<Options<'_>> {
+        let mut aliases = HashMap::from([(XML_NAMESPACE, "xml")]);
+        for (alias, uri) in &self.namespaces {
+            let invalid = |message: &str| {
+                PluginError::config(message.to_string())
+                    .with_detail("field", "namespaces")
+                    .with_detail("alias", alias.as_str())
+            };
+            let Some(uri) = uri.as_str().filter(|uri| !uri.is_empty()) else {
+                return Err(invalid("namespaces values must be non-empty URIs"));
+            };
+            if alias.is_empty() || alias.contains([':', '{', '}', '@', '#']) {
+                return Err(invalid(
+                    "namespaces aliases must be non-empty and free of : { } @ #",
+                ));
+            }
+            if aliases
+                .insert(uri, alias.as_str())
+                .is_some_and(|_| uri != XML_NAMESPACE)
+            {
+                return Err(invalid("namespaces gives one URI two aliases").with_detail("uri", uri));
+            }
+        }
+        Ok(Options {
+            aliases,
+            strip_namespaces: self.strip_namespaces,
+            force_array: &self.force_array,
+            trim_text: self.trim_text,
+        })
+    }
+}
+
+#[firelynx_plugin]
+fn xml_to_json(request: Request, static_data: StaticData) -> Result<Value> {
+    let config = Config::from_static_data(&static_data)?;
+    let options = config.options()?;
+    // Whatever the Content-Type says, the body is read as XML.
+    let xml = request.body.text();
+    ParseLimits::active().check_xml(xml.as_bytes())?;
+    let document = mapping::convert(&xml, &options)?;
+    log_debug!("converted XML to JSON", xml_bytes = xml.len());
+    Ok(document)
+}
@@ -0,0 +1,230 @@
+//! The JSON mapping of an XML document.
+//!
+//! - The document is an object with one member, named after the root
+//!   element.
+//! - An element with no attributes and no child elements maps to its text.
+//! - Any other element maps to an object: attributes as `@name`, child
+//!   elements under their names, in document order, and non-blank text as
+//!   `#text`.
+//! - Child elements that share a name become an array in document order.
+//!   Names in `force_array` are arrays even when they occur once, so a
+//!   client's JSON has the same shape however many there are.
+//! - Names in a namespace are `alias:local` for a configured alias of its
+//!   URI, whatever prefix the document used, and `{uri}local` otherwise.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use firelynx_pdk::prelude::*;
+use quick_xml::escape::resolve_xml_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{LocalName, Namespace, ResolveResult};
+use quick_xml::NsReader;
+use serde_json::{Map, Value};
+
+/// The URI the `xml` prefix is bound to by definition.
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+pub struct Options<'c> {
+    /// Namespace URI to alias.
+    pub aliases: HashMap<&'c str, &'c str>,
+    pub strip_namespaces: bool,
+    pub force_array: &'c [String],
+    pub trim_text: bool,
+}
+
+/// An element whose end tag has not been read yet.
+struct Open {
+    name: String,
+    members: Map<String, Value>,
+    text: String,
+}
+
+impl Open {
+    fn finish(self, trim_text: bool) -> Value {
+        let text = if trim_text {
+            self.text.trim()
+        } else {
+            self.text.as_str()
+        };
+        if self.members.is_empty() {
+            return Value::String(text.to_string());
+        }
+        let mut members = self.members;
+        // Indentation between child elements is not text.
+        if !text.trim().is_empty() {
+            members.insert("#text".to_string(), Value::String(text.to_string()));
+        }
+        Value::Object(members)
+    }
+
+    fn add_child(&mut self, name: String, value: Value, force_array: bool) {
+        match self.members.get_mut(&name) {
+            Some(Value::Array(items)) => items.push(value),
+            Some(first) => *first = Value::Array(vec![first.take(), value]),
+            None if force_array => {
+                self.members.insert(name, Value::Array(vec![value]));
+            }
+            None => {
+                self.members.insert(name, value);
+            }
+        }
+    }
+}
+
+/// Maps `xml` to JSON. Malformed XML, an undeclared prefix or an entity
+/// other than XML's five is `INVALID_INPUT`; DTD entities are never
+/// expanded.
+pub fn convert(xml: &str, options: &Options) -> Result<Value> {
+    let mut reader = NsReader::from_str(xml);
+    let mut open: Vec<Open> = Vec::new();
+    let mut root: Option<Map<String, Value>> = None;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| malformed(e, reader.error_position()))?;
+        let empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(start) | Event::Empty(start) => {
+                if open.is_empty() && root.is_some() {
+                    return Err(malformed(
+                        "more than one root element",
+                        reader.buffer_position(),
+                    ));
+                }
+                let element = element(&reader, &start, options)?;
+                // An empty element has no end tag, so it closes here.
+                if empty {
+                    close(element, &mut open, &mut root, options);
+                } else {
+                    open.push(element);
+                }
+            }
+            Event::End(_) => {
+                if let Some(element) = open.pop() {
+                    close(element, &mut open, &mut root, options);
+                }
+            }
+            Event::Text(text) => {
+                let text = text
+                    .decode()
+                    .map_err(|e| malformed(e, reader.buffer_position()))?;
+                push_text(&mut open, &text, reader.buffer_position())?;
+            }
+            Event::CData(cdata) => {
+                let text = cdata
+                    .decode()
+                    .map_err(|e| malformed(e, reader.buffer_position()))?;
+                push_text(&mut open, &text, reader.buffer_position())?;
+            }
+            Event::GeneralRef(reference) => {
+                let position = reader.buffer_position();
+                let name = reference.decode().map_err(|e| malformed(e, position))?;
+                let text = match reference.resolve_char_ref() {
+                    Ok(Some(c)) => Cow::Owned(c.to_string()),
+                    Ok(None) => match resolve_xml_entity(&name) {
+                        Some(text) => Cow::Borrowed(text),
+                        None => {
+                            return Err(PluginError::invalid_input(
+                                "The body refers to an entity XML does not define",
+                            )
+                            .with_detail("entity", name.as_ref())
+                            .with_detail("position", position));
+                        }
+                    },
+                    Err(e) => return Err(malformed(e, position)),
+                };
+                push_text(&mut open, &text, position)?;
+            }
+            Event::Eof => break,
+            // Declarations, comments, processing instructions and the
+            // DOCTYPE carry no data.
+            _ => {}
+        }
+    }
+    if let Some(element) = open.last() {
+        return Err(
+            PluginError::invalid_input("The body ends inside an element")
+                .with_detail("element", element.name.as_str()),
+        );
+    }
+    root.map(Value::Object)
+        .ok_or_else(|| PluginError::invalid_input("The body has no root element"))
+}
+
+/// A new element with its attributes read.
+fn element<R>(reader: &NsReader<R>, start: &BytesStart, options: &Options) -> Result<Open> {
+    let (namespace, local) = reader.resolve_element(start.name());
+    let name = options.name(namespace, local)?;
+    let mut members = Map::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| malformed(e, reader.buffer_position()))?;
+        // Declarations are resolved into the names; they are not data.
+        if attribute.key.as_namespace_binding().is_some() {
+            continue;
+        }
+        let (namespace, local) = reader.resolve_attribute(attribute.key);
+        let key = format!("@{}", options.name(namespace, local)?);
+        let value = attribute
+            .decode_and_unescape_value(reader.decoder())
+            .map_err(|e| malformed(e, reader.buffer_position()))?;
+        members.insert(key, Value::String(value.into_owned()));
+    }
+    Ok(Open {
+        name,
+        members,
+        text: String::new(),
+    })
+}
+
+fn close(
+    element: Open,
+    open: &mut [Open],
+    root: &mut Option<Map<String, Value>>,
+    options: &Options,
+) {
+    let name = element.name.clone();
+    let value = element.finish(options.trim_text);
+    match open.last_mut() {
+        Some(parent) => {
+            let force_array = options.force_array.contains(&name);
+            parent.add_child(name, value, force_array);
+        }
+        None => *root = Some(Map::from_iter([(name, value)])),
+    }
+}
+
+fn push_text(open: &mut [Open], text: &str, position: u64) -> Result<()> {
+    match open.last_mut() {
+        Some(element) => element.text.push_str(text),
+        None if text.trim().is_empty() => {}
+        None => return Err(malformed("text outside the root element", position)),
+    }
+    Ok(())
+}
+
+impl Options<'_> {
+    fn name(&self, namespace: ResolveResult, local: LocalName) -> Result<String> {
+        let local = String::from_utf8_lossy(local.as_ref());
+        match namespace {
+            ResolveResult::Unknown(prefix) => Err(PluginError::invalid_input(
+                "The body uses a namespace prefix it does not declare",
+            )
+            .with_detail("prefix", String::from_utf8_lossy(&prefix).as_ref())),
+            ResolveResult::Bound(Namespace(uri)) if !self.strip_namespaces => {
+                let uri = String::from_utf8_lossy(uri);
+                Ok(match self.aliases.get(uri.as_ref()) {
+                    Some(alias) => format!("{}:{}", alias, local),
+                    None => format!("{{{}}}{}", uri, local),
+                })
+            }
+            _ => Ok(local.into_owned()),
+        }
+    }
+}
+
+fn malformed(reason: impl ToString, position: u64) -> PluginError {
+    PluginError::invalid_input("The body is not well-formed XML")
+        .with_detail("reason", reason.to_string())
+        .with_detail("position", position)
+}
@@ -0,0 +1,93 @@
+use extism_pdk::*;
+use firelynx_fixture::RequestFixture;
+use serde_json::{json, Value};
+
+const ENVELOPE: &str = r#"<?xml version="1.0"?>
+<env:Envelope xmlns:env="http://schemas.xmlsoap.org/soap/envelope/">
+  <env:Body>
+    <GetOrder xmlns="urn:orders" id="42">
+      <Item sku="A">Widget &amp; bolt</Item>
+      <Note>rush</Note>
+    </GetOrder>
+  </env:Body>
+</env:Envelope>"#;
+
+fn xml(body: &str) -> RequestFixture {
+    RequestFixture::new(body)
+        .without_header("Content-Type")
+        .header("Content-Type", "text/xml")
+}
+
+fn convert(input: &str) -> Result<Value, Error> {
+    let Json(document): Json<Value> = xtp_test::call("XmlToJson", input)?;
+    Ok(document)
+}
+
+fn fails(input: &str) -> bool {
+    xtp_test::call::<Json<Value>>("XmlToJson", input).is_err()
+}
+
+#[plugin_fn]
+pub fn test() -> FnResult<()> {
+    xtp_test::group("mapping", || {
+        let input = xml(ENVELOPE)
+            .static_data(
+                "namespaces",
+                json!({"soap": "http://schemas.xmlsoap.org/soap/envelope/", "o": "urn:orders"}),
+            )
+            .static_data("force_array", json!(["o:Item"]))
+            .to_json();
+        xtp_test::assert_eq!(
+            "aliases name namespaces whatever the prefix",
+            convert(&input)?,
+            json!({"soap:Envelope": {"soap:Body": {"o:GetOrder": {
+                "@id": "42",
+                "o:Item": [{"@sku": "A", "#text": "Widget & bolt"}],
+                "o:Note": "rush"
+            }}}})
+        );
+
+        let input = xml(ENVELOPE).to_json();
+        xtp_test::assert_eq!(
+            "unaliased namespaces use the URI",
+            &convert(&input)?["{http://schemas.xmlsoap.org/soap/envelope/}Envelope"]
+                ["{http://schemas.xmlsoap.org/soap/envelope/}Body"]["{urn:orders}GetOrder"]["@id"],
+            &json!("42")
+        );
+
+        let input = xml(ENVELOPE)
+            .static_data("strip_namespaces", true)
+            .to_json();
+        xtp_test::assert_eq!(
+            "namespaces can be stripped",
+            &convert(&input)?["Envelope"]["Body"]["GetOrder"]["Note"],
+            &json!("rush")
+        );
+
+        xtp_test::assert_eq!(
+            "repeated elements become arrays",
+            convert(&xml("<list><n>1</n><n>2</n><empty/></list>").to_json())?,
+            json!({"list": {"n": ["1", "2"], "empty": ""}})
+        );
+        Ok(())
+    })?;
+
+    xtp_test::group("errors", || {
+        xtp_test::assert!(
+            "mismatched tags are rejected",
+            fails(&xml("<a><b></a>").to_json())
+        );
+        xtp_test::assert!(
+            "DTD entities are not expanded",
+            fails(&xml(r#"<!DOCTYPE a [<!ENTITY x "boom">]><a>&x;</a>"#).to_json())
+        );
+        xtp_test::assert!(
+            "undeclared prefixes are rejected",
+            fails(&xml("<p:a/>").to_json())
+        );
+        xtp_test::assert!("an empty body is rejected", fails(&xml("").to_json()));
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,287 @@
+//! Development tasks for the Rust example plugins.
+//!
+//! ```text
+//! cargo xtask feature-matrix [--no-bench]
+//! cargo xtask clippy
+//! ```
+//!
+//! `feature-matrix` builds char_counter once per combination of firelynx-pdk
+//! features, prints a Markdown table of the module sizes, then times every
+//! build with `envelope_bench` unless `--no-bench` is given.
+//!
+//! `clippy` lints firelynx-pdk under every feature set, natively and for
+//! wasm, and char_counter as each `feature-matrix` build, against the
+//! committed lockfiles.
+
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+/// One char_counter build in the matrix.
+struct Variant {
+    label: &'static str,
+    features: &'static [&'static str],
+    /// simd-json only takes its SIMD path with `+simd128`.
+    simd128: bool,
+}
+
+const VARIANTS: &[Variant] = &[
+    Variant {
+        label: "default",
+        features: &[],
+        simd128: false,
+    },
+    Variant {
+        label: "simd-json",
+        features: &["simd-json"],
+        simd128: true,
+    },
+    Variant {
+        label: "cbor",
+        features: &["cbor"],
+        simd128: false,
+    },
+    Variant {
+        label: "simd-json+cbor",
+        features: &["simd-json", "cbor"],
+        simd128: true,
+    },
+    Variant {
+        label: "arbitrary-precision",
+        features: &["arbitrary-precision"],
+        simd128: false,
+    },
+];
+
+/// firelynx-pdk feature sets `clippy` lints: none, each on its own, then all
+/// of them. MessagePack is always compiled in.
+const PDK_FEATURE_SETS: &[&[&str]] = &[
+    &[],
+    &["strict"],
+    &["cbor"],
+    &["simd-json"],
+    &["xml"],
+    &["arbitrary-precision"],
+    &["strict", "cbor", "simd-json", "xml", "arbitrary-precision"],
+];
+
+const TARGET: &str = "wasm32-wasip1";
+
+/// The flags char_counter's `make build` passes to wasm-opt.
+const WASM_OPT_FLAGS: &[&str] = &[
+    "-Oz",
+    "--enable-bulk-memory",
+    "--enable-nontrapping-float-to-int",
+    "--enable-sign-ext",
+    "--enable-mutable-globals",
+    "--strip-producers",
+];
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("feature-matrix") => feature_matrix(&args[1..]),
+        Some("clippy") => clippy(&args[1..]),
+        _ => Err("usage: cargo xtask feature-matrix [--no-bench]\n       \
+             cargo xtask clippy\n\n\
+             feature-matrix  build char_counter per SDK feature set and compare size and speed\n\
+             clippy          lint firelynx-pdk per feature set and char_counter per build"
+            .to_string()),
+    };
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        exit(1);
+    }
+}
+
+fn feature_matrix(args: &[String]) -> Result<(), String> {
+    let mut bench = true;
+    for arg in args {
+        match arg.as_str() {
+            "--no-bench" => bench = false,
+            other => return Err(format!("unknown feature-matrix option {}", other)),
+        }
+    }
+
+    let root = examples_dir();
+    let builds_dir = root.join("xtask/target/feature-matrix");
+    let wasm_opt = has_wasm_opt();
+
+    let mut builds = Vec::new();
+    for variant in VARIANTS {
+        eprintln!("building char_counter ({})", variant.label);
+        let wasm = build(&root.join("char_counter"), &builds_dir, variant)?;
+        let raw = file_size(&wasm)?;
+        let optimized = if wasm_opt {
+            let out = wasm.with_extension("opt.wasm");
+            run(Command::new("wasm-opt")
+                .args(WASM_OPT_FLAGS)
+                .arg("-o")
+                .arg(&out)
+                .arg(&wasm))?;
+            Some(file_size(&out)?)
+        } else {
+            None
+        };
+        builds.push((variant, wasm, raw, optimized));
+    }
+
+    let baseline = builds[0].3.unwrap_or(builds[0].2);
+    println!("| build | features | wasm size | after wasm-opt | vs default |");
+    println!("|---|---|---:|---:|---:|");
+    for (variant, _, raw, optimized) in &builds {
+        let shipped = optimized.unwrap_or(*raw);
+        println!(
+            "| {} | {} | {} | {} | {} |",
+            variant.label,
+            if variant.features.is_empty() {
+                "-".to_string()
+            } else {
+                variant.features.join(", ")
+            },
+            format_size(*raw),
+            optimized.map_or("n/a".to_string(), format_size),
+            format_delta(shipped, baseline),
+        );
+    }
+    if !wasm_opt {
+        eprintln!("wasm-opt not found (install binaryen) - only raw sizes reported");
+    }
+
+    if bench {
+        println!();
+        let mut command = Command::new("cargo");
+        command.current_dir(root.join("envelope_bench")).args([
+            "run",
+            "--quiet",
+            "--release",
+            "--",
+        ]);
+        for (variant, wasm, _, _) in &builds {
+            command.arg(format!("{}={}", variant.label, wasm.display()));
+        }
+        run(&mut command)?;
+    }
+    Ok(())
+}
+
+fn clippy(args: &[String]) -> Result<(), String> {
+    if let Some(other) = args.first() {
+        return Err(format!("unknown clippy option {}", other));
+    }
+
+    let root = examples_dir();
+    let pdk = root.join("firelynx_pdk");
+    for features in PDK_FEATURE_SETS {
+        let label = if features.is_empty() {
+            "default".to_string()
+        } else {
+            features.join("+")
+        };
+        eprintln!("clippy firelynx_pdk ({})", label);
+        // Natively with the tests, then the library as plugins build it:
+        // the host imports and `error_set` reporting only exist on wasm.
+        run(&mut clippy_command(&pdk, features, None, "--all-targets"))?;
+        run(&mut clippy_command(&pdk, features, Some(TARGET), "--lib"))?;
+    }
+    for variant in VARIANTS {
+        eprintln!("clippy char_counter ({})", variant.label);
+        let mut command = clippy_command(
+            &root.join("char_counter"),
+            variant.features,
+            Some(TARGET),
+            "--lib",
+        );
+        if variant.simd128 {
+            command.env("RUSTFLAGS", "-C target-feature=+simd128");
+        } else {
+            command.env_remove("RUSTFLAGS");
+        }
+        run(&mut command)?;
+    }
+    Ok(())
+}
+
+/// `cargo clippy` over the `targets` of `dir` (`--lib`, `--all-targets`)
+/// with `features`, for `target` when given. It fails on any warning, and
+/// on a lockfile that is out of date.
+fn clippy_command(dir: &Path, features: &[&str], target: Option<&str>, targets: &str) -> Command {
+    let mut command = Command::new("cargo");
+    command
+        .current_dir(dir)
+        .args(["clippy", "--quiet", "--locked", targets]);
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    if !features.is_empty() {
+        command.args(["--features", &features.join(",")]);
+    }
+    command.args(["--", "-D", "warnings"]);
+    command
+}
+
+/// The directory holding the example crates, this crate's parent.
+fn examples_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives inside examples/wasm/rust")
+        .to_path_buf()
+}
+
+/// Builds `plugin` in release mode into its own target directory and returns
+/// the module path.
+fn build(plugin: &Path, builds_dir: &Path, variant: &Variant) -> Result<PathBuf, String> {
+    let target_dir = builds_dir.join(variant.label);
+    let mut command = Command::new("cargo");
+    command
+        .current_dir(plugin)
+        .args(["build", "--quiet", "--release", "--target", TARGET])
+        .arg("--target-dir")
+        .arg(&target_dir);
+    if !variant.features.is_empty() {
+        command.args(["--features", &variant.features.join(",")]);
+    }
+    if variant.simd128 {
+        command.env("RUSTFLAGS", "-C target-feature=+simd128");
+    } else {
+        // Keep a RUSTFLAGS from the caller's shell out of the comparison.
+        command.env_remove("RUSTFLAGS");
+    }
+    run(&mut command)?;
+    Ok(target_dir.join(TARGET).join("release/plugin.wasm"))
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let status = command
+        .status()
+        .map_err(|e| format!("failed to start {:?}: {}", command.get_program(), e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{:?} failed with {}", command, status))
+    }
+}
+
+fn has_wasm_opt() -> bool {
+    Command::new("wasm-opt")
+        .arg("--version")
+        .output()
+        .is_ok_and(|out| out.status.success())
+}
+
+fn file_size(path: &Path) -> Result<u64, String> {
+    std::fs::metadata(path)
+        .map(|meta| meta.len())
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} KiB", bytes as f64 / 1024.0)
+}
+
+fn format_delta(size: u64, baseline: u64) -> String {
+    if size == baseline {
+        return "-".to_string();
+    }
+    let pct = (size as f64 - baseline as f64) / baseline as f64 * 100.0;
+    format!("{:+.1}%", pct)
+}